@@ -0,0 +1,53 @@
+//! Merge throughput on large env files: `cargo bench --bench merge`.
+//! Compare against a saved baseline with `-- --save-baseline main` on the
+//! base branch and `-- --baseline main` on the change.
+
+use std::{collections::HashMap, fs, hint::black_box};
+
+use criterion::{BenchmarkId, Criterion, Throughput, criterion_group, criterion_main};
+use tempfile::TempDir;
+
+#[allow(dead_code, unused_imports)]
+#[path = "../src/envfile.rs"]
+mod envfile;
+
+/// An env file of `keys` entries with a comment every tenth line, and
+/// updates that change half of them and add a tenth as many new ones.
+fn fixture(keys: usize) -> (String, HashMap<String, String>) {
+    let mut content = String::new();
+    let mut updates = HashMap::new();
+    for i in 0..keys {
+        if i % 10 == 0 {
+            content.push_str(&format!("# section {i}\n"));
+        }
+        content.push_str(&format!("KEY_{i:06}=value-{i}\n"));
+        let value = if i % 2 == 0 { "changed" } else { "value" };
+        updates.insert(format!("KEY_{i:06}"), format!("{value}-{i}"));
+    }
+    for i in 0..keys / 10 {
+        updates.insert(format!("NEW_{i:06}"), format!("new-{i}"));
+    }
+    (content, updates)
+}
+
+fn merge(c: &mut Criterion) {
+    let dir = TempDir::new().unwrap();
+    let mut group = c.benchmark_group("merge");
+    for keys in [1_000, 10_000, 50_000] {
+        let (content, updates) = fixture(keys);
+        let path = dir.path().join(format!("{keys}.env"));
+        fs::write(&path, &content).unwrap();
+        group.throughput(Throughput::Elements(keys as u64));
+        group.bench_with_input(BenchmarkId::from_parameter(keys), &path, |b, path| {
+            b.iter(|| {
+                let lines = envfile::load_for_merge(path, false).unwrap();
+                let (merged, changes) = envfile::merge(lines, &updates);
+                black_box((envfile::render(&merged), changes))
+            })
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, merge);
+criterion_main!(benches);
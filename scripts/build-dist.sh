@@ -0,0 +1,27 @@
+#!/bin/sh
+# Builds static (musl) envit binaries for each target and provider set into dist/.
+#
+#   TARGETS  space-separated rust targets   (default: x86_64 + aarch64 linux musl)
+#   SETS     space-separated name:features  (default: full build + one per provider)
+#   CARGO    cargo-compatible driver, e.g. CARGO=cross for non-native targets
+set -eu
+
+TARGETS="${TARGETS:-x86_64-unknown-linux-musl aarch64-unknown-linux-musl}"
+SETS="${SETS:-full:default azure:azure cloudflare:cloudflare exec:exec http-json:http-json keeper:keeper kms-file:kms-file local-vault:local-vault netlify:netlify railway:railway}"
+CARGO="${CARGO:-cargo}"
+
+mkdir -p dist
+for target in $TARGETS; do
+    for set in $SETS; do
+        name="${set%%:*}"
+        features="${set#*:}"
+        if [ "$features" = default ]; then
+            "$CARGO" build --locked --profile dist --target "$target"
+        else
+            "$CARGO" build --locked --profile dist --target "$target" \
+                --no-default-features --features "$features"
+        fi
+        cp "target/$target/dist/envit" "dist/envit-$name-$target"
+        echo "built dist/envit-$name-$target"
+    done
+done
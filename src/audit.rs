@@ -0,0 +1,100 @@
+use std::{
+    env,
+    fs::{self, OpenOptions},
+    io::Write,
+    path::Path,
+    time::SystemTime,
+};
+
+use anyhow::{Context, Result};
+use serde::Serialize;
+
+use crate::config::{self, Config};
+
+/// Used for break-glass records when no `[audit] path` is configured.
+pub const DEFAULT_PATH: &str = ".envit/audit.jsonl";
+
+/// One line of the audit log. Never carries secret values.
+#[derive(Debug, Serialize)]
+pub struct Entry<'a> {
+    pub timestamp: String,
+    pub command: &'a str,
+    pub user: Option<String>,
+    pub host: Option<String>,
+    pub principal: Option<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub env_file: Option<String>,
+    pub dry_run: bool,
+    pub changed_keys: Vec<&'a str>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub exported_keys: Vec<&'a str>,
+    pub error: Option<String>,
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    pub break_glass: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub reason: Option<&'a str>,
+    /// `--override-owner` changed keys owned by another team.
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    pub override_owner: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub canary: Option<&'a str>,
+    /// Some providers were unreachable and kept their last-known values.
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    pub degraded: bool,
+}
+
+impl<'a> Entry<'a> {
+    pub fn new(command: &'a str) -> Self {
+        Self {
+            timestamp: humantime::format_rfc3339_seconds(SystemTime::now()).to_string(),
+            command,
+            user: os_user(),
+            host: hostname::get()
+                .ok()
+                .map(|h| h.to_string_lossy().into_owned()),
+            principal: None,
+            env_file: None,
+            dry_run: false,
+            changed_keys: Vec::new(),
+            exported_keys: Vec::new(),
+            error: None,
+            break_glass: false,
+            reason: None,
+            override_owner: false,
+            canary: None,
+            degraded: false,
+        }
+    }
+}
+
+/// Appends to the configured audit log; a no-op when auditing is off.
+pub fn record(config_path: &Path, cfg: &Config, entry: &Entry) -> Result<()> {
+    match &cfg.audit.path {
+        Some(path) => append(&config::resolve_path(config_path, path), entry),
+        None => Ok(()),
+    }
+}
+
+pub fn append(path: &Path, entry: &Entry) -> Result<()> {
+    if let Some(dir) = path.parent().filter(|d| !d.as_os_str().is_empty()) {
+        fs::create_dir_all(dir)
+            .with_context(|| format!("failed to create audit log directory: {}", dir.display()))?;
+    }
+
+    let mut line = serde_json::to_string(entry).context("failed to serialize audit entry")?;
+    line.push('\n');
+
+    OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)
+        .and_then(|mut f| f.write_all(line.as_bytes()))
+        .with_context(|| format!("failed to append audit log: {}", path.display()))
+}
+
+fn os_user() -> Option<String> {
+    env::var("USER")
+        .or_else(|_| env::var("USERNAME"))
+        .ok()
+        .filter(|u| !u.is_empty())
+}
@@ -0,0 +1,106 @@
+//! Snapshots of the env file, taken before each write when
+//! `output.backups` is set, for `envit rollback`. A snapshot is the file as
+//! it was on disk, so an encrypted env file stays encrypted; snapshots are
+//! named after the time they were taken.
+
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+    time::SystemTime,
+};
+
+use anyhow::{Context, Result};
+
+use crate::{
+    config::{self, Config},
+    envfile,
+};
+
+/// Relative to the config file.
+pub const BACKUPS_PATH: &str = ".envit/backups";
+
+pub struct Snapshot {
+    pub taken: SystemTime,
+    pub path: PathBuf,
+}
+
+/// Copies the env file at `env_path` into `BACKUPS_PATH`, unless backups
+/// are off, the file does not exist yet, or the newest snapshot already
+/// holds it. Only the newest `output.backups` snapshots are kept.
+pub fn snapshot(config_path: &Path, cfg: &Config, env_path: &Path) -> Result<()> {
+    if cfg.output.backups == 0 {
+        return Ok(());
+    }
+    let current = match fs::read(env_path) {
+        Ok(current) => current,
+        Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(()),
+        Err(err) => {
+            return Err(err).with_context(|| format!("failed to read {}", env_path.display()));
+        }
+    };
+    let dir = config::resolve_path(config_path, BACKUPS_PATH);
+    let snapshots = list(&dir)?;
+    if let Some(newest) = snapshots.last()
+        && fs::read(&newest.path).is_ok_and(|newest| newest == current)
+    {
+        return Ok(());
+    }
+
+    fs::create_dir_all(&dir).with_context(|| format!("failed to create {}", dir.display()))?;
+    let path = dir.join(file_name(SystemTime::now()));
+    envfile::write_atomic(&path, &current)
+        .with_context(|| format!("failed to write {}", path.display()))?;
+    let snapshots = list(&dir)?;
+    for old in &snapshots[..snapshots.len().saturating_sub(cfg.output.backups)] {
+        fs::remove_file(&old.path)
+            .with_context(|| format!("failed to remove {}", old.path.display()))?;
+    }
+    Ok(())
+}
+
+/// The snapshots in `dir`, oldest first; other files are ignored.
+pub fn list(dir: &Path) -> Result<Vec<Snapshot>> {
+    let entries = match fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(err) => return Err(err).with_context(|| format!("failed to read {}", dir.display())),
+    };
+    let mut snapshots = Vec::new();
+    for entry in entries {
+        let entry = entry.with_context(|| format!("failed to read {}", dir.display()))?;
+        if let Some(taken) = entry.file_name().to_str().and_then(parse_name) {
+            snapshots.push(Snapshot {
+                taken,
+                path: entry.path(),
+            });
+        }
+    }
+    snapshots.sort_by_key(|snapshot| snapshot.taken);
+    Ok(snapshots)
+}
+
+/// RFC 3339 with millisecond precision, `-` for `:` to suit every file
+/// system.
+fn file_name(taken: SystemTime) -> String {
+    humantime::format_rfc3339_millis(taken)
+        .to_string()
+        .replace(':', "-")
+}
+
+fn parse_name(name: &str) -> Option<SystemTime> {
+    let (date, time) = name.split_once('T')?;
+    humantime::parse_rfc3339(&format!("{date}T{}", time.replace('-', ":"))).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn file_names_round_trip_to_the_snapshot_time() {
+        let taken = humantime::parse_rfc3339("2026-10-16T09:12:44.123Z").unwrap();
+        assert_eq!(file_name(taken), "2026-10-16T09-12-44.123Z");
+        assert_eq!(parse_name(&file_name(taken)), Some(taken));
+        assert_eq!(parse_name("notes.txt"), None);
+    }
+}
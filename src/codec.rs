@@ -0,0 +1,333 @@
+//! Value codecs for env files meant to be committed. Each value is encoded
+//! on its own (`KEY=ENC[age:...]`), so the file keeps its lines, comments
+//! and local keys, merges like a plain one, and a git diff shows which keys
+//! changed.
+//!
+//! `output.encrypt` applies the same codec to the whole file instead (one
+//! ASCII-armored age file), so nothing on disk is plaintext; commands read
+//! and write the env file through `load_env_file` and `write_env_file`.
+
+use std::{env, fs, io, path::Path};
+
+#[cfg(feature = "local-vault")]
+use anyhow::anyhow;
+use anyhow::{Context, Result, bail};
+
+use crate::{
+    backup,
+    config::{self, CodecConfig, Config},
+    envfile, plaintext,
+};
+
+/// Overrides the codec's `identity_file`, since every team member keeps
+/// their own key.
+pub const IDENTITY_ENV: &str = "ENVIT_AGE_IDENTITY";
+
+pub trait Codec {
+    fn encode(&self, value: &str) -> Result<String>;
+    /// The value behind `encoded`; None when it is not an encoded value
+    /// (e.g. a local key added by hand).
+    fn decode(&self, encoded: &str) -> Result<Option<String>>;
+    /// Whether `decode` has a key to work with.
+    fn can_decode(&self) -> bool;
+}
+
+/// `setting` names the config entry in errors.
+pub fn validate(setting: &str, codec: &CodecConfig, fips: bool) -> Result<()> {
+    match codec {
+        CodecConfig::Age { recipients, .. } => {
+            if fips {
+                bail!("{setting} age is not allowed with crypto.fips = true (X25519, ChaCha20)");
+            }
+            if recipients.is_empty() {
+                bail!("{setting}.recipients must list at least one age recipient");
+            }
+            validate_age_recipients(recipients)
+                .with_context(|| format!("invalid {setting}.recipients"))
+        }
+    }
+}
+
+#[cfg(feature = "local-vault")]
+fn validate_age_recipients(recipients: &[String]) -> Result<()> {
+    for recipient in recipients {
+        if let Err(err) = recipient.parse::<age::x25519::Recipient>() {
+            bail!("invalid age recipient {recipient}: {err}");
+        }
+    }
+    Ok(())
+}
+
+#[cfg(not(feature = "local-vault"))]
+fn validate_age_recipients(_recipients: &[String]) -> Result<()> {
+    bail!("age needs a build with the local-vault feature")
+}
+
+/// Builds the configured codec. `identity` (or `IDENTITY_ENV`, or the
+/// config's `identity_file`) is only needed to decode.
+pub fn build(
+    config_path: &Path,
+    codec: &CodecConfig,
+    identity: Option<&Path>,
+) -> Result<Box<dyn Codec>> {
+    build_codec(config_path, codec, identity, false)
+}
+
+/// Like `build`, for `output.encrypt`: encodes a whole file as one
+/// ASCII-armored age file.
+pub fn build_file(
+    config_path: &Path,
+    codec: &CodecConfig,
+    identity: Option<&Path>,
+) -> Result<Box<dyn Codec>> {
+    build_codec(config_path, codec, identity, true)
+}
+
+fn build_codec(
+    config_path: &Path,
+    codec: &CodecConfig,
+    identity: Option<&Path>,
+    armored: bool,
+) -> Result<Box<dyn Codec>> {
+    match codec {
+        CodecConfig::Age {
+            recipients,
+            identity_file,
+        } => {
+            let identity = identity
+                .map(Path::to_path_buf)
+                .or_else(|| env::var_os(IDENTITY_ENV).map(Into::into))
+                .or_else(|| {
+                    identity_file
+                        .as_deref()
+                        .map(|path| config::resolve_path(config_path, path))
+                });
+            age_codec(recipients, identity.as_deref(), armored)
+        }
+    }
+}
+
+#[cfg(feature = "local-vault")]
+fn age_codec(
+    recipients: &[String],
+    identity: Option<&Path>,
+    armored: bool,
+) -> Result<Box<dyn Codec>> {
+    let codec = age_codec::AgeCodec::new(recipients, identity)?;
+    Ok(if armored {
+        Box::new(age_codec::ArmoredAgeCodec(codec))
+    } else {
+        Box::new(codec)
+    })
+}
+
+#[cfg(not(feature = "local-vault"))]
+fn age_codec(
+    _recipients: &[String],
+    _identity: Option<&Path>,
+    _armored: bool,
+) -> Result<Box<dyn Codec>> {
+    bail!("age encryption needs a build with the local-vault feature")
+}
+
+/// Whether `raw` is an ASCII-armored age file.
+pub fn is_armored(raw: &str) -> bool {
+    raw.starts_with("-----BEGIN AGE ENCRYPTED FILE-----")
+}
+
+/// The env file's content, decrypted under `output.encrypt` with
+/// `identity` (see `build`); None when there is no file. A plaintext file
+/// is returned as it is, so turning encryption on takes effect with the
+/// next write.
+pub fn read_env_file(
+    config_path: &Path,
+    cfg: &Config,
+    identity: Option<&Path>,
+) -> Result<Option<String>> {
+    let path = config::resolve_path(config_path, &cfg.output.env_file);
+    let raw = match fs::read_to_string(&path) {
+        Ok(raw) => raw,
+        Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(None),
+        Err(err) => {
+            return Err(err)
+                .with_context(|| format!("failed to read env file: {}", path.display()));
+        }
+    };
+    let Some(encrypt) = &cfg.output.encrypt else {
+        return Ok(Some(raw));
+    };
+    let plain = build_file(config_path, encrypt, identity)?
+        .decode(&raw)
+        .with_context(|| format!("failed to decrypt {}", path.display()))?;
+    Ok(Some(plain.unwrap_or(raw)))
+}
+
+/// `envfile::load_for_merge` for the configured env file, decrypted first
+/// under `output.encrypt`.
+pub(crate) fn load_env_file(
+    config_path: &Path,
+    cfg: &Config,
+    create_if_missing: bool,
+) -> Result<Vec<envfile::Line>> {
+    if cfg.output.encrypt.is_none() {
+        let path = config::resolve_path(config_path, &cfg.output.env_file);
+        return envfile::load_for_merge(&path, create_if_missing);
+    }
+    match read_env_file(config_path, cfg, None)? {
+        Some(raw) => Ok(envfile::parse_lines(&raw)),
+        None if create_if_missing => Ok(Vec::new()),
+        None => bail!(
+            "env file does not exist: {}",
+            config::resolve_path(config_path, &cfg.output.env_file).display()
+        ),
+    }
+}
+
+/// Writes the configured env file, encrypted under `output.encrypt`.
+pub fn write_env_file(config_path: &Path, cfg: &Config, content: &str) -> Result<()> {
+    plaintext::check_env_file(cfg)?;
+    let path = config::resolve_path(config_path, &cfg.output.env_file);
+    let content = match &cfg.output.encrypt {
+        Some(encrypt) => build_file(config_path, encrypt, None)?.encode(content)?,
+        None => content.to_string(),
+    };
+    backup::snapshot(config_path, cfg, &path)?;
+    envfile::write_atomic(&path, content)
+        .with_context(|| format!("failed to write {}", path.display()))
+}
+
+#[cfg(feature = "local-vault")]
+mod age_codec {
+    use age::{Identity, IdentityFile, Recipient};
+    use base64::{Engine, engine::general_purpose::STANDARD};
+
+    use super::*;
+    use crate::crypto;
+
+    const PREFIX: &str = "ENC[age:";
+
+    pub struct AgeCodec {
+        recipients: Vec<Box<dyn Recipient + Send>>,
+        identities: Vec<Box<dyn Identity>>,
+    }
+
+    impl AgeCodec {
+        pub fn new(recipients: &[String], identity: Option<&Path>) -> Result<Self> {
+            let recipients = recipients
+                .iter()
+                .map(|recipient| {
+                    let parsed: age::x25519::Recipient = recipient
+                        .parse()
+                        .map_err(|e| anyhow!("invalid age recipient {recipient}: {e}"))?;
+                    Ok(Box::new(parsed) as Box<dyn Recipient + Send>)
+                })
+                .collect::<Result<_>>()?;
+            let identities = match identity {
+                Some(path) => IdentityFile::from_file(path.display().to_string())
+                    .with_context(|| {
+                        format!("failed to read age identity file {}", path.display())
+                    })?
+                    .into_identities()
+                    .map_err(|e| anyhow!("unusable age identity: {e}"))?,
+                None => Vec::new(),
+            };
+            Ok(Self {
+                recipients,
+                identities,
+            })
+        }
+    }
+
+    impl Codec for AgeCodec {
+        fn encode(&self, value: &str) -> Result<String> {
+            let ciphertext = crypto::age_encrypt_bytes(&self.recipients, value.as_bytes())?;
+            Ok(format!("{PREFIX}{}]", STANDARD.encode(ciphertext)))
+        }
+
+        fn decode(&self, encoded: &str) -> Result<Option<String>> {
+            let Some(body) = encoded
+                .strip_prefix(PREFIX)
+                .and_then(|rest| rest.strip_suffix(']'))
+            else {
+                return Ok(None);
+            };
+            if !self.can_decode() {
+                bail!("no age identity to decrypt with (set {IDENTITY_ENV} or pass --identity)");
+            }
+            let ciphertext = STANDARD.decode(body).context("invalid encrypted value")?;
+            Ok(Some(crypto::age_decrypt_bytes(
+                &self.identities,
+                &ciphertext,
+            )?))
+        }
+
+        fn can_decode(&self) -> bool {
+            !self.identities.is_empty()
+        }
+    }
+
+    /// A whole file as one ASCII-armored age file.
+    pub struct ArmoredAgeCodec(pub AgeCodec);
+
+    impl Codec for ArmoredAgeCodec {
+        fn encode(&self, value: &str) -> Result<String> {
+            Ok(crypto::age_encrypt(&self.0.recipients, value)?)
+        }
+
+        fn decode(&self, encoded: &str) -> Result<Option<String>> {
+            if !is_armored(encoded) {
+                return Ok(None);
+            }
+            if !self.can_decode() {
+                bail!("no age identity to decrypt with (set {IDENTITY_ENV} or pass --identity)");
+            }
+            Ok(Some(crypto::age_decrypt(
+                &self.0.identities,
+                encoded.as_bytes(),
+            )?))
+        }
+
+        fn can_decode(&self) -> bool {
+            self.0.can_decode()
+        }
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use age::secrecy::ExposeSecret;
+
+        use super::*;
+
+        #[test]
+        fn values_round_trip_and_plain_values_are_left_alone() {
+            let key = age::x25519::Identity::generate();
+            let dir = tempfile::TempDir::new().unwrap();
+            let identity = dir.path().join("key.txt");
+            std::fs::write(&identity, key.to_string().expose_secret()).unwrap();
+            let recipients = [key.to_public().to_string()];
+
+            let codec = AgeCodec::new(&recipients, Some(&identity)).unwrap();
+            let encoded = codec.encode("postgres://a:b@h/db").unwrap();
+            assert!(encoded.starts_with("ENC[age:") && !encoded.contains("postgres"));
+            assert_ne!(codec.encode("postgres://a:b@h/db").unwrap(), encoded);
+            assert_eq!(
+                codec.decode(&encoded).unwrap().as_deref(),
+                Some("postgres://a:b@h/db")
+            );
+            assert_eq!(codec.decode("plain").unwrap(), None);
+
+            let encrypt_only = AgeCodec::new(&recipients, None).unwrap();
+            assert!(!encrypt_only.can_decode());
+            assert!(encrypt_only.decode(&encoded).is_err());
+
+            let file = ArmoredAgeCodec(codec);
+            let encrypted = file.encode("A=1\n# note\nB=2\n").unwrap();
+            assert!(is_armored(&encrypted) && !encrypted.contains("A=1"));
+            assert_eq!(
+                file.decode(&encrypted).unwrap().as_deref(),
+                Some("A=1\n# note\nB=2\n")
+            );
+            assert_eq!(file.decode("A=1\n").unwrap(), None);
+        }
+    }
+}
@@ -0,0 +1,384 @@
+use std::{
+    collections::HashMap,
+    env,
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+use anyhow::{Result, anyhow};
+use serde::{Deserialize, Serialize};
+
+use crate::lock::Lock;
+
+pub const SOCKET_ENV: &str = "ENVIT_AGENT_SOCK";
+
+/// One request per connection, as a JSON line; the reply is one JSON line.
+#[cfg_attr(not(unix), allow(dead_code))]
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(tag = "op", rename_all = "snake_case")]
+enum Request {
+    Resolve {
+        config: PathBuf,
+        /// Pins the values to this lock, as `lock::Mode::Use` does.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        lock: Option<Lock>,
+    },
+    Stop,
+}
+
+#[cfg_attr(not(unix), allow(dead_code))]
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct Reply {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    principal: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    values: Option<HashMap<String, String>>,
+    /// The lock the values match, for a request that sent one.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    lock: Option<Lock>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    error: Option<String>,
+}
+
+pub fn socket_from_env() -> Option<PathBuf> {
+    if !cfg!(unix) {
+        return None;
+    }
+    env::var_os(SOCKET_ENV)
+        .filter(|path| !path.is_empty())
+        .map(PathBuf::from)
+}
+
+/// `$XDG_RUNTIME_DIR/envit-agent.sock`, or a per-user directory under the
+/// system temp dir.
+pub fn default_socket() -> PathBuf {
+    match env::var_os("XDG_RUNTIME_DIR").filter(|dir| !dir.is_empty()) {
+        Some(dir) => PathBuf::from(dir).join("envit-agent.sock"),
+        None => {
+            let user = env::var("USER").unwrap_or_else(|_| "user".to_string());
+            env::temp_dir()
+                .join(format!("envit-agent-{user}"))
+                .join("agent.sock")
+        }
+    }
+}
+
+#[cfg(unix)]
+pub use unix::{resolve, resolve_locked, run, start, stop};
+
+#[cfg(not(unix))]
+pub async fn resolve(
+    _socket: &Path,
+    _config_path: &Path,
+) -> Result<(Option<String>, Result<HashMap<String, String>>)> {
+    anyhow::bail!("envit agent needs unix domain sockets")
+}
+
+#[cfg(not(unix))]
+pub async fn resolve_locked(
+    _socket: &Path,
+    _config_path: &Path,
+    _lock: &Lock,
+) -> Result<(Option<String>, Result<(HashMap<String, String>, Lock)>)> {
+    anyhow::bail!("envit agent needs unix domain sockets")
+}
+
+#[cfg(not(unix))]
+pub async fn run(_socket: &Path, _idle_timeout: Duration, _cache_ttl: Duration) -> Result<()> {
+    anyhow::bail!("envit agent needs unix domain sockets")
+}
+
+#[cfg(not(unix))]
+pub async fn start(_socket: &Path, _idle_timeout: Duration, _cache_ttl: Duration) -> Result<()> {
+    anyhow::bail!("envit agent needs unix domain sockets")
+}
+
+#[cfg(not(unix))]
+pub async fn stop(_socket: &Path) -> Result<()> {
+    anyhow::bail!("envit agent needs unix domain sockets")
+}
+
+#[cfg(unix)]
+mod unix {
+    use std::{
+        fs,
+        os::unix::fs::{DirBuilderExt, MetadataExt, PermissionsExt},
+        process::{Command, Stdio},
+        time::Instant,
+    };
+
+    use anyhow::{Context, bail};
+    use rustix::{fs::Mode, process};
+    use tokio::{
+        io::{AsyncBufReadExt, AsyncWriteExt, BufReader},
+        net::{UnixListener, UnixStream},
+    };
+
+    use super::*;
+    use crate::{
+        config, lock,
+        messages::message,
+        provider::cache::{self, ProviderRegistry},
+        resolve as resolver,
+    };
+
+    /// Provider connections (and with them tokens and fetched values) shared
+    /// by every request until `cache_ttl` has passed. Requests are served one
+    /// at a time.
+    struct Session {
+        providers: ProviderRegistry,
+        started: Instant,
+    }
+
+    /// Serves requests on `socket` until stopped or idle for `idle_timeout`.
+    pub async fn run(socket: &Path, idle_timeout: Duration, cache_ttl: Duration) -> Result<()> {
+        let listener = bind(socket)?;
+        eprintln!("envit agent listening on {}", socket.display());
+
+        let mut session = Session {
+            providers: ProviderRegistry::default(),
+            started: Instant::now(),
+        };
+        loop {
+            let stream = match tokio::time::timeout(idle_timeout, listener.accept()).await {
+                Ok(accepted) => accepted.context("failed to accept agent connection")?.0,
+                Err(_) => {
+                    eprintln!("envit agent idle, exiting");
+                    break;
+                }
+            };
+            match handle(stream, &mut session, cache_ttl).await {
+                Ok(true) => break,
+                Ok(false) => {}
+                Err(err) => eprintln!("envit agent: {err:#}"),
+            }
+        }
+        let _ = fs::remove_file(socket);
+        Ok(())
+    }
+
+    /// Starts the agent in the background and prints the shell line that
+    /// points later invocations at it, like `ssh-agent`.
+    pub async fn start(socket: &Path, idle_timeout: Duration, cache_ttl: Duration) -> Result<()> {
+        if UnixStream::connect(socket).await.is_ok() {
+            println!("{SOCKET_ENV}={}; export {SOCKET_ENV};", socket.display());
+            return Ok(());
+        }
+        Command::new(env::current_exe().context("failed to locate the envit binary")?)
+            .args(["agent", "--foreground", "--socket"])
+            .arg(socket)
+            .arg("--idle-timeout")
+            .arg(humantime::format_duration(idle_timeout).to_string())
+            .arg("--cache-ttl")
+            .arg(humantime::format_duration(cache_ttl).to_string())
+            .stdin(Stdio::null())
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .spawn()
+            .context("failed to start envit agent")?;
+
+        for _ in 0..50 {
+            if UnixStream::connect(socket).await.is_ok() {
+                println!("{SOCKET_ENV}={}; export {SOCKET_ENV};", socket.display());
+                return Ok(());
+            }
+            tokio::time::sleep(Duration::from_millis(100)).await;
+        }
+        bail!("envit agent did not come up on {}", socket.display())
+    }
+
+    pub async fn stop(socket: &Path) -> Result<()> {
+        request(socket, &Request::Stop).await?;
+        println!("{}", message!("agent_stopped", socket = socket.display()));
+        Ok(())
+    }
+
+    /// Asks the agent to resolve a config. `Err` means the agent could not
+    /// be reached; provider failures come back in the inner result.
+    pub async fn resolve(
+        socket: &Path,
+        config_path: &Path,
+    ) -> Result<(Option<String>, Result<HashMap<String, String>>)> {
+        let config = fs::canonicalize(config_path)
+            .with_context(|| format!("failed to resolve {}", config_path.display()))?;
+        let reply = request(socket, &Request::Resolve { config, lock: None }).await?;
+        let values = match (reply.values, reply.error) {
+            (_, Some(error)) => Err(anyhow!("{error}")),
+            (Some(values), None) => Ok(values),
+            (None, None) => Err(anyhow!("envit agent sent an empty reply")),
+        };
+        Ok((reply.principal, values))
+    }
+
+    /// Like `resolve`, pinned to `lock`; also returns the lock the values
+    /// match.
+    pub async fn resolve_locked(
+        socket: &Path,
+        config_path: &Path,
+        lock: &Lock,
+    ) -> Result<(Option<String>, Result<(HashMap<String, String>, Lock)>)> {
+        let config = fs::canonicalize(config_path)
+            .with_context(|| format!("failed to resolve {}", config_path.display()))?;
+        let lock = Some(lock.clone());
+        let reply = request(socket, &Request::Resolve { config, lock }).await?;
+        let resolved = match (reply.values, reply.lock, reply.error) {
+            (_, _, Some(error)) => Err(anyhow!("{error}")),
+            (Some(values), Some(lock), None) => Ok((values, lock)),
+            _ => Err(anyhow!("envit agent sent an empty reply")),
+        };
+        Ok((reply.principal, resolved))
+    }
+
+    async fn request(socket: &Path, request: &Request) -> Result<Reply> {
+        let mut stream = UnixStream::connect(socket)
+            .await
+            .with_context(|| format!("failed to connect to {}", socket.display()))?;
+        let mut line = serde_json::to_string(request)?;
+        line.push('\n');
+        stream.write_all(line.as_bytes()).await?;
+
+        let mut reply = String::new();
+        BufReader::new(stream).read_line(&mut reply).await?;
+        serde_json::from_str(&reply).context("invalid reply from envit agent")
+    }
+
+    /// The socket (and a directory created for it) is private to the user;
+    /// a stale socket from a crashed agent is replaced.
+    fn bind(socket: &Path) -> Result<UnixListener> {
+        if let Some(dir) = socket.parent().filter(|d| !d.as_os_str().is_empty()) {
+            if !dir.exists() {
+                fs::DirBuilder::new()
+                    .recursive(true)
+                    .mode(0o700)
+                    .create(dir)
+                    .with_context(|| format!("failed to create {}", dir.display()))?;
+            }
+            ensure_private(dir)?;
+        }
+        if socket.exists() {
+            if std::os::unix::net::UnixStream::connect(socket).is_ok() {
+                bail!(
+                    "an envit agent is already listening on {}",
+                    socket.display()
+                );
+            }
+            fs::remove_file(socket)
+                .with_context(|| format!("failed to remove stale {}", socket.display()))?;
+        }
+        // The socket is created with the umask's mode; narrow it first so
+        // there is no window in which others can connect.
+        let umask = process::umask(Mode::from_raw_mode(0o177));
+        let listener = UnixListener::bind(socket);
+        process::umask(umask);
+        let listener =
+            listener.with_context(|| format!("failed to listen on {}", socket.display()))?;
+        fs::set_permissions(socket, fs::Permissions::from_mode(0o600))?;
+        Ok(listener)
+    }
+
+    /// Refuses a socket directory someone else could have planted: it must
+    /// be a real directory, owned by us, that no one else can enter.
+    fn ensure_private(dir: &Path) -> Result<()> {
+        let meta = fs::symlink_metadata(dir)
+            .with_context(|| format!("failed to inspect {}", dir.display()))?;
+        if !meta.is_dir() {
+            bail!("{} is not a directory", dir.display());
+        }
+        if meta.uid() != process::getuid().as_raw() {
+            bail!(
+                "{} is owned by another user; refusing to listen in it",
+                dir.display()
+            );
+        }
+        if meta.mode() & 0o777 != 0o700 {
+            bail!(
+                "{} has mode {:o}, expected 700; refusing to listen in it",
+                dir.display(),
+                meta.mode() & 0o777
+            );
+        }
+        Ok(())
+    }
+
+    /// Returns whether the agent should stop.
+    async fn handle(
+        stream: UnixStream,
+        session: &mut Session,
+        cache_ttl: Duration,
+    ) -> Result<bool> {
+        let (read, mut write) = stream.into_split();
+        let mut line = String::new();
+        BufReader::new(read).read_line(&mut line).await?;
+
+        let (reply, stop) = match serde_json::from_str(&line) {
+            Ok(Request::Resolve { config, lock }) => (
+                resolve_config(&config, lock.as_ref(), session, cache_ttl).await,
+                false,
+            ),
+            Ok(Request::Stop) => (Reply::default(), true),
+            Err(err) => (
+                Reply {
+                    error: Some(format!("invalid request: {err}")),
+                    ..Reply::default()
+                },
+                false,
+            ),
+        };
+        let mut out = serde_json::to_string(&reply)?;
+        out.push('\n');
+        write.write_all(out.as_bytes()).await?;
+        Ok(stop)
+    }
+
+    async fn resolve_config(
+        config_path: &Path,
+        lock: Option<&Lock>,
+        session: &mut Session,
+        cache_ttl: Duration,
+    ) -> Reply {
+        let cfg = match config::load(config_path) {
+            Ok(cfg) => cfg,
+            Err(err) => {
+                return Reply {
+                    error: Some(format!("{err:#}")),
+                    ..Reply::default()
+                };
+            }
+        };
+        if session.started.elapsed() >= cache_ttl {
+            session.providers = ProviderRegistry::default();
+            session.started = Instant::now();
+        }
+        let connected = match session.providers.connect(&cfg) {
+            Ok(connected) => connected,
+            Err(err) => {
+                return Reply {
+                    error: Some(format!("{err:#}")),
+                    ..Reply::default()
+                };
+            }
+        };
+        let principal = cache::principals(&connected).await;
+        let resolved = match lock {
+            Some(lock) => lock::resolve(&connected, lock, lock::Mode::Use)
+                .await
+                .map(|(values, next)| (values, Some(next))),
+            None => resolver::resolve_all(&connected)
+                .await
+                .map(|values| (values, None)),
+        };
+        match resolved {
+            Ok((values, lock)) => Reply {
+                principal,
+                values: Some(values),
+                lock,
+                error: None,
+            },
+            Err(err) => Reply {
+                principal,
+                error: Some(format!("{err:#}")),
+                ..Reply::default()
+            },
+        }
+    }
+}
@@ -0,0 +1,80 @@
+use std::{
+    env, fs, io,
+    path::{Path, PathBuf},
+};
+
+use anyhow::{Context, Result, bail};
+use sha2::{Digest, Sha256};
+
+use crate::{crypto, messages::message};
+
+/// Where allowed configs are recorded: `$XDG_DATA_HOME/envit/allow`, else
+/// `~/.local/share/envit/allow`. One file per config path, named after a
+/// hash of the path and holding a hash of the content it was allowed with.
+fn allow_dir() -> Result<PathBuf> {
+    let data = match env::var_os("XDG_DATA_HOME").filter(|dir| !dir.is_empty()) {
+        Some(dir) => PathBuf::from(dir),
+        None => env::var_os("HOME")
+            .map(|home| PathBuf::from(home).join(".local/share"))
+            .context("HOME is not set")?,
+    };
+    Ok(data.join("envit").join("allow"))
+}
+
+/// The record for `config_path` and the hash of its current content.
+fn record(config_path: &Path) -> Result<(PathBuf, String)> {
+    let path = fs::canonicalize(config_path)
+        .with_context(|| format!("failed to resolve {}", config_path.display()))?;
+    let content = fs::read(&path).with_context(|| format!("failed to read {}", path.display()))?;
+    let name = crypto::to_hex(&Sha256::digest(path.as_os_str().as_encoded_bytes()));
+    Ok((
+        allow_dir()?.join(name),
+        crypto::to_hex(&Sha256::digest(content)),
+    ))
+}
+
+/// `envit allow`: trusts the config as it is now, so `envit hook` acts on
+/// it. A config runs code (`exec` providers, `[[hooks]]` commands), so a
+/// freshly cloned one is not acted on until someone has read it. `revoke`
+/// withdraws the trust.
+pub fn run(config_path: &Path, revoke: bool) -> Result<()> {
+    let (record, hash) = record(config_path)?;
+    if revoke {
+        match fs::remove_file(&record) {
+            Ok(()) => {}
+            Err(err) if err.kind() == io::ErrorKind::NotFound => {}
+            Err(err) => {
+                return Err(err).with_context(|| format!("failed to remove {}", record.display()));
+            }
+        }
+        println!(
+            "{}",
+            message!("allow_revoked", path = config_path.display())
+        );
+        return Ok(());
+    }
+    let dir = record.parent().expect("records live in the allow dir");
+    fs::create_dir_all(dir).with_context(|| format!("failed to create {}", dir.display()))?;
+    fs::write(&record, format!("{hash}\n"))
+        .with_context(|| format!("failed to write {}", record.display()))?;
+    println!("{}", message!("allow_done", path = config_path.display()));
+    Ok(())
+}
+
+/// `envit allow --check`, run by the hook: fails unless the config was
+/// allowed with its current content.
+pub fn check(config_path: &Path) -> Result<()> {
+    let (record, hash) = record(config_path)?;
+    match fs::read_to_string(&record) {
+        Ok(allowed) if allowed.trim() == hash => Ok(()),
+        Ok(_) => bail!(
+            "{} changed since it was allowed; review it and run `envit allow`",
+            config_path.display()
+        ),
+        Err(err) if err.kind() == io::ErrorKind::NotFound => bail!(
+            "{} is not allowed; review it and run `envit allow`",
+            config_path.display()
+        ),
+        Err(err) => Err(err).with_context(|| format!("failed to read {}", record.display())),
+    }
+}
@@ -0,0 +1,124 @@
+use std::{
+    collections::{BTreeSet, HashMap},
+    env, fs,
+    path::Path,
+};
+
+use anyhow::{Context, Result, bail};
+
+use crate::{
+    classify,
+    commands::pull::{self, CANARY_KEY, PULLED_KEYS_PATH},
+    config::{self, Config},
+    messages::message,
+    owners,
+};
+
+/// Checks that a process's environment (`self`: envit's own, i.e. the
+/// calling shell's) has every key the config requires, in a valid format,
+/// as a pre-start gate for entrypoints. Nothing is written and no provider
+/// is contacted. Every problem is reported, not just the first.
+pub fn run(config_path: &Path, process: &str) -> Result<()> {
+    let cfg = config::load(config_path)?;
+    let environment = process_env(process)?;
+    let required = required_keys(config_path, &cfg)?;
+    if required.is_empty() {
+        bail!("no required keys: [map] is empty and nothing has been pulled yet");
+    }
+    let problems = check(config_path, &cfg, &required, &environment);
+    if problems.is_empty() {
+        println!(
+            "{}",
+            message!("assert_ok", count = required.len(), process = process)
+        );
+        return Ok(());
+    }
+    for problem in &problems {
+        eprintln!("error: {problem}");
+    }
+    bail!(
+        "{} problem(s) in the environment of process {process}",
+        problems.len()
+    );
+}
+
+/// The keys a pulled environment has: every `[map]` key (with its source's
+/// prefix) and the keys the last pull recorded, except the canary, which
+/// `envit run` does not pass on.
+pub fn required_keys(config_path: &Path, cfg: &Config) -> Result<BTreeSet<String>> {
+    let mut keys: BTreeSet<_> = cfg
+        .sources()
+        .into_iter()
+        .flat_map(|source| {
+            source
+                .map
+                .keys()
+                .map(move |key| format!("{}{key}", source.prefix))
+        })
+        .collect();
+    keys.extend(pull::read_pulled_keys(&config::resolve_path(
+        config_path,
+        PULLED_KEYS_PATH,
+    ))?);
+    keys.remove(CANARY_KEY);
+    Ok(keys)
+}
+
+/// What is wrong with `environment` against `required`: missing or empty
+/// keys, values that do not fit their `[classify]` class, and
+/// `[output.files]` paths that do not exist (relative ones resolved against
+/// the config). Empty when it is fine to start.
+pub fn check(
+    config_path: &Path,
+    cfg: &Config,
+    required: &BTreeSet<String>,
+    environment: &HashMap<String, String>,
+) -> Vec<String> {
+    let mut problems = Vec::new();
+    for key in required {
+        let Some(value) = environment.get(key) else {
+            problems.push(format!("{key} is not set"));
+            continue;
+        };
+        if value.is_empty() {
+            problems.push(format!("{key} is empty"));
+        } else if cfg.output.files.contains_key(key) {
+            if !config::resolve_path(config_path, value).exists() {
+                problems.push(format!("{key}: {value} does not exist"));
+            }
+        } else if let Some(&class) = owners::best_match(&cfg.classify, key)
+            && !classify::conforms(class, value)
+        {
+            problems.push(format!("{key} is not a valid {class}"));
+        }
+    }
+    problems
+}
+
+/// The environment of `process`, a pid (read from `/proc/<pid>/environ`)
+/// or `self`.
+fn process_env(process: &str) -> Result<HashMap<String, String>> {
+    if process == "self" {
+        return Ok(env::vars_os()
+            .map(|(key, value)| {
+                (
+                    key.to_string_lossy().into_owned(),
+                    value.to_string_lossy().into_owned(),
+                )
+            })
+            .collect());
+    }
+    let pid: u32 = process
+        .parse()
+        .with_context(|| format!("--env-from-process takes a pid or self, not {process}"))?;
+    let path = format!("/proc/{pid}/environ");
+    let raw = fs::read(&path).with_context(|| format!("failed to read {path}"))?;
+    Ok(raw
+        .split(|byte| *byte == 0)
+        .filter_map(|entry| {
+            let entry = String::from_utf8_lossy(entry);
+            let (key, value) = entry.split_once('=')?;
+            Some((key.to_string(), value.to_string()))
+        })
+        .collect())
+}
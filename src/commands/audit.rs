@@ -0,0 +1,206 @@
+use std::{collections::HashMap, fs, path::Path, sync::Arc, time::SystemTime};
+
+use anyhow::{Context, Result};
+use clap::ValueEnum;
+use serde_json::{Value, json};
+
+use crate::{
+    audit,
+    classify::{self, Class},
+    config::{self, Config, Source},
+    provider::{
+        SecretDetails, SecretProvider,
+        cache::{self, CachedProvider, ProviderRegistry},
+    },
+    resolve,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum Format {
+    /// Columns aligned under a header, dates only
+    Table,
+    /// One JSON array of objects with RFC 3339 times (null when unknown)
+    Json,
+}
+
+struct Row {
+    /// Index into `cfg.sources()`.
+    source: usize,
+    details: SecretDetails,
+    class: Option<Class>,
+    last_rotated: Option<SystemTime>,
+}
+
+/// Reports what the providers keep about each secret (created, updated,
+/// enabled, expiry), its class by env key (see `crate::classify`) and when
+/// `envit rotate` last rotated it according to the audit log, for periodic
+/// hygiene reviews. No values are fetched.
+pub async fn run(config_path: &Path, format: Format) -> Result<()> {
+    let cfg = config::load(config_path)?;
+    let mut providers = ProviderRegistry::default();
+    let connected = providers.connect(&cfg)?;
+    let principal = cache::principals(&connected).await;
+
+    let result = details(&connected).await;
+
+    let mut entry = audit::Entry::new("audit");
+    entry.principal = principal.as_deref();
+    entry.dry_run = true;
+    entry.error = result.as_ref().err().map(|err| format!("{err:#}"));
+    audit::record(config_path, &cfg, &entry)?;
+    let rotations = last_rotations(config_path, &cfg);
+    let sources = cfg.sources();
+    let mut rows: Vec<_> = result?
+        .into_iter()
+        .map(|(source, details)| {
+            let env_key = resolve::env_key(sources[source], &details.name);
+            Row {
+                source,
+                class: classify::class(&cfg.classify, &env_key, None),
+                last_rotated: rotations.get(&details.name).copied(),
+                details,
+            }
+        })
+        .collect();
+    rows.sort_by(|a, b| (a.source, &a.details.name).cmp(&(b.source, &b.details.name)));
+
+    match format {
+        Format::Json => {
+            let time = |time: Option<SystemTime>| {
+                time.map(|time| humantime::format_rfc3339_seconds(time).to_string())
+            };
+            let rows: Vec<Value> = rows
+                .iter()
+                .map(|row| {
+                    json!({
+                        "secret": row.details.name,
+                        "provider": row.source + 1,
+                        "kind": sources[row.source].provider.kind,
+                        "class": row.class,
+                        "enabled": row.details.enabled,
+                        "created": time(row.details.created),
+                        "updated": time(row.details.updated),
+                        "expires": time(row.details.expires),
+                        "last_rotated": time(row.last_rotated),
+                    })
+                })
+                .collect();
+            println!("{}", serde_json::to_string_pretty(&rows)?);
+        }
+        Format::Table => print_table(&rows, sources.len() > 1),
+    }
+    Ok(())
+}
+
+/// Every secret's details with the index of its source, in source order.
+pub async fn details(
+    connected: &[(Source<'_>, Arc<CachedProvider>)],
+) -> Result<Vec<(usize, SecretDetails)>> {
+    let mut all = Vec::new();
+    for (index, (source, provider)) in connected.iter().enumerate() {
+        let details = provider
+            .secret_details()
+            .await
+            .with_context(|| format!("failed to list secrets of {}", source.provider.kind))?;
+        all.extend(details.into_iter().map(|details| (index, details)));
+    }
+    Ok(all)
+}
+
+fn print_table(rows: &[Row], by_provider: bool) {
+    let date = |time: Option<SystemTime>| match time {
+        Some(time) => humantime::format_rfc3339_seconds(time).to_string()[..10].to_string(),
+        None => "-".to_string(),
+    };
+    let mut header = vec!["SECRET"];
+    if by_provider {
+        header.push("PROVIDER");
+    }
+    header.extend([
+        "CLASS",
+        "ENABLED",
+        "CREATED",
+        "UPDATED",
+        "EXPIRES",
+        "LAST ROTATED",
+    ]);
+    let header: Vec<String> = header.into_iter().map(str::to_string).collect();
+    let cells: Vec<Vec<String>> = rows
+        .iter()
+        .map(|row| {
+            let mut cells = vec![row.details.name.clone()];
+            if by_provider {
+                cells.push((row.source + 1).to_string());
+            }
+            cells.extend([
+                row.class.map_or("-".to_string(), |class| class.to_string()),
+                match row.details.enabled {
+                    Some(true) => "yes".to_string(),
+                    Some(false) => "no".to_string(),
+                    None => "-".to_string(),
+                },
+                date(row.details.created),
+                date(row.details.updated),
+                date(row.details.expires),
+                date(row.last_rotated),
+            ]);
+            cells
+        })
+        .collect();
+
+    print_aligned(&header, &cells);
+}
+
+/// Prints `header` and `cells` as left-aligned columns two spaces apart.
+pub fn print_aligned(header: &[String], cells: &[Vec<String>]) {
+    let widths: Vec<_> = (0..header.len())
+        .map(|col| {
+            cells
+                .iter()
+                .map(Vec::as_slice)
+                .chain([header])
+                .map(|row| row[col].len())
+                .max()
+                .unwrap_or_default()
+        })
+        .collect();
+    for row in [header].into_iter().chain(cells.iter().map(Vec::as_slice)) {
+        let line: Vec<_> = row
+            .iter()
+            .zip(&widths)
+            .map(|(cell, width)| format!("{cell:<width$}"))
+            .collect();
+        println!("{}", line.join("  ").trim_end());
+    }
+}
+
+/// When each secret was last rotated by `envit rotate`, from successful
+/// `rotate` entries of the audit log; empty without one.
+fn last_rotations(config_path: &Path, cfg: &Config) -> HashMap<String, SystemTime> {
+    let mut rotations = HashMap::new();
+    let Some(audit_path) = &cfg.audit.path else {
+        return rotations;
+    };
+    let Ok(raw) = fs::read_to_string(config::resolve_path(config_path, audit_path)) else {
+        return rotations;
+    };
+    let entries = raw
+        .lines()
+        .filter_map(|line| serde_json::from_str::<Value>(line).ok())
+        .filter(|entry| entry["command"] == "rotate" && entry["error"].is_null());
+    for entry in entries {
+        let Some(at) = entry["timestamp"]
+            .as_str()
+            .and_then(|at| humantime::parse_rfc3339(at).ok())
+        else {
+            continue;
+        };
+        for secret in entry["changed_keys"].as_array().into_iter().flatten() {
+            if let Some(secret) = secret.as_str() {
+                let last = rotations.entry(secret.to_string()).or_insert(at);
+                *last = (*last).max(at);
+            }
+        }
+    }
+    rotations
+}
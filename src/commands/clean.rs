@@ -0,0 +1,50 @@
+use std::path::Path;
+
+use anyhow::Result;
+
+use crate::{
+    audit, codec,
+    commands::pull::{self, PULLED_KEYS_PATH},
+    config, envfile,
+    messages::message,
+};
+
+/// Removes every key envit manages from the env file: those recorded in
+/// `PULLED_KEYS_PATH` (the canary and `[output.files]` paths included) and
+/// deprecated ones still kept. Local-only keys and comments stay, and no
+/// provider is contacted.
+pub fn run(config_path: &Path, dry_run: bool) -> Result<()> {
+    let cfg = config::load(config_path)?;
+    let env_path = config::resolve_path(config_path, &cfg.output.env_file);
+    let pulled_keys = config::resolve_path(config_path, PULLED_KEYS_PATH);
+    let mut managed = pull::read_pulled_keys(&pulled_keys)?;
+
+    let lines = codec::load_env_file(config_path, &cfg, true)?;
+    managed.extend(envfile::deprecated_keys(&lines));
+    let (lines, removed) = envfile::remove(lines, |key| managed.contains(key));
+
+    let mut entry = audit::Entry::new("clean");
+    entry.env_file = Some(env_path.display().to_string());
+    entry.dry_run = dry_run;
+    entry.changed_keys = removed.iter().map(String::as_str).collect();
+    audit::record(config_path, &cfg, &entry)?;
+
+    for key in &removed {
+        if dry_run {
+            println!("{}", message!("clean_would", key = key));
+        } else {
+            println!("{}", message!("clean_done", key = key));
+        }
+    }
+    if removed.is_empty() {
+        println!("{}", message!("clean_nothing"));
+    }
+    if dry_run {
+        return Ok(());
+    }
+    if !removed.is_empty() {
+        let (content, _) = envfile::merge(lines, &Default::default());
+        codec::write_env_file(config_path, &cfg, &envfile::render(&content))?;
+    }
+    pull::write_pulled_keys(&pulled_keys, [])
+}
@@ -0,0 +1,153 @@
+use std::{
+    collections::BTreeMap,
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+use anyhow::{Context, Result, bail};
+
+use crate::{
+    audit,
+    config::{self, Source},
+    messages::message,
+    provider::cache::{self, CachedProvider, ProviderRegistry},
+    resolve,
+};
+
+/// The config a profile names: `staging` is `envit.staging.toml`, anything
+/// with an extension or a directory is a config path as it is.
+pub fn profile_path(profile: &str) -> PathBuf {
+    let path = Path::new(profile);
+    if path.extension().is_some() || path.components().count() > 1 {
+        path.to_path_buf()
+    } else {
+        PathBuf::from(format!("envit.{profile}.toml"))
+    }
+}
+
+/// The profile a config path is, the other way round: `envit.staging.toml`
+/// is `staging`, `envit.toml` is `default`, any other config its file stem.
+pub fn profile_name(config_path: &Path) -> String {
+    let name = config_path
+        .file_name()
+        .and_then(|name| name.to_str())
+        .unwrap_or_default();
+    match name
+        .strip_prefix("envit.")
+        .and_then(|rest| rest.strip_suffix(".toml"))
+    {
+        Some(profile) => profile.to_string(),
+        None if name == "envit.toml" => "default".to_string(),
+        None => config_path
+            .file_stem()
+            .map(|stem| stem.to_string_lossy().into_owned())
+            .unwrap_or_default(),
+    }
+}
+
+/// Compares the keys two profiles pull and, with `values`, whether the keys
+/// both have hold the same value. Values are only compared in memory, never
+/// printed. Returns whether the key sets differ.
+pub async fn run(profiles: &[String], values: bool) -> Result<bool> {
+    let [left, right] = profiles else {
+        bail!("compare takes exactly two --profile options");
+    };
+    let left_keys = load(left, values).await?;
+    let right_keys = load(right, values).await?;
+
+    let only_left: Vec<_> = left_keys
+        .keys()
+        .filter(|key| !right_keys.contains_key(*key))
+        .collect();
+    let only_right: Vec<_> = right_keys
+        .keys()
+        .filter(|key| !left_keys.contains_key(*key))
+        .collect();
+    let mut same = Vec::new();
+    let mut different = Vec::new();
+    for (key, value) in &left_keys {
+        match right_keys.get(key) {
+            Some(other) if other == value => same.push(key),
+            Some(_) => different.push(key),
+            None => {}
+        }
+    }
+
+    print_group(
+        &message!("compare_only_in", profile = left, count = only_left.len()),
+        &only_left,
+    );
+    print_group(
+        &message!("compare_only_in", profile = right, count = only_right.len()),
+        &only_right,
+    );
+    if values {
+        print_group(&message!("compare_same", count = same.len()), &same);
+        print_group(
+            &message!("compare_different", count = different.len()),
+            &different,
+        );
+    } else {
+        println!(
+            "{}",
+            message!("compare_in_both", count = same.len() + different.len())
+        );
+    }
+    let differs = !only_left.is_empty() || !only_right.is_empty();
+    if !differs {
+        println!(
+            "{}",
+            message!("compare_same_keys", left = left, right = right)
+        );
+    }
+    Ok(differs)
+}
+
+fn print_group(title: &str, keys: &[&String]) {
+    if keys.is_empty() {
+        return;
+    }
+    println!("{title}");
+    for key in keys {
+        println!("  {key}");
+    }
+}
+
+/// A profile's env keys, with their values when `values` is set (None
+/// otherwise, so only names are listed).
+async fn load(profile: &str, values: bool) -> Result<BTreeMap<String, Option<String>>> {
+    let config_path = profile_path(profile);
+    let cfg = config::load(&config_path).with_context(|| format!("profile {profile}"))?;
+    let mut providers = ProviderRegistry::default();
+    let connected = providers.connect(&cfg)?;
+    let principal = cache::principals(&connected).await;
+    let result = if values {
+        resolve::resolve_all(&connected).await.map(|resolved| {
+            resolved
+                .into_iter()
+                .map(|(key, value)| (key, Some(value)))
+                .collect()
+        })
+    } else {
+        key_names(&connected).await
+    };
+
+    let mut entry = audit::Entry::new("compare");
+    entry.principal = principal.as_deref();
+    entry.dry_run = true;
+    entry.error = result.as_ref().err().map(|err| format!("{err:#}"));
+    audit::record(&config_path, &cfg, &entry)?;
+    result.with_context(|| format!("profile {profile}"))
+}
+
+async fn key_names(
+    connected: &[(Source<'_>, Arc<CachedProvider>)],
+) -> Result<BTreeMap<String, Option<String>>> {
+    let mut keys = BTreeMap::new();
+    for (source, provider) in connected {
+        for (_, key) in resolve::keys(*source, provider.as_ref()).await? {
+            keys.insert(key, None);
+        }
+    }
+    Ok(keys)
+}
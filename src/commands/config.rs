@@ -0,0 +1,139 @@
+use std::{
+    path::{Component, Path, PathBuf},
+    process::{Command, Stdio},
+};
+
+use anyhow::Result;
+
+use crate::{
+    config::{self, Config},
+    messages::message,
+};
+
+/// Flags risky but valid setups. Nothing is contacted or changed. Returns
+/// whether anything was flagged.
+pub fn lint(config_path: &Path) -> Result<bool> {
+    let cfg = config::load(config_path)?;
+    let findings = findings(config_path, &cfg);
+    for finding in &findings {
+        println!("warning: {finding}");
+    }
+    if findings.is_empty() {
+        println!("{}", message!("lint_clean", path = config_path.display()));
+    }
+    Ok(!findings.is_empty())
+}
+
+fn findings(config_path: &Path, cfg: &Config) -> Vec<String> {
+    let mut findings = Vec::new();
+    let config_dir = normalize(&config::resolve_path(config_path, "."));
+    let repo = repo_root(&config_dir);
+    let (root, root_name) = match &repo {
+        Some(root) => (root.as_path(), "the repository"),
+        None => (config_dir.as_path(), "the config's directory"),
+    };
+
+    let env_path = normalize(&config::resolve_path(config_path, &cfg.output.env_file));
+    if !env_path.starts_with(root) {
+        findings.push(format!(
+            "output.env_file {} is outside {root_name}; pulls write plaintext values there",
+            env_path.display()
+        ));
+    } else if let Some(repo) = &repo
+        && !git_ignored(repo, &env_path)
+    {
+        findings.push(format!(
+            "output.env_file {} is not ignored by git; add it to .gitignore",
+            env_path.display()
+        ));
+    }
+    if !cfg.output.create_if_missing && !env_path.exists() {
+        findings.push(format!(
+            "output.create_if_missing is false and {} does not exist; every pull will fail",
+            env_path.display()
+        ));
+    }
+
+    let mut files: Vec<_> = cfg.output.files.iter().collect();
+    files.sort_unstable_by_key(|(key, _)| *key);
+    for (key, file) in files {
+        let path = normalize(&config::resolve_path(config_path, &file.path));
+        if !path.starts_with(root) {
+            findings.push(format!(
+                "[output.files] {key} writes to {}, outside {root_name}",
+                path.display()
+            ));
+        }
+    }
+
+    let webhooks = cfg
+        .hooks
+        .iter()
+        .filter_map(|hook| Some(("hooks.url", hook.url.as_deref()?)))
+        .chain(
+            cfg.security
+                .break_glass_webhook
+                .as_deref()
+                .map(|url| ("security.break_glass_webhook", url)),
+        );
+    for (setting, url) in webhooks {
+        if url.starts_with("http://") && !is_loopback(url) {
+            findings.push(format!(
+                "{setting} {url} is plain HTTP; change summaries and audit entries travel unencrypted"
+            ));
+        }
+    }
+
+    if cfg.security.protected && cfg.audit.path.is_none() {
+        findings.push(
+            "security.protected is set without [audit] path; only break-glass exports are recorded"
+                .to_string(),
+        );
+    }
+    findings
+}
+
+/// The nearest directory at or above `dir` holding `.git`.
+fn repo_root(dir: &Path) -> Option<PathBuf> {
+    dir.ancestors()
+        .find(|dir| dir.join(".git").exists())
+        .map(Path::to_path_buf)
+}
+
+/// Asks git; when git cannot answer, the file is assumed to be ignored so
+/// lint does not cry wolf.
+fn git_ignored(repo: &Path, path: &Path) -> bool {
+    Command::new("git")
+        .arg("-C")
+        .arg(repo)
+        .args(["check-ignore", "-q"])
+        .arg(path)
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .status()
+        .map_or(true, |status| status.code() != Some(1))
+}
+
+fn is_loopback(url: &str) -> bool {
+    reqwest::Url::parse(url)
+        .ok()
+        .and_then(|url| url.host_str().map(str::to_string))
+        .is_some_and(|host| matches!(host.as_str(), "localhost" | "127.0.0.1" | "[::1]"))
+}
+
+/// Absolute path with `.` and `..` resolved lexically (the file may not
+/// exist yet).
+fn normalize(path: &Path) -> PathBuf {
+    let absolute = std::path::absolute(path).unwrap_or_else(|_| path.to_path_buf());
+    let mut out = PathBuf::new();
+    for component in absolute.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir => {
+                out.pop();
+            }
+            other => out.push(other),
+        }
+    }
+    out
+}
@@ -0,0 +1,81 @@
+use std::{
+    path::Path,
+    time::{Duration, Instant},
+};
+
+use anyhow::Result;
+
+use crate::{
+    commands::{pull, watch},
+    config, lock, provider,
+};
+
+const INITIAL_BACKOFF: Duration = Duration::from_secs(1);
+const MAX_BACKOFF: Duration = Duration::from_secs(10);
+
+/// Container entrypoint: waits until the provider's credentials work, pulls
+/// once, then either exits (init container) or keeps syncing (sidecar).
+pub async fn run(
+    config_path: &Path,
+    identity_timeout: Duration,
+    watch_interval: Option<Duration>,
+) -> Result<()> {
+    let cfg = config::load(config_path)?;
+    let deadline = Instant::now() + identity_timeout;
+    for source in cfg.sources() {
+        wait_for_identity(source.provider, deadline, identity_timeout).await?;
+    }
+
+    match watch_interval {
+        Some(interval) => watch::run(config_path, interval, false, None).await,
+        None => pull::run(
+            config_path,
+            false,
+            false,
+            false,
+            None,
+            lock::Mode::Use,
+            pull::Summary::None,
+        )
+        .await
+        .map(|_| ()),
+    }
+}
+
+/// Managed identity endpoints (IMDS, the workload identity webhook) often come
+/// up after the pod's containers start. The provider is rebuilt on every
+/// attempt so credentials injected late through the environment are picked up.
+async fn wait_for_identity(
+    cfg: &config::ProviderConfig,
+    deadline: Instant,
+    timeout: Duration,
+) -> Result<()> {
+    let mut backoff = INITIAL_BACKOFF;
+    let mut attempt = 1;
+    loop {
+        let err = match probe(cfg).await {
+            Ok(()) => return Ok(()),
+            Err(err) => err,
+        };
+        let now = Instant::now();
+        if now >= deadline {
+            return Err(err.context(format!(
+                "identity not available after {}",
+                humantime::format_duration(timeout)
+            )));
+        }
+        eprintln!("Waiting for identity (attempt {attempt}): {err:#}");
+        tokio::time::sleep(backoff.min(deadline - now)).await;
+        backoff = (backoff * 2).min(MAX_BACKOFF);
+        attempt += 1;
+    }
+}
+
+/// Providers without a principal are only ready once they can list secrets.
+async fn probe(cfg: &config::ProviderConfig) -> Result<()> {
+    let provider = provider::build_provider(cfg)?;
+    if provider.identity().await?.is_none() {
+        provider.list_secrets().await?;
+    }
+    Ok(())
+}
@@ -0,0 +1,141 @@
+use std::collections::BTreeMap;
+
+use anyhow::{Result, bail};
+
+use crate::{
+    audit,
+    commands::{
+        compare,
+        import::{self, Options},
+        push,
+    },
+    config,
+    messages::message,
+    owners,
+    provider::{
+        Capability, SecretProvider,
+        cache::{self, ProviderRegistry},
+    },
+    resolve,
+};
+
+/// Copies secrets from the providers of profile `from` (see
+/// `compare::profile_path`) to the provider of profile `to`, matched by env
+/// key and named the way `push` names them there. `keys` selects env keys
+/// (default all) and `excludes` leaves keys out; both may use `*`. Values
+/// only pass through memory. Conflicts, resuming and confirmation work as
+/// for `import`.
+pub async fn run(
+    from: &str,
+    to: &str,
+    keys: &[String],
+    excludes: &[String],
+    options: Options,
+) -> Result<()> {
+    let Options {
+        dry_run,
+        yes,
+        override_owner,
+        concurrency,
+        ..
+    } = options;
+    if concurrency == 0 {
+        bail!("--concurrency must be at least 1");
+    }
+    let (from_path, to_path) = (compare::profile_path(from), compare::profile_path(to));
+    if from_path == to_path {
+        bail!(
+            "--from and --to name the same config {}",
+            from_path.display()
+        );
+    }
+    let from_cfg = config::load(&from_path)?;
+    let to_cfg = config::load(&to_path)?;
+    let provider_cfg = to_cfg.single_provider()?;
+
+    let mut providers = ProviderRegistry::default();
+    let provider = providers.get(provider_cfg)?;
+    provider
+        .capabilities()
+        .require(Capability::Write, &provider_cfg.kind, "copy")?;
+
+    let connected = providers.connect(&from_cfg)?;
+    let principal = cache::principals(&connected).await;
+    let resolved = resolve::resolve_all(&connected).await;
+    let entries = resolved.and_then(|values| select(values, keys, excludes));
+
+    let mut entry = audit::Entry::new("copy");
+    entry.principal = principal.as_deref();
+    entry.dry_run = dry_run;
+    if let Ok(entries) = &entries {
+        entry.exported_keys = entries.keys().map(String::as_str).collect();
+    }
+    entry.error = entries.as_ref().err().map(|err| format!("{err:#}"));
+    audit::record(&from_path, &from_cfg, &entry)?;
+    let entries = entries?;
+    if entries.is_empty() {
+        bail!("no keys of {from} to copy");
+    }
+
+    let (mut plan, resumed) =
+        push::plan(&to_path, &to_cfg, &provider, &entries, concurrency).await?;
+    if resumed > 0 {
+        println!("{}", message!("import_resumed", count = resumed));
+    }
+    import::review(&mut plan, entries.len() - resumed, options.overwrite);
+    if plan.is_empty() {
+        println!("{}", message!("copy_nothing", to = to));
+        if !dry_run {
+            push::remove_progress(&to_path)?;
+        }
+        return Ok(());
+    }
+    // Before the dry-run return, so a preview shows what the real copy refuses.
+    let planned_keys: Vec<_> = plan.iter().map(|planned| planned.key).collect();
+    owners::check(&to_cfg, &planned_keys, override_owner)?;
+    if dry_run {
+        return Ok(());
+    }
+    if !yes
+        && !push::confirm(
+            &message!("copy_confirm", count = plan.len(), from = from, to = to),
+            "refusing to copy without confirmation; review with --dry-run and pass --yes",
+        )?
+    {
+        bail!("copy cancelled");
+    }
+
+    push::write(
+        &to_path,
+        &to_cfg,
+        &provider,
+        &plan,
+        "copy",
+        override_owner,
+        concurrency,
+    )
+    .await?;
+    println!("{}", message!("copy_done", count = plan.len(), to = to));
+    Ok(())
+}
+
+/// The resolved keys `keys` selects (all when empty), without `excludes`.
+/// A key named without `*` that the profile lacks is an error.
+fn select(
+    values: impl IntoIterator<Item = (String, String)>,
+    keys: &[String],
+    excludes: &[String],
+) -> Result<BTreeMap<String, String>> {
+    let values: BTreeMap<_, _> = values.into_iter().collect();
+    if let Some(key) = keys
+        .iter()
+        .find(|key| !key.contains('*') && !values.contains_key(*key))
+    {
+        bail!("{key} is not provided by the source profile");
+    }
+    Ok(values
+        .into_iter()
+        .filter(|(key, _)| keys.is_empty() || keys.iter().any(|p| owners::glob_match(p, key)))
+        .filter(|(key, _)| !excludes.iter().any(|p| owners::glob_match(p, key)))
+        .collect())
+}
@@ -0,0 +1,64 @@
+use std::{collections::HashMap, path::Path};
+
+use anyhow::{Context, Result, bail};
+
+use crate::{
+    codec,
+    config::{self, Config},
+    envfile, plaintext,
+};
+
+/// Prints the env file decrypted, whether `output.encrypt` encrypted the
+/// whole file or `output.codec` its values (comments, order and plain keys
+/// as they are), or writes it to `output`, private to the user, e.g.
+/// `.env.local` for tools that read a plain file.
+pub fn run(config_path: &Path, identity: Option<&Path>, output: Option<&Path>) -> Result<()> {
+    let cfg = config::load(config_path)?;
+    if cfg.output.encrypt.is_none() && cfg.output.codec.is_none() {
+        bail!("no output.codec or output.encrypt configured; the env file is not encrypted");
+    }
+    plaintext::refuse(&cfg, "decrypt would write the env file out in plaintext")?;
+    let rendered = decrypted(config_path, &cfg, identity)?;
+    match output {
+        Some(path) => envfile::write_atomic(path, &rendered)
+            .with_context(|| format!("failed to write {}", path.display())),
+        None => {
+            print!("{rendered}");
+            Ok(())
+        }
+    }
+}
+
+/// The entries of the env file with everything decrypted, for `run
+/// --from-env-file`.
+pub fn values(
+    config_path: &Path,
+    cfg: &Config,
+    identity: Option<&Path>,
+) -> Result<HashMap<String, String>> {
+    let rendered = decrypted(config_path, cfg, identity)?;
+    Ok(envfile::parse_entries(&rendered).into_iter().collect())
+}
+
+fn decrypted(config_path: &Path, cfg: &Config, identity: Option<&Path>) -> Result<String> {
+    let env_path = config::resolve_path(config_path, &cfg.output.env_file);
+    let raw = codec::read_env_file(config_path, cfg, identity)?
+        .with_context(|| format!("env file does not exist: {}", env_path.display()))?;
+    let Some(codec_cfg) = &cfg.output.codec else {
+        return Ok(raw);
+    };
+    let codec = codec::build(config_path, codec_cfg, identity)?;
+    let lines = envfile::parse_lines(&raw);
+
+    let mut decrypted = HashMap::new();
+    for (key, value) in envfile::values(&lines) {
+        if let Some(plain) = codec
+            .decode(value)
+            .with_context(|| format!("failed to decrypt {key}"))?
+        {
+            decrypted.insert(key.to_string(), plain);
+        }
+    }
+    let (content, _) = envfile::merge(lines, &decrypted);
+    Ok(envfile::render(&content))
+}
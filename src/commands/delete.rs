@@ -0,0 +1,108 @@
+use std::path::Path;
+
+use anyhow::{Context, Result, anyhow, bail};
+
+use crate::{
+    audit, codec,
+    commands::push,
+    config, envfile, hooks,
+    messages::message,
+    owners,
+    provider::{Capability, SecretProvider, cache::ProviderRegistry},
+};
+
+/// Removes one env key's secret from the config's provider (Key Vault
+/// disables it instead), after confirmation unless `yes`. With `strip_env`,
+/// the key's line also goes from the env file; otherwise the next pull
+/// marks it deprecated.
+pub async fn run(
+    config_path: &Path,
+    key: &str,
+    strip_env: bool,
+    yes: bool,
+    override_owner: bool,
+) -> Result<()> {
+    let cfg = config::load(config_path)?;
+    let provider_cfg = cfg.single_provider()?;
+    owners::check(&cfg, &[key], override_owner)?;
+
+    let mut providers = ProviderRegistry::default();
+    let provider = providers.get(provider_cfg)?;
+    let capabilities = provider.capabilities();
+    capabilities.require(Capability::Delete, &provider_cfg.kind, "delete")?;
+    let listed = provider
+        .list_secrets()
+        .await
+        .context("failed to list secrets")?;
+    let secret = push::secret_for(&cfg, &listed, key);
+    let exists = listed.iter().any(|meta| meta.name == secret)
+        || provider
+            .get_secret(&secret)
+            .await
+            .with_context(|| format!("failed to fetch secret {secret}"))?
+            .is_some();
+    if !exists {
+        bail!("{key}: the provider has no secret {secret}");
+    }
+
+    let env_path = config::resolve_path(config_path, &cfg.output.env_file);
+    let recoverable = if capabilities.soft_delete {
+        " (recoverable)"
+    } else {
+        ""
+    };
+    let question = if strip_env {
+        format!(
+            "Delete {secret} from {}{recoverable} and {key} from {}?",
+            provider_cfg.kind,
+            env_path.display()
+        )
+    } else {
+        format!("Delete {secret} from {}{recoverable}?", provider_cfg.kind)
+    };
+    if !yes
+        && !push::confirm(
+            &question,
+            "refusing to delete without confirmation; pass --yes",
+        )?
+    {
+        bail!("delete cancelled");
+    }
+
+    let principal = provider.identity().await.unwrap_or(None);
+    let result = provider
+        .delete_secret(&secret)
+        .await
+        .map_err(|err| anyhow!("failed to delete secret {secret}: {err}"));
+
+    let mut entry = audit::Entry::new("delete");
+    entry.principal = principal.as_deref();
+    if result.is_ok() {
+        entry.changed_keys = vec![secret.as_str()];
+    }
+    entry.override_owner = override_owner;
+    entry.error = result.as_ref().err().map(|err| format!("{err:#}"));
+    audit::record(config_path, &cfg, &entry)?;
+    if result.is_ok() {
+        let change = hooks::Change {
+            key: &secret,
+            kind: "delete",
+        };
+        hooks::notify(config_path, &cfg, &entry, &[change]).await;
+    }
+    result?;
+    println!("{}", message!("delete_done", secret = secret, key = key));
+
+    if strip_env {
+        let lines = codec::load_env_file(config_path, &cfg, true)?;
+        let (content, found) = envfile::strip(lines, key);
+        if found {
+            codec::write_env_file(config_path, &cfg, &envfile::render(&content))?;
+            println!(
+                "{}",
+                message!("delete_stripped", key = key, path = env_path.display())
+            );
+        }
+    }
+    Ok(())
+}
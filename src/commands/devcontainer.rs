@@ -0,0 +1,170 @@
+use std::{env, fs, path::Path};
+
+use anyhow::{Context, Result, bail};
+use serde_json::{Map, Value, json};
+
+use crate::{
+    config::{self, Config},
+    messages::message,
+};
+
+/// Installs envit in the container: the binary bundled next to it when it
+/// fits the container's architecture, else the feature's `installCommand`.
+const INSTALL_SH: &str = r#"#!/bin/sh
+# Generated by `envit devcontainer`.
+set -e
+if [ -x ./envit ] && [ "$(uname -m)" = "$(cat ./envit.arch 2>/dev/null)" ]; then
+  install -m 0755 ./envit /usr/local/bin/envit
+elif [ -n "$INSTALLCOMMAND" ]; then
+  sh -c "$INSTALLCOMMAND"
+else
+  echo "envit: no bundled binary for $(uname -m); set the feature's installCommand option" >&2
+  exit 1
+fi
+"#;
+
+/// Azure's DefaultAzureCredential reads a service principal from these.
+const AZURE_CREDENTIAL_VARS: &[&str] =
+    &["AZURE_TENANT_ID", "AZURE_CLIENT_ID", "AZURE_CLIENT_SECRET"];
+
+/// Scaffolds a local Dev Container Feature (`<dir>/envit/`) that installs
+/// envit, and a `devcontainer.json` using it that pulls on container
+/// creation. Credentials come from the workspace: the variables the
+/// providers read are listed as recommended Codespaces secrets. An existing
+/// `devcontainer.json` is left alone and the settings to merge are printed.
+pub fn run(config_path: &Path, dir: &Path, bundle_binary: bool, force: bool) -> Result<()> {
+    let cfg = config::load(config_path)?;
+    let feature_dir = dir.join("envit");
+    let manifest = feature_dir.join("devcontainer-feature.json");
+    if manifest.exists() && !force {
+        bail!(
+            "{} already exists (use --force to overwrite)",
+            manifest.display()
+        );
+    }
+    fs::create_dir_all(&feature_dir)
+        .with_context(|| format!("failed to create {}", feature_dir.display()))?;
+    write(&manifest, &pretty(&feature_manifest()))?;
+    let install = feature_dir.join("install.sh");
+    write(&install, INSTALL_SH)?;
+    executable(&install)?;
+    if bundle_binary {
+        bundle(&feature_dir)?;
+    }
+    println!(
+        "{}",
+        message!("devcontainer_feature", path = feature_dir.display())
+    );
+
+    let settings = pretty(&devcontainer(&cfg, config_path));
+    let devcontainer_json = dir.join("devcontainer.json");
+    if devcontainer_json.exists() {
+        println!(
+            "{}",
+            message!(
+                "devcontainer_merge",
+                path = devcontainer_json.display(),
+                settings = settings
+            )
+        );
+    } else {
+        write(&devcontainer_json, &settings)?;
+        println!("{}", message!("wrote", path = devcontainer_json.display()));
+    }
+    Ok(())
+}
+
+fn feature_manifest() -> Value {
+    json!({
+        "id": "envit",
+        "version": env!("CARGO_PKG_VERSION"),
+        "name": "envit",
+        "description": "Installs envit to materialize the env file from the secret provider",
+        "options": {
+            "installCommand": {
+                "type": "string",
+                "default": "",
+                "description": "Command installing envit when no bundled binary fits the container"
+            }
+        }
+    })
+}
+
+fn devcontainer(cfg: &Config, config_path: &Path) -> Value {
+    let mut features = Map::new();
+    features.insert("./envit".to_string(), json!({}));
+    let mut secrets = Map::new();
+    for source in cfg.sources() {
+        let vars: Vec<String> = if source.provider.kind == "azure_key_vault" {
+            // Also allows `az login` inside the container.
+            features.insert(
+                "ghcr.io/devcontainers/features/azure-cli:1".to_string(),
+                json!({}),
+            );
+            AZURE_CREDENTIAL_VARS
+                .iter()
+                .map(|v| v.to_string())
+                .collect()
+        } else {
+            credential_vars(&source.provider.settings)
+        };
+        for var in vars {
+            let description = format!("Credential for envit's {} provider", source.provider.kind);
+            secrets.insert(var, json!({ "description": description }));
+        }
+    }
+    json!({
+        "features": features,
+        "postCreateCommand": format!("envit pull --config {}", config_path.display()),
+        "secrets": secrets,
+    })
+}
+
+/// Environment variables named by `*_env` provider settings.
+fn credential_vars(settings: &toml::Table) -> Vec<String> {
+    settings
+        .iter()
+        .filter(|(name, _)| name.ends_with("_env"))
+        .filter_map(|(_, value)| value.as_str().map(str::to_string))
+        .collect()
+}
+
+/// Copies this binary into the feature, tagged with its architecture so the
+/// install script only uses it where it runs.
+fn bundle(feature_dir: &Path) -> Result<()> {
+    if !cfg!(target_os = "linux") {
+        bail!("--bundle-binary needs a Linux build of envit; set installCommand instead");
+    }
+    let exe = env::current_exe().context("failed to locate the envit binary")?;
+    let target = feature_dir.join("envit");
+    fs::copy(&exe, &target).with_context(|| format!("failed to copy {}", exe.display()))?;
+    executable(&target)?;
+    write(
+        &feature_dir.join("envit.arch"),
+        &format!("{}\n", env::consts::ARCH),
+    )
+}
+
+fn pretty(value: &Value) -> String {
+    format!(
+        "{}\n",
+        serde_json::to_string_pretty(value).expect("JSON values always serialize")
+    )
+}
+
+fn write(path: &Path, content: &str) -> Result<()> {
+    fs::write(path, content).with_context(|| format!("failed to write {}", path.display()))
+}
+
+#[cfg(unix)]
+fn executable(path: &Path) -> Result<()> {
+    use std::os::unix::fs::PermissionsExt;
+
+    fs::set_permissions(path, fs::Permissions::from_mode(0o755))
+        .with_context(|| format!("failed to set permissions on {}", path.display()))
+}
+
+#[cfg(not(unix))]
+fn executable(_path: &Path) -> Result<()> {
+    Ok(())
+}
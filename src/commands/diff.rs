@@ -0,0 +1,295 @@
+use std::{
+    collections::{BTreeMap, HashMap, HashSet},
+    fs,
+    path::Path,
+    process::Command,
+};
+
+use anyhow::{Context, Result, bail};
+
+use crate::{
+    audit, classify, codec,
+    commands::pull::{self, CANARY_KEY, PULLED_KEYS_PATH, PlanLine},
+    config::{self, Config},
+    envfile::{self, ChangeKind},
+    messages::message,
+    plaintext,
+    provider::cache::ProviderRegistry,
+};
+
+/// The env file as committed at a git revision, to compare with instead of
+/// the working copy.
+#[derive(Debug, Clone, Copy)]
+pub struct AgainstGit<'a> {
+    pub rev: &'a str,
+    /// Defaults to the config's env file.
+    pub path: Option<&'a Path>,
+    /// age identity file for an encrypted env file; without one,
+    /// `ENVIT_VAULT_PASSPHRASE` is tried.
+    pub identity: Option<&'a Path>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+enum Drift {
+    Add,
+    Update,
+    Remove,
+}
+
+/// Compares what a pull would write with the env file, without touching
+/// it, or with the env file at a git revision (`against`). Returns whether
+/// there is drift. `values` also prints the differing values (refused for
+/// protected configs).
+pub async fn run(
+    config_path: &Path,
+    values: bool,
+    against: Option<AgainstGit<'_>>,
+) -> Result<bool> {
+    let cfg = config::load(config_path)?;
+    if values && cfg.security.protected {
+        bail!("config is protected: diff --values would print plaintext values");
+    }
+    if values {
+        plaintext::refuse(&cfg, "diff --values would print plaintext values")?;
+    }
+    let env_path = config::resolve_path(config_path, &cfg.output.env_file);
+
+    let mut providers = ProviderRegistry::default();
+    let (principal, resolved) = pull::resolve_values(config_path, &cfg, &mut providers).await;
+    let mut entry = audit::Entry::new("diff");
+    entry.principal = principal.as_deref();
+    entry.env_file = Some(env_path.display().to_string());
+    entry.dry_run = true;
+    entry.error = resolved.as_ref().err().map(|err| format!("{err:#}"));
+    audit::record(config_path, &cfg, &entry)?;
+    let mut updates = resolved?;
+    // The canary changes on every pull by design.
+    updates.remove(CANARY_KEY);
+
+    let Comparison {
+        local,
+        drift,
+        in_files,
+    } = match against {
+        Some(against) => {
+            let path = against.path.unwrap_or(&env_path);
+            let committed = committed_entries(path, against.rev, against.identity)?;
+            // The file's content is not committed, only the path the env
+            // file holds.
+            for (key, file) in &cfg.output.files {
+                if let Some(value) = updates.get_mut(key) {
+                    *value = file.path.clone();
+                }
+            }
+            let drift = compare(&committed, &updates);
+            Comparison {
+                local: committed,
+                drift,
+                in_files: HashSet::new(),
+            }
+        }
+        None => env_file_drift(config_path, &cfg, &mut updates)?,
+    };
+
+    let mut sorted: Vec<_> = drift.into_iter().collect();
+    sorted.sort_by(|a, b| (a.1, &a.0).cmp(&(b.1, &b.0)));
+    let sources = pull::key_sources(&cfg, &mut providers).await;
+    let lines = sorted
+        .iter()
+        .map(|(key, kind)| {
+            let label = match kind {
+                Drift::Add => "ADD",
+                Drift::Update => "UPDATE",
+                Drift::Remove => "REMOVE",
+            };
+            let file = match cfg.output.files.get(key) {
+                Some(file) if in_files.contains(key) => file.path.clone(),
+                _ => cfg.output.env_file.clone(),
+            };
+            let text = if values {
+                format!(
+                    "{label} {key}: {} -> {}",
+                    show(&cfg, key, local.get(key)),
+                    show(&cfg, key, updates.get(key))
+                )
+            } else {
+                format!("{label} {key}")
+            };
+            PlanLine {
+                file,
+                source: sources.get(key).copied(),
+                text,
+            }
+        })
+        .collect();
+    pull::print_plan(&cfg, lines);
+    if sorted.is_empty() {
+        match against {
+            Some(against) => println!("{}", message!("diff_none_since", rev = against.rev)),
+            None => println!("{}", message!("diff_none")),
+        }
+    }
+    Ok(!sorted.is_empty())
+}
+
+/// The local entries, how they drift, and the keys whose drift is in
+/// their `[output.files]` file.
+struct Comparison {
+    local: BTreeMap<String, String>,
+    drift: BTreeMap<String, Drift>,
+    in_files: HashSet<String>,
+}
+
+/// Drift against the working env file.
+fn env_file_drift(
+    config_path: &Path,
+    cfg: &Config,
+    updates: &mut HashMap<String, String>,
+) -> Result<Comparison> {
+    let existing = codec::load_env_file(config_path, cfg, true)?;
+    let local: BTreeMap<_, _> = match codec::read_env_file(config_path, cfg, None)? {
+        Some(raw) => envfile::parse_entries(&raw).into_iter().collect(),
+        None => BTreeMap::new(),
+    };
+
+    let mut drift = BTreeMap::new();
+    // `[output.files]` keys hold a path; their value lives in the file.
+    for (key, file) in &cfg.output.files {
+        let Some(value) = updates.get_mut(key) else {
+            continue;
+        };
+        let content = pull::file_content(file, key, value)?;
+        match fs::read(config::resolve_path(config_path, &file.path)) {
+            Ok(current) if current == content => {}
+            Ok(_) => {
+                drift.insert(key.clone(), Drift::Update);
+            }
+            Err(_) => {
+                drift.insert(key.clone(), Drift::Add);
+            }
+        }
+        *value = file.path.clone();
+    }
+    let in_files = drift.keys().cloned().collect();
+    pull::encode_values(config_path, cfg, &existing, updates)?;
+    let (_, changes) = envfile::merge(existing, updates);
+    for change in changes {
+        let kind = match change.kind {
+            ChangeKind::Add => Drift::Add,
+            ChangeKind::Update => Drift::Update,
+        };
+        drift.entry(change.key).or_insert(kind);
+    }
+    // Removals are only known for keys a pull recorded; `prune` (or
+    // `output.deprecation_grace`) carries them out.
+    let previous = pull::read_pulled_keys(&config::resolve_path(config_path, PULLED_KEYS_PATH))?;
+    for key in previous {
+        if local.contains_key(&key) && !updates.contains_key(&key) {
+            drift.insert(key, Drift::Remove);
+        }
+    }
+    Ok(Comparison {
+        local,
+        drift,
+        in_files,
+    })
+}
+
+/// Every key that differs between the committed entries and the provider.
+fn compare(
+    committed: &BTreeMap<String, String>,
+    updates: &HashMap<String, String>,
+) -> BTreeMap<String, Drift> {
+    let mut drift = BTreeMap::new();
+    for (key, value) in updates {
+        match committed.get(key) {
+            None => {
+                drift.insert(key.clone(), Drift::Add);
+            }
+            Some(old) if old != value => {
+                drift.insert(key.clone(), Drift::Update);
+            }
+            Some(_) => {}
+        }
+    }
+    for key in committed.keys() {
+        if key != CANARY_KEY && !updates.contains_key(key) {
+            drift.insert(key.clone(), Drift::Remove);
+        }
+    }
+    drift
+}
+
+/// The dotenv entries of `path` at `rev`, decrypted first when the file is
+/// age-armored.
+fn committed_entries(
+    path: &Path,
+    rev: &str,
+    identity: Option<&Path>,
+) -> Result<BTreeMap<String, String>> {
+    let dir = path
+        .parent()
+        .filter(|dir| !dir.as_os_str().is_empty())
+        .unwrap_or(Path::new("."));
+    let name = path
+        .file_name()
+        .with_context(|| format!("{} is not a file", path.display()))?;
+    let spec = format!("{rev}:./{}", name.to_string_lossy());
+    let output = Command::new("git")
+        .arg("show")
+        .arg(&spec)
+        .current_dir(dir)
+        .output()
+        .context("failed to run git")?;
+    if !output.status.success() {
+        bail!(
+            "git show {spec} failed in {}: {}",
+            dir.display(),
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+    }
+    let raw = if output
+        .stdout
+        .starts_with(b"-----BEGIN AGE ENCRYPTED FILE-----")
+    {
+        decrypt(&output.stdout, identity)
+            .with_context(|| format!("failed to decrypt {} at {rev}", path.display()))?
+    } else {
+        String::from_utf8(output.stdout)
+            .with_context(|| format!("{} at {rev} is not UTF-8", path.display()))?
+    };
+    Ok(envfile::parse_entries(&raw).into_iter().collect())
+}
+
+#[cfg(feature = "local-vault")]
+fn decrypt(armored: &[u8], identity: Option<&Path>) -> Result<String> {
+    use age::{Identity, IdentityFile, secrecy::SecretString};
+
+    let identities: Vec<Box<dyn Identity>> = match identity {
+        Some(path) => IdentityFile::from_file(path.display().to_string())
+            .with_context(|| format!("failed to read age identity file {}", path.display()))?
+            .into_identities()
+            .map_err(|e| anyhow::anyhow!("unusable age identity: {e}"))?,
+        None => {
+            let passphrase = std::env::var("ENVIT_VAULT_PASSPHRASE")
+                .context("pass --identity or set ENVIT_VAULT_PASSPHRASE to decrypt")?;
+            vec![Box::new(age::scrypt::Identity::new(SecretString::from(
+                passphrase,
+            )))]
+        }
+    };
+    Ok(crate::crypto::age_decrypt(&identities, armored)?)
+}
+
+#[cfg(not(feature = "local-vault"))]
+fn decrypt(_armored: &[u8], _identity: Option<&Path>) -> Result<String> {
+    bail!("encrypted env files need a build with the local-vault feature")
+}
+
+/// A value for `--values`, masked by its class (see `classify::mask`).
+fn show(cfg: &Config, key: &str, value: Option<&String>) -> String {
+    match value {
+        Some(value) => classify::mask(classify::class(&cfg.classify, key, Some(value)), value),
+        None => "(unset)".to_string(),
+    }
+}
@@ -0,0 +1,56 @@
+use std::{collections::HashMap, path::Path};
+
+use anyhow::{Result, bail};
+
+use crate::{commands::export, config, plaintext, shell};
+
+/// direnv stdlib extension; installed into `~/.config/direnv/lib/` it makes
+/// `use envit [config]` available in any `.envrc`.
+pub const STDLIB: &str = r#"# envit integration for direnv: `use envit [path/to/envit.toml]`
+use_envit() {
+  local config="${1:-envit.toml}"
+  watch_file "$config"
+  eval "$(envit direnv-export --config "$config")"
+}
+"#;
+
+/// Prints `export` statements for the resolved values, to be evaluated by
+/// an `.envrc`. direnv diffs the environment around the `.envrc`, so the
+/// variables are unloaded again when leaving the directory.
+pub async fn run(config_path: &Path) -> Result<()> {
+    let cfg = config::load(config_path)?;
+    if cfg.security.protected {
+        bail!("config is protected: use `envit pull` and dotenv in .envrc instead");
+    }
+    plaintext::refuse(&cfg, "direnv-export would print plaintext values")?;
+
+    let values = export::resolve_audited(config_path, &cfg, "direnv-export", None, Ok).await?;
+    print!("{}", exports(&values));
+    Ok(())
+}
+
+fn exports(values: &HashMap<String, String>) -> String {
+    let mut keys: Vec<&String> = values.keys().collect();
+    keys.sort_unstable();
+    keys.into_iter()
+        .map(|key| format!("export {key}={}\n", shell::quote(&values[key])))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn exports_are_sorted_and_single_quoted() {
+        let values = HashMap::from([
+            ("B".to_string(), "it's $HOME `x`".to_string()),
+            ("A".to_string(), "line1\nline2".to_string()),
+        ]);
+
+        assert_eq!(
+            exports(&values),
+            "export A='line1\nline2'\nexport B='it'\\''s $HOME `x`'\n"
+        );
+    }
+}
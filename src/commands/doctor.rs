@@ -0,0 +1,193 @@
+use std::{fs, path::Path, time::Duration};
+
+use anyhow::Result;
+use reqwest::Url;
+use tokio::net::TcpStream;
+
+use crate::{
+    config::{self, ProviderConfig},
+    messages::message,
+    provider::{ProviderError, SecretProvider, cache::ProviderRegistry},
+};
+
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Checks, per provider, network reachability, credentials and list/get
+/// permissions, then whether the env file can be written. Each failure
+/// comes with what to do about it. Returns whether everything passed.
+pub async fn run(config_path: &Path) -> Result<bool> {
+    let cfg = config::load(config_path)?;
+    let mut report = Report::default();
+
+    let mut providers = ProviderRegistry::default();
+    for (index, source) in cfg.sources().into_iter().enumerate() {
+        let kind = &source.provider.kind;
+        println!(
+            "{}",
+            message!("doctor_provider", index = index + 1, kind = kind)
+        );
+        if let Some((host, port)) = endpoint(source.provider) {
+            match tokio::time::timeout(CONNECT_TIMEOUT, TcpStream::connect((host.as_str(), port)))
+                .await
+            {
+                Ok(Ok(_)) => report.ok(&format!("network: reached {host}:{port}")),
+                Ok(Err(err)) => report.fail(
+                    &format!("network: cannot connect to {host}:{port}: {err}"),
+                    "check the URL in the config, DNS, VPN/private endpoint access and HTTPS_PROXY",
+                ),
+                Err(_) => report.fail(
+                    &format!("network: no answer from {host}:{port} within {CONNECT_TIMEOUT:?}"),
+                    "check firewalls, VPN/private endpoint access and HTTPS_PROXY",
+                ),
+            }
+        }
+
+        let provider = match providers.get(source.provider) {
+            Ok(provider) => provider,
+            Err(err) => {
+                report.fail(&format!("setup: {err:#}"), "fix the provider settings");
+                continue;
+            }
+        };
+        match provider.identity().await {
+            Ok(principal) => report.ok(&format!(
+                "credentials: {}",
+                principal.as_deref().unwrap_or("accepted")
+            )),
+            Err(err) => {
+                report.fail(&format!("credentials: {err}"), credentials_fix(kind));
+                report.skip("list and get permissions");
+                continue;
+            }
+        }
+        check_access(&mut report, provider.as_ref(), kind).await;
+    }
+
+    println!("{}", message!("doctor_env_file"));
+    let env_path = config::resolve_path(config_path, &cfg.output.env_file);
+    match writable(&env_path) {
+        Ok(()) => report.ok(&format!("{} is writable", env_path.display())),
+        Err(err) => report.fail(
+            &format!("{}: {err}", env_path.display()),
+            "create the directory or fix its permissions, or change output.env_file",
+        ),
+    }
+
+    if report.failures == 0 {
+        println!("{}", message!("doctor_passed"));
+    } else {
+        println!("{}", message!("doctor_failed", count = report.failures));
+    }
+    Ok(report.failures == 0)
+}
+
+async fn check_access(report: &mut Report, provider: &dyn SecretProvider, kind: &str) {
+    let listed = match provider.list_secrets().await {
+        Ok(listed) => {
+            report.ok(&format!("list: {} secrets", listed.len()));
+            listed
+        }
+        Err(err) => {
+            report.fail(
+                &format!("list: {err}"),
+                access_fix(kind, is_denied(&err), "list"),
+            );
+            report.skip("get permission");
+            return;
+        }
+    };
+    let Some(first) = listed.first() else {
+        report.skip("get permission (no secrets to read)");
+        return;
+    };
+    match provider.get_secret(&first.name).await {
+        Ok(_) => report.ok(&format!("get: read {}", first.name)),
+        Err(err) => report.fail(
+            &format!("get {}: {err}", first.name),
+            access_fix(kind, is_denied(&err), "get"),
+        ),
+    }
+}
+
+/// Host and port of the provider's endpoint, for settings that name one.
+fn endpoint(provider: &ProviderConfig) -> Option<(String, u16)> {
+    provider.settings.values().find_map(|value| {
+        let url = Url::parse(value.as_str()?).ok()?;
+        if !matches!(url.scheme(), "http" | "https") {
+            return None;
+        }
+        Some((url.host_str()?.to_string(), url.port_or_known_default()?))
+    })
+}
+
+pub fn credentials_fix(kind: &str) -> &'static str {
+    match kind {
+        "azure_key_vault" | "kms_file" => {
+            "sign in with `az login`, or set AZURE_TENANT_ID, AZURE_CLIENT_ID and \
+             AZURE_CLIENT_SECRET for a service principal; in Azure, check the managed or \
+             workload identity assignment"
+        }
+        _ => {
+            "check the credentials this provider kind reads (see the Providers section of the README)"
+        }
+    }
+}
+
+fn is_denied(err: &ProviderError) -> bool {
+    matches!(err, ProviderError::Auth(_))
+}
+
+/// What to do when `operation` failed; `denied` when the credentials were
+/// rejected for it rather than the request failing.
+pub fn access_fix(kind: &str, denied: bool, operation: &str) -> &'static str {
+    match (denied, kind) {
+        (true, "azure_key_vault") if operation == "list" => {
+            "grant the identity the Key Vault Secrets User role (RBAC vaults) or List and Get \
+             secret permissions (access policy vaults)"
+        }
+        (true, "azure_key_vault") => {
+            "grant the identity Get secret permission, or check the secret is enabled"
+        }
+        (true, _) => "grant the credentials read access to these secrets",
+        _ => "retry; if it persists, check the provider's status and the error above",
+    }
+}
+
+/// Whether the env file could be written, without changing it: the file is
+/// opened for appending, or a scratch file is created next to it.
+fn writable(path: &Path) -> std::io::Result<()> {
+    if path.exists() {
+        return fs::OpenOptions::new().append(true).open(path).map(|_| ());
+    }
+    let dir = path
+        .parent()
+        .filter(|d| !d.as_os_str().is_empty())
+        .unwrap_or(Path::new("."));
+    let probe = dir.join(format!(".envit-doctor-{}", std::process::id()));
+    fs::OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .open(&probe)?;
+    fs::remove_file(probe)
+}
+
+#[derive(Default)]
+struct Report {
+    failures: usize,
+}
+
+impl Report {
+    fn ok(&self, what: &str) {
+        println!("{}", message!("doctor_ok", check = what));
+    }
+
+    fn fail(&mut self, what: &str, fix: &str) {
+        self.failures += 1;
+        println!("{}", message!("doctor_fail", check = what));
+        println!("{}", message!("doctor_fix", fix = fix));
+    }
+
+    fn skip(&self, what: &str) {
+        println!("{}", message!("doctor_skip", check = what));
+    }
+}
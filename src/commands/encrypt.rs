@@ -0,0 +1,46 @@
+use std::{fs, path::Path};
+
+use anyhow::{Context, Result, bail};
+
+use crate::{codec, config, messages::message};
+
+/// Encrypts a plaintext env file under `output.encrypt`: the env file in
+/// place, or `input` into it (which must not exist yet). `input` itself is
+/// left for the user to delete.
+pub fn run(config_path: &Path, input: Option<&Path>) -> Result<()> {
+    let cfg = config::load(config_path)?;
+    if cfg.output.encrypt.is_none() {
+        bail!("no output.encrypt configured; add its recipients to the config first");
+    }
+    let env_path = config::resolve_path(config_path, &cfg.output.env_file);
+    let source = input.unwrap_or(&env_path);
+    let raw = fs::read_to_string(source)
+        .with_context(|| format!("failed to read env file: {}", source.display()))?;
+    if codec::is_armored(&raw) {
+        bail!("{} is already encrypted", source.display());
+    }
+    if source != env_path && env_path.exists() {
+        bail!(
+            "{} already exists; decrypt or remove it before encrypting {} into it",
+            env_path.display(),
+            source.display()
+        );
+    }
+
+    codec::write_env_file(config_path, &cfg, &raw)?;
+    println!(
+        "{}",
+        message!(
+            "encrypt_done",
+            source = source.display(),
+            path = env_path.display()
+        )
+    );
+    if source != env_path {
+        println!(
+            "{}",
+            message!("encrypt_plaintext_left", source = source.display())
+        );
+    }
+    Ok(())
+}
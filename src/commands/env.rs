@@ -0,0 +1,41 @@
+use std::{
+    io::{self, IsTerminal},
+    path::Path,
+};
+
+use anyhow::{Result, bail};
+
+use crate::{
+    commands::export::{self, dotenv, emit, shell_exports},
+    config, plaintext,
+};
+
+/// `envit env`: the resolved values on stdout as dotenv lines, or as
+/// `export` lines for `eval` with `shell`. Refused for protected configs,
+/// which have no break-glass path here; with `no_plaintext` only shell
+/// exports to a terminal are printed, as for `export`.
+pub async fn run(config_path: &Path, shell: bool) -> Result<()> {
+    let cfg = config::load(config_path)?;
+    if cfg.security.protected {
+        bail!(
+            "config is protected: `envit env` would print plaintext values; use `envit run` to \
+             pass them to a command"
+        );
+    }
+    if !(shell && io::stdout().is_terminal()) {
+        plaintext::refuse(
+            &cfg,
+            "env only prints --export lines to a terminal; use `envit run` to pass values to a \
+             command",
+        )?;
+    }
+
+    let values = export::resolve_audited(config_path, &cfg, "env", None, Ok).await?;
+    let keys = export::sorted_keys(&values);
+    let document = if shell {
+        shell_exports(&values, &keys)?
+    } else {
+        dotenv(&values, &keys)
+    };
+    emit(None, &document)
+}
@@ -0,0 +1,182 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    fs, io,
+    path::Path,
+    sync::Arc,
+};
+
+use anyhow::{Context, Result};
+
+use crate::{
+    audit, classify,
+    config::{self, Config, Source},
+    envfile,
+    messages::message,
+    provider::cache::{self, CachedProvider, ProviderRegistry},
+    resolve,
+};
+
+pub const DEFAULT_PATH: &str = ".env.example";
+
+/// Marks the comment above each key `example` manages.
+const MARKER: &str = "# envit: ";
+
+/// Writes every key the providers currently pull to `output` (default
+/// `.env.example` next to the config), each under a comment naming the
+/// secret it comes from and its class. Values are left blank, or are
+/// `<class>` placeholders with `placeholders`; a value someone filled in by
+/// hand is kept. Lines without an envit comment stay as they are and keys
+/// that are no longer provided go. With `check`, nothing is written; returns
+/// whether the file is out of date.
+pub async fn run(
+    config_path: &Path,
+    output: Option<&Path>,
+    placeholders: bool,
+    check: bool,
+) -> Result<bool> {
+    let cfg = config::load(config_path)?;
+    let path = match output {
+        Some(path) => path.to_path_buf(),
+        None => config::resolve_path(config_path, DEFAULT_PATH),
+    };
+    let mut providers = ProviderRegistry::default();
+    let connected = providers.connect(&cfg)?;
+    let principal = cache::principals(&connected).await;
+
+    let result = managed_keys(&connected).await;
+
+    let mut entry = audit::Entry::new("example");
+    entry.principal = principal.as_deref();
+    entry.dry_run = true;
+    entry.error = result.as_ref().err().map(|err| format!("{err:#}"));
+    audit::record(config_path, &cfg, &entry)?;
+    let managed = result?;
+
+    let existing = match fs::read_to_string(&path) {
+        Ok(existing) => existing,
+        Err(err) if err.kind() == io::ErrorKind::NotFound => String::new(),
+        Err(err) => {
+            return Err(err).with_context(|| format!("failed to read {}", path.display()));
+        }
+    };
+    let rendered = render(&cfg, &existing, &managed, placeholders);
+    let stale = rendered != existing;
+    if check {
+        if stale {
+            println!("{}", message!("example_stale", path = path.display()));
+        } else {
+            println!("{}", message!("example_current", path = path.display()));
+        }
+    } else if stale {
+        envfile::write_atomic(&path, &rendered)
+            .with_context(|| format!("failed to write {}", path.display()))?;
+        println!(
+            "{}",
+            message!(
+                "example_written",
+                count = managed.len(),
+                path = path.display()
+            )
+        );
+    } else {
+        println!("{}", message!("example_current", path = path.display()));
+    }
+    Ok(stale)
+}
+
+/// Env key -> where it comes from, e.g. `azure_key_vault secret db-url`;
+/// the last provider producing a key wins, as in a pull.
+async fn managed_keys(
+    connected: &[(Source<'_>, Arc<CachedProvider>)],
+) -> Result<BTreeMap<String, String>> {
+    let mut managed = BTreeMap::new();
+    for (index, (source, provider)) in connected.iter().enumerate() {
+        let position = if connected.len() > 1 {
+            format!("providers[{index}] ")
+        } else {
+            String::new()
+        };
+        for (secret, key) in resolve::keys(*source, provider.as_ref()).await? {
+            let from = format!("{position}{} secret {secret}", source.provider.kind);
+            managed.insert(key, from);
+        }
+    }
+    Ok(managed)
+}
+
+/// `existing` without its envit-managed entries, then one entry per
+/// managed key.
+fn render(
+    cfg: &Config,
+    existing: &str,
+    managed: &BTreeMap<String, String>,
+    placeholders: bool,
+) -> String {
+    let mut kept = Vec::new();
+    let mut filled_in = HashMap::new();
+    let mut lines = existing.lines();
+    while let Some(line) = lines.next() {
+        if !line.starts_with(MARKER) {
+            kept.push(line);
+            continue;
+        }
+        if let Some(next) = lines.next() {
+            filled_in.extend(envfile::parse_entries(next));
+        }
+    }
+    while kept.last().is_some_and(|line| line.trim().is_empty()) {
+        kept.pop();
+    }
+
+    let mut out = kept.join("\n");
+    if !out.is_empty() {
+        out.push_str("\n\n");
+    }
+    for (key, from) in managed {
+        let class = classify::class(&cfg.classify, key, None);
+        let value = match filled_in.get(key) {
+            Some(value) if !value.is_empty() => value.clone(),
+            _ if placeholders => {
+                format!("<{}>", class.map_or("value".to_string(), |c| c.to_string()))
+            }
+            _ => String::new(),
+        };
+        let class = class.map(|class| format!(" ({class})")).unwrap_or_default();
+        out.push_str(&format!("{MARKER}{from}{class}\n{key}={value}\n"));
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn render_replaces_managed_entries_and_keeps_the_rest() {
+        let cfg: Config = toml::from_str("version = 1\n[output]\nenv_file = \".env\"\n").unwrap();
+        let existing = "# Local settings\nLOG_LEVEL=debug\n\n\
+                        # envit: azure_key_vault secret gone\nGONE=\n\
+                        # envit: azure_key_vault secret public-url\nPUBLIC_URL=https://example.com\n";
+        let managed = BTreeMap::from([
+            (
+                "API_TOKEN".to_string(),
+                "azure_key_vault secret api-token".to_string(),
+            ),
+            (
+                "PUBLIC_URL".to_string(),
+                "azure_key_vault secret public-url".to_string(),
+            ),
+        ]);
+
+        let rendered = render(&cfg, existing, &managed, false);
+        assert_eq!(
+            rendered,
+            "# Local settings\nLOG_LEVEL=debug\n\n\
+             # envit: azure_key_vault secret api-token (token)\nAPI_TOKEN=\n\
+             # envit: azure_key_vault secret public-url (connection_string)\n\
+             PUBLIC_URL=https://example.com\n"
+        );
+        assert_eq!(render(&cfg, &rendered, &managed, false), rendered);
+        assert!(render(&cfg, "", &managed, true).contains("API_TOKEN=<token>\n"));
+    }
+}
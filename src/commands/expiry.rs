@@ -0,0 +1,81 @@
+use std::{
+    path::Path,
+    time::{Duration, SystemTime},
+};
+
+use anyhow::Result;
+
+use crate::{
+    audit,
+    commands::audit::details,
+    config,
+    messages::message,
+    provider::cache::{self, ProviderRegistry},
+};
+
+const DAY: u64 = 24 * 60 * 60;
+
+/// Lists secrets whose expiry (Key Vault `exp`) falls before now + `within`,
+/// already expired ones included, soonest first. Disabled secrets are left
+/// out since nothing reads them. Returns whether any were found, so a
+/// scheduled job can alert before credentials lapse.
+pub async fn run(config_path: &Path, within: Duration) -> Result<bool> {
+    let cfg = config::load(config_path)?;
+    let mut providers = ProviderRegistry::default();
+    let connected = providers.connect(&cfg)?;
+    let principal = cache::principals(&connected).await;
+
+    let result = details(&connected).await;
+
+    let mut entry = audit::Entry::new("expiry");
+    entry.principal = principal.as_deref();
+    entry.dry_run = true;
+    entry.error = result.as_ref().err().map(|err| format!("{err:#}"));
+    audit::record(config_path, &cfg, &entry)?;
+
+    let now = SystemTime::now();
+    let deadline = now + within;
+    let mut expiring: Vec<_> = result?
+        .into_iter()
+        .filter(|(_, details)| details.enabled != Some(false))
+        .filter_map(|(_, details)| {
+            let expires = details.expires.filter(|expires| *expires <= deadline)?;
+            Some((expires, details.name))
+        })
+        .collect();
+    expiring.sort();
+
+    let window = humantime::format_duration(within);
+    for (expires, name) in &expiring {
+        let at = humantime::format_rfc3339_seconds(*expires);
+        match expires.duration_since(now) {
+            Ok(left) => println!(
+                "{}",
+                message!(
+                    "expiry_expires",
+                    secret = name,
+                    at = at,
+                    days = left.as_secs() / DAY
+                )
+            ),
+            Err(past) => println!(
+                "{}",
+                message!(
+                    "expiry_expired",
+                    secret = name,
+                    at = at,
+                    days = past.duration().as_secs() / DAY
+                )
+            ),
+        }
+    }
+    if expiring.is_empty() {
+        println!("{}", message!("expiry_none", window = window));
+    } else {
+        println!(
+            "{}",
+            message!("expiry_total", count = expiring.len(), window = window)
+        );
+    }
+    Ok(!expiring.is_empty())
+}
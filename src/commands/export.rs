@@ -0,0 +1,639 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    io::{self, IsTerminal},
+    path::Path,
+    process::Stdio,
+    sync::Arc,
+};
+
+use anyhow::{Context, Result, bail};
+use base64::{Engine, engine::general_purpose::STANDARD};
+use clap::ValueEnum;
+use tokio::io::AsyncWriteExt;
+
+use crate::{
+    audit,
+    config::{self, Config, Source},
+    envfile, plaintext,
+    provider::cache::{self, CachedProvider, ProviderRegistry},
+    resolve, shell,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum Format {
+    /// KEY=VALUE lines
+    Dotenv,
+    /// One JSON object of key -> value
+    Json,
+    /// YAML mapping of key -> value
+    Yaml,
+    /// `export KEY='value'` lines for `source` or `eval`
+    Shell,
+    /// Kubernetes `Secret` manifest (base64 `data`)
+    K8sSecret,
+    /// YAML values file for `helm upgrade -f -`
+    HelmValues,
+    /// Ansible group_vars YAML
+    Ansible,
+    /// Unquoted KEY=VALUE lines for GitLab's `artifacts:reports:dotenv`
+    GitlabDotenv,
+    /// External Secrets Operator `ExternalSecret` (secret names, no values)
+    ExternalSecret,
+    /// docker compose `secrets:` backed by `[output.files]` (paths, no values)
+    ComposeSecrets,
+}
+
+/// How the exported values are rendered.
+#[derive(Debug, Clone, Copy)]
+pub struct Output<'a> {
+    pub format: Format,
+    /// Dotted path the keys are nested under (YAML formats).
+    pub under: Option<&'a str>,
+    /// Pipe the document through `ansible-vault encrypt` (ansible format).
+    pub ansible_vault: bool,
+    /// Name of the Secret (k8s-secret) or the ExternalSecret and the Secret
+    /// it creates.
+    pub name: Option<&'a str>,
+    /// SecretStore per provider, in config order (external-secret format).
+    pub secret_stores: &'a [String],
+    /// Compose services given every secret (compose-secrets format).
+    pub services: &'a [String],
+    /// File written (private to the user) instead of stdout.
+    pub path: Option<&'a Path>,
+}
+
+pub async fn run(
+    config_path: &Path,
+    output: Output<'_>,
+    break_glass: bool,
+    reason: Option<&str>,
+) -> Result<()> {
+    let cfg = config::load(config_path)?;
+    let under = output
+        .under
+        .map(parse_under)
+        .transpose()?
+        .unwrap_or_default();
+    if !under.is_empty()
+        && !matches!(
+            output.format,
+            Format::Yaml | Format::HelmValues | Format::Ansible
+        )
+    {
+        bail!("--under only applies to the YAML formats (yaml, helm-values, ansible)");
+    }
+    if output.ansible_vault && output.format != Format::Ansible {
+        bail!("--ansible-vault requires --format ansible");
+    }
+    if output.format != Format::ExternalSecret && !output.secret_stores.is_empty() {
+        bail!("--secret-store requires --format external-secret");
+    }
+    if !matches!(output.format, Format::ExternalSecret | Format::K8sSecret) && output.name.is_some()
+    {
+        bail!("--name requires --format external-secret or k8s-secret");
+    }
+    if output.format != Format::ComposeSecrets && !output.services.is_empty() {
+        bail!("--service requires --format compose-secrets");
+    }
+    match output.format {
+        Format::ExternalSecret => return export_external_secret(config_path, &cfg, output).await,
+        Format::ComposeSecrets => {
+            return emit(output.path, &compose_secrets(&cfg, output.services)?);
+        }
+        _ => {}
+    }
+    let reason = reason.map(str::trim);
+    if break_glass && reason.is_none_or(str::is_empty) {
+        bail!("--break-glass requires a non-empty --reason");
+    }
+    // An ansible-vault document is not plaintext, so protection allows it.
+    if cfg.security.protected && !break_glass && !output.ansible_vault {
+        bail!("config is protected: plaintext export requires --break-glass --reason \"...\"");
+    }
+    // Shell exports shown on a terminal are read, not stored.
+    let to_terminal =
+        output.format == Format::Shell && output.path.is_none() && io::stdout().is_terminal();
+    if !output.ansible_vault && !to_terminal {
+        plaintext::refuse(
+            &cfg,
+            "export only allows --format shell to a terminal or --ansible-vault",
+        )?;
+    }
+
+    let resolved = resolve_audited(
+        config_path,
+        &cfg,
+        "export",
+        reason.filter(|_| break_glass),
+        Ok,
+    )
+    .await?;
+    let values = &resolved;
+    let keys = sorted_keys(values);
+    let document = match output.format {
+        Format::Dotenv => dotenv(values, &keys),
+        Format::Json => json_object(values, &keys),
+        Format::Shell => shell_exports(values, &keys)?,
+        Format::K8sSecret => k8s_secret(output.name.unwrap_or("envit"), values, &keys)?,
+        Format::GitlabDotenv => gitlab_dotenv(values, &keys)?,
+        Format::Yaml | Format::HelmValues => yaml_values(values, &keys, &under, ""),
+        Format::ExternalSecret | Format::ComposeSecrets => {
+            unreachable!("exported without values above")
+        }
+        Format::Ansible => {
+            if under.is_empty()
+                && let Some(key) = keys.iter().find(|key| !is_identifier(key))
+            {
+                bail!("{key} is not a valid Ansible variable name; nest the keys with --under");
+            }
+            // Values are tagged !unsafe so Ansible never templates a secret
+            // that happens to contain "{{".
+            let document = yaml_values(values, &keys, &under, "!unsafe ");
+            if output.ansible_vault {
+                ansible_vault_encrypt(&document).await?
+            } else {
+                document
+            }
+        }
+    };
+    emit(output.path, &document)
+}
+
+/// Resolves every value for a command that hands plaintext out (`env`,
+/// `export`, `direnv-export`, `tf-external`), narrowed by `pick`, and
+/// records the keys that leave, or why none did, in the audit log as
+/// `command`. The record is written before the caller prints anything, so
+/// a break-glass export (`break_glass` holds its reason) can never happen
+/// unrecorded; it is logged even without an `[audit]` path, and announced.
+pub(crate) async fn resolve_audited(
+    config_path: &Path,
+    cfg: &Config,
+    command: &str,
+    break_glass: Option<&str>,
+    pick: impl FnOnce(HashMap<String, String>) -> Result<HashMap<String, String>>,
+) -> Result<HashMap<String, String>> {
+    let mut providers = ProviderRegistry::default();
+    let connected = providers.connect(cfg)?;
+    let principal = cache::principals(&connected).await;
+    let result = resolve::resolve_all(&connected).await.and_then(pick);
+
+    let mut entry = audit::Entry::new(command);
+    entry.principal = principal.as_deref();
+    if let Ok(values) = &result {
+        entry.exported_keys = sorted_keys(values);
+    }
+    entry.error = result.as_ref().err().map(|err| format!("{err:#}"));
+    entry.break_glass = break_glass.is_some();
+    entry.reason = break_glass;
+    if entry.break_glass {
+        let audit_path = cfg.audit.path.as_deref().unwrap_or(audit::DEFAULT_PATH);
+        audit::append(&config::resolve_path(config_path, audit_path), &entry)?;
+        announce_break_glass(cfg, &entry).await;
+    } else {
+        audit::record(config_path, cfg, &entry)?;
+    }
+    result
+}
+
+pub(crate) fn sorted_keys(values: &HashMap<String, String>) -> Vec<&str> {
+    let mut keys: Vec<&str> = values.keys().map(String::as_str).collect();
+    keys.sort_unstable();
+    keys
+}
+
+/// Prints the document, or writes it to `path` when given. The file is
+/// created private to the user, like the env file.
+pub(crate) fn emit(path: Option<&Path>, document: &str) -> Result<()> {
+    match path {
+        Some(path) => envfile::write_atomic(path, document)
+            .with_context(|| format!("failed to write {}", path.display())),
+        None => {
+            print!("{document}");
+            Ok(())
+        }
+    }
+}
+
+/// Prints an ExternalSecret for the operator to fetch the same secrets
+/// in-cluster. Only names are listed, nothing secret is read, so protected
+/// configs may export it.
+async fn export_external_secret(
+    config_path: &Path,
+    cfg: &Config,
+    output: Output<'_>,
+) -> Result<()> {
+    let sources = cfg.sources();
+    if output.secret_stores.len() != sources.len() {
+        bail!(
+            "--format external-secret needs one --secret-store per provider ({} configured)",
+            sources.len()
+        );
+    }
+    let mut providers = ProviderRegistry::default();
+    let connected = providers.connect(cfg)?;
+    let principal = cache::principals(&connected).await;
+    let result = remote_refs(&connected).await;
+
+    let mut entry = audit::Entry::new("export");
+    entry.principal = principal.as_deref();
+    entry.error = result.as_ref().err().map(|err| format!("{err:#}"));
+    audit::record(config_path, cfg, &entry)?;
+
+    let name = output.name.unwrap_or("envit");
+    emit(
+        output.path,
+        &external_secret(name, output.secret_stores, &result?),
+    )
+}
+
+/// Env key -> (provider index, secret name); later providers win, as in a
+/// pull.
+async fn remote_refs(
+    connected: &[(Source<'_>, Arc<CachedProvider>)],
+) -> Result<BTreeMap<String, (usize, String)>> {
+    let mut refs = BTreeMap::new();
+    for (index, (source, provider)) in connected.iter().enumerate() {
+        for (secret, key) in resolve::keys(*source, provider.as_ref()).await? {
+            refs.insert(key, (index, secret));
+        }
+    }
+    Ok(refs)
+}
+
+/// Renders the ExternalSecret. Keys from providers after the first name
+/// their store per entry (`sourceRef`).
+fn external_secret(
+    name: &str,
+    stores: &[String],
+    refs: &BTreeMap<String, (usize, String)>,
+) -> String {
+    let name = yaml_string(name);
+    let mut out = format!(
+        "apiVersion: external-secrets.io/v1\n\
+         kind: ExternalSecret\n\
+         metadata:\n  name: {name}\n\
+         spec:\n  refreshInterval: 1h\n  \
+         secretStoreRef:\n    kind: SecretStore\n    name: {}\n  \
+         target:\n    name: {name}\n",
+        yaml_string(&stores[0])
+    );
+    if refs.is_empty() {
+        out.push_str("  data: []\n");
+        return out;
+    }
+    out.push_str("  data:\n");
+    for (key, (index, secret)) in refs {
+        out.push_str(&format!(
+            "    - secretKey: {}\n      remoteRef:\n        key: {}\n",
+            yaml_string(key),
+            yaml_string(secret)
+        ));
+        if *index > 0 {
+            out.push_str(&format!(
+                "      sourceRef:\n        storeRef:\n          kind: SecretStore\n          name: {}\n",
+                yaml_string(&stores[*index])
+            ));
+        }
+    }
+    out
+}
+
+/// A compose `secrets:` block for the `[output.files]` envit writes, named
+/// after their lowercased keys, and each of `services` referencing all of
+/// them. Paths stay as configured (relative to the config), so the result
+/// belongs next to it, e.g. as a `compose.secrets.yaml` override.
+fn compose_secrets(cfg: &Config, services: &[String]) -> Result<String> {
+    if cfg.output.files.is_empty() {
+        bail!("no [output.files] configured; compose secrets are read from the files envit writes");
+    }
+    let mut files: Vec<_> = cfg.output.files.iter().collect();
+    files.sort_by_key(|(key, _)| *key);
+
+    let mut out = String::from("secrets:\n");
+    for (key, file) in &files {
+        let path = if Path::new(&file.path).is_absolute() || file.path.starts_with('.') {
+            file.path.clone()
+        } else {
+            format!("./{}", file.path)
+        };
+        out.push_str(&format!(
+            "  {}:\n    file: {}\n",
+            yaml_string(&key.to_ascii_lowercase()),
+            yaml_string(&path)
+        ));
+    }
+    if services.is_empty() {
+        return Ok(out);
+    }
+    out.push_str("services:\n");
+    for service in services {
+        out.push_str(&format!("  {}:\n    secrets:\n", yaml_string(service)));
+        for (key, _) in &files {
+            out.push_str(&format!(
+                "      - {}\n",
+                yaml_string(&key.to_ascii_lowercase())
+            ));
+        }
+    }
+    Ok(out)
+}
+
+async fn announce_break_glass(cfg: &Config, entry: &audit::Entry<'_>) {
+    eprintln!(
+        "*** BREAK-GLASS EXPORT by {} ({}): {} ***",
+        entry.user.as_deref().unwrap_or("unknown user"),
+        entry.principal.unwrap_or("unknown principal"),
+        entry.reason.unwrap_or_default(),
+    );
+    eprintln!("*** This export has been recorded in the audit log. ***");
+
+    if let Some(url) = &cfg.security.break_glass_webhook
+        && let Err(err) = notify(url, entry).await
+    {
+        // Incident response must not be blocked by a flaky webhook.
+        eprintln!("warning: failed to notify break-glass webhook: {err:#}");
+    }
+}
+
+async fn notify(url: &str, entry: &audit::Entry<'_>) -> Result<()> {
+    reqwest::Client::new()
+        .post(url)
+        .json(entry)
+        .send()
+        .await
+        .and_then(|res| res.error_for_status())
+        .with_context(|| format!("POST {url}"))?;
+    Ok(())
+}
+
+pub(crate) fn dotenv(values: &HashMap<String, String>, keys: &[&str]) -> String {
+    keys.iter()
+        .map(|key| format!("{key}={}\n", values[*key]))
+        .collect()
+}
+
+fn json_object(values: &HashMap<String, String>, keys: &[&str]) -> String {
+    let object: BTreeMap<&str, &str> = keys
+        .iter()
+        .map(|key| (*key, values[*key].as_str()))
+        .collect();
+    let json = serde_json::to_string_pretty(&object).expect("strings always serialize");
+    format!("{json}\n")
+}
+
+/// `export` lines for bash and POSIX shells; names that are not shell
+/// identifiers fail the export, as `export` would reject them.
+pub(crate) fn shell_exports(values: &HashMap<String, String>, keys: &[&str]) -> Result<String> {
+    let mut out = String::new();
+    for key in keys {
+        if !is_identifier(key) {
+            bail!("{key} is not a valid shell variable name");
+        }
+        out.push_str(&format!("export {key}={}\n", shell::quote(&values[*key])));
+    }
+    Ok(out)
+}
+
+/// An Opaque Secret for `kubectl apply -f -`. Values go in base64 `data`
+/// so no content can alter the manifest.
+fn k8s_secret(name: &str, values: &HashMap<String, String>, keys: &[&str]) -> Result<String> {
+    let mut out = format!(
+        "apiVersion: v1\nkind: Secret\nmetadata:\n  name: {}\ntype: Opaque\n",
+        yaml_string(name)
+    );
+    if keys.is_empty() {
+        out.push_str("data: {}\n");
+        return Ok(out);
+    }
+    out.push_str("data:\n");
+    for key in keys {
+        let valid = key
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.'));
+        if !valid {
+            bail!("{key} is not a valid Kubernetes Secret key");
+        }
+        out.push_str(&format!(
+            "  {}: {}\n",
+            yaml_string(key),
+            STANDARD.encode(&values[*key])
+        ));
+    }
+    Ok(out)
+}
+
+/// GitLab's dotenv reports take `KEY=VALUE` lines only: no quoting, no
+/// escapes, no multiline values, and (like Ansible) identifier names. Values
+/// it would mangle fail the whole export rather than arrive altered.
+fn gitlab_dotenv(values: &HashMap<String, String>, keys: &[&str]) -> Result<String> {
+    let mut out = String::new();
+    for key in keys {
+        let value = &values[*key];
+        if !is_identifier(key) {
+            bail!("{key} is not a valid GitLab variable name");
+        }
+        if value.contains(['\n', '\r']) {
+            bail!("{key} has a multiline value, which GitLab dotenv reports cannot hold");
+        }
+        // GitLab strips one pair of surrounding quotes.
+        let quoted = value.len() >= 2
+            && (value.starts_with('"') && value.ends_with('"')
+                || value.starts_with('\'') && value.ends_with('\''));
+        if quoted {
+            bail!("{key} has a quoted value, which GitLab dotenv reports would unquote");
+        }
+        out.push_str(&format!("{key}={value}\n"));
+    }
+    Ok(out)
+}
+
+/// `--under secrets.app` names the nested mapping the keys are placed in.
+fn parse_under(path: &str) -> Result<Vec<&str>> {
+    let segments: Vec<&str> = path.split('.').map(str::trim).collect();
+    if segments.iter().any(|s| s.is_empty()) {
+        bail!("--under must be a dotted path like secrets or app.env, got {path:?}");
+    }
+    Ok(segments)
+}
+
+/// Renders a YAML mapping. Keys and values are emitted as double-quoted
+/// scalars (JSON string syntax is valid YAML), so no value can change type
+/// or break the document. `value_tag` is prepended to every value.
+fn yaml_values(
+    values: &HashMap<String, String>,
+    keys: &[&str],
+    under: &[&str],
+    value_tag: &str,
+) -> String {
+    let mut out = String::new();
+    for (depth, segment) in under.iter().enumerate() {
+        out.push_str(&format!("{}{}:", "  ".repeat(depth), yaml_string(segment)));
+        if depth + 1 < under.len() || !keys.is_empty() {
+            out.push('\n');
+        }
+    }
+    let indent = "  ".repeat(under.len());
+    if keys.is_empty() {
+        out.push_str(if under.is_empty() { "{}\n" } else { " {}\n" });
+    }
+    for key in keys {
+        out.push_str(&format!(
+            "{indent}{}: {value_tag}{}\n",
+            yaml_string(key),
+            yaml_string(&values[*key])
+        ));
+    }
+    out
+}
+
+fn yaml_string(value: &str) -> String {
+    serde_json::to_string(value).expect("strings always serialize")
+}
+
+fn is_identifier(name: &str) -> bool {
+    let mut chars = name.chars();
+    chars
+        .next()
+        .is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+/// Encrypts with the user's ansible-vault setup (ansible.cfg or
+/// `ANSIBLE_VAULT_PASSWORD_FILE` supply the password).
+async fn ansible_vault_encrypt(plaintext: &str) -> Result<String> {
+    let mut child = tokio::process::Command::new("ansible-vault")
+        .args(["encrypt", "--output", "-"])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .context("failed to run ansible-vault")?;
+
+    let mut stdin = child.stdin.take().expect("stdin is piped");
+    stdin
+        .write_all(plaintext.as_bytes())
+        .await
+        .context("failed to send values to ansible-vault")?;
+    drop(stdin);
+
+    let output = child
+        .wait_with_output()
+        .await
+        .context("failed to run ansible-vault")?;
+    if !output.status.success() {
+        bail!(
+            "ansible-vault encrypt failed ({}): {}",
+            output.status,
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+    }
+    String::from_utf8(output.stdout).context("ansible-vault output is not UTF-8")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn yaml_values_nest_under_path_and_quote_everything() {
+        let values = HashMap::from([
+            ("DB_URL".to_string(), "postgres://a:b@h/db".to_string()),
+            ("FLAG".to_string(), "true".to_string()),
+            ("MULTI".to_string(), "line1\nline2 \"q\"".to_string()),
+        ]);
+        let keys = ["DB_URL", "FLAG", "MULTI"];
+
+        assert_eq!(
+            yaml_values(&values, &keys, &["app", "secrets"], ""),
+            "\"app\":\n  \"secrets\":\n    \"DB_URL\": \"postgres://a:b@h/db\"\n    \"FLAG\": \"true\"\n    \"MULTI\": \"line1\\nline2 \\\"q\\\"\"\n"
+        );
+        assert_eq!(
+            yaml_values(&values, &[], &["secrets"], ""),
+            "\"secrets\": {}\n"
+        );
+        assert_eq!(yaml_values(&values, &[], &[], ""), "{}\n");
+        assert!(parse_under("a..b").is_err());
+    }
+
+    #[test]
+    fn ansible_values_are_tagged_unsafe_and_names_checked() {
+        let values = HashMap::from([("TOKEN".to_string(), "{{ not_a_var }}".to_string())]);
+
+        assert_eq!(
+            yaml_values(&values, &["TOKEN"], &[], "!unsafe "),
+            "\"TOKEN\": !unsafe \"{{ not_a_var }}\"\n"
+        );
+        assert!(is_identifier("DATABASE_URL"));
+        assert!(is_identifier("_private"));
+        assert!(!is_identifier("2FA_SECRET"));
+        assert!(!is_identifier("api-key"));
+    }
+
+    #[test]
+    fn shell_and_k8s_secret_reject_keys_they_cannot_hold() {
+        let values = HashMap::from([
+            ("A".to_string(), "it's $HOME".to_string()),
+            ("api-key".to_string(), "k".to_string()),
+            ("a b".to_string(), "k".to_string()),
+        ]);
+
+        assert_eq!(
+            shell_exports(&values, &["A"]).unwrap(),
+            "export A='it'\\''s $HOME'\n"
+        );
+        assert!(shell_exports(&values, &["api-key"]).is_err());
+        assert!(k8s_secret("app", &values, &["api-key"]).is_ok());
+        assert!(k8s_secret("app", &values, &["a b"]).is_err());
+        assert!(
+            k8s_secret("app", &values, &[])
+                .unwrap()
+                .ends_with("data: {}\n")
+        );
+    }
+
+    #[test]
+    fn external_secret_references_each_key_through_its_providers_store() {
+        let stores = ["vault".to_string(), "legacy".to_string()];
+        let refs = BTreeMap::from([
+            ("DATABASE_URL".to_string(), (0, "database-url".to_string())),
+            ("LEGACY_TOKEN".to_string(), (1, "token".to_string())),
+        ]);
+
+        assert_eq!(
+            external_secret("app", &stores, &refs),
+            "apiVersion: external-secrets.io/v1\n\
+             kind: ExternalSecret\n\
+             metadata:\n  name: \"app\"\n\
+             spec:\n  refreshInterval: 1h\n  \
+             secretStoreRef:\n    kind: SecretStore\n    name: \"vault\"\n  \
+             target:\n    name: \"app\"\n  \
+             data:\n    \
+             - secretKey: \"DATABASE_URL\"\n      remoteRef:\n        key: \"database-url\"\n    \
+             - secretKey: \"LEGACY_TOKEN\"\n      remoteRef:\n        key: \"token\"\n      \
+             sourceRef:\n        storeRef:\n          kind: SecretStore\n          name: \"legacy\"\n"
+        );
+        assert!(external_secret("app", &stores, &BTreeMap::new()).ends_with("  data: []\n"));
+    }
+
+    #[test]
+    fn gitlab_dotenv_rejects_values_it_cannot_represent() {
+        let mut values = HashMap::from([
+            (
+                "DB_URL".to_string(),
+                "postgres://a:b@h/db?x=\"y\"".to_string(),
+            ),
+            ("EMPTY".to_string(), String::new()),
+        ]);
+        assert_eq!(
+            gitlab_dotenv(&values, &["DB_URL", "EMPTY"]).unwrap(),
+            "DB_URL=postgres://a:b@h/db?x=\"y\"\nEMPTY=\n"
+        );
+
+        values.insert("CERT".to_string(), "line1\nline2".to_string());
+        values.insert("QUOTED".to_string(), "'abc'".to_string());
+        values.insert("api-key".to_string(), "k".to_string());
+        for key in ["CERT", "QUOTED", "api-key"] {
+            assert!(gitlab_dotenv(&values, &[key]).is_err(), "{key}");
+        }
+    }
+}
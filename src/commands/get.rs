@@ -0,0 +1,53 @@
+use std::path::Path;
+
+use anyhow::{Context, Result, anyhow, bail};
+
+use crate::{
+    audit, config, plaintext,
+    provider::{SecretProvider, cache::ProviderRegistry},
+    resolve,
+};
+
+/// Prints one env key's value, fetching only the secret behind it (after
+/// `[map]` and prefixes; the last provider producing the key wins, as in a
+/// pull).
+pub async fn run(config_path: &Path, key: &str) -> Result<()> {
+    let cfg = config::load(config_path)?;
+    if cfg.security.protected {
+        bail!("config is protected: get would print a plaintext value");
+    }
+    plaintext::refuse(&cfg, "get would print a plaintext value")?;
+    let mut providers = ProviderRegistry::default();
+    let connected = providers.connect(&cfg)?;
+
+    let mut result = Err(anyhow!(
+        "no secret pulls to {key} (see `envit which --key {key}`)"
+    ));
+    for (source, provider) in connected.iter().rev() {
+        let secret = match resolve::keys(*source, provider.as_ref()).await {
+            Ok(keys) => keys.into_iter().find(|(_, k)| k == key).map(|(s, _)| s),
+            Err(err) => {
+                result = Err(err);
+                break;
+            }
+        };
+        if let Some(secret) = secret {
+            result = provider
+                .get_secret(&secret)
+                .await
+                .with_context(|| format!("failed to fetch secret {secret}"))
+                .and_then(|value| value.ok_or_else(|| anyhow!("secret {secret} has no value")));
+            break;
+        }
+    }
+
+    let mut entry = audit::Entry::new("get");
+    if result.is_ok() {
+        entry.exported_keys = vec![key];
+    }
+    entry.error = result.as_ref().err().map(|err| format!("{err:#}"));
+    audit::record(config_path, &cfg, &entry)?;
+
+    println!("{}", result?);
+    Ok(())
+}
@@ -0,0 +1,143 @@
+use std::{path::Path, time::SystemTime};
+
+use anyhow::{Context, Result, anyhow, bail};
+
+use crate::{
+    audit,
+    commands::{self, decrypt},
+    config::{self, Config},
+    messages::message,
+    provider::{SecretProvider, SecretVersion, cache::ProviderRegistry},
+    resolve,
+};
+
+/// Lists the versions of the secret behind `key`, oldest first, and marks
+/// the one the env file holds. That version is found by comparing values,
+/// newest first, so usually only one older value is fetched; values are
+/// never printed.
+pub async fn run(config_path: &Path, key: &str) -> Result<()> {
+    let cfg = config::load(config_path)?;
+    let mut providers = ProviderRegistry::default();
+    let connected = providers.connect(&cfg)?;
+
+    let mut found = None;
+    for (source, provider) in connected.iter().rev() {
+        let keys = resolve::keys(*source, provider.as_ref()).await?;
+        if let Some((secret, _)) = keys.into_iter().find(|(_, k)| k == key) {
+            found = Some((source.provider.kind.as_str(), secret, provider));
+            break;
+        }
+    }
+    let (kind, secret, provider) =
+        found.ok_or_else(|| anyhow!("no secret pulls to {key} (see `envit which --key {key}`)"))?;
+    if !provider.capabilities().versions {
+        bail!("provider {kind} keeps no version history");
+    }
+    let principal = provider.identity().await.unwrap_or(None);
+    let result = history(config_path, &cfg, key, &secret, provider.as_ref()).await;
+
+    let mut entry = audit::Entry::new("history");
+    entry.principal = principal.as_deref();
+    entry.dry_run = true;
+    entry.error = result.as_ref().err().map(|err| format!("{err:#}"));
+    audit::record(config_path, &cfg, &entry)?;
+    let (versions, local) = result?;
+
+    let env_file = &cfg.output.env_file;
+    let date = |time: Option<SystemTime>| {
+        time.map_or("-".to_string(), |time| {
+            humantime::format_rfc3339_seconds(time).to_string()
+        })
+    };
+    let header: Vec<String> = ["VERSION", "CREATED", "UPDATED", "ENABLED", "LOCAL"]
+        .into_iter()
+        .map(str::to_string)
+        .collect();
+    let cells: Vec<Vec<String>> = versions
+        .iter()
+        .map(|version| {
+            vec![
+                version.id.clone(),
+                date(version.created),
+                date(version.updated),
+                match version.enabled {
+                    Some(true) => "yes".to_string(),
+                    Some(false) => "no".to_string(),
+                    None => "-".to_string(),
+                },
+                match &local {
+                    Local::Version(id) if *id == version.id => env_file.clone(),
+                    _ => String::new(),
+                },
+            ]
+        })
+        .collect();
+    println!(
+        "{}",
+        message!("history_versions", secret = secret, count = versions.len())
+    );
+    commands::audit::print_aligned(&header, &cells);
+    match local {
+        Local::Version(_) | Local::Unknown => {}
+        Local::Absent => println!("{}", message!("history_absent", key = key, path = env_file)),
+        Local::NoMatch => println!(
+            "{}",
+            message!(
+                "history_no_match",
+                key = key,
+                path = env_file,
+                secret = secret
+            )
+        ),
+    }
+    Ok(())
+}
+
+/// Which version the env file holds.
+enum Local {
+    Version(String),
+    /// The key is not in the env file.
+    Absent,
+    /// Its value matches no readable version.
+    NoMatch,
+    /// The env file holds a path (`[output.files]`), not the value.
+    Unknown,
+}
+
+async fn history(
+    config_path: &Path,
+    cfg: &Config,
+    key: &str,
+    secret: &str,
+    provider: &dyn SecretProvider,
+) -> Result<(Vec<SecretVersion>, Local)> {
+    let versions = provider
+        .secret_versions(secret)
+        .await
+        .with_context(|| format!("failed to list the versions of secret {secret}"))?;
+    if cfg.output.files.contains_key(key) {
+        return Ok((versions, Local::Unknown));
+    }
+    let env_path = config::resolve_path(config_path, &cfg.output.env_file);
+    let local = if env_path.exists() {
+        decrypt::values(config_path, cfg, None)?.remove(key)
+    } else {
+        None
+    };
+    let Some(local) = local else {
+        return Ok((versions, Local::Absent));
+    };
+
+    // Disabled versions cannot be read.
+    for version in versions.iter().rev().filter(|v| v.enabled != Some(false)) {
+        let value = provider
+            .get_secret_version(secret, &version.id)
+            .await
+            .with_context(|| format!("failed to fetch version {} of {secret}", version.id))?;
+        if value.as_deref() == Some(local.as_str()) {
+            let id = version.id.clone();
+            return Ok((versions, Local::Version(id)));
+        }
+    }
+    Ok((versions, Local::NoMatch))
+}
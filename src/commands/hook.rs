@@ -0,0 +1,123 @@
+use clap::ValueEnum;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum Shell {
+    Bash,
+    Zsh,
+    Fish,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum Action {
+    /// Refresh the env file with `envit pull --quiet`
+    Pull,
+    /// Export the values into the shell (`envit env --export`) and unset
+    /// them again on leaving the directory
+    Export,
+}
+
+/// Exports the values, first recording how to put back what the keys held
+/// before (`export KEY='old'` or `unset KEY`) in `_ENVIT_RESTORE`.
+const POSIX_EXPORT: &str = r#"  local exports key
+  exports=$(envit env --export) || return
+  _ENVIT_RESTORE=
+  for key in $(printf '%s\n' "$exports" | sed -n 's/^export \([A-Za-z_][A-Za-z0-9_]*\)=.*/\1/p'); do
+    if eval "[ -n \"\${$key+x}\" ]"; then
+      _ENVIT_RESTORE="$_ENVIT_RESTORE export $key=$(eval "_envit_quote \"\$$key\"");"
+    else
+      _ENVIT_RESTORE="$_ENVIT_RESTORE unset $key;"
+    fi
+  done
+  eval "$exports"
+"#;
+
+const FISH_EXPORT: &str = r#"  set -l exports (envit env --export | string collect); or return
+  set -g _envit_keys (printf '%s\n' $exports | string replace -rf '^export ([A-Za-z_][A-Za-z0-9_]*)=.*' '$1')
+  for key in $_envit_keys
+    if set -q $key
+      set -g _envit_saved_$key $$key
+    end
+  end
+  printf '%s\n' $exports | source
+"#;
+
+const PULL: &str = "  envit pull --quiet\n";
+
+/// The shell code `eval "$(envit hook bash)"` (or `envit hook fish |
+/// source`) installs: it runs `action` on entering a directory with an
+/// `envit.toml` that `envit allow` trusts as it is now, since a config can
+/// run commands. Leaving puts back the values exported keys had before.
+/// Unlike direnv, parent directories are not searched.
+pub fn script(shell: Shell, action: Action) -> String {
+    let body = match (shell, action) {
+        (_, Action::Pull) => PULL,
+        (Shell::Fish, Action::Export) => FISH_EXPORT,
+        (Shell::Bash | Shell::Zsh, Action::Export) => POSIX_EXPORT,
+    };
+    match shell {
+        Shell::Bash => posix(
+            "bash",
+            body,
+            r#"case ";${PROMPT_COMMAND-};" in
+  *";_envit_hook;"*) ;;
+  *) PROMPT_COMMAND="_envit_hook${PROMPT_COMMAND:+;$PROMPT_COMMAND}" ;;
+esac
+"#,
+        ),
+        Shell::Zsh => posix(
+            "zsh",
+            body,
+            "autoload -Uz add-zsh-hook\nadd-zsh-hook chpwd _envit_hook\n_envit_hook\n",
+        ),
+        Shell::Fish => format!(
+            r#"# envit hook for fish: `envit hook fish | source` in config.fish
+function _envit_hook --on-variable PWD
+  if set -q _envit_keys
+    for key in $_envit_keys
+      set -l saved _envit_saved_$key
+      if set -q $saved
+        set -gx $key $$saved
+        set -e $saved
+      else
+        set -e $key
+      end
+    end
+    set -e _envit_keys
+  end
+  test -f envit.toml; or return
+  envit allow --check; or return
+{body}end
+_envit_hook
+"#
+        ),
+    }
+}
+
+/// bash and zsh share the hook; they differ in how it is registered. The
+/// hook keeps `$?` for the prompt.
+fn posix(shell: &str, body: &str, register: &str) -> String {
+    format!(
+        r#"# envit hook for {shell}: `eval "$(envit hook {shell})"` in ~/.{shell}rc
+_envit_quote() {{
+  local quote="'\''"
+  printf "'%s'" "${{1//\'/$quote}}"
+}}
+_envit_enter() {{
+  if [ -n "${{_ENVIT_RESTORE-}}" ]; then
+    eval "$_ENVIT_RESTORE"
+    unset _ENVIT_RESTORE
+  fi
+  [ -f envit.toml ] || return
+  envit allow --check || return
+{body}}}
+_envit_hook() {{
+  local exit_status=$?
+  if [ "$PWD" != "${{_ENVIT_DIR-}}" ]; then
+    _ENVIT_DIR=$PWD
+    _envit_enter
+  fi
+  return $exit_status
+}}
+{register}"#
+    )
+}
@@ -0,0 +1,197 @@
+use std::{collections::BTreeMap, fs, path::Path};
+
+use anyhow::{Context, Result, bail};
+use clap::ValueEnum;
+use serde_json::Value;
+
+use crate::{
+    commands::{
+        pull::CANARY_KEY,
+        push::{self, Action},
+    },
+    config, envfile,
+    messages::message,
+    owners,
+    provider::{Capability, SecretProvider, cache::ProviderRegistry},
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum Format {
+    /// KEY=VALUE lines, as in a .env file
+    Dotenv,
+    /// One JSON object of key -> value
+    Json,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct Options {
+    /// Show the plan and stop.
+    pub dry_run: bool,
+    /// Write without asking for confirmation.
+    pub yes: bool,
+    /// Replace secrets holding a different value instead of reporting them.
+    pub overwrite: bool,
+    pub override_owner: bool,
+    /// Secrets read and written at once.
+    pub concurrency: usize,
+}
+
+/// Creates the secrets behind the keys of an existing `.env` or JSON file
+/// in the config's provider, named the way `push` names them. Secrets that
+/// already hold a different value are reported as conflicts and left alone
+/// unless `overwrite`. Writes are batched, throttling-aware and resumable
+/// like `push`.
+pub async fn run(
+    config_path: &Path,
+    file: &Path,
+    format: Option<Format>,
+    options: Options,
+) -> Result<()> {
+    let Options {
+        dry_run,
+        yes,
+        overwrite,
+        override_owner,
+        concurrency,
+    } = options;
+    if concurrency == 0 {
+        bail!("--concurrency must be at least 1");
+    }
+    let cfg = config::load(config_path)?;
+    let provider_cfg = cfg.single_provider()?;
+    let raw =
+        fs::read_to_string(file).with_context(|| format!("failed to read {}", file.display()))?;
+    let format = format.unwrap_or(match file.extension() {
+        Some(ext) if ext == "json" => Format::Json,
+        _ => Format::Dotenv,
+    });
+    let mut entries = match format {
+        Format::Dotenv => envfile::parse_entries(&raw).into_iter().collect(),
+        Format::Json => {
+            json_entries(&raw).with_context(|| format!("invalid {}", file.display()))?
+        }
+    };
+    entries.remove(CANARY_KEY);
+    if entries.is_empty() {
+        bail!("{} has no keys to import", file.display());
+    }
+
+    let mut providers = ProviderRegistry::default();
+    let provider = providers.get(provider_cfg)?;
+    provider
+        .capabilities()
+        .require(Capability::Write, &provider_cfg.kind, "import")?;
+    let (mut plan, resumed) =
+        push::plan(config_path, &cfg, &provider, &entries, concurrency).await?;
+    if resumed > 0 {
+        println!("{}", message!("import_resumed", count = resumed));
+    }
+
+    review(&mut plan, entries.len() - resumed, overwrite);
+    if plan.is_empty() {
+        println!("{}", message!("import_nothing"));
+        if !dry_run {
+            push::remove_progress(config_path)?;
+        }
+        return Ok(());
+    }
+    if dry_run {
+        return Ok(());
+    }
+    let planned_keys: Vec<_> = plan.iter().map(|planned| planned.key).collect();
+    owners::check(&cfg, &planned_keys, override_owner)?;
+    if !yes
+        && !push::confirm(
+            &message!(
+                "import_confirm",
+                count = plan.len(),
+                kind = provider_cfg.kind
+            ),
+            "refusing to import without confirmation; review with --dry-run and pass --yes",
+        )?
+    {
+        bail!("import cancelled");
+    }
+
+    push::write(
+        config_path,
+        &cfg,
+        &provider,
+        &plan,
+        "import",
+        override_owner,
+        concurrency,
+    )
+    .await?;
+    println!("{}", message!("import_done", count = plan.len()));
+    Ok(())
+}
+
+/// Prints the plan for `entries` keys (those not resumed), marking secrets
+/// that hold a different value as conflicts and dropping them from `plan`
+/// unless `overwrite`. Shared with `copy`.
+pub fn review(plan: &mut Vec<push::Planned<'_>>, entries: usize, overwrite: bool) {
+    let mut conflicts = 0;
+    for planned in plan.iter() {
+        match planned.action {
+            Action::Add => println!("ADD {} <- {}=********", planned.secret, planned.key),
+            Action::Update if overwrite => {
+                println!("UPDATE {} <- {}=********", planned.secret, planned.key)
+            }
+            Action::Update => {
+                conflicts += 1;
+                println!(
+                    "CONFLICT {} <- {}: the provider holds a different value",
+                    planned.secret, planned.key
+                );
+            }
+        }
+    }
+    if !overwrite {
+        plan.retain(|planned| planned.action == Action::Add);
+    }
+    let unchanged = entries - plan.len() - conflicts;
+    if unchanged > 0 {
+        println!("{}", message!("import_unchanged", count = unchanged));
+    }
+    if conflicts > 0 {
+        println!("{}", message!("import_conflicts", count = conflicts));
+    }
+}
+
+/// A flat JSON object; numbers and booleans are taken as their text.
+fn json_entries(raw: &str) -> Result<BTreeMap<String, String>> {
+    let Value::Object(object) = serde_json::from_str(raw)? else {
+        bail!("expected a JSON object of key -> value");
+    };
+    object
+        .into_iter()
+        .map(|(key, value)| {
+            let value = match value {
+                Value::String(value) => value,
+                Value::Number(_) | Value::Bool(_) => value.to_string(),
+                _ => bail!("{key} must be a string, number or boolean"),
+            };
+            Ok((key, value))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn json_entries_take_scalars_as_text() {
+        assert_eq!(
+            json_entries(r#"{"A": "x", "PORT": 5432, "DEBUG": false}"#).unwrap(),
+            BTreeMap::from([
+                ("A".to_string(), "x".to_string()),
+                ("DEBUG".to_string(), "false".to_string()),
+                ("PORT".to_string(), "5432".to_string()),
+            ])
+        );
+        assert!(json_entries(r#"{"A": {"nested": 1}}"#).is_err());
+        assert!(json_entries(r#"["A"]"#).is_err());
+    }
+}
@@ -0,0 +1,240 @@
+use std::{
+    collections::BTreeMap,
+    fs,
+    io::{self, BufRead, Write},
+    path::Path,
+};
+
+use anyhow::{Context, Result, bail};
+use clap::ValueEnum;
+
+use crate::{
+    commands::schema,
+    config::{self, Config},
+    messages::message,
+    provider::{self, cache::ProviderRegistry},
+    resolve,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum Template {
+    /// Azure Key Vault service: audit log, rollback snapshots, expiry
+    /// comments, deprecation grace, and a protected `prod` profile on its own
+    /// vault
+    MicroserviceAzure,
+}
+
+/// Asks for the provider kind, vault URL and env file, optionally lists the
+/// provider's secrets into `[map]`, and writes the config. Questions go to
+/// stderr and answers are read line by line from stdin, so it can be
+/// scripted; an empty answer takes the default. With a `template`, only
+/// the template's questions are asked and it writes a complete setup.
+pub async fn run(config_path: &Path, force: bool, template: Option<Template>) -> Result<()> {
+    if config_path.exists() && !force {
+        bail!(
+            "{} already exists (use --force to overwrite)",
+            config_path.display()
+        );
+    }
+    let mut stdin = io::stdin().lock();
+    if let Some(template) = template {
+        return from_template(config_path, force, template, &mut stdin);
+    }
+
+    let kinds: Vec<_> = provider::KINDS
+        .iter()
+        .filter(|kind| kind.compiled)
+        .map(|kind| kind.name)
+        .collect();
+    let default_kind = kinds.first().copied().unwrap_or("azure_key_vault");
+    let kind = ask(
+        &mut stdin,
+        &format!("Provider kind ({})", kinds.join(", ")),
+        Some(default_kind),
+    )?;
+    if !kinds.contains(&kind.as_str()) {
+        bail!(
+            "provider kind {kind} is not available (expected one of: {})",
+            kinds.join(", ")
+        );
+    }
+    let vault_url = if kind == "azure_key_vault" {
+        Some(ask(&mut stdin, "Vault URL", None)?)
+    } else {
+        None
+    };
+    let env_file = ask(&mut stdin, "Env file", Some(".env"))?;
+    let probe = ask(
+        &mut stdin,
+        "Seed [map] from the provider's existing secrets? (y/n)",
+        Some("n"),
+    )?;
+
+    let mut content = format!(
+        "version = 1\n\n[output]\nenv_file = {}\ncreate_if_missing = true\n\n[provider]\nkind = {}\n",
+        quote(&env_file),
+        quote(&kind)
+    );
+    match &vault_url {
+        Some(url) => content.push_str(&format!("vault_url = {}\n", quote(url))),
+        None => {
+            content.push_str("# Settings for this kind: see the Providers section of the README.\n")
+        }
+    }
+
+    let mut mapped = 0;
+    if matches!(probe.as_str(), "y" | "Y" | "yes") {
+        let cfg: Config = toml::from_str(&content).expect("generated config parses");
+        config::validate(&cfg).context("cannot probe the provider with these settings")?;
+        let map = seed_map(&cfg).await?;
+        mapped = map.len();
+        content.push_str("\n[map]\n");
+        for (key, secret) in &map {
+            content.push_str(&format!("{key} = {}\n", quote(secret)));
+        }
+    }
+
+    fs::write(config_path, &content)
+        .with_context(|| format!("failed to write {}", config_path.display()))?;
+    println!(
+        "{}",
+        message!("init_done", path = config_path.display(), count = mapped)
+    );
+    Ok(())
+}
+
+/// Writes the config, the `prod` profile next to it (`envit.prod.toml`, see
+/// `envit compare`) and the JSON Schema both point editors at.
+fn from_template(
+    config_path: &Path,
+    force: bool,
+    template: Template,
+    stdin: &mut impl BufRead,
+) -> Result<()> {
+    let Template::MicroserviceAzure = template;
+    if !provider::KINDS
+        .iter()
+        .any(|kind| kind.name == "azure_key_vault" && kind.compiled)
+    {
+        bail!("the microservice-azure template needs the azure_key_vault provider (feature azure)");
+    }
+    let prod_path = config_path.with_file_name("envit.prod.toml");
+    let schema_path = config_path.with_file_name(SCHEMA_FILE);
+    if prod_path.exists() && !force {
+        bail!(
+            "{} already exists (use --force to overwrite)",
+            prod_path.display()
+        );
+    }
+    let vault_url = ask(stdin, "Vault URL (development)", None)?;
+    let prod_vault_url = ask(stdin, "Vault URL (production)", None)?;
+
+    let files = [
+        (config_path, microservice_azure(&vault_url, ".env", false)),
+        (
+            prod_path.as_path(),
+            microservice_azure(&prod_vault_url, ".env.prod", true),
+        ),
+    ];
+    for (path, content) in &files {
+        let cfg: Config = toml::from_str(content).expect("template config parses");
+        config::validate(&cfg)
+            .with_context(|| format!("the answers do not make a valid {}", path.display()))?;
+    }
+    for (path, content) in files
+        .into_iter()
+        .chain([(schema_path.as_path(), schema::render()? + "\n")])
+    {
+        fs::write(path, content).with_context(|| format!("failed to write {}", path.display()))?;
+    }
+    println!(
+        "{}",
+        message!(
+            "init_template_done",
+            path = config_path.display(),
+            profile = prod_path.display(),
+            schema = schema_path.display(),
+        )
+    );
+    Ok(())
+}
+
+const SCHEMA_FILE: &str = "envit.schema.json";
+
+fn microservice_azure(vault_url: &str, env_file: &str, protected: bool) -> String {
+    let mut content = format!(
+        "#:schema ./{SCHEMA_FILE}
+# Regenerate the schema after upgrading envit: envit schema > {SCHEMA_FILE}
+version = 1
+
+[output]
+env_file = {env_file}
+create_if_missing = true
+# Keys the vault stops providing stay two weeks behind a comment.
+deprecation_grace = \"14d\"
+# Snapshots for `envit rollback`.
+backups = 5
+expiry_comments = true
+verify_versions = true
+
+[provider]
+kind = \"azure_key_vault\"
+vault_url = {vault_url}
+
+# Env key = secret name, where the name a pull derives does not fit.
+[map]
+
+# Overrides for keys the name heuristics classify wrongly (see `envit audit`).
+[classify]
+\"*_CONNECTION_STRING\" = \"connection_string\"
+
+[audit]
+path = \".envit/audit.jsonl\"
+",
+        env_file = quote(env_file),
+        vault_url = quote(vault_url),
+    );
+    if protected {
+        content.push_str(
+            "
+# Production: `get`, `diff --values`, `direnv` and `tf-external` refuse to hand
+# out values, and `export` needs --break-glass.
+[security]
+protected = true
+",
+        );
+    }
+    content
+}
+
+/// Every secret the provider lists, under the env key a pull derives for it.
+async fn seed_map(cfg: &Config) -> Result<BTreeMap<String, String>> {
+    let mut providers = ProviderRegistry::default();
+    let mut map = BTreeMap::new();
+    for (source, provider) in providers.connect(cfg)? {
+        for (secret, key) in resolve::keys(source, provider.as_ref()).await? {
+            map.insert(key, secret);
+        }
+    }
+    Ok(map)
+}
+
+fn ask(stdin: &mut impl BufRead, question: &str, default: Option<&str>) -> Result<String> {
+    match default {
+        Some(default) => eprint!("{question} [{default}]: "),
+        None => eprint!("{question}: "),
+    }
+    io::stderr().flush()?;
+    let mut answer = String::new();
+    stdin.read_line(&mut answer)?;
+    match (answer.trim(), default) {
+        ("", Some(default)) => Ok(default.to_string()),
+        ("", None) => bail!("{question} is required"),
+        (answer, _) => Ok(answer.to_string()),
+    }
+}
+
+/// A TOML basic string.
+fn quote(value: &str) -> String {
+    toml::Value::String(value.to_string()).to_string()
+}
@@ -0,0 +1,128 @@
+use std::path::Path;
+
+use anyhow::{Context, Result};
+use serde::Serialize;
+
+use crate::{commands::pull, config, exit, lock, provider::ProviderError};
+
+#[derive(Debug, Serialize)]
+struct Status<'a> {
+    status: &'static str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    env_file: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    principal: Option<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    keys: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    changed_keys: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<String>,
+}
+
+impl Status<'_> {
+    fn failed(status: &'static str, error: &anyhow::Error) -> Self {
+        Status {
+            status,
+            env_file: None,
+            principal: None,
+            keys: None,
+            changed_keys: None,
+            error: Some(format!("{error:#}")),
+        }
+    }
+}
+
+/// One pull for an init container: prints a single JSON status line and
+/// returns the process exit code.
+pub async fn run(config_path: &Path, env_file: Option<&Path>, mode: u32) -> i32 {
+    let mut cfg = match config::load(config_path) {
+        Ok(cfg) => cfg,
+        Err(err) => return report(&Status::failed("config_error", &err), exit::CONFIG),
+    };
+    if let Some(env_file) = env_file {
+        cfg.output.env_file = env_file.display().to_string();
+    }
+
+    let outcome = pull::sync(config_path, &cfg, false, false, lock::Mode::Use).await;
+    let result = outcome
+        .result
+        .and_then(|written| restrict(&outcome.env_path, mode).map(|()| written));
+    match result {
+        Ok(written) => report(
+            &Status {
+                status: "ok",
+                env_file: Some(outcome.env_path.display().to_string()),
+                principal: outcome.principal.as_deref(),
+                keys: Some(written.keys),
+                changed_keys: Some(written.changes.len()),
+                error: None,
+            },
+            0,
+        ),
+        Err(err) => {
+            let (status, code) = if ProviderError::is_auth(&err) {
+                ("auth_error", exit::AUTH)
+            } else {
+                ("transient_error", exit::TRANSIENT)
+            };
+            let mut status = Status::failed(status, &err);
+            status.principal = outcome.principal.as_deref();
+            report(&status, code)
+        }
+    }
+}
+
+/// Parses an octal permission mode such as `0600` or `640`.
+pub fn parse_mode(raw: &str) -> Result<u32, String> {
+    u32::from_str_radix(raw, 8)
+        .ok()
+        .filter(|mode| *mode <= 0o777)
+        .ok_or_else(|| format!("invalid file mode {raw:?}, expected octal like 0600"))
+}
+
+fn report(status: &Status, code: i32) -> i32 {
+    println!(
+        "{}",
+        serde_json::to_string(status).expect("status serializes")
+    );
+    code
+}
+
+/// The env file lands on a volume shared with the app container; only the
+/// requested permission bits are left on it.
+#[cfg(unix)]
+fn restrict(path: &Path, mode: u32) -> Result<()> {
+    use std::{fs, os::unix::fs::PermissionsExt};
+
+    fs::set_permissions(path, fs::Permissions::from_mode(mode))
+        .with_context(|| format!("failed to set permissions on {}", path.display()))
+}
+
+#[cfg(not(unix))]
+fn restrict(_path: &Path, _mode: u32) -> Result<()> {
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn modes_are_octal_permission_bits() {
+        assert_eq!(parse_mode("0600"), Ok(0o600));
+        assert_eq!(parse_mode("640"), Ok(0o640));
+        assert!(parse_mode("0800").is_err());
+        assert!(parse_mode("4755").is_err());
+    }
+
+    #[test]
+    fn auth_errors_are_found_through_context() {
+        let err = anyhow::Error::new(ProviderError::Auth("expired".to_string()))
+            .context("failed to list secrets");
+        assert!(ProviderError::is_auth(&err));
+        assert!(!ProviderError::is_auth(&anyhow::anyhow!(
+            "connection reset"
+        )));
+    }
+}
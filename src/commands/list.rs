@@ -0,0 +1,107 @@
+use std::{collections::HashMap, fs, path::Path, sync::Arc};
+
+use anyhow::Result;
+
+use crate::{
+    audit, codec,
+    commands::pull,
+    config::{self, Config, Source},
+    envfile,
+    provider::cache::{self, CachedProvider, ProviderRegistry},
+    resolve::{self, Resolved},
+};
+
+/// Prints every provider secret with its env key and what a pull would do
+/// with it (ADD, UPDATE or unchanged), sorted by key. Values are fetched to
+/// compare but never printed. With `table`, columns are aligned under a
+/// header; otherwise lines are tab-separated for scripts.
+pub async fn run(config_path: &Path, table: bool) -> Result<()> {
+    let cfg = config::load(config_path)?;
+    let env_path = config::resolve_path(config_path, &cfg.output.env_file);
+
+    let mut providers = ProviderRegistry::default();
+    let connected = providers.connect(&cfg)?;
+    let principal = cache::principals(&connected).await;
+    let result = entries(&connected).await;
+
+    let mut entry = audit::Entry::new("list");
+    entry.principal = principal.as_deref();
+    entry.env_file = Some(env_path.display().to_string());
+    entry.dry_run = true;
+    entry.error = result.as_ref().err().map(|err| format!("{err:#}"));
+    audit::record(config_path, &cfg, &entry)?;
+    let mut entries = result?;
+    entries.sort_by(|a, b| (&a.key, &a.secret).cmp(&(&b.key, &b.secret)));
+
+    let local: HashMap<_, _> = match codec::read_env_file(config_path, &cfg, None)? {
+        Some(raw) => envfile::parse_entries(&raw).into_iter().collect(),
+        None => HashMap::new(),
+    };
+    let rows: Vec<_> = entries
+        .iter()
+        .map(|entry| {
+            let status = status(config_path, &cfg, &local, entry);
+            [entry.secret.as_str(), entry.key.as_str(), status]
+        })
+        .collect();
+
+    if !table {
+        for row in &rows {
+            println!("{}", row.join("\t"));
+        }
+        return Ok(());
+    }
+    let header = ["SECRET", "KEY", "STATUS"];
+    let widths: Vec<_> = (0..header.len())
+        .map(|col| {
+            rows.iter()
+                .chain([&header])
+                .map(|row| row[col].len())
+                .max()
+                .unwrap_or_default()
+        })
+        .collect();
+    for row in [&header].into_iter().chain(&rows) {
+        println!(
+            "{:<w0$}  {:<w1$}  {}",
+            row[0],
+            row[1],
+            row[2],
+            w0 = widths[0],
+            w1 = widths[1]
+        );
+    }
+    Ok(())
+}
+
+async fn entries(connected: &[(Source<'_>, Arc<CachedProvider>)]) -> Result<Vec<Resolved>> {
+    let mut entries = Vec::new();
+    for (source, provider) in connected {
+        entries.extend(resolve::resolve_entries(*source, provider.as_ref()).await?);
+    }
+    Ok(entries)
+}
+
+fn status(
+    config_path: &Path,
+    cfg: &Config,
+    local: &HashMap<String, String>,
+    entry: &Resolved,
+) -> &'static str {
+    // `[output.files]` keys hold a path; their value lives in the file.
+    let (current, wanted) = match cfg.output.files.get(&entry.key) {
+        Some(file) if local.contains_key(&entry.key) => (
+            fs::read(config::resolve_path(config_path, &file.path)).ok(),
+            pull::file_content(file, &entry.key, &entry.value).ok(),
+        ),
+        _ => (
+            local.get(&entry.key).map(|value| value.as_bytes().to_vec()),
+            Some(entry.value.as_bytes().to_vec()),
+        ),
+    };
+    match current {
+        None => "ADD",
+        Some(current) if Some(&current) != wanted.as_ref() => "UPDATE",
+        Some(_) => "unchanged",
+    }
+}
@@ -0,0 +1,166 @@
+use std::{collections::BTreeMap, env, fs, path::Path, sync::Arc, time::SystemTime};
+
+use anyhow::{Context, Result, anyhow, bail};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    audit,
+    config::{self, Source},
+    crypto::{MacAlgorithm, to_hex},
+    envfile,
+    messages::message,
+    provider::{
+        SecretProvider,
+        cache::{self, CachedProvider, ProviderRegistry},
+    },
+    resolve,
+};
+
+const KEY_ENV: &str = "ENVIT_MANIFEST_KEY";
+const FORMAT_VERSION: u32 = 1;
+
+/// Which secrets a build depends on, without their values. Value hashes and
+/// the signature are MACs keyed with `ENVIT_MANIFEST_KEY` (`[crypto] mac`),
+/// so a leaked manifest cannot be used to brute-force low-entropy secrets.
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct Manifest {
+    version: u32,
+    generated_at: String,
+    config: String,
+    algorithm: MacAlgorithm,
+    secrets: Vec<Entry>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    signature: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct Entry {
+    key: String,
+    provider: String,
+    secret: String,
+    version: Option<String>,
+    hash: String,
+}
+
+/// Writes a signed manifest of every resolved key to `output`, or stdout.
+pub async fn run(config_path: &Path, output: Option<&Path>) -> Result<()> {
+    let key = signing_key()?;
+    let cfg = config::load(config_path)?;
+
+    let mut providers = ProviderRegistry::default();
+    let connected = providers.connect(&cfg)?;
+    let principal = cache::principals(&connected).await;
+
+    let algorithm = cfg.crypto.mac;
+    let result = collect(algorithm, &key, &connected).await;
+
+    let mut entry = audit::Entry::new("manifest");
+    entry.principal = principal.as_deref();
+    entry.error = result.as_ref().err().map(|err| format!("{err:#}"));
+    audit::record(config_path, &cfg, &entry)?;
+    let secrets = result?;
+
+    let mut manifest = Manifest {
+        version: FORMAT_VERSION,
+        generated_at: humantime::format_rfc3339_seconds(SystemTime::now()).to_string(),
+        config: config_path.display().to_string(),
+        algorithm,
+        secrets: secrets.into_values().collect(),
+        signature: None,
+    };
+    manifest.signature = Some(to_hex(
+        &algorithm.mac(&key, &serde_json::to_vec(&manifest)?),
+    ));
+
+    let mut rendered = serde_json::to_string_pretty(&manifest)?;
+    rendered.push('\n');
+    match output {
+        Some(path) => {
+            envfile::write_atomic(path, &rendered)
+                .with_context(|| format!("failed to write {}", path.display()))?;
+            println!(
+                "{}",
+                message!(
+                    "manifest_written",
+                    count = manifest.secrets.len(),
+                    path = path.display()
+                )
+            );
+        }
+        None => print!("{rendered}"),
+    }
+    Ok(())
+}
+
+async fn collect(
+    algorithm: MacAlgorithm,
+    key: &[u8],
+    connected: &[(Source<'_>, Arc<CachedProvider>)],
+) -> Result<BTreeMap<String, Entry>> {
+    let mut secrets = BTreeMap::new();
+    for (source, provider) in connected {
+        for (meta, env_key) in resolve::listed_keys(*source, provider.as_ref()).await? {
+            // One read, so the hash and the version describe the same value.
+            let Some(fetched) = provider
+                .fetch_secret(&meta.name)
+                .await
+                .with_context(|| format!("failed to fetch secret {}", meta.name))?
+            else {
+                continue;
+            };
+            // Later sources win, as in `pull`.
+            secrets.insert(
+                env_key.clone(),
+                Entry {
+                    key: env_key,
+                    provider: source.provider.kind.clone(),
+                    secret: meta.name,
+                    version: fetched.version,
+                    hash: format!(
+                        "{}:{}",
+                        algorithm.name(),
+                        to_hex(&algorithm.mac(key, fetched.value.as_bytes()))
+                    ),
+                },
+            );
+        }
+    }
+    Ok(secrets)
+}
+
+/// Checks a manifest's signature against `ENVIT_MANIFEST_KEY`.
+pub fn verify(path: &Path) -> Result<()> {
+    let key = signing_key()?;
+    let raw = fs::read_to_string(path)
+        .with_context(|| format!("failed to read manifest: {}", path.display()))?;
+    let mut manifest: Manifest = serde_json::from_str(&raw)
+        .with_context(|| format!("invalid manifest: {}", path.display()))?;
+    let signature = manifest
+        .signature
+        .take()
+        .ok_or_else(|| anyhow!("manifest is not signed"))?;
+
+    let signed = serde_json::to_vec(&manifest)?;
+    if !manifest.algorithm.verify(&key, &signed, &signature) {
+        bail!("manifest signature does not match (modified manifest or different {KEY_ENV})");
+    }
+    println!(
+        "{}",
+        message!(
+            "manifest_valid",
+            count = manifest.secrets.len(),
+            config = manifest.config,
+            at = manifest.generated_at,
+        )
+    );
+    Ok(())
+}
+
+fn signing_key() -> Result<Vec<u8>> {
+    match env::var(KEY_ENV) {
+        Ok(key) if !key.is_empty() => Ok(key.into_bytes()),
+        _ => bail!("{KEY_ENV} is not set (manifest signing key)"),
+    }
+}
@@ -0,0 +1,189 @@
+use std::{fs, path::Path};
+
+use anyhow::{Context, Result, bail};
+use toml_edit::{DocumentMut, value};
+
+use crate::{config::CURRENT_VERSION, messages::message};
+
+/// One config version bump: rewrites a version `from` document (renamed or
+/// restructured fields) into version `from + 1`.
+struct Migration {
+    from: u32,
+    summary: &'static str,
+    apply: fn(&mut DocumentMut) -> Result<()>,
+}
+
+/// Every bump of `config::CURRENT_VERSION` adds its step here, so older
+/// configs keep working through `envit migrate`.
+const MIGRATIONS: &[Migration] = &[];
+
+/// Upgrades the config to `CURRENT_VERSION` in place, keeping comments and
+/// layout, and prints a diff of what changed. With `dry_run`, only prints.
+pub fn run(config_path: &Path, dry_run: bool) -> Result<()> {
+    let raw = fs::read_to_string(config_path)
+        .with_context(|| format!("failed to read config file: {}", config_path.display()))?;
+    let mut doc: DocumentMut = raw
+        .parse()
+        .with_context(|| format!("failed to parse TOML config: {}", config_path.display()))?;
+
+    let from = version(&doc)?;
+    let summaries = upgrade(&mut doc, MIGRATIONS)?;
+    if summaries.is_empty() {
+        println!(
+            "{}",
+            message!(
+                "migrate_current",
+                path = config_path.display(),
+                version = CURRENT_VERSION
+            )
+        );
+        return Ok(());
+    }
+    let migrated = doc.to_string();
+
+    println!("--- {} (version {from})", config_path.display());
+    println!("+++ {} (version {CURRENT_VERSION})", config_path.display());
+    for line in diff(&raw, &migrated) {
+        println!("{line}");
+    }
+    for summary in &summaries {
+        println!("* {summary}");
+    }
+    if dry_run {
+        return Ok(());
+    }
+    fs::write(config_path, migrated)
+        .with_context(|| format!("failed to write {}", config_path.display()))?;
+    println!(
+        "{}",
+        message!(
+            "migrate_done",
+            path = config_path.display(),
+            version = CURRENT_VERSION
+        )
+    );
+    Ok(())
+}
+
+fn version(doc: &DocumentMut) -> Result<u32> {
+    doc.get("version")
+        .and_then(|item| item.as_integer())
+        .and_then(|version| u32::try_from(version).ok())
+        .context("config has no valid `version`")
+}
+
+/// Applies `migrations` one version at a time up to `CURRENT_VERSION`;
+/// returns what each step did (nothing when the config is current).
+fn upgrade(doc: &mut DocumentMut, migrations: &[Migration]) -> Result<Vec<&'static str>> {
+    let mut current = version(doc)?;
+    if current > CURRENT_VERSION {
+        bail!(
+            "config version {current} is newer than this envit supports ({CURRENT_VERSION}); upgrade envit"
+        );
+    }
+    let mut summaries = Vec::new();
+    while current < CURRENT_VERSION {
+        let Some(migration) = migrations.iter().find(|m| m.from == current) else {
+            bail!("no migration from config version {current}");
+        };
+        (migration.apply)(doc)
+            .with_context(|| format!("failed to migrate from version {current}"))?;
+        current += 1;
+        doc["version"] = value(i64::from(current));
+        summaries.push(migration.summary);
+    }
+    Ok(summaries)
+}
+
+/// Line diff of `old` and `new` (longest common subsequence), with the
+/// unchanged lines left out: `-` for removed, `+` for added lines.
+fn diff(old: &str, new: &str) -> Vec<String> {
+    let old: Vec<_> = old.lines().collect();
+    let new: Vec<_> = new.lines().collect();
+    let mut common = vec![vec![0usize; new.len() + 1]; old.len() + 1];
+    for i in (0..old.len()).rev() {
+        for j in (0..new.len()).rev() {
+            common[i][j] = if old[i] == new[j] {
+                common[i + 1][j + 1] + 1
+            } else {
+                common[i + 1][j].max(common[i][j + 1])
+            };
+        }
+    }
+
+    let mut out = Vec::new();
+    let (mut i, mut j) = (0, 0);
+    while i < old.len() || j < new.len() {
+        if i < old.len() && j < new.len() && old[i] == new[j] {
+            i += 1;
+            j += 1;
+        } else if i < old.len() && (j == new.len() || common[i + 1][j] >= common[i][j + 1]) {
+            out.push(format!("-{}", old[i]));
+            i += 1;
+        } else {
+            out.push(format!("+{}", new[j]));
+            j += 1;
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Stands in for a real step: `[output] file` became `env_file`.
+    fn rename_output_file(doc: &mut DocumentMut) -> Result<()> {
+        if let Some(output) = doc.get_mut("output").and_then(|o| o.as_table_like_mut())
+            && let Some(file) = output.remove("file")
+        {
+            output.insert("env_file", file);
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn upgrade_rewrites_renamed_fields_and_keeps_comments() {
+        let steps = [Migration {
+            from: CURRENT_VERSION - 1,
+            summary: "renamed output.file to output.env_file",
+            apply: rename_output_file,
+        }];
+        let old = format!(
+            "version = {}\n\n# where pulls write\n[output]\nfile = \".env.local\"\n",
+            CURRENT_VERSION - 1
+        );
+        let mut doc: DocumentMut = old.parse().unwrap();
+
+        let summaries = upgrade(&mut doc, &steps).unwrap();
+
+        assert_eq!(summaries, ["renamed output.file to output.env_file"]);
+        let new = doc.to_string();
+        assert_eq!(
+            new,
+            format!(
+                "version = {CURRENT_VERSION}\n\n# where pulls write\n[output]\nenv_file = \".env.local\"\n"
+            )
+        );
+        assert_eq!(
+            diff(&old, &new),
+            [
+                format!("-version = {}", CURRENT_VERSION - 1),
+                format!("+version = {CURRENT_VERSION}"),
+                "-file = \".env.local\"".to_string(),
+                "+env_file = \".env.local\"".to_string(),
+            ]
+        );
+        assert!(upgrade(&mut doc, &steps).unwrap().is_empty());
+    }
+
+    #[test]
+    fn upgrade_refuses_newer_and_unknown_versions() {
+        let mut newer: DocumentMut = format!("version = {}\n", CURRENT_VERSION + 1)
+            .parse()
+            .unwrap();
+        assert!(upgrade(&mut newer, MIGRATIONS).is_err());
+        let mut unknown: DocumentMut = "version = 0\n".parse().unwrap();
+        assert!(upgrade(&mut unknown, &[]).is_err());
+    }
+}
@@ -0,0 +1,47 @@
+pub mod agent;
+pub mod allow;
+pub mod assert;
+pub mod audit;
+pub mod clean;
+pub mod compare;
+pub mod config;
+pub mod container;
+pub mod copy;
+pub mod decrypt;
+pub mod delete;
+pub mod devcontainer;
+pub mod diff;
+pub mod direnv;
+pub mod doctor;
+pub mod encrypt;
+pub mod env;
+pub mod example;
+pub mod expiry;
+pub mod export;
+pub mod get;
+pub mod history;
+pub mod hook;
+pub mod import;
+pub mod init;
+pub mod k8s_init;
+pub mod list;
+pub mod manifest;
+pub mod migrate;
+pub mod owners;
+pub mod prune;
+pub mod pull;
+pub mod push;
+pub mod rename;
+pub mod rollback;
+pub mod rotate;
+pub mod run;
+pub mod schedule;
+pub mod schema;
+pub mod set;
+pub mod status;
+pub mod tf_external;
+pub mod validate;
+pub mod vault;
+pub mod watch;
+pub mod which;
+pub mod why_failed;
@@ -0,0 +1,42 @@
+use std::{collections::BTreeMap, env, path::Path};
+
+use anyhow::Result;
+
+use crate::{
+    config,
+    messages::message,
+    owners::{self, TEAM_ENV},
+    provider::cache::ProviderRegistry,
+    resolve,
+};
+
+/// Prints every key the config's providers produce with its owning team.
+/// Only secret names are listed; no values are fetched.
+pub async fn run(config_path: &Path) -> Result<()> {
+    let cfg = config::load(config_path)?;
+    let mut providers = ProviderRegistry::default();
+    let connected = providers.connect(&cfg)?;
+
+    let mut keys = BTreeMap::new();
+    for (source, provider) in &connected {
+        for (_, key) in resolve::keys(*source, provider.as_ref()).await? {
+            let owner = owners::owner(&cfg.owners, &key).unwrap_or("-");
+            keys.insert(key, owner);
+        }
+    }
+
+    let width = keys.keys().map(String::len).max().unwrap_or_default();
+    for (key, owner) in &keys {
+        println!("{key:<width$}  {owner}");
+    }
+    if cfg.security.enforce_owners {
+        match env::var(TEAM_ENV).ok().filter(|team| !team.is_empty()) {
+            Some(team) => println!(
+                "{}",
+                message!("owners_enforced", team = team, var = TEAM_ENV)
+            ),
+            None => println!("{}", message!("owners_enforced_unset", var = TEAM_ENV)),
+        }
+    }
+    Ok(())
+}
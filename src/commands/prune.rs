@@ -0,0 +1,93 @@
+use std::{collections::HashSet, path::Path};
+
+use anyhow::Result;
+
+use crate::{
+    audit, codec,
+    commands::pull::{self, CANARY_KEY, PULLED_KEYS_PATH},
+    config::{self, Config},
+    envfile,
+    messages::message,
+    provider::cache::{self, ProviderRegistry},
+    resolve,
+};
+
+/// Removes env file keys an earlier pull wrote whose secrets no longer
+/// exist, without waiting for `output.deprecation_grace`. Only keys recorded
+/// in `PULLED_KEYS_PATH` are candidates, so local-only keys are never
+/// touched. Only secret names are looked up.
+pub async fn run(config_path: &Path, dry_run: bool) -> Result<()> {
+    let cfg = config::load(config_path)?;
+    let env_path = config::resolve_path(config_path, &cfg.output.env_file);
+    let pulled_keys = config::resolve_path(config_path, PULLED_KEYS_PATH);
+    let previous = pull::read_pulled_keys(&pulled_keys)?;
+    if previous.is_empty() {
+        println!("{}", message!("prune_no_record"));
+        return Ok(());
+    }
+
+    let mut providers = ProviderRegistry::default();
+    let connected = providers.connect(&cfg)?;
+    let principal = cache::principals(&connected).await;
+    let mut provided = HashSet::new();
+    let mut result = Ok(());
+    for (source, provider) in &connected {
+        match resolve::keys(*source, provider.as_ref()).await {
+            Ok(keys) => provided.extend(keys.into_iter().map(|(_, key)| key)),
+            Err(err) => {
+                result = Err(err);
+                break;
+            }
+        }
+    }
+    let stale: HashSet<&str> = previous
+        .iter()
+        .map(String::as_str)
+        .filter(|key| !provided.contains(*key) && !is_canary(&cfg, key))
+        .collect();
+
+    let (lines, removed) = match &result {
+        Ok(()) => {
+            let lines = codec::load_env_file(config_path, &cfg, true)?;
+            envfile::remove(lines, |key| stale.contains(key))
+        }
+        Err(_) => (Vec::new(), Vec::new()),
+    };
+
+    let mut entry = audit::Entry::new("prune");
+    entry.principal = principal.as_deref();
+    entry.env_file = Some(env_path.display().to_string());
+    entry.dry_run = dry_run;
+    entry.changed_keys = removed.iter().map(String::as_str).collect();
+    entry.error = result.as_ref().err().map(|err| format!("{err:#}"));
+    audit::record(config_path, &cfg, &entry)?;
+    result?;
+
+    for key in &removed {
+        if dry_run {
+            println!("{}", message!("prune_would", key = key));
+        } else {
+            println!("{}", message!("prune_done", key = key));
+        }
+    }
+    if removed.is_empty() {
+        println!("{}", message!("prune_nothing"));
+    }
+    if dry_run {
+        return Ok(());
+    }
+    if !removed.is_empty() {
+        let (content, _) = envfile::merge(lines, &Default::default());
+        codec::write_env_file(config_path, &cfg, &envfile::render(&content))?;
+    }
+    let kept = previous
+        .iter()
+        .map(String::as_str)
+        .filter(|key| !stale.contains(key));
+    pull::write_pulled_keys(&pulled_keys, kept)
+}
+
+/// The canary is written by pulls, not backed by a secret.
+fn is_canary(cfg: &Config, key: &str) -> bool {
+    cfg.output.canary && key == CANARY_KEY
+}
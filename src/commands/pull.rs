@@ -0,0 +1,979 @@
+use std::{
+    collections::{HashMap, HashSet},
+    fs,
+    path::{Path, PathBuf},
+    time::{Duration, Instant, SystemTime},
+};
+
+use anyhow::{Context, Result, anyhow, bail};
+use base64::{Engine, engine::general_purpose::STANDARD};
+use clap::ValueEnum;
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+use tokio::task::JoinSet;
+
+use crate::{
+    audit, codec,
+    commands::{agent, compare, why_failed},
+    config::{self, Config, FileEncoding, FileOutput},
+    crypto,
+    envfile::{self, ChangeKind, Deprecation},
+    hooks,
+    lock::{self, Lock},
+    messages::message,
+    plaintext,
+    provider::{
+        ProviderError, SecretProvider,
+        cache::{self, ProviderRegistry},
+    },
+    resolve,
+};
+
+pub const CANARY_KEY: &str = "ENVIT_CANARY";
+/// Keys the last pull provided, i.e. the ones envit manages, for
+/// `output.deprecation_grace` and `prune`; relative to the config file.
+pub const PULLED_KEYS_PATH: &str = ".envit/pulled-keys";
+/// When the last complete pull finished (RFC 3339), for `status`; relative
+/// to the config file.
+pub const LAST_SYNC_PATH: &str = ".envit/last-sync";
+
+/// With `quiet`, only errors are reported (scheduled runs). With
+/// `degraded_ok`, providers that are temporarily unreachable are skipped and
+/// their keys keep their last-known values; returns whether that happened.
+/// `on_change` runs when keys changed. `mode` says how `envit.lock` pins
+/// versions (`pull --frozen`, `update`). `summary` ends the output with one
+/// line for scripts, also when the pull fails.
+pub async fn run(
+    config_path: &Path,
+    dry_run: bool,
+    quiet: bool,
+    degraded_ok: bool,
+    on_change: Option<&str>,
+    mode: lock::Mode<'_>,
+    summary: Summary,
+) -> Result<bool> {
+    let started = Instant::now();
+    let result = report(config_path, dry_run, quiet, degraded_ok, on_change, mode).await;
+    summary.print(config_path, result.as_ref().ok(), started.elapsed());
+    result.map(|written| !written.degraded.is_empty())
+}
+
+async fn report(
+    config_path: &Path,
+    dry_run: bool,
+    quiet: bool,
+    degraded_ok: bool,
+    on_change: Option<&str>,
+    mode: lock::Mode<'_>,
+) -> Result<Written> {
+    let cfg = config::load(config_path)?;
+    let outcome = sync(config_path, &cfg, dry_run, degraded_ok, mode).await;
+    if let Ok(written) = &outcome.result {
+        for skipped in &written.degraded {
+            eprintln!("{}", message!("pull_degraded_warning", source = skipped));
+        }
+    }
+    if let (Ok(written), Some(command)) = (&outcome.result, on_change)
+        && !dry_run
+    {
+        self::on_change(config_path, command, outcome.principal.as_deref(), written).await?;
+    }
+    if quiet {
+        return outcome.result;
+    }
+    // Auth failures surface from the pull with a proper error; the principal
+    // only labels the run.
+    if let Some(principal) = &outcome.principal {
+        println!("{}", message!("pull_authenticated", principal = principal));
+    }
+    let written = outcome.result?;
+
+    for deprecation in &written.deprecations {
+        match deprecation {
+            Deprecation::Marked {
+                key,
+                renamed_to,
+                until,
+            } => {
+                let reason = match renamed_to {
+                    Some(new_key) => message!("pull_deprecated_renamed", key = new_key),
+                    None => message!("pull_deprecated_gone"),
+                };
+                println!(
+                    "{}",
+                    message!(
+                        "pull_deprecated",
+                        key = key,
+                        reason = reason,
+                        until = humantime::format_rfc3339_seconds(*until)
+                    )
+                );
+            }
+            Deprecation::Removed { key } => {
+                println!("{}", message!("pull_removed_deprecated", key = key))
+            }
+        }
+    }
+    if dry_run {
+        print_dry_run(&cfg, &written);
+    } else if let Some(changed) = written.bytes_changed {
+        println!(
+            "{}",
+            message!(
+                "pull_updated",
+                count = written.changes.len(),
+                path = outcome.env_path.display(),
+                bytes = changed
+            )
+        );
+    } else {
+        println!("{}", message!("pull_unchanged"));
+    }
+    if !written.lock_changes.is_empty() {
+        println!(
+            "{}",
+            message!(
+                "pull_locked",
+                keys = written.lock_changes.join(", "),
+                path = lock::path(config_path).display()
+            )
+        );
+    }
+    if !written.degraded.is_empty() {
+        println!(
+            "{}",
+            message!(
+                "pull_degraded",
+                unreachable = written.degraded.len(),
+                total = cfg.sources().len()
+            )
+        );
+    }
+    Ok(written)
+}
+
+/// `pull --env a,b --out-dir DIR`: pulls each profile (see
+/// `compare::profile_path`) concurrently into `DIR/.env.<profile>`. The
+/// pulls share one provider registry, so profiles backed by the same
+/// provider log in and list it once. Locks, audit logs and `[output.files]`
+/// stay per profile. Returns whether any pull was degraded.
+pub async fn run_profiles(
+    profiles: &[String],
+    out_dir: &Path,
+    dry_run: bool,
+    quiet: bool,
+    degraded_ok: bool,
+    mode: lock::Mode<'static>,
+) -> Result<bool> {
+    if !dry_run {
+        fs::create_dir_all(out_dir)
+            .with_context(|| format!("failed to create {}", out_dir.display()))?;
+    }
+    // Env file paths are relative to their config otherwise.
+    let absolute = std::path::absolute(out_dir)
+        .with_context(|| format!("failed to resolve {}", out_dir.display()))?;
+
+    let mut providers = ProviderRegistry::default();
+    let mut paths = Vec::new();
+    let mut pulls = JoinSet::new();
+    for (index, profile) in profiles.iter().enumerate() {
+        let config_path = compare::profile_path(profile);
+        let mut cfg = config::load(&config_path).with_context(|| format!("profile {profile}"))?;
+        let file_name = format!(".env.{}", compare::profile_name(&config_path));
+        cfg.output.env_file = absolute.join(&file_name).display().to_string();
+        paths.push(out_dir.join(file_name));
+        // Connected before the pulls start, so they all share these.
+        providers.connect(&cfg)?;
+        let mut shared = providers.clone();
+        pulls.spawn(async move {
+            let outcome =
+                sync_with(&config_path, &cfg, &mut shared, dry_run, degraded_ok, mode).await;
+            (index, outcome.result)
+        });
+    }
+    let mut results: Vec<_> = profiles.iter().map(|_| None).collect();
+    while let Some(joined) = pulls.join_next().await {
+        let (index, result) = joined.context("a profile pull task failed")?;
+        results[index] = Some(result);
+    }
+
+    let mut failed = 0;
+    let mut degraded = false;
+    for ((profile, path), result) in profiles
+        .iter()
+        .zip(&paths)
+        .zip(results.into_iter().flatten())
+    {
+        let written = match result {
+            Ok(written) => written,
+            Err(err) => {
+                eprintln!("error: {profile}: {err:#}");
+                failed += 1;
+                continue;
+            }
+        };
+        for skipped in &written.degraded {
+            eprintln!("{}", message!("pull_degraded_warning", source = skipped));
+        }
+        degraded |= !written.degraded.is_empty();
+        if !quiet {
+            println!(
+                "{}",
+                message!(
+                    "pull_profile",
+                    profile = profile,
+                    changes = changes(written.hook_changes().len()),
+                    path = path.display()
+                )
+            );
+        }
+    }
+    if failed > 0 {
+        bail!("{failed} of {} profiles failed", profiles.len());
+    }
+    Ok(degraded)
+}
+
+/// `--summary`: the last line of a pull's output, for shell prompts and
+/// wrapper scripts.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum Summary {
+    /// `envit: 3 added, 1 updated, 0 removed, 0.8s, profile=dev`
+    Line,
+    /// One JSON object: status, added, updated, removed, seconds, profile
+    Json,
+    None,
+}
+
+#[derive(Serialize)]
+struct SummaryJson<'a> {
+    status: &'static str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    added: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    updated: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    removed: Option<usize>,
+    seconds: f64,
+    profile: &'a str,
+}
+
+impl Summary {
+    /// `written` is None when the pull failed. The canary is not counted.
+    fn print(self, config_path: &Path, written: Option<&Written>, elapsed: Duration) {
+        let profile = compare::profile_name(config_path);
+        let seconds = (elapsed.as_secs_f64() * 10.0).round() / 10.0;
+        let counts = written.map(|written| {
+            let count = |kind| {
+                written
+                    .changes
+                    .iter()
+                    .filter(|change| change.key != CANARY_KEY && change.kind == kind)
+                    .count()
+            };
+            let removed = written
+                .deprecations
+                .iter()
+                .filter(|deprecation| matches!(deprecation, Deprecation::Removed { .. }))
+                .count();
+            (count(ChangeKind::Add), count(ChangeKind::Update), removed)
+        });
+        let status = match written {
+            None => "failed",
+            Some(written) if !written.degraded.is_empty() => "degraded",
+            Some(_) => "ok",
+        };
+        match self {
+            Self::None => {}
+            Self::Line => match counts {
+                Some((added, updated, removed)) => println!(
+                    "envit: {added} added, {updated} updated, {removed} removed, {seconds}s, \
+                     profile={profile}{}",
+                    if status == "degraded" {
+                        ", degraded"
+                    } else {
+                        ""
+                    }
+                ),
+                None => println!("envit: failed, {seconds}s, profile={profile}"),
+            },
+            Self::Json => println!(
+                "{}",
+                serde_json::to_string(&SummaryJson {
+                    status,
+                    added: counts.map(|(added, _, _)| added),
+                    updated: counts.map(|(_, updated, _)| updated),
+                    removed: counts.map(|(_, _, removed)| removed),
+                    seconds,
+                    profile: &profile,
+                })
+                .expect("summary serializes")
+            ),
+        }
+    }
+}
+
+/// What a pull did, for callers that report it themselves.
+pub struct Outcome {
+    pub env_path: PathBuf,
+    pub principal: Option<String>,
+    pub result: Result<Written>,
+}
+
+pub struct Written {
+    /// Number of keys resolved from the provider.
+    pub keys: usize,
+    pub changes: Vec<envfile::Change>,
+    /// `[output.files]` keys whose file content changed (or would).
+    pub file_changes: Vec<envfile::Change>,
+    /// Stale keys marked or removed under `output.deprecation_grace`.
+    pub deprecations: Vec<Deprecation>,
+    /// None when the file was left untouched (dry run or identical content).
+    pub bytes_changed: Option<usize>,
+    /// Providers skipped by a degraded pull, with their errors.
+    pub degraded: Vec<String>,
+    /// Keys whose `envit.lock` entry was added, removed or advanced.
+    pub lock_changes: Vec<String>,
+    /// Which source each key comes from, for grouping a dry run (see
+    /// `key_sources`).
+    pub sources: HashMap<String, usize>,
+}
+
+impl Written {
+    /// The changed keys for hooks: env file entries and `[output.files]`
+    /// contents, without the canary.
+    pub fn hook_changes(&self) -> Vec<hooks::Change<'_>> {
+        let mut changes: Vec<_> = self
+            .changes
+            .iter()
+            .filter(|change| change.key != CANARY_KEY)
+            .collect();
+        changes.extend(
+            self.file_changes
+                .iter()
+                .filter(|file| !self.changes.iter().any(|change| change.key == file.key)),
+        );
+        changes
+            .into_iter()
+            .map(|change| hooks::Change {
+                key: &change.key,
+                kind: match change.kind {
+                    ChangeKind::Add => "add",
+                    ChangeKind::Update => "update",
+                },
+            })
+            .collect()
+    }
+}
+
+/// Runs `--on-change` after a pull that changed keys (see
+/// `hooks::run_command`).
+pub async fn on_change(
+    config_path: &Path,
+    command: &str,
+    principal: Option<&str>,
+    written: &Written,
+) -> Result<()> {
+    let mut entry = audit::Entry::new("pull");
+    entry.principal = principal;
+    hooks::run_command(command, config_path, &entry, &written.hook_changes())
+        .await
+        .context("--on-change failed")
+}
+
+/// Pulls into the configured env file and records the audit entry, without
+/// printing anything.
+pub async fn sync(
+    config_path: &Path,
+    cfg: &Config,
+    dry_run: bool,
+    degraded_ok: bool,
+    mode: lock::Mode<'_>,
+) -> Outcome {
+    // One registry for the whole pull, so providers log in and list once.
+    let mut providers = ProviderRegistry::default();
+    sync_with(config_path, cfg, &mut providers, dry_run, degraded_ok, mode).await
+}
+
+/// Like `sync`, through providers that may already be connected.
+async fn sync_with(
+    config_path: &Path,
+    cfg: &Config,
+    providers: &mut ProviderRegistry,
+    dry_run: bool,
+    degraded_ok: bool,
+    mode: lock::Mode<'_>,
+) -> Outcome {
+    let env_path = config::resolve_path(config_path, &cfg.output.env_file);
+    let lock_path = lock::path(config_path);
+
+    let (principal, resolved) = resolve_pinned(config_path, cfg, providers, &lock_path, mode).await;
+    let (mut updates, relock) = match resolved {
+        Ok((values, relock)) => (Ok(values), relock),
+        Err(err) => (Err(err), None),
+    };
+    let mut degraded = Vec::new();
+    // Skipping providers would drop their pins, so a locked pull never
+    // degrades.
+    if degraded_ok
+        && matches!(mode, lock::Mode::Use)
+        && !lock_path.exists()
+        && updates
+            .as_ref()
+            .is_err_and(|err| !ProviderError::is_auth(err))
+    {
+        (updates, degraded) = resolve_available(cfg, providers).await;
+    }
+
+    let expiries = match &updates {
+        Ok(_) if cfg.output.expiry_comments => expiries(cfg, providers).await,
+        _ => None,
+    };
+    let canary = cfg.output.canary.then(|| uuid::Uuid::new_v4().to_string());
+    let mut result = match updates {
+        Ok(updates) => pull(
+            config_path,
+            cfg,
+            updates,
+            canary.as_deref(),
+            dry_run,
+            degraded,
+            expiries.as_ref(),
+        ),
+        Err(err) => Err(err),
+    };
+    if let Ok(written) = &mut result
+        && dry_run
+    {
+        written.sources = key_sources(cfg, providers).await;
+    }
+    if let (Ok(written), Some((current, next))) = (&mut result, &relock) {
+        written.lock_changes = current.changed(next);
+        if !dry_run
+            && !written.lock_changes.is_empty()
+            && let Err(err) = lock::save(&lock_path, next)
+        {
+            result = Err(err);
+        }
+    }
+
+    let mut entry = audit::Entry::new("pull");
+    entry.canary = canary.as_deref();
+    entry.env_file = Some(env_path.display().to_string());
+    entry.principal = principal.as_deref();
+    entry.dry_run = dry_run;
+    match &result {
+        Ok(written) => {
+            entry.changed_keys = written.changes.iter().map(|c| c.key.as_str()).collect();
+            entry.degraded = !written.degraded.is_empty();
+        }
+        Err(err) => entry.error = Some(format!("{err:#}")),
+    }
+    let recorded = audit::record(config_path, cfg, &entry);
+    if let Ok(written) = &result
+        && !dry_run
+    {
+        hooks::notify(config_path, cfg, &entry, &written.hook_changes()).await;
+    }
+    if let Err(err) = recorded {
+        result = Err(err);
+    }
+    match &result {
+        Err(err) => why_failed::record(config_path, "pull", err),
+        Ok(written) if !dry_run && written.degraded.is_empty() => why_failed::clear(config_path),
+        Ok(_) => {}
+    }
+
+    Outcome {
+        env_path,
+        principal,
+        result,
+    }
+}
+
+/// Resolves pinned to `envit.lock` when there is one or `mode` updates it,
+/// returning the lock before and after; like `resolve_values` otherwise,
+/// including the session agent.
+async fn resolve_pinned(
+    config_path: &Path,
+    cfg: &Config,
+    providers: &mut ProviderRegistry,
+    lock_path: &Path,
+    mode: lock::Mode<'_>,
+) -> (
+    Option<String>,
+    Result<(HashMap<String, String>, Option<(Lock, Lock)>)>,
+) {
+    let current = match (lock::load(lock_path), mode) {
+        (Ok(None), lock::Mode::Use) => {
+            let (principal, values) = resolve_values(config_path, cfg, providers).await;
+            return (principal, values.map(|values| (values, None)));
+        }
+        (Ok(None), lock::Mode::Frozen) => {
+            return (
+                None,
+                Err(anyhow!(
+                    "--frozen needs {}; create it with `envit update`",
+                    lock_path.display()
+                )),
+            );
+        }
+        (Ok(current), _) => current.unwrap_or_default(),
+        (Err(err), _) => return (None, Err(err)),
+    };
+    // `envit update` wants current versions, not the agent's session.
+    if !matches!(mode, lock::Mode::Update(_))
+        && let Some(socket) = agent::socket_from_env()
+    {
+        match agent::resolve_locked(&socket, config_path, &current).await {
+            Ok((principal, resolved)) => {
+                let resolved = resolved.and_then(|(values, next)| {
+                    lock::check(&current, &next, mode)?;
+                    Ok((values, Some((current, next))))
+                });
+                return (principal, resolved);
+            }
+            Err(err) => eprintln!("warning: envit agent unavailable, resolving directly: {err:#}"),
+        }
+    }
+    let connected = match providers.connect(cfg) {
+        Ok(connected) => connected,
+        Err(err) => return (None, Err(err)),
+    };
+    let principal = cache::principals(&connected).await;
+    let resolved = lock::resolve(&connected, &current, mode)
+        .await
+        .map(|(values, next)| (values, Some((current, next))));
+    (principal, resolved)
+}
+
+/// Resolves through the session agent when `ENVIT_AGENT_SOCK` points at
+/// one, directly through `providers` otherwise. An unreachable agent is
+/// not fatal.
+pub async fn resolve_values(
+    config_path: &Path,
+    cfg: &Config,
+    providers: &mut ProviderRegistry,
+) -> (Option<String>, Result<HashMap<String, String>>) {
+    if let Some(socket) = agent::socket_from_env() {
+        match agent::resolve(&socket, config_path).await {
+            Ok(resolved) => return resolved,
+            Err(err) => eprintln!("warning: envit agent unavailable, resolving directly: {err:#}"),
+        }
+    }
+    let connected = match providers.connect(cfg) {
+        Ok(connected) => connected,
+        Err(err) => return (None, Err(err)),
+    };
+    let principal = cache::principals(&connected).await;
+    (principal, resolve::resolve_all(&connected).await)
+}
+
+/// Resolves each provider on its own, skipping (and naming) those that
+/// fail for any reason but rejected credentials.
+async fn resolve_available(
+    cfg: &Config,
+    providers: &mut ProviderRegistry,
+) -> (Result<HashMap<String, String>>, Vec<String>) {
+    let connected = match providers.connect(cfg) {
+        Ok(connected) => connected,
+        Err(err) => return (Err(err), Vec::new()),
+    };
+    let mut values = HashMap::new();
+    let mut skipped = Vec::new();
+    for (source, provider) in &connected {
+        match resolve::resolve(*source, provider.as_ref()).await {
+            Ok(resolved) => values.extend(resolved),
+            Err(err) if !ProviderError::is_auth(&err) => {
+                skipped.push(format!("{}: {err:#}", source.provider.kind));
+            }
+            Err(err) => return (Err(err), skipped),
+        }
+    }
+    (Ok(values), skipped)
+}
+
+/// Env key -> the expiry of its secret, for `output.expiry_comments`, from
+/// the listing the pull already made. When that fails the pull goes on
+/// with a warning and the comments stay as they are.
+async fn expiries(
+    cfg: &Config,
+    providers: &mut ProviderRegistry,
+) -> Option<HashMap<String, Option<SystemTime>>> {
+    let result = async {
+        let mut expiries = HashMap::new();
+        for (source, provider) in &providers.connect(cfg)? {
+            let listed = provider
+                .list_secrets()
+                .await
+                .with_context(|| format!("failed to list secrets of {}", source.provider.kind))?;
+            for meta in listed {
+                expiries.insert(resolve::env_key(*source, &meta.name), meta.expires);
+            }
+        }
+        anyhow::Ok(expiries)
+    }
+    .await;
+    match result {
+        Ok(expiries) => Some(expiries),
+        Err(err) => {
+            eprintln!("warning: expiry comments not updated: {err:#}");
+            None
+        }
+    }
+}
+
+fn pull(
+    config_path: &Path,
+    cfg: &Config,
+    mut updates: HashMap<String, String>,
+    canary: Option<&str>,
+    dry_run: bool,
+    degraded: Vec<String>,
+    expiries: Option<&HashMap<String, Option<SystemTime>>>,
+) -> Result<Written> {
+    if !dry_run {
+        plaintext::check_outputs(cfg)?;
+    }
+    if let Some(canary) = canary {
+        updates.insert(CANARY_KEY.to_string(), canary.to_string());
+    }
+    let file_changes = write_files(config_path, cfg, &mut updates, dry_run)?;
+
+    let mut existing = codec::load_env_file(config_path, cfg, cfg.output.create_if_missing)?;
+    encode_values(config_path, cfg, &existing, &mut updates)?;
+    let mut deprecations = Vec::new();
+    let pulled_keys = config::resolve_path(config_path, PULLED_KEYS_PATH);
+    // Keys of skipped providers are missing from `updates`, not stale.
+    let track_keys = degraded.is_empty();
+    if let Some(grace) = cfg.output.deprecation_grace
+        && track_keys
+    {
+        let previous = read_pulled_keys(&pulled_keys)?;
+        (existing, deprecations) =
+            envfile::deprecate(existing, &previous, &updates, grace, SystemTime::now());
+    }
+    let (mut merged_content, changes) = envfile::merge(existing, &updates);
+    if let Some(expiries) = expiries {
+        // Annotated after the merge so new keys get a comment too; keys
+        // this pull did not write keep theirs.
+        let expiries: HashMap<_, _> = expiries
+            .iter()
+            .filter(|(key, _)| updates.contains_key(*key))
+            .map(|(key, expires)| (key.clone(), *expires))
+            .collect();
+        let lines = envfile::annotate_expiries(
+            envfile::parse_lines(&merged_content),
+            &expiries,
+            SystemTime::now(),
+        );
+        (merged_content, _) = envfile::merge(lines, &HashMap::new());
+    }
+    let mut written = Written {
+        keys: updates.len(),
+        changes,
+        file_changes,
+        deprecations,
+        bytes_changed: None,
+        degraded,
+        lock_changes: Vec::new(),
+        sources: HashMap::new(),
+    };
+    if dry_run {
+        return Ok(written);
+    }
+    if track_keys {
+        write_pulled_keys(&pulled_keys, updates.keys().map(String::as_str))?;
+    }
+
+    let rendered = envfile::render(&merged_content);
+    // Compared as plaintext: age output differs on every encryption.
+    let previous = codec::read_env_file(config_path, cfg, None)?;
+    if !cfg.output.skip_identical || previous.as_deref() != Some(rendered.as_str()) {
+        codec::write_env_file(config_path, cfg, &rendered)?;
+        written.bytes_changed = Some(envfile::changed_bytes(
+            previous.as_deref().unwrap_or_default().as_bytes(),
+            rendered.as_bytes(),
+        ));
+    }
+
+    if written.degraded.is_empty() {
+        let now = humantime::format_rfc3339_seconds(SystemTime::now());
+        write_state(
+            &config::resolve_path(config_path, LAST_SYNC_PATH),
+            &format!("{now}\n"),
+        )?;
+    }
+    Ok(written)
+}
+
+/// Encodes the values with `output.codec`, if configured. A value the env
+/// file already holds keeps its encoding when it can be decoded, so
+/// unchanged keys do not change in the committed file. The canary and
+/// `[output.files]` paths stay plain.
+pub fn encode_values(
+    config_path: &Path,
+    cfg: &Config,
+    lines: &[envfile::Line],
+    updates: &mut HashMap<String, String>,
+) -> Result<()> {
+    let Some(codec_cfg) = &cfg.output.codec else {
+        return Ok(());
+    };
+    let codec = codec::build(config_path, codec_cfg, None)?;
+    let existing = envfile::values(lines);
+    for (key, value) in updates.iter_mut() {
+        if key == CANARY_KEY || cfg.output.files.contains_key(key) {
+            continue;
+        }
+        if let Some(&encoded) = existing.get(key.as_str())
+            && codec.can_decode()
+            && codec.decode(encoded).ok().flatten().as_deref() == Some(value.as_str())
+        {
+            *value = encoded.to_string();
+        } else {
+            *value = codec
+                .encode(value)
+                .with_context(|| format!("failed to encrypt {key}"))?;
+        }
+    }
+    Ok(())
+}
+
+pub fn read_pulled_keys(path: &Path) -> Result<HashSet<String>> {
+    match fs::read_to_string(path) {
+        Ok(raw) => Ok(raw.lines().map(str::to_string).collect()),
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => Ok(HashSet::new()),
+        Err(err) => Err(err).with_context(|| format!("failed to read {}", path.display())),
+    }
+}
+
+pub fn write_pulled_keys<'a>(path: &Path, keys: impl IntoIterator<Item = &'a str>) -> Result<()> {
+    let mut keys: Vec<_> = keys.into_iter().collect();
+    keys.sort_unstable();
+    write_state(path, &format!("{}\n", keys.join("\n")))
+}
+
+/// Reads the time `LAST_SYNC_PATH` records, if any pull has completed.
+pub fn read_last_sync(path: &Path) -> Result<Option<SystemTime>> {
+    match fs::read_to_string(path) {
+        Ok(raw) => humantime::parse_rfc3339(raw.trim())
+            .map(Some)
+            .with_context(|| format!("invalid timestamp in {}", path.display())),
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => Ok(None),
+        Err(err) => Err(err).with_context(|| format!("failed to read {}", path.display())),
+    }
+}
+
+pub(crate) fn write_state(path: &Path, content: &str) -> Result<()> {
+    if let Some(dir) = path.parent().filter(|d| !d.as_os_str().is_empty()) {
+        fs::create_dir_all(dir).with_context(|| format!("failed to create {}", dir.display()))?;
+    }
+    envfile::write_atomic(path, content)
+        .with_context(|| format!("failed to write {}", path.display()))
+}
+
+/// Moves `[output.files]` values out to their files (private to the user),
+/// leaving the configured path as the env value. Files (and checksums)
+/// already holding the content are not rewritten. Returns the keys whose
+/// file changed.
+pub fn write_files(
+    config_path: &Path,
+    cfg: &Config,
+    updates: &mut HashMap<String, String>,
+    dry_run: bool,
+) -> Result<Vec<envfile::Change>> {
+    let mut changes = Vec::new();
+    for (key, file) in &cfg.output.files {
+        let Some(value) = updates.get_mut(key) else {
+            continue;
+        };
+        let content = file_content(file, key, value)?;
+        let path = config::resolve_path(config_path, &file.path);
+        match fs::read(&path) {
+            Ok(current) if current == content => {}
+            current => changes.push(envfile::Change {
+                key: key.clone(),
+                kind: match current {
+                    Ok(_) => ChangeKind::Update,
+                    Err(_) => ChangeKind::Add,
+                },
+            }),
+        }
+        if !dry_run {
+            if let Some(dir) = path.parent().filter(|d| !d.as_os_str().is_empty()) {
+                fs::create_dir_all(dir)
+                    .with_context(|| format!("failed to create {}", dir.display()))?;
+            }
+            write_if_changed(&path, &content)
+                .with_context(|| format!("failed to write {key} to {}", path.display()))?;
+            if file.checksum {
+                let mut checksum = path.clone().into_os_string();
+                checksum.push(".sha256");
+                let name = path.file_name().unwrap_or_default().to_string_lossy();
+                let digest = crypto::to_hex(&Sha256::digest(&content));
+                write_if_changed(
+                    Path::new(&checksum),
+                    format!("{digest}  {name}\n").as_bytes(),
+                )
+                .with_context(|| format!("failed to write the checksum of {key}"))?;
+            }
+        }
+        *value = file.path.clone();
+    }
+    Ok(changes)
+}
+
+/// The bytes an `[output.files]` entry holds for `value`.
+pub fn file_content(file: &FileOutput, key: &str, value: &str) -> Result<Vec<u8>> {
+    match file.encoding {
+        FileEncoding::Text => Ok(value.as_bytes().to_vec()),
+        FileEncoding::Base64 => STANDARD
+            .decode(value.trim())
+            .with_context(|| format!("{key} is not valid base64 (encoding = \"base64\")")),
+    }
+}
+
+fn write_if_changed(path: &Path, content: &[u8]) -> Result<()> {
+    if fs::read(path).ok().as_deref() == Some(content) {
+        return Ok(());
+    }
+    envfile::write_atomic(path, content)
+}
+
+fn print_dry_run(cfg: &Config, written: &Written) {
+    let sources = &written.sources;
+    if written.changes.is_empty() && written.file_changes.is_empty() {
+        println!("{}", message!("dry_run_unchanged"));
+        return;
+    }
+
+    let label = |kind: &ChangeKind| match kind {
+        ChangeKind::Add => "ADD",
+        ChangeKind::Update => "UPDATE",
+    };
+    let mut lines: Vec<_> = written
+        .changes
+        .iter()
+        .map(|change| PlanLine {
+            file: cfg.output.env_file.clone(),
+            source: sources.get(&change.key).copied(),
+            text: format!("{} {}=********", label(&change.kind), change.key),
+        })
+        .collect();
+    lines.extend(written.file_changes.iter().map(|change| PlanLine {
+        file: cfg.output.files[&change.key].path.clone(),
+        source: sources.get(&change.key).copied(),
+        text: format!("{} {}", label(&change.kind), change.key),
+    }));
+    print_plan(cfg, lines);
+}
+
+/// One line of a dry run or diff, with the file it lands in and the source
+/// (index into `cfg.sources()`) its key comes from.
+pub struct PlanLine {
+    pub file: String,
+    pub source: Option<usize>,
+    pub text: String,
+}
+
+/// Prints `lines` as they are for one env file and provider. With several
+/// files or providers they are grouped under a header per file, then per
+/// provider, with counts and a total, so a pull across many files stays
+/// readable.
+pub fn print_plan(cfg: &Config, mut lines: Vec<PlanLine>) {
+    let sources = cfg.sources();
+    let by_provider = sources.len() > 1;
+    if !by_provider && lines.iter().all(|line| line.file == cfg.output.env_file) {
+        for line in &lines {
+            println!("{}", line.text);
+        }
+        return;
+    }
+
+    // Stable, so each group keeps the caller's order; the env file first
+    // and keys no provider gives last.
+    lines.sort_by(|a, b| {
+        let order = |line: &PlanLine| {
+            (
+                line.file != cfg.output.env_file,
+                line.file.clone(),
+                line.source.is_none(),
+                line.source,
+            )
+        };
+        order(a).cmp(&order(b))
+    });
+    let mut files = 0;
+    for group in lines.chunk_by(|a, b| a.file == b.file) {
+        files += 1;
+        println!("{}: {}", group[0].file, changes(group.len()));
+        if !by_provider {
+            for line in group {
+                println!("  {}", line.text);
+            }
+            continue;
+        }
+        for group in group.chunk_by(|a, b| a.source == b.source) {
+            let provider = match group[0].source {
+                Some(index) => source_label(index, &sources[index]),
+                None => "not from a provider".to_string(),
+            };
+            println!("  {provider}: {}", changes(group.len()));
+            for line in group {
+                println!("    {}", line.text);
+            }
+        }
+    }
+    let mut providers: Vec<_> = lines.iter().filter_map(|line| line.source).collect();
+    providers.sort_unstable();
+    providers.dedup();
+    let providers = if by_provider { providers.len() } else { 1 };
+    println!(
+        "{}",
+        message!(
+            "plan_total",
+            changes = changes(lines.len()),
+            files = files,
+            providers = providers
+        )
+    );
+}
+
+fn source_label(index: usize, source: &config::Source) -> String {
+    match source.prefix {
+        "" => format!("provider {} ({})", index + 1, source.provider.kind),
+        prefix => format!(
+            "provider {} ({}, {prefix})",
+            index + 1,
+            source.provider.kind
+        ),
+    }
+}
+
+fn changes(count: usize) -> String {
+    match count {
+        1 => "1 change".to_string(),
+        count => format!("{count} changes"),
+    }
+}
+
+/// Which source each key comes from, later sources winning as in a pull.
+/// Only names are listed, and only with several sources; a source that
+/// cannot be listed leaves its keys unattributed. Listings come from
+/// `providers`' cache, so after a pull this costs no further requests.
+pub async fn key_sources(cfg: &Config, providers: &mut ProviderRegistry) -> HashMap<String, usize> {
+    let mut origins = HashMap::new();
+    if cfg.sources().len() < 2 {
+        return origins;
+    }
+    let Ok(connected) = providers.connect(cfg) else {
+        return origins;
+    };
+    for (index, (source, provider)) in connected.iter().enumerate() {
+        if let Ok(keys) = resolve::keys(*source, provider.as_ref()).await {
+            origins.extend(keys.into_iter().map(|(_, key)| (key, index)));
+        }
+    }
+    origins
+}
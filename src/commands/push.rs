@@ -0,0 +1,416 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    fs,
+    io::{self, BufRead, IsTerminal, Write},
+    path::Path,
+    sync::Arc,
+    time::Duration,
+};
+
+use anyhow::{Context, Result, anyhow, bail};
+use sha2::{Digest, Sha256};
+use tokio::task::JoinSet;
+
+use crate::{
+    audit, codec,
+    commands::pull::CANARY_KEY,
+    config::{self, Config},
+    crypto, envfile, hooks,
+    messages::message,
+    owners,
+    provider::{
+        Capability, ProviderError, SecretMeta, SecretProvider,
+        cache::{CachedProvider, ProviderRegistry},
+    },
+    resolve,
+};
+
+/// Reads and writes in flight at once unless `--concurrency` says
+/// otherwise.
+pub const DEFAULT_CONCURRENCY: usize = 4;
+/// Secrets an unfinished push wrote, with a hash of the value, so running
+/// it again resumes where it stopped; relative to the config file and
+/// removed once a push completes.
+pub const PROGRESS_PATH: &str = ".envit/push-progress";
+
+/// Throttled requests are retried this many times in all, waiting twice as
+/// long each time.
+const MAX_ATTEMPTS: u32 = 6;
+const INITIAL_BACKOFF: Duration = Duration::from_millis(500);
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum Action {
+    Add,
+    Update,
+}
+
+/// One secret the push would write.
+pub struct Planned<'a> {
+    pub key: &'a str,
+    pub secret: String,
+    pub value: &'a str,
+    pub action: Action,
+}
+
+/// Writes env file values (all of them, or `keys`) back to the config's
+/// provider. The plan is always shown first; nothing is written without
+/// `yes` or an interactive confirmation. Up to `concurrency` secrets are
+/// compared and written at once, backing off while the provider throttles.
+pub async fn run(
+    config_path: &Path,
+    keys: &[String],
+    dry_run: bool,
+    yes: bool,
+    override_owner: bool,
+    concurrency: usize,
+) -> Result<()> {
+    if concurrency == 0 {
+        bail!("--concurrency must be at least 1");
+    }
+    let cfg = config::load(config_path)?;
+    let provider_cfg = cfg.single_provider()?;
+    let env_path = config::resolve_path(config_path, &cfg.output.env_file);
+    let raw = codec::read_env_file(config_path, &cfg, None)?
+        .with_context(|| format!("env file does not exist: {}", env_path.display()))?;
+    let entries = select(
+        &cfg,
+        envfile::parse_entries(&raw).into_iter().collect(),
+        keys,
+    )?;
+
+    let mut providers = ProviderRegistry::default();
+    let provider = providers.get(provider_cfg)?;
+    provider
+        .capabilities()
+        .require(Capability::Write, &provider_cfg.kind, "push")?;
+    let (plan, resumed) = plan(config_path, &cfg, &provider, &entries, concurrency).await?;
+
+    if resumed > 0 {
+        println!("{}", message!("push_resumed", count = resumed));
+    }
+    if plan.is_empty() {
+        println!("{}", message!("push_nothing"));
+        if !dry_run {
+            remove_progress(config_path)?;
+        }
+        return Ok(());
+    }
+    for planned in &plan {
+        let label = match planned.action {
+            Action::Add => "ADD",
+            Action::Update => "UPDATE",
+        };
+        println!("{label} {} <- {}=********", planned.secret, planned.key);
+    }
+    if dry_run {
+        return Ok(());
+    }
+    let planned_keys: Vec<_> = plan.iter().map(|planned| planned.key).collect();
+    owners::check(&cfg, &planned_keys, override_owner)?;
+    if !yes
+        && !confirm(
+            &message!("push_confirm", count = plan.len(), kind = provider_cfg.kind),
+            "refusing to push without confirmation; review with --dry-run and pass --yes",
+        )?
+    {
+        bail!("push cancelled");
+    }
+
+    write(
+        config_path,
+        &cfg,
+        &provider,
+        &plan,
+        "push",
+        override_owner,
+        concurrency,
+    )
+    .await?;
+    println!("{}", message!("push_done", count = plan.len()));
+    Ok(())
+}
+
+/// Writes `plan` with up to `concurrency` writes at once, backing off while
+/// the provider throttles. Each write is recorded in `PROGRESS_PATH` as it
+/// lands. The run is audited and hooks are notified under `command`.
+pub async fn write(
+    config_path: &Path,
+    cfg: &Config,
+    provider: &Arc<CachedProvider>,
+    plan: &[Planned<'_>],
+    command: &str,
+    override_owner: bool,
+    concurrency: usize,
+) -> Result<()> {
+    let progress_path = config::resolve_path(config_path, PROGRESS_PATH);
+    let principal = provider.identity().await.unwrap_or(None);
+    // Indices into `plan`, in the order the writes finished.
+    let mut pushed = Vec::new();
+    let mut result = Ok(());
+    let writes = plan.iter().map(|planned| {
+        let provider = Arc::clone(provider);
+        let (secret, value) = (planned.secret.clone(), planned.value.to_string());
+        async move { with_backoff(|| provider.set_secret(&secret, &value)).await }
+    });
+    bounded(concurrency, writes, |index, written| {
+        let planned = &plan[index];
+        let recorded = written
+            .map_err(|err| anyhow!("failed to write secret {}: {err}", planned.secret))
+            .and_then(|()| record_progress(&progress_path, &planned.secret, planned.value));
+        match recorded {
+            Ok(()) => pushed.push(index),
+            Err(err) => result = Err(err),
+        }
+        result.is_ok()
+    })
+    .await;
+
+    let mut entry = audit::Entry::new(command);
+    entry.principal = principal.as_deref();
+    entry.changed_keys = pushed
+        .iter()
+        .map(|&index| plan[index].secret.as_str())
+        .collect();
+    entry.override_owner = override_owner;
+    entry.error = result.as_ref().err().map(|err| format!("{err:#}"));
+    audit::record(config_path, cfg, &entry)?;
+    let changes: Vec<_> = pushed
+        .iter()
+        .map(|&index| hooks::Change {
+            key: &plan[index].secret,
+            kind: match plan[index].action {
+                Action::Add => "add",
+                Action::Update => "update",
+            },
+        })
+        .collect();
+    hooks::notify(config_path, cfg, &entry, &changes).await;
+
+    result.with_context(|| {
+        format!(
+            "{command} stopped after writing {} of {} secrets; run it again to resume",
+            pushed.len(),
+            plan.len()
+        )
+    })?;
+    remove_progress(config_path)
+}
+
+/// Env file entries to push. Keys `pull` writes itself (the canary, paths
+/// of `[output.files]`) are not secret values and are left out.
+fn select(
+    cfg: &Config,
+    mut entries: BTreeMap<String, String>,
+    keys: &[String],
+) -> Result<BTreeMap<String, String>> {
+    let generated = |key: &str| key == CANARY_KEY || cfg.output.files.contains_key(key);
+    if keys.is_empty() {
+        entries.retain(|key, _| !generated(key));
+        return Ok(entries);
+    }
+    keys.iter()
+        .map(|key| {
+            if generated(key) {
+                bail!("{key} is written by pull and cannot be pushed");
+            }
+            let value = entries
+                .remove(key)
+                .ok_or_else(|| anyhow!("{key} is not in the env file"))?;
+            Ok((key.clone(), value))
+        })
+        .collect()
+}
+
+/// Maps each key to its secret and compares with what the provider holds.
+/// Secrets `PROGRESS_PATH` records with the same value are not fetched
+/// again; their number is returned with the plan.
+pub async fn plan<'a>(
+    config_path: &Path,
+    cfg: &Config,
+    provider: &Arc<CachedProvider>,
+    entries: &'a BTreeMap<String, String>,
+    concurrency: usize,
+) -> Result<(Vec<Planned<'a>>, usize)> {
+    let progress = read_progress(&config::resolve_path(config_path, PROGRESS_PATH))?;
+    let listed = provider
+        .list_secrets()
+        .await
+        .context("failed to list secrets")?;
+
+    let mut candidates = Vec::new();
+    let mut resumed = 0;
+    for (key, value) in entries {
+        let secret = secret_for(cfg, &listed, key);
+        if progress.get(&secret) == Some(&value_hash(value)) {
+            resumed += 1;
+        } else {
+            candidates.push((key, secret, value));
+        }
+    }
+
+    let mut current = vec![None; candidates.len()];
+    let mut result = Ok(());
+    let reads = candidates.iter().map(|(_, secret, _)| {
+        let provider = Arc::clone(provider);
+        let secret = secret.clone();
+        async move { with_backoff(|| provider.get_secret(&secret)).await }
+    });
+    bounded(concurrency, reads, |index, read| {
+        match read {
+            Ok(value) => current[index] = value,
+            Err(err) => {
+                let secret = &candidates[index].1;
+                result = Err(anyhow!(err).context(format!("failed to fetch secret {secret}")));
+            }
+        }
+        result.is_ok()
+    })
+    .await;
+    result?;
+
+    let mut plan = Vec::new();
+    for ((key, secret, value), current) in candidates.into_iter().zip(current) {
+        let action = match current {
+            Some(current) if current == *value => continue,
+            Some(_) => Action::Update,
+            None => Action::Add,
+        };
+        plan.push(Planned {
+            key,
+            secret,
+            value,
+            action,
+        });
+    }
+    Ok((plan, resumed))
+}
+
+/// Runs `tasks` with at most `concurrency` in flight, handing each result
+/// to `done` with the task's index as it finishes. Once `done` returns
+/// false no further tasks start; those already running are awaited.
+pub async fn bounded<T, F>(
+    concurrency: usize,
+    tasks: impl IntoIterator<Item = F>,
+    mut done: impl FnMut(usize, T) -> bool,
+) where
+    F: Future<Output = T> + Send + 'static,
+    T: Send + 'static,
+{
+    let mut tasks = tasks.into_iter().enumerate();
+    let mut running = JoinSet::new();
+    let mut more = true;
+    loop {
+        while more && running.len() < concurrency {
+            match tasks.next() {
+                Some((index, task)) => {
+                    running.spawn(async move { (index, task.await) });
+                }
+                None => more = false,
+            }
+        }
+        let Some(joined) = running.join_next().await else {
+            return;
+        };
+        let (index, output) = joined.expect("push tasks do not panic");
+        more &= done(index, output);
+    }
+}
+
+/// Retries `op` while the provider reports throttling (Key Vault answers
+/// 429 once a vault's request rate is exceeded), doubling the wait each
+/// time.
+async fn with_backoff<T, Fut>(mut op: impl FnMut() -> Fut) -> Result<T, ProviderError>
+where
+    Fut: Future<Output = Result<T, ProviderError>>,
+{
+    let mut backoff = INITIAL_BACKOFF;
+    let mut attempt = 1;
+    loop {
+        match op().await {
+            Err(ProviderError::Throttled(message)) if attempt < MAX_ATTEMPTS => {
+                eprintln!(
+                    "{}",
+                    message!(
+                        "push_throttled",
+                        message = message,
+                        delay = humantime::format_duration(backoff)
+                    )
+                );
+                tokio::time::sleep(backoff).await;
+                backoff *= 2;
+                attempt += 1;
+            }
+            result => return result,
+        }
+    }
+}
+
+fn value_hash(value: &str) -> String {
+    crypto::to_hex(&Sha256::digest(value.as_bytes()))
+}
+
+/// Secret name -> value hash, from `PROGRESS_PATH` lines.
+fn read_progress(path: &Path) -> Result<HashMap<String, String>> {
+    match fs::read_to_string(path) {
+        Ok(raw) => Ok(raw
+            .lines()
+            .filter_map(|line| line.split_once(' '))
+            .map(|(secret, hash)| (secret.to_string(), hash.to_string()))
+            .collect()),
+        Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(HashMap::new()),
+        Err(err) => Err(err).with_context(|| format!("failed to read {}", path.display())),
+    }
+}
+
+/// Appends one written secret, so an interrupted push loses nothing it has
+/// done.
+fn record_progress(path: &Path, secret: &str, value: &str) -> Result<()> {
+    if let Some(dir) = path.parent().filter(|d| !d.as_os_str().is_empty()) {
+        fs::create_dir_all(dir).with_context(|| format!("failed to create {}", dir.display()))?;
+    }
+    fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)
+        .and_then(|mut file| writeln!(file, "{secret} {}", value_hash(value)))
+        .with_context(|| format!("failed to write {}", path.display()))
+}
+
+pub fn remove_progress(config_path: &Path) -> Result<()> {
+    let path = config::resolve_path(config_path, PROGRESS_PATH);
+    match fs::remove_file(&path) {
+        Err(err) if err.kind() != io::ErrorKind::NotFound => {
+            Err(err).with_context(|| format!("failed to remove {}", path.display()))
+        }
+        _ => Ok(()),
+    }
+}
+
+/// The secret behind an env key: `[map]` names it explicitly; otherwise an
+/// existing secret that pulls to this key, or a new one named the way Key
+/// Vault names must be (`DATABASE_URL` -> `database-url`).
+pub fn secret_for(cfg: &Config, listed: &[SecretMeta], key: &str) -> String {
+    cfg.map
+        .get(key)
+        .cloned()
+        .or_else(|| {
+            listed
+                .iter()
+                .find(|meta| resolve::to_env_key(&meta.name) == key)
+                .map(|meta| meta.name.clone())
+        })
+        .unwrap_or_else(|| key.to_ascii_lowercase().replace('_', "-"))
+}
+
+/// Asks `question` on the terminal; without one (CI, pipes) fails with
+/// `refusal` instead of guessing.
+pub fn confirm(question: &str, refusal: &str) -> Result<bool> {
+    if !io::stdin().is_terminal() {
+        bail!("{refusal}");
+    }
+    eprint!("{question} [y/N] ");
+    io::stderr().flush()?;
+    let mut answer = String::new();
+    io::stdin().lock().read_line(&mut answer)?;
+    Ok(matches!(answer.trim(), "y" | "Y" | "yes"))
+}
@@ -0,0 +1,242 @@
+use std::{fs, path::Path};
+
+use anyhow::{Context, Result, anyhow, bail};
+use toml_edit::{DocumentMut, Item, value};
+
+use crate::{
+    audit, codec,
+    commands::push,
+    config::{self, Config},
+    envfile, hooks,
+    messages::message,
+    owners,
+    provider::{Capability, SecretProvider, cache::ProviderRegistry},
+    resolve,
+};
+
+/// Renames an env key and the secret behind it: the value is copied to the
+/// new secret (`secret`, or the name `new_key` derives), `[map]` and the env
+/// file are rewritten, then the old secret is deleted. If a local file
+/// cannot be written, the new secret is removed again and nothing changed.
+/// When both keys resolve to the same secret, only the key is renamed.
+pub async fn run(
+    config_path: &Path,
+    old_key: &str,
+    new_key: &str,
+    secret: Option<&str>,
+    dry_run: bool,
+    yes: bool,
+    override_owner: bool,
+) -> Result<()> {
+    let cfg = config::load(config_path)?;
+    let provider_cfg = cfg.single_provider()?;
+    if old_key == new_key {
+        bail!("{old_key} is already called that");
+    }
+    owners::check(&cfg, &[old_key, new_key], override_owner)?;
+
+    let mut providers = ProviderRegistry::default();
+    let provider = providers.get(provider_cfg)?;
+    let listed = provider
+        .list_secrets()
+        .await
+        .context("failed to list secrets")?;
+    let old_secret = push::secret_for(&cfg, &listed, old_key);
+    let new_secret = match secret {
+        Some(secret) => secret.to_string(),
+        None => push::secret_for(&cfg, &listed, new_key),
+    };
+    if cfg.map.contains_key(new_key) {
+        bail!("[map] already has {new_key}");
+    }
+    let value = provider
+        .get_secret(&old_secret)
+        .await
+        .with_context(|| format!("failed to fetch secret {old_secret}"))?
+        .ok_or_else(|| anyhow!("{old_key}: the provider has no secret {old_secret}"))?;
+    let moves = new_secret != old_secret;
+    if moves {
+        let capabilities = provider.capabilities();
+        capabilities.require(Capability::Write, &provider_cfg.kind, "rename")?;
+        capabilities.require(Capability::Delete, &provider_cfg.kind, "rename")?;
+        if listed.iter().any(|meta| meta.name == new_secret)
+            || provider
+                .get_secret(&new_secret)
+                .await
+                .with_context(|| format!("failed to fetch secret {new_secret}"))?
+                .is_some()
+        {
+            bail!("{new_key}: the provider already has a secret {new_secret}");
+        }
+    }
+
+    let raw_config = fs::read_to_string(config_path)
+        .with_context(|| format!("failed to read {}", config_path.display()))?;
+    let new_config = rewrite_map(&raw_config, old_key, new_key, &new_secret)?;
+    let env_path = config::resolve_path(config_path, &cfg.output.env_file);
+    let mut lines = codec::load_env_file(config_path, &cfg, true)?;
+    if envfile::values(&lines).contains_key(new_key) {
+        bail!("{} already has {new_key}", env_path.display());
+    }
+    let in_env = envfile::rename(&mut lines, old_key, new_key);
+
+    if moves {
+        println!("RENAME {old_secret} -> {new_secret}");
+    }
+    if new_config != raw_config {
+        println!("MAP {old_key} -> {new_key} in {}", config_path.display());
+    }
+    if in_env {
+        println!("ENV {old_key} -> {new_key} in {}", env_path.display());
+    }
+    if dry_run {
+        return Ok(());
+    }
+    if !yes
+        && !push::confirm(
+            &format!("Rename {old_key} to {new_key}?"),
+            "refusing to rename without confirmation; review with --dry-run and pass --yes",
+        )?
+    {
+        bail!("rename cancelled");
+    }
+
+    let principal = provider.identity().await.unwrap_or(None);
+    let result = copy_and_rewrite(
+        provider.as_ref(),
+        &old_secret,
+        &new_secret,
+        moves.then_some(value.as_str()),
+        (config_path, &raw_config, &new_config),
+        in_env.then_some((&cfg, &lines)),
+    )
+    .await;
+
+    let mut entry = audit::Entry::new("rename");
+    entry.principal = principal.as_deref();
+    if result.is_ok() {
+        entry.changed_keys = vec![old_key, new_key];
+    }
+    entry.override_owner = override_owner;
+    entry.error = result.as_ref().err().map(|err| format!("{err:#}"));
+    audit::record(config_path, &cfg, &entry)?;
+    result?;
+    if moves {
+        let changes = [
+            hooks::Change {
+                key: &new_secret,
+                kind: "add",
+            },
+            hooks::Change {
+                key: &old_secret,
+                kind: "delete",
+            },
+        ];
+        hooks::notify(config_path, &cfg, &entry, &changes).await;
+    }
+    println!(
+        "{}",
+        message!(
+            "rename_done",
+            old_key = old_key,
+            old_secret = old_secret,
+            new_key = new_key,
+            new_secret = new_secret,
+        )
+    );
+    Ok(())
+}
+
+/// Creates the new secret (when `value` is given) and points the config and
+/// env file at it; the old secret only goes once both are written. A failed
+/// local write restores the config and deletes the new secret.
+async fn copy_and_rewrite(
+    provider: &dyn SecretProvider,
+    old_secret: &str,
+    new_secret: &str,
+    value: Option<&str>,
+    (config_path, raw_config, new_config): (&Path, &str, &str),
+    env: Option<(&Config, &[envfile::Line])>,
+) -> Result<()> {
+    if let Some(value) = value {
+        provider
+            .set_secret(new_secret, value)
+            .await
+            .map_err(|err| anyhow!("failed to write secret {new_secret}: {err}"))?;
+    }
+
+    let written = write_config(config_path, raw_config, new_config).and_then(|()| {
+        let Some((cfg, lines)) = env else {
+            return Ok(());
+        };
+        let (content, _) = envfile::merge(lines.to_vec(), &Default::default());
+        codec::write_env_file(config_path, cfg, &envfile::render(&content)).inspect_err(|_| {
+            let _ = write_config(config_path, new_config, raw_config);
+        })
+    });
+    if let Err(err) = written {
+        if value.is_some()
+            && let Err(undo) = provider.delete_secret(new_secret).await
+        {
+            eprintln!("warning: could not delete {new_secret} again: {undo}");
+        }
+        return Err(err);
+    }
+    if value.is_none() {
+        return Ok(());
+    }
+
+    provider.delete_secret(old_secret).await.map_err(|err| {
+        anyhow!(
+            "copied to {new_secret} and updated the local files, but failed to delete \
+             {old_secret}: {err}"
+        )
+    })
+}
+
+fn write_config(config_path: &Path, from: &str, to: &str) -> Result<()> {
+    if from == to {
+        return Ok(());
+    }
+    fs::write(config_path, to).with_context(|| format!("failed to write {}", config_path.display()))
+}
+
+/// Drops `old_key`'s `[map]` entry and maps `new_key` to `secret` unless
+/// that is the name `new_key` derives anyway. Comments and layout stay.
+fn rewrite_map(raw: &str, old_key: &str, new_key: &str, secret: &str) -> Result<String> {
+    let mut doc: DocumentMut = raw.parse().context("failed to parse config")?;
+    let needs_entry = resolve::to_env_key(secret) != new_key;
+    if let Some(map) = doc.get_mut("map").and_then(Item::as_table_like_mut) {
+        map.remove(old_key);
+    }
+    if needs_entry {
+        if doc.get("map").is_none() {
+            doc["map"] = toml_edit::table();
+        }
+        doc["map"][new_key] = value(secret);
+    }
+    Ok(doc.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rewrite_map_moves_or_drops_the_entry() {
+        let raw = "version = 1\n\n[map]\n# primary db\nDB = \"database-url\"\nREDIS = \"cache\"\n";
+
+        assert_eq!(
+            rewrite_map(raw, "DB", "DATABASE_URL", "database-url").unwrap(),
+            "version = 1\n\n[map]\nREDIS = \"cache\"\n"
+        );
+        assert_eq!(
+            rewrite_map(raw, "REDIS", "CACHE_URL", "cache").unwrap(),
+            "version = 1\n\n[map]\n# primary db\nDB = \"database-url\"\nCACHE_URL = \"cache\"\n"
+        );
+        assert_eq!(
+            rewrite_map("version = 1\n", "API", "API_TOKEN", "api").unwrap(),
+            "version = 1\n\n[map]\nAPI_TOKEN = \"api\"\n"
+        );
+    }
+}
@@ -0,0 +1,65 @@
+use std::{fs, path::Path, time::SystemTime};
+
+use anyhow::{Context, Result, anyhow};
+
+use crate::{
+    audit,
+    backup::{self, BACKUPS_PATH},
+    config, envfile,
+    messages::message,
+    plaintext,
+};
+
+/// Restores the env file from its newest snapshot, or the newest taken at
+/// or before `to` (RFC 3339). The current file is snapshotted first, so a
+/// rollback can be rolled back. With `list`, prints the snapshots instead.
+pub fn run(config_path: &Path, to: Option<&str>, list: bool) -> Result<()> {
+    let cfg = config::load(config_path)?;
+    let dir = config::resolve_path(config_path, BACKUPS_PATH);
+    let snapshots = backup::list(&dir)?;
+    if list {
+        for snapshot in snapshots.iter().rev() {
+            println!("{}", humantime::format_rfc3339_millis(snapshot.taken));
+        }
+        if snapshots.is_empty() {
+            println!("{}", message!("rollback_none", path = dir.display()));
+        }
+        return Ok(());
+    }
+
+    let to: Option<SystemTime> = to
+        .map(|to| humantime::parse_rfc3339_weak(to).with_context(|| format!("invalid --to {to}")))
+        .transpose()?;
+    let snapshot = snapshots
+        .iter()
+        .rev()
+        .find(|snapshot| to.is_none_or(|to| snapshot.taken <= to))
+        .ok_or_else(|| match to {
+            Some(_) => anyhow!("no snapshot taken at or before that time (see --list)"),
+            None if cfg.output.backups == 0 => {
+                anyhow!("no snapshots; set output.backups to take them before each write")
+            }
+            None => anyhow!("no snapshots in {}", dir.display()),
+        })?;
+    plaintext::check_env_file(&cfg)?;
+    let content = fs::read(&snapshot.path)
+        .with_context(|| format!("failed to read {}", snapshot.path.display()))?;
+
+    let env_path = config::resolve_path(config_path, &cfg.output.env_file);
+    let taken = humantime::format_rfc3339_millis(snapshot.taken).to_string();
+    let result = backup::snapshot(config_path, &cfg, &env_path).and_then(|()| {
+        envfile::write_atomic(&env_path, &content)
+            .with_context(|| format!("failed to write {}", env_path.display()))
+    });
+
+    let mut entry = audit::Entry::new("rollback");
+    entry.env_file = Some(env_path.display().to_string());
+    entry.error = result.as_ref().err().map(|err| format!("{err:#}"));
+    audit::record(config_path, &cfg, &entry)?;
+    result?;
+    println!(
+        "{}",
+        message!("rollback_done", path = env_path.display(), at = taken)
+    );
+    Ok(())
+}
@@ -0,0 +1,385 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    fs,
+    path::{Path, PathBuf},
+    time::{Duration, Instant},
+};
+
+use anyhow::{Context, Result, anyhow, bail};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    audit, classify, codec,
+    commands::{pull, push},
+    config::{self, Config},
+    envfile,
+    generate::{self, Policy},
+    hooks,
+    messages::message,
+    owners, plaintext,
+    provider::{Capability, SecretProvider, cache::ProviderRegistry},
+    resolve, shell,
+};
+
+const VERIFY_POLL_INTERVAL: Duration = Duration::from_secs(2);
+
+/// A rotation campaign file, e.g. `rotate-2024q3.toml`.
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct Campaign {
+    /// Pause between two rotations, e.g. "30s".
+    #[serde(default, with = "humantime_serde_opt")]
+    stagger: Option<Duration>,
+    /// Default verification run after every rotation.
+    #[serde(default)]
+    verify: Option<Verify>,
+    keys: Vec<CampaignKey>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct CampaignKey {
+    secret: String,
+    #[serde(default)]
+    generator: Policy,
+    #[serde(default)]
+    verify: Option<Verify>,
+}
+
+/// Either (or both) of a health check URL that must answer 2xx and a shell
+/// command that must exit 0, retried until `timeout`.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct Verify {
+    url: Option<String>,
+    command: Option<String>,
+    #[serde(default, with = "humantime_serde_opt")]
+    timeout: Option<Duration>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+enum Stage {
+    Rotated,
+    Verified,
+}
+
+/// Per-secret progress persisted next to the campaign file so an interrupted
+/// campaign resumes instead of rotating everything again.
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct Progress {
+    keys: BTreeMap<String, Stage>,
+}
+
+pub async fn run_campaign(
+    config_path: &Path,
+    campaign_path: &Path,
+    override_owner: bool,
+) -> Result<()> {
+    let cfg = config::load(config_path)?;
+    let campaign = load_campaign(campaign_path)?;
+    let progress_path = progress_path(campaign_path);
+    let mut progress = load_progress(&progress_path)?;
+
+    // Ownership is declared on env keys; campaigns name secrets.
+    let env_keys: Vec<_> = campaign
+        .keys
+        .iter()
+        .map(|key| {
+            cfg.map
+                .iter()
+                .find(|(_, secret)| **secret == key.secret)
+                .map(|(env_key, _)| env_key.clone())
+                .unwrap_or_else(|| resolve::to_env_key(&key.secret))
+        })
+        .collect();
+    owners::check(
+        &cfg,
+        &env_keys.iter().map(String::as_str).collect::<Vec<_>>(),
+        override_owner,
+    )?;
+
+    let provider_cfg = cfg.single_provider()?;
+    let mut providers = ProviderRegistry::default();
+    let provider = providers.get(provider_cfg)?;
+    provider
+        .capabilities()
+        .require(Capability::Write, &provider_cfg.kind, "rotate")?;
+    let principal = provider.identity().await.unwrap_or(None);
+
+    let mut first = true;
+    for key in &campaign.keys {
+        let stage = progress.keys.get(&key.secret).copied();
+        if stage == Some(Stage::Verified) {
+            println!("SKIP {} (already rotated)", key.secret);
+            continue;
+        }
+
+        if !first && let Some(stagger) = campaign.stagger {
+            tokio::time::sleep(stagger).await;
+        }
+        first = false;
+
+        let verify = key.verify.as_ref().or(campaign.verify.as_ref());
+        let result = rotate_key(
+            provider.as_ref(),
+            key,
+            stage,
+            verify,
+            &mut progress,
+            &progress_path,
+        )
+        .await;
+
+        let mut entry = audit::Entry::new("rotate");
+        entry.principal = principal.as_deref();
+        entry.override_owner = override_owner;
+        if stage.is_none() && progress.keys.contains_key(&key.secret) {
+            entry.changed_keys = vec![key.secret.as_str()];
+        }
+        entry.error = result.as_ref().err().map(|err| format!("{err:#}"));
+        audit::record(config_path, &cfg, &entry)?;
+        let changes: Vec<_> = entry
+            .changed_keys
+            .iter()
+            .map(|key| hooks::Change {
+                key,
+                kind: "rotate",
+            })
+            .collect();
+        hooks::notify(config_path, &cfg, &entry, &changes).await;
+
+        result.with_context(|| {
+            format!(
+                "campaign stopped at {}; fix the problem and re-run to resume",
+                key.secret
+            )
+        })?;
+    }
+
+    println!(
+        "{}",
+        message!("rotate_campaign_done", count = campaign.keys.len())
+    );
+    Ok(())
+}
+
+/// Rotates one env key's secret with its `[rotate]` policy (by default the
+/// one its class calls for, see `classify::rotation_policy`) and writes the
+/// new value to the env file. If that write fails, the provider gets the
+/// previous value back, so the two never disagree.
+pub async fn run_key(config_path: &Path, key: &str, override_owner: bool) -> Result<()> {
+    let cfg = config::load(config_path)?;
+    let provider_cfg = cfg.single_provider()?;
+    owners::check(&cfg, &[key], override_owner)?;
+    plaintext::check_outputs(&cfg)?;
+
+    let mut providers = ProviderRegistry::default();
+    let provider = providers.get(provider_cfg)?;
+    provider
+        .capabilities()
+        .require(Capability::Write, &provider_cfg.kind, "rotate")?;
+    let listed = provider
+        .list_secrets()
+        .await
+        .context("failed to list secrets")?;
+    let secret = push::secret_for(&cfg, &listed, key);
+    let previous = provider
+        .get_secret(&secret)
+        .await
+        .with_context(|| format!("failed to fetch secret {secret}"))?;
+    // The current value tells a connection string apart from a password.
+    let policy = match cfg.rotate.get(key) {
+        Some(policy) => policy.clone(),
+        None => {
+            let class = classify::class(&cfg.classify, key, previous.as_deref());
+            classify::rotation_policy(key, class)?
+        }
+    };
+    let principal = provider.identity().await.unwrap_or(None);
+
+    let value = generate::generate(&policy);
+    let result = rotate_with_env(
+        config_path,
+        &cfg,
+        provider.as_ref(),
+        (key, &secret),
+        &value,
+        previous.as_deref(),
+    )
+    .await;
+
+    let mut entry = audit::Entry::new("rotate");
+    entry.principal = principal.as_deref();
+    entry.override_owner = override_owner;
+    if result.is_ok() {
+        entry.changed_keys = vec![secret.as_str()];
+    }
+    entry.error = result.as_ref().err().map(|err| format!("{err:#}"));
+    audit::record(config_path, &cfg, &entry)?;
+    if result.is_ok() {
+        let change = hooks::Change {
+            key: &secret,
+            kind: "rotate",
+        };
+        hooks::notify(config_path, &cfg, &entry, &[change]).await;
+    }
+    result?;
+    println!("ROTATED {secret} ({key})");
+    Ok(())
+}
+
+async fn rotate_with_env(
+    config_path: &Path,
+    cfg: &Config,
+    provider: &dyn SecretProvider,
+    (key, secret): (&str, &str),
+    value: &str,
+    previous: Option<&str>,
+) -> Result<()> {
+    provider
+        .set_secret(secret, value)
+        .await
+        .map_err(|e| anyhow!("failed to write secret {secret}: {e}"))?;
+    let Err(err) = write_env(config_path, cfg, key, value) else {
+        return Ok(());
+    };
+    let restored = match previous {
+        Some(previous) => provider.set_secret(secret, previous).await,
+        None => provider.delete_secret(secret).await,
+    };
+    Err(match restored {
+        Ok(()) => err.context(format!(
+            "env file not updated; {secret} was restored to its previous value"
+        )),
+        Err(restore_err) => err.context(format!(
+            "env file not updated and {secret} could not be restored ({restore_err}); \
+             run envit pull to catch up"
+        )),
+    })
+}
+
+/// Writes `key` like a pull would, to its `[output.files]` file if it has one.
+fn write_env(config_path: &Path, cfg: &Config, key: &str, value: &str) -> Result<()> {
+    let mut updates = HashMap::from([(key.to_string(), value.to_string())]);
+    pull::write_files(config_path, cfg, &mut updates, false)?;
+    let lines = codec::load_env_file(config_path, cfg, cfg.output.create_if_missing)?;
+    let (content, _) = envfile::merge(lines, &updates);
+    codec::write_env_file(config_path, cfg, &envfile::render(&content))
+}
+
+async fn rotate_key(
+    provider: &dyn SecretProvider,
+    key: &CampaignKey,
+    stage: Option<Stage>,
+    verify: Option<&Verify>,
+    progress: &mut Progress,
+    progress_path: &Path,
+) -> Result<()> {
+    // A key that was rotated but failed verification is only re-verified;
+    // rotating it again would throw away the value already in use.
+    if stage.is_none() {
+        let value = generate::generate(&key.generator);
+        provider
+            .set_secret(&key.secret, &value)
+            .await
+            .map_err(|e| anyhow!("failed to write secret {}: {e}", key.secret))?;
+        progress.keys.insert(key.secret.clone(), Stage::Rotated);
+        save_progress(progress_path, progress)?;
+        println!("ROTATED {}", key.secret);
+    }
+
+    if let Some(verify) = verify {
+        run_verify(verify)
+            .await
+            .with_context(|| format!("verification failed after rotating {}", key.secret))?;
+    }
+    progress.keys.insert(key.secret.clone(), Stage::Verified);
+    save_progress(progress_path, progress)?;
+    println!("VERIFIED {}", key.secret);
+    Ok(())
+}
+
+async fn run_verify(verify: &Verify) -> Result<()> {
+    let deadline = Instant::now() + verify.timeout.unwrap_or(Duration::ZERO);
+    loop {
+        let attempt = verify_once(verify).await;
+        if attempt.is_ok() || Instant::now() >= deadline {
+            return attempt;
+        }
+        tokio::time::sleep(VERIFY_POLL_INTERVAL).await;
+    }
+}
+
+async fn verify_once(verify: &Verify) -> Result<()> {
+    if let Some(url) = &verify.url {
+        reqwest::get(url)
+            .await
+            .and_then(|res| res.error_for_status())
+            .with_context(|| format!("health check {url}"))?;
+    }
+    if let Some(command) = &verify.command {
+        let status = shell::command(command)
+            .status()
+            .await
+            .with_context(|| format!("failed to run verify command: {command}"))?;
+        if !status.success() {
+            bail!("verify command exited with {status}: {command}");
+        }
+    }
+    Ok(())
+}
+
+fn load_campaign(path: &Path) -> Result<Campaign> {
+    let raw = fs::read_to_string(path)
+        .with_context(|| format!("failed to read campaign file: {}", path.display()))?;
+    let campaign: Campaign = toml::from_str(&raw)
+        .with_context(|| format!("failed to parse campaign file: {}", path.display()))?;
+    if campaign.keys.is_empty() {
+        bail!("campaign has no [[keys]]");
+    }
+    for key in &campaign.keys {
+        if key.secret.trim().is_empty() {
+            bail!("campaign [[keys]] entries need a secret name");
+        }
+        key.generator
+            .validate()
+            .with_context(|| format!("invalid generator for {}", key.secret))?;
+    }
+    Ok(campaign)
+}
+
+fn progress_path(campaign_path: &Path) -> PathBuf {
+    let mut name = campaign_path.as_os_str().to_owned();
+    name.push(".progress.json");
+    PathBuf::from(name)
+}
+
+fn load_progress(path: &Path) -> Result<Progress> {
+    if !path.exists() {
+        return Ok(Progress::default());
+    }
+    let raw = fs::read_to_string(path)
+        .with_context(|| format!("failed to read campaign progress: {}", path.display()))?;
+    serde_json::from_str(&raw)
+        .with_context(|| format!("failed to parse campaign progress: {}", path.display()))
+}
+
+fn save_progress(path: &Path, progress: &Progress) -> Result<()> {
+    let raw = serde_json::to_string_pretty(progress).context("failed to serialize progress")?;
+    fs::write(path, raw)
+        .with_context(|| format!("failed to write campaign progress: {}", path.display()))
+}
+
+/// `Option<Duration>` fields written as humantime strings ("30s", "2m").
+mod humantime_serde_opt {
+    use std::time::Duration;
+
+    use serde::{Deserialize, Deserializer, de::Error};
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(d: D) -> Result<Option<Duration>, D::Error> {
+        Option::<String>::deserialize(d)?
+            .map(|raw| humantime::parse_duration(&raw).map_err(D::Error::custom))
+            .transpose()
+    }
+}
@@ -0,0 +1,115 @@
+use std::{path::Path, process::ExitStatus};
+
+use anyhow::{Context, Result, bail};
+
+use crate::{
+    audit,
+    commands::{decrypt, pull},
+    config,
+    provider::cache::ProviderRegistry,
+};
+
+/// Set for the child under `--memfd`: the inherited descriptor's number.
+pub const MEMFD_ENV: &str = "ENVIT_ENV_FD";
+/// Set for the child under `--memfd`: `/proc/self/fd/<n>`, for applications
+/// that read a dotenv file from a path.
+pub const MEMFD_PATH_ENV: &str = "ENVIT_ENV_FILE";
+
+/// Runs `command` with the resolved values added to its environment (over
+/// inherited variables of the same name). Nothing is written to disk.
+/// With `from_env_file`, the values come from the env file instead,
+/// decrypted in memory when it is encrypted. With `memfd` (Linux only) the
+/// values stay out of the environment, and so out of `/proc/<pid>/environ`:
+/// the child inherits a sealed memfd holding them in env file format, named
+/// by `MEMFD_ENV` and `MEMFD_PATH_ENV`. Returns the exit code to leave with,
+/// mirroring the child's.
+pub async fn run(
+    config_path: &Path,
+    command: &[String],
+    from_env_file: bool,
+    memfd: bool,
+) -> Result<i32> {
+    let Some((program, args)) = command.split_first() else {
+        bail!("no command given (usage: envit run -- <command> [args...])");
+    };
+    if memfd && !cfg!(target_os = "linux") {
+        bail!("--memfd needs Linux");
+    }
+    let cfg = config::load(config_path)?;
+    let (principal, result) = if from_env_file {
+        (None, decrypt::values(config_path, &cfg, None))
+    } else {
+        pull::resolve_values(config_path, &cfg, &mut ProviderRegistry::default()).await
+    };
+
+    let mut entry = audit::Entry::new("run");
+    entry.principal = principal.as_deref();
+    if let Ok(values) = &result {
+        entry.exported_keys = values.keys().map(String::as_str).collect();
+        entry.exported_keys.sort_unstable();
+    }
+    entry.error = result.as_ref().err().map(|err| format!("{err:#}"));
+    audit::record(config_path, &cfg, &entry)?;
+    let values = result?;
+
+    let mut child = tokio::process::Command::new(program);
+    child.args(args);
+    if !memfd {
+        child.envs(&values);
+    }
+    // Held open until the child exits.
+    #[cfg(target_os = "linux")]
+    let _memfd = if memfd {
+        let file = sealed_memfd(&values)?;
+        let fd = std::os::fd::AsRawFd::as_raw_fd(&file);
+        child
+            .env(MEMFD_ENV, fd.to_string())
+            .env(MEMFD_PATH_ENV, format!("/proc/self/fd/{fd}"));
+        Some(file)
+    } else {
+        None
+    };
+    let status = child
+        .status()
+        .await
+        .with_context(|| format!("failed to run {program}"))?;
+    Ok(exit_code(status))
+}
+
+/// An anonymous in-memory file holding `values` as an env file, sealed so
+/// neither side can change it, and left inheritable (no close-on-exec).
+#[cfg(target_os = "linux")]
+fn sealed_memfd(values: &std::collections::HashMap<String, String>) -> Result<std::fs::File> {
+    use std::io::{Seek, Write};
+
+    use rustix::fs::{self, MemfdFlags, SealFlags};
+
+    use crate::envfile;
+
+    let fd = fs::memfd_create("envit-env", MemfdFlags::ALLOW_SEALING)
+        .context("failed to create a memfd")?;
+    let (content, _) = envfile::merge(Vec::new(), values);
+    let mut file = std::fs::File::from(fd);
+    file.write_all(envfile::render(&content).as_bytes())
+        .and_then(|()| file.rewind())
+        .context("failed to write the memfd")?;
+    fs::fcntl_add_seals(
+        &file,
+        SealFlags::SHRINK | SealFlags::GROW | SealFlags::WRITE | SealFlags::SEAL,
+    )
+    .context("failed to seal the memfd")?;
+    Ok(file)
+}
+
+/// The child's exit code; on unix, death by signal N becomes 128 + N as in
+/// shells.
+fn exit_code(status: ExitStatus) -> i32 {
+    #[cfg(unix)]
+    {
+        use std::os::unix::process::ExitStatusExt;
+        if let Some(signal) = status.signal() {
+            return 128 + signal;
+        }
+    }
+    status.code().unwrap_or(1)
+}
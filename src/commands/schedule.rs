@@ -0,0 +1,385 @@
+use std::{
+    env, fs,
+    path::{Path, PathBuf},
+    process::Command,
+    time::Duration,
+};
+
+use anyhow::{Context, Result, bail};
+
+use crate::{envfile, messages::message};
+
+const MIN_INTERVAL: Duration = Duration::from_secs(60);
+const LAUNCHD_PREFIX: &str = "dev.envit.";
+
+/// Installs a user-level systemd timer (Linux) or launchd agent (macOS) that
+/// runs `envit pull --quiet` for this config every `every`.
+pub fn install(config_path: &Path, every: Duration, dry_run: bool) -> Result<()> {
+    if every < MIN_INTERVAL {
+        bail!(
+            "--every must be at least {}",
+            humantime::format_duration(MIN_INTERVAL)
+        );
+    }
+    let job = Job::new(config_path)?;
+    let platform = Platform::current()?;
+    let files = platform.files(&job, every)?;
+
+    if dry_run {
+        for (path, content) in &files {
+            println!("# {}\n{content}", path.display());
+        }
+        for args in platform.activate(&job)? {
+            println!("$ {}", args.join(" "));
+        }
+        return Ok(());
+    }
+
+    for (path, content) in &files {
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)
+                .with_context(|| format!("failed to create {}", dir.display()))?;
+        }
+        envfile::write_atomic(path, content)
+            .with_context(|| format!("failed to write {}", path.display()))?;
+        println!("{}", message!("wrote", path = path.display()));
+    }
+    for args in platform.activate(&job)? {
+        run(&args)?;
+    }
+    println!(
+        "{}",
+        message!(
+            "schedule_done",
+            every = humantime::format_duration(every),
+            unit = platform.unit(&job),
+        )
+    );
+    Ok(())
+}
+
+/// Stops and removes the schedule installed for this config.
+pub fn uninstall(config_path: &Path) -> Result<()> {
+    let job = Job::new(config_path)?;
+    let platform = Platform::current()?;
+    // Not loaded (or already gone) is fine; the files are what matter.
+    for args in platform.deactivate(&job)? {
+        let _ = Command::new(&args[0]).args(&args[1..]).status();
+    }
+    let mut removed = false;
+    for path in platform.paths(&job)? {
+        match fs::remove_file(&path) {
+            Ok(()) => {
+                removed = true;
+                println!("{}", message!("schedule_removed", path = path.display()));
+            }
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => {}
+            Err(err) => {
+                return Err(err).with_context(|| format!("failed to remove {}", path.display()));
+            }
+        }
+    }
+    if !removed {
+        println!(
+            "{}",
+            message!("schedule_none", config = job.config.display())
+        );
+    }
+    Ok(())
+}
+
+/// What gets scheduled: this binary, pulling one config.
+struct Job {
+    /// Unique per config path, so several projects can be scheduled.
+    name: String,
+    exe: PathBuf,
+    config: PathBuf,
+    dir: PathBuf,
+}
+
+impl Job {
+    fn new(config_path: &Path) -> Result<Self> {
+        let config = fs::canonicalize(config_path)
+            .with_context(|| format!("failed to resolve {}", config_path.display()))?;
+        let dir = config
+            .parent()
+            .map(Path::to_path_buf)
+            .unwrap_or_else(|| PathBuf::from("/"));
+        let project: String = dir
+            .file_name()
+            .map(|name| name.to_string_lossy().into_owned())
+            .unwrap_or_default()
+            .chars()
+            .map(|c| if c.is_ascii_alphanumeric() { c } else { '-' })
+            .collect();
+        let name = format!(
+            "{}-{:08x}",
+            project.trim_matches('-'),
+            fnv1a(config.to_string_lossy().as_bytes())
+        );
+        Ok(Self {
+            name: name.trim_start_matches('-').to_string(),
+            exe: env::current_exe().context("failed to locate the envit binary")?,
+            config,
+            dir,
+        })
+    }
+}
+
+/// Stable across builds, unlike `DefaultHasher`; uninstall must find the
+/// names install picked.
+fn fnv1a(bytes: &[u8]) -> u32 {
+    bytes.iter().fold(0x811c_9dc5, |hash, &byte| {
+        (hash ^ u32::from(byte)).wrapping_mul(0x0100_0193)
+    })
+}
+
+#[derive(Debug, Clone, Copy)]
+enum Platform {
+    Systemd,
+    Launchd,
+}
+
+impl Platform {
+    fn current() -> Result<Self> {
+        if cfg!(target_os = "macos") {
+            Ok(Self::Launchd)
+        } else if cfg!(target_os = "linux") {
+            Ok(Self::Systemd)
+        } else {
+            bail!("schedule supports systemd (Linux) and launchd (macOS) only")
+        }
+    }
+
+    fn unit(self, job: &Job) -> String {
+        match self {
+            Self::Systemd => format!("envit-{}.timer", job.name),
+            Self::Launchd => format!("{LAUNCHD_PREFIX}{}", job.name),
+        }
+    }
+
+    fn paths(self, job: &Job) -> Result<Vec<PathBuf>> {
+        Ok(match self {
+            Self::Systemd => {
+                let dir = config_home()?.join("systemd/user");
+                vec![
+                    dir.join(format!("envit-{}.service", job.name)),
+                    dir.join(format!("envit-{}.timer", job.name)),
+                ]
+            }
+            Self::Launchd => {
+                vec![home()?.join(format!("Library/LaunchAgents/{}.plist", self.unit(job)))]
+            }
+        })
+    }
+
+    fn files(self, job: &Job, every: Duration) -> Result<Vec<(PathBuf, String)>> {
+        let contents = match self {
+            Self::Systemd => vec![systemd_service(job), systemd_timer(job, every)],
+            Self::Launchd => {
+                let log = home()?.join(format!("Library/Logs/envit-{}.log", job.name));
+                vec![launchd_plist(job, &self.unit(job), &log, every)]
+            }
+        };
+        Ok(self.paths(job)?.into_iter().zip(contents).collect())
+    }
+
+    fn activate(self, job: &Job) -> Result<Vec<Vec<String>>> {
+        Ok(match self {
+            Self::Systemd => vec![
+                systemctl(&["daemon-reload"]),
+                systemctl(&["enable", "--now", &self.unit(job)]),
+            ],
+            Self::Launchd => {
+                let plist = self.plist(job)?;
+                vec![launchctl(&["load", "-w", &plist])]
+            }
+        })
+    }
+
+    fn deactivate(self, job: &Job) -> Result<Vec<Vec<String>>> {
+        Ok(match self {
+            Self::Systemd => vec![systemctl(&["disable", "--now", &self.unit(job)])],
+            Self::Launchd => vec![launchctl(&["unload", "-w", &self.plist(job)?])],
+        })
+    }
+
+    fn plist(self, job: &Job) -> Result<String> {
+        Ok(self.paths(job)?[0].display().to_string())
+    }
+}
+
+fn systemctl(args: &[&str]) -> Vec<String> {
+    ["systemctl", "--user"]
+        .iter()
+        .chain(args)
+        .map(|arg| arg.to_string())
+        .collect()
+}
+
+fn launchctl(args: &[&str]) -> Vec<String> {
+    std::iter::once(&"launchctl")
+        .chain(args)
+        .map(|arg| arg.to_string())
+        .collect()
+}
+
+fn run(args: &[String]) -> Result<()> {
+    let status = Command::new(&args[0])
+        .args(&args[1..])
+        .status()
+        .with_context(|| format!("failed to run {}", args[0]))?;
+    if !status.success() {
+        bail!("`{}` failed ({status})", args.join(" "));
+    }
+    Ok(())
+}
+
+fn home() -> Result<PathBuf> {
+    env::var_os("HOME")
+        .map(PathBuf::from)
+        .context("HOME is not set")
+}
+
+fn config_home() -> Result<PathBuf> {
+    match env::var_os("XDG_CONFIG_HOME") {
+        Some(dir) if !dir.is_empty() => Ok(PathBuf::from(dir)),
+        _ => Ok(home()?.join(".config")),
+    }
+}
+
+fn systemd_service(job: &Job) -> String {
+    format!(
+        "[Unit]\n\
+         Description=envit pull for {config}\n\
+         \n\
+         [Service]\n\
+         Type=oneshot\n\
+         WorkingDirectory={dir}\n\
+         ExecStart={exe} pull --quiet --config {config_arg}\n",
+        config = job.config.display(),
+        dir = systemd_quote(&job.dir),
+        exe = systemd_quote(&job.exe),
+        config_arg = systemd_quote(&job.config),
+    )
+}
+
+fn systemd_timer(job: &Job, every: Duration) -> String {
+    format!(
+        "[Unit]\n\
+         Description=Refresh envit env file for {config} every {every}\n\
+         \n\
+         [Timer]\n\
+         OnStartupSec=1min\n\
+         OnUnitActiveSec={secs}s\n\
+         \n\
+         [Install]\n\
+         WantedBy=timers.target\n",
+        config = job.config.display(),
+        every = humantime::format_duration(every),
+        secs = every.as_secs(),
+    )
+}
+
+/// Double-quoted for systemd, with `%` specifiers escaped.
+fn systemd_quote(path: &Path) -> String {
+    let escaped = path
+        .display()
+        .to_string()
+        .replace('\\', r"\\")
+        .replace('"', "\\\"")
+        .replace('%', "%%");
+    format!("\"{escaped}\"")
+}
+
+fn launchd_plist(job: &Job, label: &str, log: &Path, every: Duration) -> String {
+    let args: String = [
+        job.exe.display().to_string(),
+        "pull".to_string(),
+        "--quiet".to_string(),
+        "--config".to_string(),
+        job.config.display().to_string(),
+    ]
+    .iter()
+    .map(|arg| format!("    <string>{}</string>\n", xml_escape(arg)))
+    .collect();
+    format!(
+        r#"<?xml version="1.0" encoding="UTF-8"?>
+<!DOCTYPE plist PUBLIC "-//Apple//DTD PLIST 1.0//EN" "http://www.apple.com/DTDs/PropertyList-1.0.dtd">
+<plist version="1.0">
+<dict>
+  <key>Label</key>
+  <string>{label}</string>
+  <key>ProgramArguments</key>
+  <array>
+{args}  </array>
+  <key>WorkingDirectory</key>
+  <string>{dir}</string>
+  <key>StartInterval</key>
+  <integer>{secs}</integer>
+  <key>RunAtLoad</key>
+  <true/>
+  <key>StandardErrorPath</key>
+  <string>{log}</string>
+</dict>
+</plist>
+"#,
+        label = xml_escape(label),
+        dir = xml_escape(&job.dir.display().to_string()),
+        secs = every.as_secs(),
+        log = xml_escape(&log.display().to_string()),
+    )
+}
+
+fn xml_escape(value: &str) -> String {
+    value
+        .replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn job() -> Job {
+        Job {
+            name: "my-app-0123abcd".to_string(),
+            exe: PathBuf::from("/usr/local/bin/envit"),
+            config: PathBuf::from("/home/me/my app/envit.toml"),
+            dir: PathBuf::from("/home/me/my app"),
+        }
+    }
+
+    #[test]
+    fn systemd_units_quote_paths_and_use_the_interval() {
+        let service = systemd_service(&job());
+        assert!(service.contains(
+            "ExecStart=\"/usr/local/bin/envit\" pull --quiet --config \"/home/me/my app/envit.toml\"\n"
+        ));
+        assert!(
+            systemd_timer(&job(), Duration::from_secs(6 * 3600))
+                .contains("OnUnitActiveSec=21600s\n")
+        );
+        assert_eq!(systemd_quote(Path::new("/a/100%")), "\"/a/100%%\"");
+    }
+
+    #[test]
+    fn launchd_plist_lists_arguments_and_interval() {
+        let plist = launchd_plist(
+            &job(),
+            "dev.envit.my-app-0123abcd",
+            Path::new("/home/me/Library/Logs/envit.log"),
+            Duration::from_secs(3600),
+        );
+        assert!(plist.contains("    <string>--quiet</string>\n"));
+        assert!(plist.contains("    <string>/home/me/my app/envit.toml</string>\n"));
+        assert!(plist.contains("<integer>3600</integer>"));
+    }
+
+    #[test]
+    fn fnv1a_is_stable() {
+        assert_eq!(fnv1a(b""), 0x811c_9dc5);
+        assert_eq!(fnv1a(b"a"), 0xe40c_292c);
+    }
+}
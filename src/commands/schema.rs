@@ -0,0 +1,20 @@
+use anyhow::Result;
+use schemars::generate::SchemaSettings;
+
+use crate::config::Config;
+
+/// Prints a JSON Schema for `envit.toml`, derived from the config types and
+/// the settings of each compiled-in provider kind, for editors that
+/// validate TOML (taplo, Even Better TOML) via a `#:schema` comment.
+pub fn run() -> Result<()> {
+    println!("{}", render()?);
+    Ok(())
+}
+
+pub fn render() -> Result<String> {
+    let mut schema = SchemaSettings::draft07()
+        .into_generator()
+        .into_root_schema_for::<Config>();
+    schema.insert("title".to_string(), "envit.toml".into());
+    Ok(serde_json::to_string_pretty(&schema)?)
+}
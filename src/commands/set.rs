@@ -0,0 +1,143 @@
+use std::{
+    fs,
+    io::{self, BufRead, IsTerminal, Write},
+    path::Path,
+    process::{Command, Stdio},
+};
+
+use anyhow::{Context, Result, anyhow, bail};
+
+use crate::{
+    audit,
+    commands::{push, vault},
+    config, hooks,
+    messages::message,
+    owners,
+    provider::{Capability, SecretProvider, cache::ProviderRegistry},
+    resolve,
+};
+
+/// Writes one env key's secret to the config's provider. The value comes
+/// from `value`, else from stdin when it is piped, else from a prompt that
+/// does not echo. With `secret`, that name is used; `save_map` records it
+/// in `[map]` when the key would not derive from it.
+pub async fn run(
+    config_path: &Path,
+    key: &str,
+    value: Option<String>,
+    secret: Option<&str>,
+    save_map: bool,
+    override_owner: bool,
+) -> Result<()> {
+    let cfg = config::load(config_path)?;
+    let provider_cfg = cfg.single_provider()?;
+    match (save_map, secret, cfg.map.get(key)) {
+        (true, None, _) => bail!("--save-map needs --secret"),
+        (true, Some(secret), Some(mapped)) if mapped != secret => {
+            bail!("[map] already maps {key} to {mapped}; edit it in the config")
+        }
+        _ => {}
+    }
+    owners::check(&cfg, &[key], override_owner)?;
+    let mut providers = ProviderRegistry::default();
+    let provider = providers.get(provider_cfg)?;
+    provider
+        .capabilities()
+        .require(Capability::Write, &provider_cfg.kind, "set")?;
+    let value = match value {
+        Some(value) => value,
+        None if !io::stdin().is_terminal() => vault::read_stdin()?,
+        None => prompt_hidden(&format!("Value for {key}: "))?,
+    };
+
+    let secret = match secret {
+        Some(secret) => secret.to_string(),
+        None => {
+            let listed = provider
+                .list_secrets()
+                .await
+                .context("failed to list secrets")?;
+            push::secret_for(&cfg, &listed, key)
+        }
+    };
+
+    let principal = provider.identity().await.unwrap_or(None);
+    let existed = provider
+        .get_secret(&secret)
+        .await
+        .with_context(|| format!("failed to fetch secret {secret}"))?
+        .is_some();
+    let result = provider
+        .set_secret(&secret, &value)
+        .await
+        .map_err(|err| anyhow!("failed to write secret {secret}: {err}"));
+
+    let mut entry = audit::Entry::new("set");
+    entry.principal = principal.as_deref();
+    if result.is_ok() {
+        entry.changed_keys = vec![secret.as_str()];
+    }
+    entry.override_owner = override_owner;
+    entry.error = result.as_ref().err().map(|err| format!("{err:#}"));
+    audit::record(config_path, &cfg, &entry)?;
+    if result.is_ok() {
+        let change = hooks::Change {
+            key: &secret,
+            kind: if existed { "update" } else { "add" },
+        };
+        hooks::notify(config_path, &cfg, &entry, &[change]).await;
+    }
+    result?;
+    println!("{}", message!("set_done", key = key, secret = secret));
+
+    if save_map && resolve::to_env_key(&secret) != key && cfg.map.get(key) != Some(&secret) {
+        add_map_entry(config_path, key, &secret)?;
+        println!("{}", message!("set_mapped", key = key, secret = secret));
+    }
+    Ok(())
+}
+
+/// Inserts `KEY = "secret"` at the top of the config's `[map]` table (or a
+/// new one at the end), leaving the rest of the file as written.
+fn add_map_entry(config_path: &Path, key: &str, secret: &str) -> Result<()> {
+    let raw = fs::read_to_string(config_path)
+        .with_context(|| format!("failed to read {}", config_path.display()))?;
+    let line = format!("{key} = {}", toml::Value::String(secret.to_string()));
+    let mut lines: Vec<&str> = raw.lines().collect();
+    match lines.iter().position(|l| l.trim() == "[map]") {
+        Some(header) => lines.insert(header + 1, &line),
+        None => {
+            lines.extend(["", "[map]"]);
+            lines.push(&line);
+        }
+    }
+    fs::write(config_path, format!("{}\n", lines.join("\n")))
+        .with_context(|| format!("failed to write {}", config_path.display()))
+}
+
+/// Reads a line from the terminal with echo turned off (via `stty`, where
+/// available).
+fn prompt_hidden(prompt: &str) -> Result<String> {
+    eprint!("{prompt}");
+    io::stderr().flush()?;
+    let stty = |arg: &str| {
+        Command::new("stty")
+            .arg(arg)
+            .stdin(Stdio::inherit())
+            .status()
+            .is_ok_and(|status| status.success())
+    };
+    let hidden = stty("-echo");
+    let mut value = String::new();
+    let read = io::stdin().lock().read_line(&mut value);
+    if hidden {
+        stty("echo");
+    }
+    eprintln!();
+    read.context("failed to read the value")?;
+    let value = value.trim_end_matches(['\r', '\n']).to_string();
+    if value.is_empty() {
+        bail!("no value entered");
+    }
+    Ok(value)
+}
@@ -0,0 +1,79 @@
+use std::{
+    collections::BTreeSet,
+    path::Path,
+    time::{Duration, SystemTime},
+};
+
+use anyhow::Result;
+
+use crate::{
+    codec,
+    commands::pull::{self, CANARY_KEY, LAST_SYNC_PATH},
+    config, envfile,
+    messages::{self, message},
+    provider::cache::ProviderRegistry,
+    resolve,
+};
+
+/// Summarizes the env file against the providers: keys envit manages, keys
+/// only set locally, provider keys not yet pulled, and when the last pull
+/// completed. Only secret names are listed; no values are fetched.
+pub async fn run(config_path: &Path) -> Result<()> {
+    let cfg = config::load(config_path)?;
+    let env_path = config::resolve_path(config_path, &cfg.output.env_file);
+
+    let mut providers = ProviderRegistry::default();
+    let connected = providers.connect(&cfg)?;
+    let mut provided = BTreeSet::new();
+    for (source, provider) in &connected {
+        for (_, key) in resolve::keys(*source, provider.as_ref()).await? {
+            provided.insert(key);
+        }
+    }
+    if cfg.output.canary {
+        provided.insert(CANARY_KEY.to_string());
+    }
+
+    let local: BTreeSet<_> = match codec::read_env_file(config_path, &cfg, None)? {
+        Some(raw) => envfile::parse_entries(&raw)
+            .into_iter()
+            .map(|(key, _)| key)
+            .collect(),
+        None => BTreeSet::new(),
+    };
+
+    match pull::read_last_sync(&config::resolve_path(config_path, LAST_SYNC_PATH))? {
+        Some(at) => {
+            let age = SystemTime::now()
+                .duration_since(at)
+                .unwrap_or_default()
+                .as_secs();
+            println!(
+                "{}",
+                message!(
+                    "status_synced",
+                    path = env_path.display(),
+                    at = humantime::format_rfc3339_seconds(at),
+                    age = humantime::format_duration(Duration::from_secs(age)),
+                )
+            );
+        }
+        None => println!(
+            "{}",
+            message!("status_never_synced", path = env_path.display())
+        ),
+    }
+    section("status_managed", local.intersection(&provided));
+    section("status_local_only", local.difference(&provided));
+    section("status_not_materialized", provided.difference(&local));
+    Ok(())
+}
+
+/// `heading` is a message id taking the key `{count}`.
+fn section<'a>(heading: &str, keys: impl Iterator<Item = &'a String>) {
+    let keys: Vec<_> = keys.collect();
+    println!("{}", messages::text(heading, &[("count", &keys.len())]));
+    for key in keys {
+        println!("  {key}");
+    }
+}
@@ -0,0 +1,120 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    io::{self, Read},
+    path::PathBuf,
+};
+
+use anyhow::{Context, Result, anyhow, bail};
+
+use crate::{commands::export, config, plaintext};
+
+const DEFAULT_CONFIG: &str = "envit.toml";
+
+/// Terraform's `external` data source protocol: a JSON object of strings on
+/// stdin, a JSON object of strings on stdout, errors on stderr.
+///
+/// Query arguments:
+/// - `config`: config path, relative to Terraform's working directory
+/// - `keys`: optional comma-separated env keys to return (all by default)
+pub async fn run() -> Result<()> {
+    let mut input = String::new();
+    io::stdin()
+        .read_to_string(&mut input)
+        .context("failed to read the query from stdin")?;
+    let query = parse_query(&input)?;
+
+    let config_path = PathBuf::from(query.config.as_deref().unwrap_or(DEFAULT_CONFIG));
+    let cfg = config::load(&config_path)?;
+    // Data source results are stored in Terraform state in plaintext.
+    if cfg.security.protected {
+        bail!("config is protected: its values cannot be exported into Terraform state");
+    }
+    plaintext::refuse(&cfg, "values cannot be exported into Terraform state")?;
+
+    let values = export::resolve_audited(&config_path, &cfg, "tf-external", None, |values| {
+        select(values, query.keys.as_deref())
+    })
+    .await?;
+    println!(
+        "{}",
+        serde_json::to_string(&values.into_iter().collect::<BTreeMap<_, _>>())?
+    );
+    Ok(())
+}
+
+#[derive(Debug, Default, PartialEq)]
+struct Query {
+    config: Option<String>,
+    keys: Option<String>,
+}
+
+fn parse_query(input: &str) -> Result<Query> {
+    // Terraform sends `{}` for an absent query; tolerate an empty stdin too.
+    if input.trim().is_empty() {
+        return Ok(Query::default());
+    }
+    let raw: HashMap<String, String> =
+        serde_json::from_str(input).context("query must be a JSON object with string values")?;
+
+    let mut query = Query::default();
+    for (name, value) in raw {
+        match name.as_str() {
+            "config" => query.config = Some(value),
+            "keys" => query.keys = Some(value),
+            other => bail!("unknown query argument {other:?} (expected config, keys)"),
+        }
+    }
+    Ok(query)
+}
+
+fn select(values: HashMap<String, String>, keys: Option<&str>) -> Result<HashMap<String, String>> {
+    let Some(keys) = keys else {
+        return Ok(values);
+    };
+    keys.split(',')
+        .map(str::trim)
+        .filter(|key| !key.is_empty())
+        .map(|key| {
+            values
+                .get(key)
+                .map(|value| (key.to_string(), value.clone()))
+                .ok_or_else(|| anyhow!("key {key} was not resolved from any provider"))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn query_accepts_known_arguments_only() {
+        assert_eq!(parse_query("").unwrap(), Query::default());
+        assert_eq!(
+            parse_query(r#"{"config": "infra/envit.toml", "keys": "A,B"}"#).unwrap(),
+            Query {
+                config: Some("infra/envit.toml".to_string()),
+                keys: Some("A,B".to_string()),
+            }
+        );
+        assert!(parse_query(r#"{"cfg": "x"}"#).is_err());
+        assert!(parse_query(r#"{"keys": 1}"#).is_err());
+    }
+
+    #[test]
+    fn select_returns_requested_keys_or_fails() {
+        let values = HashMap::from([
+            ("A".to_string(), "1".to_string()),
+            ("B".to_string(), "2".to_string()),
+        ]);
+
+        let mut picked: Vec<_> = select(values.clone(), Some("B, A"))
+            .unwrap()
+            .into_keys()
+            .collect();
+        picked.sort_unstable();
+        assert_eq!(picked, ["A", "B"]);
+        assert!(select(values.clone(), Some("C")).is_err());
+        assert_eq!(select(values, None).unwrap().len(), 2);
+    }
+}
@@ -0,0 +1,71 @@
+use std::{collections::BTreeMap, path::Path};
+
+use anyhow::{Result, bail};
+
+use crate::{
+    config::{self, Config},
+    messages::message,
+};
+
+/// Checks the config without contacting any provider: the schema and the
+/// checks every command runs, then `[map]` collisions and env key names.
+/// Every problem is reported, not just the first.
+pub fn run(config_path: &Path) -> Result<()> {
+    let cfg = config::load(config_path)?;
+    let problems = problems(&cfg);
+    if problems.is_empty() {
+        println!("{}", message!("validate_ok", path = config_path.display()));
+        return Ok(());
+    }
+    for problem in &problems {
+        eprintln!("error: {problem}");
+    }
+    bail!("{} problem(s) in {}", problems.len(), config_path.display());
+}
+
+fn problems(cfg: &Config) -> Vec<String> {
+    let mut problems = Vec::new();
+    for (index, source) in cfg.sources().into_iter().enumerate() {
+        let table = if cfg.providers.is_empty() {
+            "[map]".to_string()
+        } else {
+            format!("providers[{index}].map")
+        };
+        let mut by_secret: BTreeMap<&str, Vec<&str>> = BTreeMap::new();
+        for (env_key, secret) in source.map {
+            by_secret.entry(secret).or_default().push(env_key);
+        }
+        for (secret, mut env_keys) in by_secret {
+            env_keys.sort_unstable();
+            for env_key in &env_keys {
+                let key = format!("{}{env_key}", source.prefix);
+                if !is_env_key(&key) {
+                    problems.push(format!("{table}: {key} is not a valid env key"));
+                }
+            }
+            if env_keys.len() > 1 {
+                problems.push(format!(
+                    "{table}: secret {secret} is mapped to more than one key ({})",
+                    env_keys.join(", ")
+                ));
+            }
+        }
+    }
+    let mut files: Vec<_> = cfg.output.files.keys().collect();
+    files.sort_unstable();
+    for key in files {
+        if !is_env_key(key) {
+            problems.push(format!("[output.files]: {key} is not a valid env key"));
+        }
+    }
+    problems
+}
+
+/// Letters, digits and underscores, not starting with a digit.
+fn is_env_key(key: &str) -> bool {
+    let mut chars = key.chars();
+    chars
+        .next()
+        .is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
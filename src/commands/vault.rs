@@ -0,0 +1,87 @@
+use std::{
+    io::{self, Read},
+    path::Path,
+};
+
+use anyhow::{Context, Result, anyhow, bail};
+
+use crate::{
+    audit,
+    config::{self, Config, ProviderConfig},
+    messages::message,
+    plaintext, provider,
+};
+
+const KIND: &str = "local_vault";
+
+/// Prints one secret from the config's `local_vault` provider.
+pub async fn get(config_path: &Path, name: &str) -> Result<()> {
+    let cfg = config::load(config_path)?;
+    if cfg.security.protected {
+        bail!("config is protected: read vault secrets with `envit pull` instead");
+    }
+    plaintext::refuse(&cfg, "vault get would print a plaintext value")?;
+    let vault = provider::build_provider(local_vault(&cfg)?)?;
+    let result = vault
+        .get_secret(name)
+        .await
+        .with_context(|| format!("failed to read {name} from the vault"))
+        .and_then(|value| value.ok_or_else(|| anyhow!("secret {name} is not in the vault")));
+
+    let mut entry = audit::Entry::new("vault-get");
+    if result.is_ok() {
+        entry.exported_keys = vec![name];
+    }
+    entry.error = result.as_ref().err().map(|err| format!("{err:#}"));
+    audit::record(config_path, &cfg, &entry)?;
+
+    println!("{}", result?);
+    Ok(())
+}
+
+/// Stores a secret in the config's `local_vault` provider. Without `value`
+/// it is read from stdin, so it stays out of shell history.
+pub async fn set(config_path: &Path, name: &str, value: Option<String>) -> Result<()> {
+    let cfg = config::load(config_path)?;
+    let value = match value {
+        Some(value) => value,
+        None => read_stdin()?,
+    };
+    let vault = provider::build_provider(local_vault(&cfg)?)?;
+    let result = vault
+        .set_secret(name, &value)
+        .await
+        .with_context(|| format!("failed to write {name} to the vault"));
+
+    let mut entry = audit::Entry::new("vault-set");
+    entry.changed_keys = vec![name];
+    entry.error = result.as_ref().err().map(|err| format!("{err:#}"));
+    audit::record(config_path, &cfg, &entry)?;
+
+    result?;
+    println!("{}", message!("vault_stored", name = name));
+    Ok(())
+}
+
+fn local_vault(cfg: &Config) -> Result<&ProviderConfig> {
+    cfg.sources()
+        .into_iter()
+        .map(|source| source.provider)
+        .find(|provider| provider.kind == KIND)
+        .ok_or_else(|| anyhow!("no {KIND} provider in this config"))
+}
+
+/// All of stdin, without the trailing newline.
+pub fn read_stdin() -> Result<String> {
+    let mut value = String::new();
+    io::stdin()
+        .read_to_string(&mut value)
+        .context("failed to read the secret value from stdin")?;
+    if value.ends_with('\n') {
+        value.pop();
+        if value.ends_with('\r') {
+            value.pop();
+        }
+    }
+    Ok(value)
+}
@@ -0,0 +1,173 @@
+use std::{
+    collections::HashSet,
+    fs,
+    path::{Path, PathBuf},
+    time::{Duration, SystemTime},
+};
+
+use anyhow::{Context, Result};
+use notify::{EventKind, RecommendedWatcher, RecursiveMode, Watcher};
+use tokio::sync::mpsc;
+
+use crate::{
+    commands::pull::{self, CANARY_KEY},
+    config::{self, Config},
+    envfile::{ChangeKind, Deprecation},
+    lock,
+    messages::message,
+};
+
+/// Pulls every `interval`, and immediately whenever the config or the env
+/// file changes on disk, logging each changed key with a timestamp. A
+/// broken config is reported and the previous cycle's state is kept until
+/// the file is fixed. `on_change` runs after each cycle that changed keys.
+pub async fn run(
+    config_path: &Path,
+    interval: Duration,
+    degraded_ok: bool,
+    on_change: Option<&str>,
+) -> Result<()> {
+    let (tx, mut rx) = mpsc::unbounded_channel::<PathBuf>();
+    let mut watcher = notify::recommended_watcher(move |res: notify::Result<notify::Event>| {
+        if let Ok(event) = res
+            && !matches!(event.kind, EventKind::Access(_))
+        {
+            for path in event.paths {
+                let _ = tx.send(path);
+            }
+        }
+    })
+    .context("failed to start file watcher")?;
+
+    let mut files = WatchedFiles::default();
+    let mut last_written: Option<Vec<u8>> = None;
+
+    loop {
+        match config::load(config_path) {
+            Ok(cfg) => {
+                let env_path = config::resolve_path(config_path, &cfg.output.env_file);
+                files.watch(&mut watcher, config_path, &env_path)?;
+                sync(config_path, &cfg, degraded_ok, on_change).await;
+                last_written = fs::read(&env_path).ok();
+            }
+            Err(err) => eprintln!("error: config invalid, waiting for a fix: {err:#}"),
+        }
+
+        let trigger = tokio::select! {
+            _ = tokio::time::sleep(interval) => None,
+            Some(path) = next_relevant(&mut rx, &files, last_written.as_deref()) => Some(path),
+        };
+        if let Some(path) = trigger {
+            println!("{}", message!("watch_change", path = path.display()));
+        }
+    }
+}
+
+/// One pull, logged as a line per changed key; quiet when nothing changed.
+async fn sync(config_path: &Path, cfg: &Config, degraded_ok: bool, on_change: Option<&str>) {
+    let outcome = pull::sync(config_path, cfg, false, degraded_ok, lock::Mode::Use).await;
+    let now = humantime::format_rfc3339_seconds(SystemTime::now());
+    let written = match outcome.result {
+        Ok(written) => written,
+        Err(err) => {
+            eprintln!("{now} error: {err:#}");
+            return;
+        }
+    };
+    for change in &written.changes {
+        if change.key == CANARY_KEY {
+            continue;
+        }
+        let label = match change.kind {
+            ChangeKind::Add => "ADD",
+            ChangeKind::Update => "UPDATE",
+        };
+        println!("{now} {label} {}", change.key);
+    }
+    for deprecation in &written.deprecations {
+        match deprecation {
+            Deprecation::Marked { key, .. } => println!("{now} DEPRECATED {key}"),
+            Deprecation::Removed { key } => println!("{now} REMOVED {key}"),
+        }
+    }
+    for skipped in &written.degraded {
+        eprintln!("{now} warning: DEGRADED: {skipped}; keeping last-known values");
+    }
+    if let Some(command) = on_change
+        && let Err(err) =
+            pull::on_change(config_path, command, outcome.principal.as_deref(), &written).await
+    {
+        eprintln!("{now} warning: {err:#}");
+    }
+}
+
+/// Waits for a change to one of the watched files, ignoring the echo of
+/// envit's own write to the env file.
+async fn next_relevant(
+    rx: &mut mpsc::UnboundedReceiver<PathBuf>,
+    files: &WatchedFiles,
+    last_written: Option<&[u8]>,
+) -> Option<PathBuf> {
+    loop {
+        let path = rx.recv().await?;
+        if !files.contains(&path) {
+            continue;
+        }
+        if files.is_env_file(&path) && fs::read(&path).ok().as_deref() == last_written {
+            continue;
+        }
+        // Editors and atomic renames emit bursts; collapse them into one trigger.
+        tokio::time::sleep(Duration::from_millis(200)).await;
+        while rx.try_recv().is_ok() {}
+        return Some(path);
+    }
+}
+
+/// Directories are watched rather than the files themselves, because atomic
+/// replacement (ours and most editors') swaps the inode out from under a
+/// file watch.
+#[derive(Default)]
+struct WatchedFiles {
+    dirs: HashSet<PathBuf>,
+    config: PathBuf,
+    env: PathBuf,
+}
+
+impl WatchedFiles {
+    fn watch(
+        &mut self,
+        watcher: &mut RecommendedWatcher,
+        config_path: &Path,
+        env_path: &Path,
+    ) -> Result<()> {
+        self.config = absolute(config_path)?;
+        self.env = absolute(env_path)?;
+        for file in [&self.config, &self.env] {
+            let Some(dir) = file.parent() else { continue };
+            if self.dirs.insert(dir.to_path_buf()) {
+                watcher
+                    .watch(dir, RecursiveMode::NonRecursive)
+                    .with_context(|| format!("failed to watch {}", dir.display()))?;
+            }
+        }
+        Ok(())
+    }
+
+    fn contains(&self, path: &Path) -> bool {
+        path == self.config || self.is_env_file(path)
+    }
+
+    fn is_env_file(&self, path: &Path) -> bool {
+        path == self.env
+    }
+}
+
+fn absolute(path: &Path) -> Result<PathBuf> {
+    let path = std::path::absolute(path)
+        .with_context(|| format!("failed to resolve {}", path.display()))?;
+    // Resolve symlinked directories (e.g. /tmp on macOS) the way notify reports them.
+    Ok(match (path.parent(), path.file_name()) {
+        (Some(dir), Some(name)) => fs::canonicalize(dir).map_or(path.clone(), |d| d.join(name)),
+        _ => path,
+    })
+}
@@ -0,0 +1,145 @@
+use std::path::Path;
+
+use anyhow::Result;
+
+use crate::{
+    config::{self, Config, Source},
+    messages::message,
+    provider::{SecretProvider, cache::ProviderRegistry},
+    resolve,
+};
+
+/// Which secret or env key to explain.
+pub enum Query<'a> {
+    Secret(&'a str),
+    Key(&'a str),
+}
+
+/// One way a secret reaches an env key.
+struct Route {
+    source: usize,
+    secret: String,
+    key: String,
+    listed: bool,
+}
+
+/// Explains how a secret becomes an env key, or which secret feeds a key:
+/// the provider that lists it, the mapping rule, the prefix and where the
+/// value ends up. Only names are looked up. Returns whether anything
+/// matched.
+pub async fn run(config_path: &Path, query: Query<'_>) -> Result<bool> {
+    let cfg = config::load(config_path)?;
+    let mut providers = ProviderRegistry::default();
+    let connected = providers.connect(&cfg)?;
+
+    let mut routes = Vec::new();
+    for (index, (source, provider)) in connected.iter().enumerate() {
+        routes.extend(routes_of(index, *source, provider.as_ref()).await?);
+    }
+    let matched: Vec<_> = routes
+        .iter()
+        .filter(|route| match query {
+            Query::Secret(secret) => route.secret == secret,
+            Query::Key(key) => route.key == key,
+        })
+        .collect();
+
+    if matched.is_empty() {
+        match query {
+            Query::Secret(secret) => {
+                println!("{}", message!("which_no_secret", secret = secret))
+            }
+            Query::Key(key) => println!("{}", message!("which_no_key", key = key)),
+        }
+        return Ok(false);
+    }
+
+    let sources = cfg.sources();
+    for route in &matched {
+        let source = sources[route.source];
+        println!(
+            "{}",
+            message!(
+                "which_route",
+                secret = route.secret,
+                index = route.source + 1,
+                kind = source.provider.kind,
+            )
+        );
+        println!(
+            "{}",
+            if route.listed {
+                message!("which_listed")
+            } else {
+                message!("which_map_only")
+            }
+        );
+        let unprefixed = &route.key[source.prefix.len()..];
+        match source.map.get(unprefixed) {
+            Some(secret) if *secret == route.secret => {
+                println!(
+                    "{}",
+                    message!("which_rule_map", key = unprefixed, secret = secret)
+                );
+            }
+            _ => println!("{}", message!("which_rule_derived", key = unprefixed)),
+        }
+        if !source.prefix.is_empty() {
+            println!("{}", message!("which_prefix", prefix = source.prefix));
+        }
+        println!("{}", message!("which_key", key = route.key));
+        println!(
+            "{}",
+            message!(
+                "which_output",
+                output = output(config_path, &cfg, &route.key)
+            )
+        );
+        // Later sources win when two produce the same key.
+        if let Some(winner) = routes
+            .iter()
+            .rev()
+            .find(|other| other.key == route.key && other.source > route.source)
+        {
+            println!(
+                "{}",
+                message!(
+                    "which_overridden",
+                    secret = winner.secret,
+                    index = winner.source + 1,
+                )
+            );
+        }
+    }
+    Ok(true)
+}
+
+async fn routes_of(
+    index: usize,
+    source: Source<'_>,
+    provider: &dyn SecretProvider,
+) -> Result<Vec<Route>> {
+    let listed = provider.list_secrets().await?;
+    Ok(resolve::keys(source, provider)
+        .await?
+        .into_iter()
+        .map(|(secret, key)| Route {
+            source: index,
+            listed: listed.iter().any(|meta| meta.name == secret),
+            secret,
+            key,
+        })
+        .collect())
+}
+
+fn output(config_path: &Path, cfg: &Config, key: &str) -> String {
+    let env_path = config::resolve_path(config_path, &cfg.output.env_file);
+    match cfg.output.files.get(key) {
+        Some(file) => format!(
+            "{} (the value; {} gets the path)",
+            config::resolve_path(config_path, &file.path).display(),
+            env_path.display()
+        ),
+        None => env_path.display().to_string(),
+    }
+}
@@ -0,0 +1,283 @@
+use std::{fs, path::Path, time::SystemTime};
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    commands::{doctor, pull},
+    config::{self, Config},
+    messages::message,
+};
+
+/// The last failed pull, cleared by the next complete one; relative to the
+/// config file.
+pub const LAST_FAILURE_PATH: &str = ".envit/last-failure.json";
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct LastFailure {
+    pub timestamp: String,
+    pub command: String,
+    pub error: String,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Cause {
+    /// Credentials missing, expired or rejected.
+    Auth,
+    /// Credentials accepted but not allowed to read (HTTP 403).
+    Permission,
+    Network,
+    Config,
+    /// The env file (or its directory) cannot be read or written.
+    LocalFile,
+    Unknown,
+}
+
+/// Remembers a failed run for `why-failed`. Best effort: a state file that
+/// cannot be written must not hide the original error.
+pub fn record(config_path: &Path, command: &str, err: &anyhow::Error) {
+    let failure = LastFailure {
+        timestamp: humantime::format_rfc3339_seconds(SystemTime::now()).to_string(),
+        command: command.to_string(),
+        error: format!("{err:#}"),
+    };
+    let path = config::resolve_path(config_path, LAST_FAILURE_PATH);
+    let written = serde_json::to_string(&failure)
+        .map_err(anyhow::Error::from)
+        .and_then(|raw| pull::write_state(&path, &format!("{raw}\n")));
+    if let Err(err) = written {
+        eprintln!("warning: {err:#}");
+    }
+}
+
+pub fn clear(config_path: &Path) {
+    let _ = fs::remove_file(config::resolve_path(config_path, LAST_FAILURE_PATH));
+}
+
+/// Explains the last failed pull: what kind of problem it was and the
+/// steps to fix it for the configured providers. Falls back to the audit
+/// log when no failure was recorded.
+pub fn run(config_path: &Path) -> Result<()> {
+    let cfg = match config::load(config_path) {
+        Ok(cfg) => cfg,
+        Err(err) => {
+            println!(
+                "{}",
+                message!("why_failed_config", error = format!("{err:#}"))
+            );
+            print_steps(Cause::Config, &[], config_path, None);
+            return Ok(());
+        }
+    };
+    let Some(failure) = last_failure(config_path, &cfg)? else {
+        match pull::read_last_sync(&config::resolve_path(config_path, pull::LAST_SYNC_PATH))? {
+            Some(at) => println!(
+                "{}",
+                message!(
+                    "why_failed_none_since",
+                    at = humantime::format_rfc3339_seconds(at)
+                )
+            ),
+            None => println!("{}", message!("why_failed_none")),
+        }
+        return Ok(());
+    };
+
+    let cause = classify(&failure.error);
+    println!(
+        "{}",
+        message!(
+            "why_failed_last",
+            command = failure.command,
+            at = failure.timestamp
+        )
+    );
+    println!("{}", message!("why_failed_error", error = failure.error));
+    println!("{}", message!("why_failed_cause", cause = describe(cause)));
+    let mut kinds: Vec<&str> = Vec::new();
+    for source in cfg.sources() {
+        if !kinds.contains(&source.provider.kind.as_str()) {
+            kinds.push(&source.provider.kind);
+        }
+    }
+    let env_path = config::resolve_path(config_path, &cfg.output.env_file);
+    print_steps(cause, &kinds, config_path, Some(&env_path));
+    Ok(())
+}
+
+/// The recorded failure, else the newest audit entry with an error.
+fn last_failure(config_path: &Path, cfg: &Config) -> Result<Option<LastFailure>> {
+    let path = config::resolve_path(config_path, LAST_FAILURE_PATH);
+    match fs::read_to_string(&path) {
+        Ok(raw) => {
+            return serde_json::from_str(&raw)
+                .map(Some)
+                .with_context(|| format!("invalid {}", path.display()));
+        }
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => {}
+        Err(err) => return Err(err).with_context(|| format!("failed to read {}", path.display())),
+    }
+
+    let Some(audit_path) = &cfg.audit.path else {
+        return Ok(None);
+    };
+    let Ok(raw) = fs::read_to_string(config::resolve_path(config_path, audit_path)) else {
+        return Ok(None);
+    };
+    Ok(raw
+        .lines()
+        .rev()
+        .filter_map(|line| serde_json::from_str::<serde_json::Value>(line).ok())
+        .find_map(|entry| {
+            Some(LastFailure {
+                timestamp: entry["timestamp"].as_str()?.to_string(),
+                command: entry["command"].as_str()?.to_string(),
+                error: entry["error"].as_str()?.to_string(),
+            })
+        }))
+}
+
+/// Classifies by the error text, which is all the audit log keeps.
+fn classify(error: &str) -> Cause {
+    let error = error.to_ascii_lowercase();
+    let has = |needles: &[&str]| needles.iter().any(|needle| error.contains(needle));
+    if has(&["403", "forbidden"]) {
+        Cause::Permission
+    } else if has(&[
+        "authentication failed",
+        "401",
+        "unauthorized",
+        "failed to get azure token",
+    ]) {
+        Cause::Auth
+    } else if has(&["env file", "permission denied", "read-only file system"]) {
+        Cause::LocalFile
+    } else if has(&[
+        "request failed",
+        "error sending request",
+        "dns error",
+        "connection refused",
+        "timed out",
+        "unreachable",
+    ]) {
+        Cause::Network
+    } else if has(&["config", "settings", "[map]", "unsupported provider kind"]) {
+        Cause::Config
+    } else {
+        Cause::Unknown
+    }
+}
+
+fn describe(cause: Cause) -> &'static str {
+    match cause {
+        Cause::Auth => "authentication (credentials missing, expired or rejected)",
+        Cause::Permission => "permission (credentials accepted, but not allowed to read)",
+        Cause::Network => "network (the provider could not be reached)",
+        Cause::Config => "configuration",
+        Cause::LocalFile => "local file (the env file could not be read or written)",
+        Cause::Unknown => "unclassified",
+    }
+}
+
+fn print_steps(cause: Cause, kinds: &[&str], config_path: &Path, env_path: Option<&Path>) {
+    let mut steps: Vec<String> = Vec::new();
+    let config = config_path.display();
+    match cause {
+        Cause::Auth => {
+            steps.extend(
+                kinds
+                    .iter()
+                    .map(|kind| format!("{kind}: {}", doctor::credentials_fix(kind))),
+            );
+            steps.push(format!(
+                "confirm the credentials work: envit doctor --config {config}"
+            ));
+        }
+        Cause::Permission => {
+            steps.extend(
+                kinds
+                    .iter()
+                    .map(|kind| format!("{kind}: {}", doctor::access_fix(kind, true, "list"))),
+            );
+            steps.push("role assignments can take a few minutes to apply; retry after that".into());
+            steps.push(format!(
+                "confirm list and get work: envit doctor --config {config}"
+            ));
+        }
+        Cause::Network => {
+            steps.push(format!(
+                "find the unreachable host: envit doctor --config {config}"
+            ));
+            steps.push(
+                "check the URL in the config, DNS, VPN or private endpoint access and HTTPS_PROXY"
+                    .into(),
+            );
+            steps.push("to keep last-known values during outages, pull with --degraded-ok".into());
+        }
+        Cause::Config => {
+            steps.push(format!(
+                "list the problems: envit validate --config {config}"
+            ));
+            steps.push(format!(
+                "check risky settings: envit config lint --config {config}"
+            ));
+        }
+        Cause::LocalFile => {
+            let env = env_path.map_or("output.env_file".to_string(), |p| p.display().to_string());
+            steps.push(format!(
+                "make sure {env} and its directory exist and are writable by you"
+            ));
+            steps.push(
+                "with create_if_missing = false the env file must exist before the first pull"
+                    .into(),
+            );
+        }
+        Cause::Unknown => {
+            steps.push(format!(
+                "run envit doctor --config {config} to narrow it down"
+            ));
+            steps
+                .push("if every check passes, retry; report the error above if it persists".into());
+        }
+    }
+    println!();
+    println!("{}", message!("why_failed_steps"));
+    for (index, step) in steps.iter().enumerate() {
+        println!(
+            "{}",
+            message!("why_failed_step", index = index + 1, step = step)
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn errors_are_classified_by_their_text() {
+        for (error, cause) in [
+            (
+                "provider authentication failed: failed to get Azure token: no credential",
+                Cause::Auth,
+            ),
+            (
+                "failed to get secret db-url (403 Forbidden)",
+                Cause::Permission,
+            ),
+            (
+                "failed to list secrets: provider transport/auth error: request failed: error \
+                 sending request for url",
+                Cause::Network,
+            ),
+            ("env file does not exist: ./.env", Cause::LocalFile),
+            (
+                "invalid [provider] settings for kind netlify",
+                Cause::Config,
+            ),
+            ("something else", Cause::Unknown),
+        ] {
+            assert_eq!(classify(error), cause, "{error}");
+        }
+    }
+}
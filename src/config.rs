@@ -1,30 +1,289 @@
-use std::{collections::HashMap, fs, path::Path};
+use std::{
+    borrow::Cow,
+    collections::HashMap,
+    fs,
+    path::{Path, PathBuf},
+    time::Duration,
+};
 
 use anyhow::{Context, Result, bail};
-use serde::Deserialize;
+use schemars::{JsonSchema, Schema, SchemaGenerator, json_schema};
+use serde::{Deserialize, Deserializer};
 
-#[derive(Debug, Clone, Deserialize)]
+use crate::{
+    classify::Class,
+    crypto::{self, MacAlgorithm},
+    generate::Policy,
+    provider,
+};
+
+/// The `version` this build reads; `envit migrate` upgrades older configs
+/// (see `commands::migrate`).
+pub const CURRENT_VERSION: u32 = 1;
+
+#[derive(Debug, Clone, Deserialize, JsonSchema)]
 pub struct Config {
     pub version: u32,
     #[serde(default)]
     pub output: OutputConfig,
-    pub provider: ProviderConfig,
+    pub provider: Option<ProviderConfig>,
+    /// Several providers aggregated into one pull; mutually exclusive with
+    /// `provider`. Later entries take precedence on key collisions.
+    #[serde(default)]
+    pub providers: Vec<SourceConfig>,
     #[serde(default)]
     pub map: HashMap<String, String>,
+    #[serde(default)]
+    pub audit: AuditConfig,
+    #[serde(default)]
+    pub security: SecurityConfig,
+    #[serde(default)]
+    pub crypto: CryptoConfig,
+    /// Env key pattern -> owning team (see `crate::owners`).
+    #[serde(default)]
+    pub owners: HashMap<String, String>,
+    #[serde(default)]
+    pub hooks: Vec<HookConfig>,
+    /// Env key -> how `rotate` generates its next value.
+    #[serde(default)]
+    pub rotate: HashMap<String, Policy>,
+    /// Env key pattern -> secret class (see `crate::classify`).
+    #[serde(default)]
+    pub classify: HashMap<String, Class>,
 }
 
-#[derive(Debug, Clone, Deserialize)]
+#[derive(Debug, Clone, Deserialize, JsonSchema)]
 pub struct OutputConfig {
     #[serde(default = "default_env_file")]
     pub env_file: String,
     #[serde(default = "default_create_if_missing")]
     pub create_if_missing: bool,
+    /// Writes a fresh `ENVIT_CANARY=<uuid>` on every pull and records it in
+    /// the audit log, so a leaked env file can be traced to its pull.
+    #[serde(default)]
+    pub canary: bool,
+    /// Leaves the file (and its mtime) untouched when the merged content is
+    /// byte-identical. Set to false to always rewrite.
+    #[serde(default = "default_skip_identical")]
+    pub skip_identical: bool,
+    /// Env keys whose value is written to a file (relative to the config)
+    /// instead; the env file gets the path. For certificates and keys that
+    /// tools only read from disk.
+    #[serde(default)]
+    pub files: HashMap<String, FileOutput>,
+    /// Keys a pull stops providing (e.g. renamed in `[map]`) are kept this
+    /// long behind a deprecation comment instead of lingering silently.
+    #[serde(default, deserialize_with = "deserialize_duration")]
+    #[schemars(with = "Option<String>")]
+    pub deprecation_grace: Option<Duration>,
+    /// Reads each value with its version and compares it with the version
+    /// listed, warning about secrets that changed mid-pull (e.g. during a
+    /// rotation). Costs no extra requests on Key Vault.
+    #[serde(default)]
+    pub verify_versions: bool,
+    /// Encrypts each value the env file gets, so it can be committed; see
+    /// `crate::codec`.
+    #[serde(default)]
+    pub codec: Option<CodecConfig>,
+    /// Encrypts the whole env file instead (one ASCII-armored age file), for
+    /// teams that allow no plaintext secrets on disk; see `crate::codec`.
+    #[serde(default)]
+    pub encrypt: Option<CodecConfig>,
+    /// Snapshots of the env file kept for `envit rollback`, taken before
+    /// each write; 0 takes none. See `crate::backup`.
+    #[serde(default)]
+    pub backups: usize,
+    /// Writes `# envit: expires <date> (<n> days)` above each key whose
+    /// secret has an expiry, refreshed on every pull.
+    #[serde(default)]
+    pub expiry_comments: bool,
 }
 
+#[derive(Debug, Clone, Deserialize, JsonSchema)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum CodecConfig {
+    /// Every value age-encrypted to all `recipients` (one per team member).
+    Age {
+        recipients: Vec<String>,
+        /// Key to decrypt with; `ENVIT_AGE_IDENTITY` overrides it.
+        identity_file: Option<String>,
+    },
+}
+
+/// One `[output.files]` entry: a path, or a table with `path`, `encoding`
+/// and `checksum`.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, JsonSchema)]
+#[serde(from = "FileOutputSpec")]
+pub struct FileOutput {
+    pub path: String,
+    pub encoding: FileEncoding,
+    /// Also write `<path>.sha256` in `sha256sum` format.
+    pub checksum: bool,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum FileEncoding {
+    /// The value is written as is.
+    #[default]
+    Text,
+    /// The value is base64 and written decoded, byte for byte (keystores,
+    /// PFX bundles, DER certificates).
+    Base64,
+}
+
+#[derive(Deserialize, JsonSchema)]
+#[serde(untagged)]
+enum FileOutputSpec {
+    Path(String),
+    Table(FileOutputTable),
+}
+
+#[derive(Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+struct FileOutputTable {
+    path: String,
+    #[serde(default)]
+    encoding: FileEncoding,
+    #[serde(default)]
+    checksum: bool,
+}
+
+impl From<FileOutputSpec> for FileOutput {
+    fn from(spec: FileOutputSpec) -> Self {
+        match spec {
+            FileOutputSpec::Path(path) => Self {
+                path,
+                encoding: FileEncoding::Text,
+                checksum: false,
+            },
+            FileOutputSpec::Table(table) => Self {
+                path: table.path,
+                encoding: table.encoding,
+                checksum: table.checksum,
+            },
+        }
+    }
+}
+
+/// Provider selection plus its kind-specific settings.
+///
+/// Everything besides `kind` is kept as a raw table and interpreted by the
+/// provider implementation itself (see `provider::validate`).
 #[derive(Debug, Clone, Deserialize)]
 pub struct ProviderConfig {
     pub kind: String,
-    pub vault_url: String,
+    #[serde(flatten)]
+    pub settings: toml::Table,
+}
+
+/// One `[[providers]]` entry: a provider plus its own `map` and key prefix.
+#[derive(Debug, Clone, Deserialize)]
+pub struct SourceConfig {
+    /// Prepended to every env key this provider produces, mapped or derived.
+    #[serde(default)]
+    pub prefix: String,
+    #[serde(default)]
+    pub map: HashMap<String, String>,
+    #[serde(flatten)]
+    pub provider: ProviderConfig,
+}
+
+impl JsonSchema for ProviderConfig {
+    fn schema_name() -> Cow<'static, str> {
+        "ProviderConfig".into()
+    }
+
+    fn json_schema(generator: &mut SchemaGenerator) -> Schema {
+        json_schema!({ "oneOf": provider::settings_schemas(generator, &[]) })
+    }
+}
+
+impl JsonSchema for SourceConfig {
+    fn schema_name() -> Cow<'static, str> {
+        "SourceConfig".into()
+    }
+
+    fn json_schema(generator: &mut SchemaGenerator) -> Schema {
+        let shared = [
+            ("prefix", generator.subschema_for::<String>()),
+            ("map", generator.subschema_for::<HashMap<String, String>>()),
+        ];
+        json_schema!({ "oneOf": provider::settings_schemas(generator, &shared) })
+    }
+}
+
+/// A provider with the `[map]` and prefix that apply to its secrets.
+#[derive(Debug, Clone, Copy)]
+pub struct Source<'a> {
+    pub provider: &'a ProviderConfig,
+    pub map: &'a HashMap<String, String>,
+    pub prefix: &'a str,
+    /// See `OutputConfig::verify_versions`.
+    pub verify_versions: bool,
+}
+
+/// Opt-in JSON Lines audit trail; `path` is relative to the config file.
+#[derive(Debug, Clone, Default, Deserialize, JsonSchema)]
+pub struct AuditConfig {
+    pub path: Option<String>,
+}
+
+/// `protected` refuses plaintext export unless `--break-glass` is used;
+/// `break_glass_webhook` is notified whenever that happens.
+/// `enforce_owners` refuses `push`/`rotate` on keys `[owners]` assigns to
+/// another team unless `--override-owner` is used.
+/// `no_plaintext` refuses everything that would leave plaintext secrets on
+/// disk (see `crate::plaintext`).
+#[derive(Debug, Clone, Default, Deserialize, JsonSchema)]
+pub struct SecurityConfig {
+    #[serde(default)]
+    pub protected: bool,
+    pub break_glass_webhook: Option<String>,
+    #[serde(default)]
+    pub enforce_owners: bool,
+    #[serde(default)]
+    pub no_plaintext: bool,
+}
+
+/// A webhook (`url`) or local command (`command`) told about changed keys
+/// (see `crate::hooks`). `template` is the JSON body with
+/// `{{placeholders}}`; `headers_env` maps header names to the environment
+/// variables holding their values.
+#[derive(Debug, Clone, Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct HookConfig {
+    pub url: Option<String>,
+    pub command: Option<String>,
+    #[serde(default = "default_hook_events")]
+    pub on: Vec<String>,
+    pub template: Option<String>,
+    #[serde(default)]
+    pub headers_env: HashMap<String, String>,
+}
+
+fn default_hook_events() -> Vec<String> {
+    crate::hooks::EVENTS.iter().map(|e| e.to_string()).collect()
+}
+
+/// Algorithms for artifacts envit signs or stores (see `crate::crypto`).
+/// `fips` rejects providers whose storage format needs non-FIPS primitives.
+#[derive(Debug, Clone, Default, Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct CryptoConfig {
+    #[serde(default)]
+    pub mac: MacAlgorithm,
+    #[serde(default)]
+    pub fips: bool,
+}
+
+fn deserialize_duration<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<Option<Duration>, D::Error> {
+    let raw = String::deserialize(deserializer)?;
+    humantime::parse_duration(&raw)
+        .map(Some)
+        .map_err(serde::de::Error::custom)
 }
 
 fn default_env_file() -> String {
@@ -35,11 +294,57 @@ fn default_create_if_missing() -> bool {
     true
 }
 
+fn default_skip_identical() -> bool {
+    true
+}
+
 impl Default for OutputConfig {
     fn default() -> Self {
         Self {
             env_file: default_env_file(),
             create_if_missing: default_create_if_missing(),
+            canary: false,
+            skip_identical: default_skip_identical(),
+            files: HashMap::new(),
+            deprecation_grace: None,
+            verify_versions: false,
+            codec: None,
+            encrypt: None,
+            backups: 0,
+            expiry_comments: false,
+        }
+    }
+}
+
+impl Config {
+    /// Every configured provider in precedence order (lowest first).
+    pub fn sources(&self) -> Vec<Source<'_>> {
+        match &self.provider {
+            Some(provider) => vec![Source {
+                provider,
+                map: &self.map,
+                prefix: "",
+                verify_versions: self.output.verify_versions,
+            }],
+            None => self
+                .providers
+                .iter()
+                .map(|source| Source {
+                    provider: &source.provider,
+                    map: &source.map,
+                    prefix: &source.prefix,
+                    verify_versions: self.output.verify_versions,
+                })
+                .collect(),
+        }
+    }
+
+    /// The provider written to by commands that change secrets, which need
+    /// an unambiguous target.
+    pub fn single_provider(&self) -> Result<&ProviderConfig> {
+        match &self.provider {
+            Some(provider) => Ok(provider),
+            None => bail!("this command needs a single [provider]; [[providers]] is read-only"),
         }
     }
 }
@@ -54,29 +359,129 @@ pub fn load(path: &Path) -> Result<Config> {
 }
 
 pub fn validate(cfg: &Config) -> Result<()> {
-    if cfg.version != 1 {
-        bail!("unsupported config version: {} (expected 1)", cfg.version);
+    if cfg.version < CURRENT_VERSION {
+        bail!(
+            "config version {} is older than {CURRENT_VERSION}; run `envit migrate` to upgrade it",
+            cfg.version
+        );
     }
-    if cfg.provider.kind != "azure_key_vault" {
+    if cfg.version > CURRENT_VERSION {
         bail!(
-            "unsupported provider kind: {} (expected azure_key_vault)",
-            cfg.provider.kind
+            "config version {} is newer than this envit supports ({CURRENT_VERSION}); upgrade envit",
+            cfg.version
         );
     }
-    if cfg.provider.vault_url.trim().is_empty() {
-        bail!("provider.vault_url must not be empty");
+    match (&cfg.provider, cfg.providers.is_empty()) {
+        (None, true) => bail!("config needs a [provider] or at least one [[providers]] entry"),
+        (Some(_), false) => bail!("use either [provider] or [[providers]], not both"),
+        (None, false) if !cfg.map.is_empty() => {
+            bail!("top-level [map] only applies to [provider]; set map on each [[providers]] entry")
+        }
+        _ => {}
+    }
+    for source in cfg.sources() {
+        if cfg.crypto.fips
+            && let Some((kind, primitives)) = crypto::NON_FIPS_KINDS
+                .iter()
+                .find(|(kind, _)| *kind == source.provider.kind)
+        {
+            bail!("provider kind {kind} is not allowed with crypto.fips = true ({primitives})");
+        }
+        crate::provider::validate(source.provider)?;
+        for (env_key, secret_name) in source.map {
+            if env_key.trim().is_empty() || secret_name.trim().is_empty() {
+                bail!("[map] entries must not be empty");
+            }
+        }
     }
     if cfg.output.env_file.trim().is_empty() {
         bail!("output.env_file must not be empty");
     }
-    for (env_key, secret_name) in &cfg.map {
-        if env_key.trim().is_empty() || secret_name.trim().is_empty() {
-            bail!("[map] entries must not be empty");
+    if let Some(codec) = &cfg.output.codec {
+        crate::codec::validate("output.codec", codec, cfg.crypto.fips)?;
+    }
+    if let Some(encrypt) = &cfg.output.encrypt {
+        if cfg.output.codec.is_some() {
+            bail!("use either output.codec or output.encrypt, not both");
         }
+        crate::codec::validate("output.encrypt", encrypt, cfg.crypto.fips)?;
+    }
+    if cfg
+        .audit
+        .path
+        .as_deref()
+        .is_some_and(|p| p.trim().is_empty())
+    {
+        bail!("audit.path must not be empty");
+    }
+    for (env_key, file) in &cfg.output.files {
+        if env_key.trim().is_empty() || file.path.trim().is_empty() {
+            bail!("[output.files] entries must not be empty");
+        }
+    }
+    for (env_key, policy) in &cfg.rotate {
+        policy
+            .validate()
+            .with_context(|| format!("invalid [rotate] policy for {env_key}"))?;
+    }
+    for (pattern, team) in &cfg.owners {
+        if pattern.trim().is_empty() || team.trim().is_empty() {
+            bail!("[owners] entries must not be empty");
+        }
+    }
+    if cfg.classify.keys().any(|pattern| pattern.trim().is_empty()) {
+        bail!("[classify] patterns must not be empty");
+    }
+    for hook in &cfg.hooks {
+        match (&hook.url, &hook.command) {
+            (Some(url), None) if url.starts_with("https://") || url.starts_with("http://") => {}
+            (Some(_), None) => bail!("hooks.url must be an http(s) URL"),
+            (None, Some(command)) if command.trim().is_empty() => {
+                bail!("hooks.command must not be empty")
+            }
+            (None, Some(_)) => {
+                if hook.template.is_some() || !hook.headers_env.is_empty() {
+                    bail!("hooks.template and hooks.headers_env only apply to hooks.url");
+                }
+            }
+            _ => bail!("each [[hooks]] entry needs either url or command"),
+        }
+        if let Some(event) = hook
+            .on
+            .iter()
+            .find(|e| !crate::hooks::EVENTS.contains(&e.as_str()))
+        {
+            bail!(
+                "unknown hook event {event} (expected one of: {})",
+                crate::hooks::EVENTS.join(", ")
+            );
+        }
+    }
+    if cfg.output.canary && cfg.audit.path.is_none() {
+        bail!("output.canary requires [audit] path, otherwise the canary cannot be traced");
+    }
+    if let Some(url) = &cfg.security.break_glass_webhook
+        && !(url.starts_with("https://") || url.starts_with("http://"))
+    {
+        bail!("security.break_glass_webhook must be an http(s) URL");
     }
     Ok(())
 }
 
+/// Resolves a path from the config file relative to the config's directory.
+pub fn resolve_path(config_path: &Path, path: &str) -> PathBuf {
+    let path = PathBuf::from(path);
+    if path.is_absolute() {
+        return path;
+    }
+
+    if let Some(parent) = config_path.parent() {
+        return parent.join(path);
+    }
+
+    path
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -86,13 +491,119 @@ mod tests {
         let cfg = Config {
             version: 2,
             output: OutputConfig::default(),
-            provider: ProviderConfig {
+            provider: Some(ProviderConfig {
                 kind: "azure_key_vault".to_string(),
-                vault_url: "https://example.vault.azure.net".to_string(),
-            },
+                settings: toml::Table::from_iter([(
+                    "vault_url".to_string(),
+                    toml::Value::String("https://example.vault.azure.net".to_string()),
+                )]),
+            }),
+            providers: Vec::new(),
             map: HashMap::new(),
+            audit: AuditConfig::default(),
+            security: SecurityConfig::default(),
+            crypto: CryptoConfig::default(),
+            owners: HashMap::new(),
+            hooks: Vec::new(),
+            rotate: HashMap::new(),
+            classify: HashMap::new(),
         };
 
         assert!(validate(&cfg).is_err());
     }
+
+    #[test]
+    fn validate_rejects_unknown_provider_kind() {
+        let cfg = Config {
+            version: 1,
+            output: OutputConfig::default(),
+            provider: Some(ProviderConfig {
+                kind: "nope".to_string(),
+                settings: toml::Table::new(),
+            }),
+            providers: Vec::new(),
+            map: HashMap::new(),
+            audit: AuditConfig::default(),
+            security: SecurityConfig::default(),
+            crypto: CryptoConfig::default(),
+            owners: HashMap::new(),
+            hooks: Vec::new(),
+            rotate: HashMap::new(),
+            classify: HashMap::new(),
+        };
+
+        let err = validate(&cfg).unwrap_err().to_string();
+        assert!(err.contains("unsupported provider kind: nope"));
+    }
+
+    #[test]
+    fn providers_list_keeps_per_entry_prefix_map_and_settings() {
+        let cfg: Config = toml::from_str(
+            r#"
+version = 1
+
+[[providers]]
+kind = "azure_key_vault"
+vault_url = "https://prod.vault.azure.net"
+
+[[providers]]
+kind = "http_json"
+prefix = "SHARED_"
+url = "https://tokens.example.com/"
+map = { SENTRY = "sentry-dsn" }
+"#,
+        )
+        .unwrap();
+
+        let sources = cfg.sources();
+        assert_eq!(sources.len(), 2);
+        assert_eq!(sources[0].prefix, "");
+        assert_eq!(sources[1].prefix, "SHARED_");
+        assert_eq!(sources[1].map["SENTRY"], "sentry-dsn");
+        assert_eq!(sources[1].provider.kind, "http_json");
+        assert!(!sources[1].provider.settings.contains_key("prefix"));
+        assert!(cfg.single_provider().is_err());
+    }
+
+    #[test]
+    fn validate_rejects_provider_and_providers_together() {
+        let cfg: Config = toml::from_str(
+            r#"
+version = 1
+
+[provider]
+kind = "azure_key_vault"
+vault_url = "https://prod.vault.azure.net"
+
+[[providers]]
+kind = "azure_key_vault"
+vault_url = "https://shared.vault.azure.net"
+"#,
+        )
+        .unwrap();
+
+        assert!(validate(&cfg).is_err());
+    }
+
+    #[test]
+    fn fips_mode_rejects_age_backed_providers() {
+        let cfg: Config = toml::from_str(
+            r#"
+version = 1
+
+[provider]
+kind = "local_vault"
+path = "secrets.age"
+
+[crypto]
+mac = "hmac-sha512"
+fips = true
+"#,
+        )
+        .unwrap();
+
+        assert_eq!(cfg.crypto.mac, MacAlgorithm::HmacSha512);
+        let err = validate(&cfg).unwrap_err().to_string();
+        assert!(err.contains("not allowed with crypto.fips"), "{err}");
+    }
 }
@@ -0,0 +1,199 @@
+//! Every primitive envit applies to data it stores or signs, kept in one
+//! place so it can be reviewed (and swapped) once.
+//!
+//! - MACs: manifest value hashes and signatures, selected by `[crypto] mac`
+//! - age (X25519, ChaCha20-Poly1305, scrypt for passphrases): the
+//!   `local_vault` store
+
+use hmac::{Hmac, Mac};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use sha2::{Sha256, Sha512};
+use thiserror::Error;
+
+/// Provider kinds whose at-rest format relies on primitives outside FIPS
+/// 140, with what they use; refused when `[crypto] fips = true`.
+pub const NON_FIPS_KINDS: &[(&str, &str)] =
+    &[("local_vault", "age: X25519, ChaCha20-Poly1305, scrypt")];
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub enum MacAlgorithm {
+    #[default]
+    #[serde(rename = "hmac-sha256")]
+    HmacSha256,
+    #[serde(rename = "hmac-sha512")]
+    HmacSha512,
+}
+
+impl MacAlgorithm {
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::HmacSha256 => "hmac-sha256",
+            Self::HmacSha512 => "hmac-sha512",
+        }
+    }
+
+    pub fn mac(self, key: &[u8], data: &[u8]) -> Vec<u8> {
+        match self {
+            Self::HmacSha256 => {
+                let mut mac = Hmac::<Sha256>::new_from_slice(key).expect("HMAC takes any key");
+                mac.update(data);
+                mac.finalize().into_bytes().to_vec()
+            }
+            Self::HmacSha512 => {
+                let mut mac = Hmac::<Sha512>::new_from_slice(key).expect("HMAC takes any key");
+                mac.update(data);
+                mac.finalize().into_bytes().to_vec()
+            }
+        }
+    }
+
+    /// Constant-time check of a hex-encoded tag.
+    pub fn verify(self, key: &[u8], data: &[u8], tag_hex: &str) -> bool {
+        let Some(tag) = from_hex(tag_hex) else {
+            return false;
+        };
+        match self {
+            Self::HmacSha256 => {
+                let mut mac = Hmac::<Sha256>::new_from_slice(key).expect("HMAC takes any key");
+                mac.update(data);
+                mac.verify_slice(&tag).is_ok()
+            }
+            Self::HmacSha512 => {
+                let mut mac = Hmac::<Sha512>::new_from_slice(key).expect("HMAC takes any key");
+                mac.update(data);
+                mac.verify_slice(&tag).is_ok()
+            }
+        }
+    }
+}
+
+pub fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{b:02x}")).collect()
+}
+
+fn from_hex(hex: &str) -> Option<Vec<u8>> {
+    if !hex.len().is_multiple_of(2) {
+        return None;
+    }
+    (0..hex.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(hex.get(i..i + 2)?, 16).ok())
+        .collect()
+}
+
+#[cfg_attr(not(feature = "local-vault"), allow(dead_code))]
+#[derive(Debug, Error)]
+pub enum CryptoError {
+    /// Wrong key, wrong passphrase, or tampered ciphertext.
+    #[error("decryption failed: {0}")]
+    Decrypt(String),
+    #[error("{0}")]
+    Format(String),
+}
+
+/// Decrypts an ASCII-armored age file with any of `identities`.
+#[cfg(feature = "local-vault")]
+pub fn age_decrypt(
+    identities: &[Box<dyn age::Identity>],
+    armored: &[u8],
+) -> Result<String, CryptoError> {
+    use std::io::Read;
+
+    let decryptor = age::Decryptor::new_buffered(age::armor::ArmoredReader::new(armored))
+        .map_err(|e| CryptoError::Format(format!("not an age file: {e}")))?;
+    let mut reader = decryptor
+        .decrypt(identities.iter().map(|i| i.as_ref() as &dyn age::Identity))
+        .map_err(|e| CryptoError::Decrypt(e.to_string()))?;
+
+    let mut plaintext = String::new();
+    reader
+        .read_to_string(&mut plaintext)
+        .map_err(|e| CryptoError::Decrypt(e.to_string()))?;
+    Ok(plaintext)
+}
+
+/// Encrypts to every recipient, unarmored (for values embedded elsewhere).
+#[cfg(feature = "local-vault")]
+pub fn age_encrypt_bytes(
+    recipients: &[Box<dyn age::Recipient + Send>],
+    plaintext: &[u8],
+) -> Result<Vec<u8>, CryptoError> {
+    use std::io::Write;
+
+    let encryptor = age::Encryptor::with_recipients(
+        recipients.iter().map(|r| r.as_ref() as &dyn age::Recipient),
+    )
+    .map_err(|e| CryptoError::Format(format!("failed to encrypt: {e}")))?;
+    let mut ciphertext = Vec::new();
+    (|| {
+        let mut writer = encryptor.wrap_output(&mut ciphertext)?;
+        writer.write_all(plaintext)?;
+        writer.finish()
+    })()
+    .map_err(|e| CryptoError::Format(format!("failed to encrypt: {e}")))?;
+    Ok(ciphertext)
+}
+
+/// Decrypts unarmored age ciphertext with any of `identities`.
+#[cfg(feature = "local-vault")]
+pub fn age_decrypt_bytes(
+    identities: &[Box<dyn age::Identity>],
+    ciphertext: &[u8],
+) -> Result<String, CryptoError> {
+    use std::io::Read;
+
+    let decryptor = age::Decryptor::new(ciphertext)
+        .map_err(|e| CryptoError::Format(format!("not an age ciphertext: {e}")))?;
+    let mut reader = decryptor
+        .decrypt(identities.iter().map(|i| i.as_ref() as &dyn age::Identity))
+        .map_err(|e| CryptoError::Decrypt(e.to_string()))?;
+    let mut plaintext = String::new();
+    reader
+        .read_to_string(&mut plaintext)
+        .map_err(|e| CryptoError::Decrypt(e.to_string()))?;
+    Ok(plaintext)
+}
+
+/// Encrypts to every recipient and ASCII-armors the result.
+#[cfg(feature = "local-vault")]
+pub fn age_encrypt(
+    recipients: &[Box<dyn age::Recipient + Send>],
+    plaintext: &str,
+) -> Result<String, CryptoError> {
+    use std::io::Write;
+
+    use age::armor::{ArmoredWriter, Format};
+
+    let encryptor = age::Encryptor::with_recipients(
+        recipients.iter().map(|r| r.as_ref() as &dyn age::Recipient),
+    )
+    .map_err(|e| CryptoError::Format(format!("failed to encrypt: {e}")))?;
+    let armored = (|| {
+        let mut writer =
+            encryptor.wrap_output(ArmoredWriter::wrap_output(Vec::new(), Format::AsciiArmor)?)?;
+        writer.write_all(plaintext.as_bytes())?;
+        writer.finish()?.finish()
+    })()
+    .map_err(|e| CryptoError::Format(format!("failed to encrypt: {e}")))?;
+    String::from_utf8(armored).map_err(|e| CryptoError::Format(format!("failed to encrypt: {e}")))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn hmacs_match_rfc_4231_test_case_2() {
+        let (key, data) = (b"Jefe", b"what do ya want for nothing?");
+        let sha256 = "5bdcc146bf60754e6a042426089575c75a003f089d2739839dec58b964ec3843";
+        assert_eq!(to_hex(&MacAlgorithm::HmacSha256.mac(key, data)), sha256);
+        assert!(MacAlgorithm::HmacSha256.verify(key, data, sha256));
+        assert!(!MacAlgorithm::HmacSha256.verify(key, b"other", sha256));
+        assert!(!MacAlgorithm::HmacSha256.verify(key, data, "zz"));
+        assert!(
+            to_hex(&MacAlgorithm::HmacSha512.mac(key, data))
+                .starts_with("164b7a7bfcf819e2e395fbe7")
+        );
+    }
+}
@@ -1,4 +1,10 @@
-use std::{collections::HashMap, fs, io::Write, path::Path};
+use std::{
+    collections::{HashMap, HashSet},
+    fs::File,
+    io::{self, BufRead, BufReader, Write},
+    path::Path,
+    time::{Duration, SystemTime},
+};
 
 use anyhow::{Context, Result, bail};
 use tempfile::NamedTempFile;
@@ -62,6 +68,29 @@ fn is_valid_env_key(key: &str) -> bool {
     chars.all(|c| c == '_' || c.is_ascii_alphanumeric())
 }
 
+/// Key/value pairs of a dotenv document; comments and other lines are skipped.
+#[cfg_attr(not(feature = "kms-file"), allow(dead_code))]
+pub fn parse_entries(content: &str) -> Vec<(String, String)> {
+    content
+        .lines()
+        .filter_map(|line| match parse_line(line) {
+            Line::Entry(entry) => Some((entry.key, entry.value)),
+            Line::Raw(_) => None,
+        })
+        .collect()
+}
+
+/// Key -> value of the entry lines.
+pub(crate) fn values(lines: &[Line]) -> HashMap<&str, &str> {
+    lines
+        .iter()
+        .filter_map(|line| match line {
+            Line::Entry(entry) => Some((entry.key.as_str(), entry.value.as_str())),
+            Line::Raw(_) => None,
+        })
+        .collect()
+}
+
 pub fn load_for_merge(path: &Path, create_if_missing: bool) -> Result<Vec<Line>> {
     if !path.exists() {
         if create_if_missing {
@@ -70,49 +99,300 @@ pub fn load_for_merge(path: &Path, create_if_missing: bool) -> Result<Vec<Line>>
         bail!("env file does not exist: {}", path.display());
     }
 
-    let content = fs::read_to_string(path)
-        .with_context(|| format!("failed to read env file: {}", path.display()))?;
-    Ok(content.lines().map(parse_line).collect())
+    let file =
+        File::open(path).with_context(|| format!("failed to read env file: {}", path.display()))?;
+    BufReader::new(file)
+        .lines()
+        .map(|line| line.map(|line| parse_line(&line)))
+        .collect::<io::Result<_>>()
+        .with_context(|| format!("failed to read env file: {}", path.display()))
+}
+
+/// `load_for_merge` for content already in memory.
+pub(crate) fn parse_lines(content: &str) -> Vec<Line> {
+    content.lines().map(parse_line).collect()
 }
 
+/// Writes `updates` into `lines` in one pass: entries are replaced in
+/// place, keys the file does not have yet are appended in key order.
+///
+/// The updates are sorted once and looked up by binary search, so nothing
+/// besides the output is copied; env files with thousands of keys merge in
+/// about the time it takes to read them (see `benches/merge.rs`).
 pub fn merge(lines: Vec<Line>, updates: &HashMap<String, String>) -> (String, Vec<Change>) {
-    let mut remaining = updates.clone();
-    let mut out_lines = Vec::with_capacity(lines.len() + remaining.len());
+    let mut sorted: Vec<(&str, &str)> = updates
+        .iter()
+        .map(|(key, value)| (key.as_str(), value.as_str()))
+        .collect();
+    sorted.sort_unstable_by_key(|(key, _)| *key);
+    let mut taken = vec![false; sorted.len()];
+    let mut out = String::new();
     let mut changes = Vec::new();
 
+    let mut first = true;
+    let mut start_line = |out: &mut String| {
+        if !std::mem::take(&mut first) {
+            out.push('\n');
+        }
+    };
+
     for line in lines {
+        start_line(&mut out);
         match line {
-            Line::Raw(raw) => out_lines.push(raw),
+            Line::Raw(raw) => out.push_str(&raw),
             Line::Entry(entry) => {
-                if let Some(new_value) = remaining.remove(&entry.key) {
-                    if new_value != entry.value {
-                        changes.push(Change {
-                            key: entry.key.clone(),
-                            kind: ChangeKind::Update,
-                        });
+                out.push_str(&entry.prefix);
+                match sorted.binary_search_by_key(&entry.key.as_str(), |(key, _)| key) {
+                    // Only the first of duplicate entries is updated.
+                    Ok(i) if !taken[i] => {
+                        taken[i] = true;
+                        let new_value = sorted[i].1;
+                        if new_value != entry.value {
+                            changes.push(Change {
+                                key: entry.key,
+                                kind: ChangeKind::Update,
+                            });
+                        }
+                        out.push_str(new_value);
                     }
-                    out_lines.push(format!("{}{}", entry.prefix, new_value));
-                } else {
-                    out_lines.push(format!("{}{}", entry.prefix, entry.value));
+                    _ => out.push_str(&entry.value),
                 }
             }
         }
     }
 
-    let mut added: Vec<_> = remaining.into_iter().collect();
-    added.sort_by(|a, b| a.0.cmp(&b.0));
-    for (key, value) in added {
+    for ((key, value), _) in sorted.into_iter().zip(taken).filter(|(_, taken)| !taken) {
         changes.push(Change {
-            key: key.clone(),
+            key: key.to_string(),
             kind: ChangeKind::Add,
         });
-        out_lines.push(format!("{}={}", key, value));
+        start_line(&mut out);
+        out.push_str(key);
+        out.push('=');
+        out.push_str(value);
+    }
+
+    (out, changes)
+}
+
+/// Drops the entries whose key `drop` selects, with a deprecation comment
+/// right above them; returns the remaining lines and the dropped keys.
+pub fn remove(lines: Vec<Line>, drop: impl Fn(&str) -> bool) -> (Vec<Line>, Vec<String>) {
+    let mut out = Vec::with_capacity(lines.len());
+    let mut removed = Vec::new();
+    for line in lines {
+        match line {
+            Line::Entry(entry) if drop(&entry.key) => {
+                if let Some(Line::Raw(raw)) = out.last()
+                    && parse_marker(raw).is_some_and(|(marked, _)| marked == entry.key)
+                {
+                    out.pop();
+                }
+                removed.push(entry.key);
+            }
+            line => out.push(line),
+        }
+    }
+    (out, removed)
+}
+
+/// Keys kept behind a deprecation comment (see `deprecate`).
+pub fn deprecated_keys(lines: &[Line]) -> HashSet<String> {
+    lines
+        .windows(2)
+        .filter_map(|pair| match pair {
+            [Line::Raw(raw), Line::Entry(entry)] => parse_marker(raw)
+                .filter(|(key, _)| *key == entry.key)
+                .map(|(key, _)| key),
+            _ => None,
+        })
+        .collect()
+}
+
+/// Renames `old`'s entry to `new` in place, keeping its value and spacing;
+/// returns whether the key was there.
+pub fn rename(lines: &mut [Line], old: &str, new: &str) -> bool {
+    let Some(entry) = lines.iter_mut().find_map(|line| match line {
+        Line::Entry(entry) if entry.key == old => Some(entry),
+        _ => None,
+    }) else {
+        return false;
+    };
+    entry.prefix = entry.prefix.replacen(old, new, 1);
+    entry.key = new.to_string();
+    true
+}
+
+/// Drops `key`'s entry; returns the remaining content and whether the key
+/// was there.
+pub fn strip(lines: Vec<Line>, key: &str) -> (String, bool) {
+    let (lines, removed) = remove(lines, |it| it == key);
+    let (content, _) = merge(lines, &HashMap::new());
+    (content, !removed.is_empty())
+}
+
+const DEPRECATION_MARKER: &str = "# envit: deprecated ";
+
+/// A key envit used to provide but no longer does.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Deprecation {
+    /// Kept with a comment above it until `until`.
+    Marked {
+        key: String,
+        renamed_to: Option<String>,
+        until: SystemTime,
+    },
+    /// Grace period over; the entry is gone.
+    Removed { key: String },
+}
+
+/// Keys from the `previous` pull that `updates` no longer provides are kept
+/// for `grace` behind a deprecation comment, which also records when they
+/// go. A key whose value now lives under a newly provided key is reported
+/// as renamed. Keys provided again lose their comment.
+pub fn deprecate(
+    lines: Vec<Line>,
+    previous: &HashSet<String>,
+    updates: &HashMap<String, String>,
+    grace: Duration,
+    now: SystemTime,
+) -> (Vec<Line>, Vec<Deprecation>) {
+    let mut out = Vec::with_capacity(lines.len());
+    let mut deprecations = Vec::new();
+    let mut marker: Option<(String, SystemTime, String)> = None;
+
+    for line in lines {
+        let entry = match line {
+            Line::Raw(raw) => {
+                // A marker not directly above its entry (edited by hand) is dropped.
+                marker = None;
+                match parse_marker(&raw) {
+                    Some((key, until)) => marker = Some((raw, until, key)),
+                    None => out.push(Line::Raw(raw)),
+                }
+                continue;
+            }
+            Line::Entry(entry) => entry,
+        };
+
+        if let Some((raw, until, key)) = marker.take()
+            && key == entry.key
+        {
+            if updates.contains_key(&entry.key) {
+                out.push(Line::Entry(entry));
+            } else if now >= until {
+                deprecations.push(Deprecation::Removed { key: entry.key });
+            } else {
+                out.push(Line::Raw(raw));
+                out.push(Line::Entry(entry));
+            }
+            continue;
+        }
+
+        if previous.contains(&entry.key) && !updates.contains_key(&entry.key) {
+            let renamed_to = updates
+                .iter()
+                .filter(|(key, value)| !previous.contains(*key) && **value == entry.value)
+                .map(|(key, _)| key.clone())
+                .min();
+            let until = now + grace;
+            let reason = match &renamed_to {
+                Some(new_key) => format!("renamed to {new_key}"),
+                None => "no longer provided".to_string(),
+            };
+            out.push(Line::Raw(format!(
+                "{DEPRECATION_MARKER}{} ({reason}), remove after {}",
+                entry.key,
+                humantime::format_rfc3339_seconds(until)
+            )));
+            deprecations.push(Deprecation::Marked {
+                key: entry.key.clone(),
+                renamed_to,
+                until,
+            });
+        }
+        out.push(Line::Entry(entry));
+    }
+    (out, deprecations)
+}
+
+const EXPIRES_MARKER: &str = "# envit: expires ";
+const EXPIRED_MARKER: &str = "# envit: expired ";
+const DAY: u64 = 24 * 60 * 60;
+
+/// Puts `# envit: expires 2025-03-01 (45 days)` (or `expired ... ago`)
+/// above each key of `expiries` that has an expiry, replacing the comment
+/// an earlier pull left; keys mapped to None lose theirs. Other keys keep
+/// whatever is above them.
+pub fn annotate_expiries(
+    lines: Vec<Line>,
+    expiries: &HashMap<String, Option<SystemTime>>,
+    now: SystemTime,
+) -> Vec<Line> {
+    let mut out = Vec::with_capacity(lines.len());
+    for line in lines {
+        let Line::Entry(entry) = &line else {
+            out.push(line);
+            continue;
+        };
+        let Some(expires) = expiries.get(&entry.key) else {
+            out.push(line);
+            continue;
+        };
+        if let Some(Line::Raw(raw)) = out.last()
+            && (raw.starts_with(EXPIRES_MARKER) || raw.starts_with(EXPIRED_MARKER))
+        {
+            out.pop();
+        }
+        if let Some(expires) = *expires {
+            let date = &humantime::format_rfc3339_seconds(expires).to_string()[..10];
+            out.push(Line::Raw(match expires.duration_since(now) {
+                Ok(left) => format!("{EXPIRES_MARKER}{date} ({} days)", left.as_secs() / DAY),
+                Err(past) => format!(
+                    "{EXPIRED_MARKER}{date} ({} days ago)",
+                    past.duration().as_secs() / DAY
+                ),
+            }));
+        }
+        out.push(line);
     }
+    out
+}
 
-    (out_lines.join("\n"), changes)
+/// `# envit: deprecated KEY (...), remove after <rfc3339>` -> key and expiry.
+fn parse_marker(line: &str) -> Option<(String, SystemTime)> {
+    let rest = line.trim_start().strip_prefix(DEPRECATION_MARKER)?;
+    let (key, _) = rest.split_once(' ')?;
+    let (_, until) = rest.rsplit_once(", remove after ")?;
+    let until = humantime::parse_rfc3339(until.trim()).ok()?;
+    Some((key.to_string(), until))
 }
 
-pub fn write_atomic(path: &Path, content: &str) -> Result<()> {
+/// Final file bytes for merged content: one trailing newline, or nothing at
+/// all when there are no lines.
+pub fn render(content: &str) -> String {
+    if content.is_empty() {
+        String::new()
+    } else {
+        format!("{content}\n")
+    }
+}
+
+/// Size of the differing region between two versions of a file, after
+/// trimming their common prefix and suffix.
+pub fn changed_bytes(old: &[u8], new: &[u8]) -> usize {
+    let prefix = old.iter().zip(new).take_while(|(a, b)| a == b).count();
+    let (old, new) = (&old[prefix..], &new[prefix..]);
+    let suffix = old
+        .iter()
+        .rev()
+        .zip(new.iter().rev())
+        .take_while(|(a, b)| a == b)
+        .count();
+    old.len().max(new.len()) - suffix
+}
+
+pub fn write_atomic(path: &Path, content: impl AsRef<[u8]>) -> Result<()> {
     let dir = path
         .parent()
         .map(ToOwned::to_owned)
@@ -120,10 +400,8 @@ pub fn write_atomic(path: &Path, content: &str) -> Result<()> {
 
     let mut tmp = NamedTempFile::new_in(&dir)
         .with_context(|| format!("failed to create temp file in {}", dir.display()))?;
-    tmp.write_all(content.as_bytes())
+    tmp.write_all(content.as_ref())
         .context("failed to write temp env content")?;
-    tmp.write_all(b"\n")
-        .context("failed to finalize temp env content")?;
     tmp.flush().context("failed to flush temp env content")?;
 
     tmp.persist(path)
@@ -157,4 +435,132 @@ mod tests {
         assert!(merged.contains("C=3"));
         assert_eq!(changes.len(), 2);
     }
+
+    #[test]
+    fn merge_appends_new_keys_sorted_and_updates_only_the_first_duplicate() {
+        let lines = vec![parse_line("B=1"), parse_line("B=old"), parse_line("")];
+        let updates = HashMap::from([
+            ("B".to_string(), "2".to_string()),
+            ("D".to_string(), "4".to_string()),
+            ("A".to_string(), "3".to_string()),
+        ]);
+
+        let (merged, changes) = merge(lines, &updates);
+
+        assert_eq!(merged, "B=2\nB=old\n\nA=3\nD=4");
+        let keys: Vec<_> = changes.iter().map(|c| (c.key.as_str(), &c.kind)).collect();
+        assert_eq!(
+            keys,
+            [
+                ("B", &ChangeKind::Update),
+                ("A", &ChangeKind::Add),
+                ("D", &ChangeKind::Add)
+            ]
+        );
+        assert_eq!(merge(vec![parse_line("")], &updates).0, "\nA=3\nB=2\nD=4");
+    }
+
+    #[test]
+    fn stale_keys_are_marked_then_removed_after_the_grace_period() {
+        let lines = vec![
+            parse_line("OLD_DB=postgres://x"),
+            parse_line("GONE=1"),
+            parse_line("LOCAL_ONLY=keep"),
+        ];
+        let previous: HashSet<String> = ["OLD_DB", "GONE"].map(String::from).into();
+        let updates = HashMap::from([("DATABASE_URL".to_string(), "postgres://x".to_string())]);
+        let grace = Duration::from_secs(86_400);
+        let now = humantime::parse_rfc3339("2026-01-01T00:00:00Z").unwrap();
+
+        let (lines, deprecations) = deprecate(lines, &previous, &updates, grace, now);
+        assert_eq!(
+            deprecations,
+            vec![
+                Deprecation::Marked {
+                    key: "OLD_DB".to_string(),
+                    renamed_to: Some("DATABASE_URL".to_string()),
+                    until: now + grace,
+                },
+                Deprecation::Marked {
+                    key: "GONE".to_string(),
+                    renamed_to: None,
+                    until: now + grace,
+                },
+            ]
+        );
+        let (content, _) = merge(lines.clone(), &updates);
+        assert_eq!(
+            content,
+            "# envit: deprecated OLD_DB (renamed to DATABASE_URL), remove after 2026-01-02T00:00:00Z\n\
+             OLD_DB=postgres://x\n\
+             # envit: deprecated GONE (no longer provided), remove after 2026-01-02T00:00:00Z\n\
+             GONE=1\n\
+             LOCAL_ONLY=keep\n\
+             DATABASE_URL=postgres://x"
+        );
+
+        // Still within the grace period: untouched, not reported again.
+        let previous: HashSet<String> = ["DATABASE_URL"].map(String::from).into();
+        let (kept, deprecations) = deprecate(lines.clone(), &previous, &updates, grace, now);
+        assert!(deprecations.is_empty());
+        assert_eq!(kept.len(), 5);
+
+        let later = now + grace;
+        let (lines, deprecations) = deprecate(lines, &previous, &updates, grace, later);
+        assert_eq!(deprecations.len(), 2);
+        assert!(matches!(&deprecations[0], Deprecation::Removed { key } if key == "OLD_DB"));
+        let (content, _) = merge(lines, &updates);
+        assert_eq!(content, "LOCAL_ONLY=keep\nDATABASE_URL=postgres://x");
+    }
+
+    #[test]
+    fn remove_drops_selected_entries_with_their_deprecation_comment() {
+        let lines = vec![
+            parse_line(
+                "# envit: deprecated OLD (no longer provided), remove after 2026-01-02T00:00:00Z",
+            ),
+            parse_line("OLD=1"),
+            parse_line("# local"),
+            parse_line("LOCAL=2"),
+            parse_line("GONE=3"),
+        ];
+
+        assert_eq!(deprecated_keys(&lines), HashSet::from(["OLD".to_string()]));
+        let (lines, removed) = remove(lines, |key| key == "OLD" || key == "GONE");
+        assert_eq!(removed, ["OLD", "GONE"]);
+        let (content, _) = merge(lines, &HashMap::new());
+        assert_eq!(content, "# local\nLOCAL=2");
+    }
+
+    #[test]
+    fn expiry_comments_are_replaced_and_dropped_with_the_expiry() {
+        let now = humantime::parse_rfc3339("2025-01-15T12:00:00Z").unwrap();
+        let at = |time| Some(humantime::parse_rfc3339(time).unwrap());
+        let lines = parse_lines(
+            "# envit: expires 2025-01-20 (10 days)\nTOKEN=1\n\
+             # envit: expires 2025-02-01 (20 days)\nGONE=2\n\
+             # envit: expires 2030-01-01 (9 days)\nLOCAL=3\nOLD=4",
+        );
+        let expiries = HashMap::from([
+            ("TOKEN".to_string(), at("2025-03-01T12:00:00Z")),
+            ("GONE".to_string(), None),
+            ("OLD".to_string(), at("2025-01-10T00:00:00Z")),
+        ]);
+
+        let (content, _) = merge(annotate_expiries(lines, &expiries, now), &HashMap::new());
+        assert_eq!(
+            content,
+            "# envit: expires 2025-03-01 (45 days)\nTOKEN=1\nGONE=2\n\
+             # envit: expires 2030-01-01 (9 days)\nLOCAL=3\n\
+             # envit: expired 2025-01-10 (5 days ago)\nOLD=4"
+        );
+    }
+
+    #[test]
+    fn changed_bytes_counts_only_the_differing_region() {
+        assert_eq!(changed_bytes(b"A=1\nB=2\n", b"A=1\nB=2\n"), 0);
+        assert_eq!(changed_bytes(b"A=1\nB=2\n", b"A=1\nB=22\n"), 1);
+        assert_eq!(changed_bytes(b"", render("").as_bytes()), 0);
+        assert_eq!(changed_bytes(b"A=old\n", b"A=new\n"), 3);
+    }
 }
@@ -0,0 +1,15 @@
+//! Exit codes shared by commands, so CI and supervisors can branch on the
+//! outcome without parsing output. 1 is any error, usage errors included.
+
+/// The command ran and found something to act on: drift (`check`, `diff`,
+/// `example --check`), keys only one profile has (`compare`) or expiring
+/// secrets (`expiry`).
+pub const DRIFT: i32 = 2;
+
+/// sysexits(3), so restart policies and alerts can tell a retryable
+/// failure from one that needs a human: a degraded pull or an unreachable
+/// provider (EX_TEMPFAIL), rejected credentials (EX_NOPERM), a broken
+/// config (EX_CONFIG).
+pub const TRANSIENT: i32 = 75;
+pub const AUTH: i32 = 77;
+pub const CONFIG: i32 = 78;
@@ -0,0 +1,136 @@
+use anyhow::{Result, bail};
+use base64::{Engine, engine::general_purpose::STANDARD};
+use rand::{Rng, RngCore, distributions::Alphanumeric, rngs::OsRng};
+use schemars::JsonSchema;
+use serde::Deserialize;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum Format {
+    #[default]
+    Alphanumeric,
+    Hex,
+    Base64,
+    Uuid,
+}
+
+/// How to generate a new secret value. `length` counts characters for
+/// `alphanumeric`, random bytes for `hex`/`base64`, and is ignored for `uuid`.
+/// `charset` replaces the alphanumeric alphabet, e.g. to allow symbols.
+#[derive(Debug, Clone, Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct Policy {
+    #[serde(default)]
+    pub format: Format,
+    #[serde(default = "default_length")]
+    pub length: usize,
+    #[serde(default)]
+    pub charset: Option<String>,
+}
+
+fn default_length() -> usize {
+    32
+}
+
+impl Default for Policy {
+    fn default() -> Self {
+        Self {
+            format: Format::default(),
+            length: default_length(),
+            charset: None,
+        }
+    }
+}
+
+impl Policy {
+    pub fn validate(&self) -> Result<()> {
+        if self.length == 0 && self.format != Format::Uuid {
+            bail!("generator length must be at least 1");
+        }
+        match &self.charset {
+            Some(_) if self.format != Format::Alphanumeric => {
+                bail!("generator charset only applies to the alphanumeric format")
+            }
+            Some(charset) if charset.is_empty() => bail!("generator charset must not be empty"),
+            _ => Ok(()),
+        }
+    }
+}
+
+pub fn generate(policy: &Policy) -> String {
+    match policy.format {
+        Format::Alphanumeric => match &policy.charset {
+            Some(charset) => {
+                let chars: Vec<char> = charset.chars().collect();
+                (0..policy.length)
+                    .map(|_| chars[OsRng.gen_range(0..chars.len())])
+                    .collect()
+            }
+            None => OsRng
+                .sample_iter(&Alphanumeric)
+                .take(policy.length)
+                .map(char::from)
+                .collect(),
+        },
+        Format::Hex => random_bytes(policy.length)
+            .iter()
+            .map(|b| format!("{b:02x}"))
+            .collect(),
+        Format::Base64 => STANDARD.encode(random_bytes(policy.length)),
+        Format::Uuid => uuid::Uuid::new_v4().to_string(),
+    }
+}
+
+fn random_bytes(len: usize) -> Vec<u8> {
+    let mut bytes = vec![0; len];
+    OsRng.fill_bytes(&mut bytes);
+    bytes
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn generated_values_follow_policy() {
+        let policy = |format, length| Policy {
+            format,
+            length,
+            charset: None,
+        };
+
+        let alnum = generate(&policy(Format::Alphanumeric, 40));
+        assert_eq!(alnum.len(), 40);
+        assert!(alnum.chars().all(|c| c.is_ascii_alphanumeric()));
+
+        let hex = generate(&policy(Format::Hex, 16));
+        assert_eq!(hex.len(), 32);
+        assert!(hex.chars().all(|c| c.is_ascii_hexdigit()));
+
+        assert_eq!(
+            STANDARD
+                .decode(generate(&policy(Format::Base64, 24)))
+                .unwrap()
+                .len(),
+            24
+        );
+        assert!(uuid::Uuid::parse_str(&generate(&policy(Format::Uuid, 0))).is_ok());
+
+        let pin = Policy {
+            charset: Some("0123456789".to_string()),
+            ..policy(Format::Alphanumeric, 6)
+        };
+        let value = generate(&pin);
+        assert_eq!(value.len(), 6);
+        assert!(value.chars().all(|c| c.is_ascii_digit()));
+        assert!(pin.validate().is_ok());
+        assert!(
+            Policy {
+                format: Format::Hex,
+                ..pin
+            }
+            .validate()
+            .is_err()
+        );
+    }
+}
@@ -0,0 +1,244 @@
+//! `[[hooks]]`: webhooks told about changes (keys and change kinds, never
+//! values), e.g. a Slack channel or an issue tracker's comment API, and
+//! local commands (hook `command`s and `--on-change`) that restart what
+//! uses the changed keys.
+
+use std::{env, path::Path};
+
+use anyhow::{Context, Result, anyhow, bail};
+use serde_json::{Value, json};
+
+use crate::{
+    audit,
+    commands::pull,
+    config::{self, Config, HookConfig},
+    shell,
+};
+
+/// Commands that change keys and can trigger hooks.
+pub const EVENTS: &[&str] = &["delete", "pull", "push", "rename", "rotate"];
+/// Comma-separated changed keys, for hook commands.
+pub const CHANGED_KEYS_ENV: &str = "ENVIT_CHANGED_KEYS";
+/// The JSON file hook commands also get as their last argument.
+pub const CHANGES_FILE_ENV: &str = "ENVIT_CHANGES_FILE";
+/// Where that file is written, relative to the config file.
+pub const CHANGES_PATH: &str = ".envit/changes.json";
+
+/// One changed key and what happened to it (`add`, `update`, `rotate`, `delete`).
+pub struct Change<'a> {
+    pub key: &'a str,
+    pub kind: &'a str,
+}
+
+/// Posts `changes` to every hook subscribed to `entry.command`. Failures
+/// are reported as warnings: a flaky webhook must not fail the change.
+pub async fn notify(
+    config_path: &Path,
+    cfg: &Config,
+    entry: &audit::Entry<'_>,
+    changes: &[Change<'_>],
+) {
+    if changes.is_empty() {
+        return;
+    }
+    let hooks = cfg
+        .hooks
+        .iter()
+        .filter(|hook| hook.on.iter().any(|event| event == entry.command));
+    for hook in hooks {
+        let (target, result) = match (&hook.url, &hook.command) {
+            (Some(url), _) => (url, post(hook, url, config_path, entry, changes).await),
+            (None, Some(command)) => (
+                command,
+                run_command(command, config_path, entry, changes).await,
+            ),
+            (None, None) => continue,
+        };
+        if let Err(err) = result {
+            eprintln!("warning: hook {target} failed: {err:#}");
+        }
+    }
+}
+
+/// Runs `command` through the shell with the changed keys in
+/// `CHANGED_KEYS_ENV` and the path of a file holding the default JSON
+/// payload as its last argument (and in `CHANGES_FILE_ENV`), so it can
+/// restart only what uses those keys. Nothing runs without changes.
+pub async fn run_command(
+    command: &str,
+    config_path: &Path,
+    entry: &audit::Entry<'_>,
+    changes: &[Change<'_>],
+) -> Result<()> {
+    if changes.is_empty() {
+        return Ok(());
+    }
+    let vars = Vars::new(config_path, entry, changes);
+    let path = config::resolve_path(config_path, CHANGES_PATH);
+    pull::write_state(&path, &format!("{:#}\n", vars.default_payload()))?;
+    let path = path.display().to_string();
+    let status = shell::command(&format!("{command} {}", shell::quote(&path)))
+        .env(CHANGED_KEYS_ENV, vars.keys())
+        .env(CHANGES_FILE_ENV, &path)
+        .status()
+        .await
+        .with_context(|| format!("failed to run {command}"))?;
+    if !status.success() {
+        bail!("{command} exited with {status}");
+    }
+    Ok(())
+}
+
+async fn post(
+    hook: &HookConfig,
+    url: &str,
+    config_path: &Path,
+    entry: &audit::Entry<'_>,
+    changes: &[Change<'_>],
+) -> Result<()> {
+    let vars = Vars::new(config_path, entry, changes);
+    let body = match &hook.template {
+        Some(template) => vars.render(template),
+        None => vars.default_payload().to_string(),
+    };
+
+    let mut req = reqwest::Client::new()
+        .post(url)
+        .header(reqwest::header::CONTENT_TYPE, "application/json")
+        .body(body);
+    for (name, var) in &hook.headers_env {
+        let value = env::var(var).map_err(|_| anyhow!("{var} is not set (header {name})"))?;
+        req = req.header(name, value);
+    }
+    req.send()
+        .await
+        .and_then(|res| res.error_for_status())
+        .with_context(|| format!("POST {url}"))?;
+    Ok(())
+}
+
+/// Placeholder values for templates.
+struct Vars {
+    command: String,
+    actor: String,
+    principal: String,
+    host: String,
+    config: String,
+    changes: Vec<(String, String)>,
+}
+
+impl Vars {
+    fn new(config_path: &Path, entry: &audit::Entry<'_>, changes: &[Change<'_>]) -> Self {
+        Self {
+            command: entry.command.to_string(),
+            actor: entry.user.clone().unwrap_or_else(|| "unknown".to_string()),
+            principal: entry.principal.unwrap_or_default().to_string(),
+            host: entry.host.clone().unwrap_or_default(),
+            config: config_path.display().to_string(),
+            changes: changes
+                .iter()
+                .map(|c| (c.key.to_string(), c.kind.to_string()))
+                .collect(),
+        }
+    }
+
+    /// `REDIS_URL,DATABASE_URL`
+    fn keys(&self) -> String {
+        self.changes
+            .iter()
+            .map(|(key, _)| key.as_str())
+            .collect::<Vec<_>>()
+            .join(",")
+    }
+
+    /// `ADD REDIS_URL, UPDATE DATABASE_URL`
+    fn summary(&self) -> String {
+        self.changes
+            .iter()
+            .map(|(key, kind)| format!("{} {key}", kind.to_ascii_uppercase()))
+            .collect::<Vec<_>>()
+            .join(", ")
+    }
+
+    /// Also usable as a Slack incoming webhook message through `text`.
+    fn default_payload(&self) -> Value {
+        json!({
+            "text": format!(
+                "envit {} by {}: {} ({})",
+                self.command,
+                self.actor,
+                self.summary(),
+                self.config
+            ),
+            "command": self.command,
+            "actor": self.actor,
+            "principal": self.principal,
+            "host": self.host,
+            "config": self.config,
+            "changes": self
+                .changes
+                .iter()
+                .map(|(key, kind)| json!({ "key": key, "kind": kind }))
+                .collect::<Vec<_>>(),
+        })
+    }
+
+    /// Replaces `{{name}}` placeholders with JSON-escaped text (without
+    /// quotes), so they belong inside string literals of a JSON template.
+    fn render(&self, template: &str) -> String {
+        let keys = self
+            .changes
+            .iter()
+            .map(|(key, _)| key.as_str())
+            .collect::<Vec<_>>()
+            .join(", ");
+        let vars = [
+            ("command", self.command.clone()),
+            ("actor", self.actor.clone()),
+            ("principal", self.principal.clone()),
+            ("host", self.host.clone()),
+            ("config", self.config.clone()),
+            ("count", self.changes.len().to_string()),
+            ("keys", keys),
+            ("changes", self.summary()),
+        ];
+        let mut out = template.to_string();
+        for (name, value) in vars {
+            let escaped = serde_json::to_string(&value).expect("strings always serialize");
+            out = out.replace(&format!("{{{{{name}}}}}"), &escaped[1..escaped.len() - 1]);
+        }
+        out
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn template_placeholders_are_json_escaped() {
+        let mut entry = audit::Entry::new("push");
+        entry.user = Some("ana \"ops\"".to_string());
+        let changes = [
+            Change {
+                key: "REDIS_URL",
+                kind: "add",
+            },
+            Change {
+                key: "DATABASE_URL",
+                kind: "update",
+            },
+        ];
+        let vars = Vars::new(Path::new("envit.toml"), &entry, &changes);
+
+        let body = vars.render(
+            r#"{"body": "{{actor}} ran {{command}}: {{changes}} ({{count}} in {{config}})"}"#,
+        );
+        let parsed: Value = serde_json::from_str(&body).unwrap();
+        assert_eq!(
+            parsed["body"],
+            "ana \"ops\" ran push: ADD REDIS_URL, UPDATE DATABASE_URL (2 in envit.toml)"
+        );
+        assert_eq!(vars.default_payload()["changes"][1]["kind"], "update");
+    }
+}
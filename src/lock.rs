@@ -0,0 +1,252 @@
+//! `envit.lock` (next to the config, named after it): the secret version
+//! each key was last pulled at, so everyone pulls the same values until
+//! someone runs `envit update`. Optional: pulls pin nothing until a lock
+//! exists. Keys of providers that keep no versions are locked by secret
+//! only and always get the latest value.
+
+use std::{
+    collections::{BTreeMap, HashMap},
+    fs, io,
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+use anyhow::{Context, Result, anyhow, bail};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    commands::push,
+    config::Source,
+    envfile,
+    provider::{Fetched, SecretProvider, cache::CachedProvider},
+    resolve,
+};
+
+const FORMAT_VERSION: u32 = 1;
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Lock {
+    version: u32,
+    #[serde(default)]
+    keys: BTreeMap<String, Locked>,
+}
+
+impl Default for Lock {
+    fn default() -> Self {
+        Self {
+            version: FORMAT_VERSION,
+            keys: BTreeMap::new(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct Locked {
+    secret: String,
+    version: Option<String>,
+}
+
+/// How a pull treats the lock.
+#[derive(Debug, Clone, Copy)]
+pub enum Mode<'a> {
+    /// Pin to the lock when there is one; keys it lacks are locked at their
+    /// current version.
+    Use,
+    /// Fail unless the lock covers exactly the keys the pull produces.
+    Frozen,
+    /// Lock these keys (all when empty) at their current version, creating
+    /// the lock if needed.
+    Update(&'a [String]),
+}
+
+impl Mode<'_> {
+    fn refreshes(self, key: &str) -> bool {
+        match self {
+            Self::Update(keys) => keys.is_empty() || keys.iter().any(|k| k == key),
+            Self::Use | Self::Frozen => false,
+        }
+    }
+}
+
+/// `envit.toml` -> `envit.lock`, `envit.prod.toml` -> `envit.prod.lock`.
+pub fn path(config_path: &Path) -> PathBuf {
+    config_path.with_extension("lock")
+}
+
+pub fn load(path: &Path) -> Result<Option<Lock>> {
+    let raw = match fs::read_to_string(path) {
+        Ok(raw) => raw,
+        Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(None),
+        Err(err) => return Err(err).with_context(|| format!("failed to read {}", path.display())),
+    };
+    let lock: Lock =
+        toml::from_str(&raw).with_context(|| format!("invalid lockfile {}", path.display()))?;
+    if lock.version != FORMAT_VERSION {
+        bail!(
+            "{}: unsupported lockfile version {}",
+            path.display(),
+            lock.version
+        );
+    }
+    Ok(Some(lock))
+}
+
+pub fn save(path: &Path, lock: &Lock) -> Result<()> {
+    let content = format!(
+        "# Written by envit; `envit update` advances it. Commit it.\n{}",
+        toml::to_string(lock)?
+    );
+    envfile::write_atomic(path, content)
+}
+
+impl Lock {
+    /// Keys added, removed or moved to another secret or version in `next`.
+    pub fn changed(&self, next: &Lock) -> Vec<String> {
+        let mut keys: Vec<_> = self
+            .keys
+            .keys()
+            .chain(next.keys.keys())
+            .filter(|key| self.keys.get(*key) != next.keys.get(*key))
+            .cloned()
+            .collect();
+        keys.sort_unstable();
+        keys.dedup();
+        keys
+    }
+}
+
+/// Resolves every source (later ones win, as in a pull) with each key at
+/// its locked version, and returns the values with the lock they match.
+/// A locked version the provider no longer has is an error. Keys the lock
+/// does not pin are read with their current version, checked against the
+/// listing under `verify_versions` as in an unlocked pull.
+pub async fn resolve(
+    connected: &[(Source<'_>, Arc<CachedProvider>)],
+    lock: &Lock,
+    mode: Mode<'_>,
+) -> Result<(HashMap<String, String>, Lock)> {
+    let mut values = HashMap::new();
+    let mut next = Lock::default();
+    for (source, provider) in connected {
+        let versioned = provider.capabilities().versions;
+        let listed = resolve::listed_keys(*source, provider.as_ref()).await?;
+
+        let mut fetched = vec![None; listed.len()];
+        let mut result = Ok(());
+        let mut reads = Vec::new();
+        for (meta, key) in &listed {
+            let pinned = lock
+                .keys
+                .get(key)
+                .filter(|locked| locked.secret == meta.name && !mode.refreshes(key))
+                .map(|locked| locked.version.clone());
+            let provider = Arc::clone(provider);
+            let secret = meta.name.clone();
+            let key = key.clone();
+            reads.push(
+                async move { read(provider.as_ref(), &key, &secret, pinned, versioned).await },
+            );
+        }
+        push::bounded(push::DEFAULT_CONCURRENCY, reads, |index, read| {
+            match read {
+                Ok(read) => fetched[index] = read,
+                Err(err) => result = Err(err),
+            }
+            result.is_ok()
+        })
+        .await;
+        result?;
+
+        let mut moved = Vec::new();
+        for ((meta, key), read) in listed.iter().zip(fetched) {
+            let Some((value, pinned)) = read else {
+                continue;
+            };
+            if source.verify_versions && versioned && !pinned && meta.moved(&value) {
+                moved.push(meta.name.as_str());
+            }
+            values.insert(key.clone(), value.value);
+            next.keys.insert(
+                key.clone(),
+                Locked {
+                    secret: meta.name.clone(),
+                    version: value.version,
+                },
+            );
+        }
+        resolve::warn_moved(&moved);
+    }
+
+    check(lock, &next, mode)?;
+    Ok((values, next))
+}
+
+/// Reads `secret` at its `pinned` version when the lock has one, otherwise
+/// its current value with the version it is at, in one request where the
+/// provider allows. Also returns whether the read was pinned.
+async fn read(
+    provider: &CachedProvider,
+    key: &str,
+    secret: &str,
+    pinned: Option<Option<String>>,
+    versioned: bool,
+) -> Result<Option<(Fetched, bool)>> {
+    let is_pinned = pinned.is_some();
+    let fetched = match pinned {
+        Some(Some(version)) => {
+            let value = provider
+                .get_secret_version(secret, &version)
+                .await
+                .with_context(|| format!("failed to fetch secret {secret}"))?
+                .ok_or_else(|| {
+                    anyhow!(
+                        "{key}: secret {secret} has no version {version} any more; run `envit \
+                         update {key}`"
+                    )
+                })?;
+            Some(Fetched {
+                value,
+                version: Some(version),
+                ..Fetched::default()
+            })
+        }
+        None if versioned => provider
+            .fetch_secret(secret)
+            .await
+            .with_context(|| format!("failed to fetch secret {secret}"))?,
+        Some(None) | None => provider
+            .get_secret(secret)
+            .await
+            .with_context(|| format!("failed to fetch secret {secret}"))?
+            .map(|value| Fetched {
+                value,
+                ..Fetched::default()
+            }),
+    };
+    Ok(fetched.map(|fetched| (fetched, is_pinned)))
+}
+
+/// Fails when `next`, resolved from `lock`, breaks what `mode` asks for:
+/// `--frozen` with keys the lock does not pin, `update` with unknown keys.
+pub fn check(lock: &Lock, next: &Lock, mode: Mode<'_>) -> Result<()> {
+    match mode {
+        Mode::Frozen => {
+            let changed = lock.changed(next);
+            if !changed.is_empty() {
+                bail!(
+                    "the lockfile does not pin {}; run `envit update`",
+                    changed.join(", ")
+                );
+            }
+        }
+        Mode::Update(keys) => {
+            if let Some(key) = keys.iter().find(|key| !next.keys.contains_key(*key)) {
+                bail!("no source provides {key}");
+            }
+        }
+        Mode::Use => {}
+    }
+    Ok(())
+}
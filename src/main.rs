@@ -1,21 +1,40 @@
+mod audit;
+mod backup;
+mod classify;
+mod codec;
+mod commands;
 mod config;
+mod crypto;
 mod envfile;
+mod exit;
+mod generate;
+mod hooks;
+mod lock;
+mod messages;
+mod owners;
+mod plaintext;
 mod provider;
+mod resolve;
+mod shell;
+mod verbose;
 
-use std::{collections::HashMap, path::{Path, PathBuf}};
+use std::{io, path::PathBuf, time::Duration};
 
-use anyhow::{Context, Result, bail};
-use clap::{Parser, Subcommand};
-use config::Config;
-use envfile::ChangeKind;
-use provider::build_provider;
+use anyhow::Result;
+use clap::{CommandFactory, Parser, Subcommand, ValueHint};
 
 #[derive(Debug, Parser)]
 #[command(name = "envit")]
 #[command(about = "Secret-backed .env materializer")]
 struct Cli {
+    /// List the provider kinds compiled into this binary and exit
+    #[arg(long)]
+    features: bool,
+    /// Print diagnostics such as request timings to stderr
+    #[arg(long, short, global = true)]
+    verbose: bool,
     #[command(subcommand)]
-    command: Commands,
+    command: Option<Commands>,
 }
 
 #[derive(Debug, Subcommand)]
@@ -25,6 +44,614 @@ enum Commands {
         config: PathBuf,
         #[arg(long)]
         dry_run: bool,
+        /// Print nothing unless the pull fails
+        #[arg(long, short)]
+        quiet: bool,
+        /// Keep last-known values for providers that are temporarily
+        /// unreachable instead of failing (exits 75 when that happens)
+        #[arg(long)]
+        degraded_ok: bool,
+        /// Shell command to run when keys changed; gets them in
+        /// ENVIT_CHANGED_KEYS and a JSON file path as its last argument
+        #[arg(long, value_name = "COMMAND")]
+        on_change: Option<String>,
+        /// Fail instead of pulling versions or keys envit.lock does not pin
+        #[arg(long, conflicts_with = "degraded_ok")]
+        frozen: bool,
+        /// End with a one-line summary for prompts and scripts
+        #[arg(long, value_enum, default_value = "none")]
+        summary: commands::pull::Summary,
+        /// Pull these profiles concurrently instead (`staging` reads
+        /// envit.staging.toml); needs --out-dir
+        #[arg(
+            long = "env",
+            value_name = "PROFILE",
+            value_hint = ValueHint::FilePath,
+            value_delimiter = ',',
+            requires = "out_dir",
+            conflicts_with_all = ["config", "on_change", "summary"]
+        )]
+        envs: Vec<String>,
+        /// Directory the --env profiles are written to, as .env.<profile>
+        #[arg(long, value_name = "DIR", value_hint = ValueHint::DirPath, requires = "envs")]
+        out_dir: Option<PathBuf>,
+    },
+    /// Pull the latest versions and advance envit.lock (creating it if needed)
+    Update {
+        #[arg(long, default_value = "envit.toml")]
+        config: PathBuf,
+        /// Only advance these env keys (default: all)
+        keys: Vec<String>,
+        /// End with a one-line summary for prompts and scripts
+        #[arg(long, value_enum, default_value = "none")]
+        summary: commands::pull::Summary,
+    },
+    /// CI drift check: prints drifted keys only; exits 0 in sync, 2 on drift, 1 on errors
+    Check {
+        #[arg(long, default_value = "envit.toml")]
+        config: PathBuf,
+    },
+    /// Show keys a pull would add, update or remove; exits 2 on drift
+    Diff {
+        #[arg(long, default_value = "envit.toml")]
+        config: PathBuf,
+        /// Also print the differing values (plaintext)
+        #[arg(long)]
+        values: bool,
+        /// Compare with the env file as committed at this git revision, e.g. HEAD~1
+        #[arg(long, value_name = "REV")]
+        against_git: Option<String>,
+        /// Committed env file to read (default: output.env_file); may be age-encrypted
+        #[arg(requires = "against_git")]
+        path: Option<PathBuf>,
+        /// age identity file decrypting an encrypted env file (default: ENVIT_VAULT_PASSPHRASE)
+        #[arg(long, requires = "against_git")]
+        identity: Option<PathBuf>,
+    },
+    /// Compare the keys two environments pull; exits with 2 when one has keys the other lacks
+    Compare {
+        /// Two profiles: `staging` reads envit.staging.toml, a path names a config
+        #[arg(
+            long = "profile",
+            value_name = "PROFILE",
+            value_hint = ValueHint::FilePath,
+            required = true
+        )]
+        profiles: Vec<String>,
+        /// Also report which shared keys hold the same value (values are never printed)
+        #[arg(long)]
+        values: bool,
+    },
+    /// Print one env key's value (plaintext), fetching only its secret
+    Get {
+        #[arg(long, default_value = "envit.toml")]
+        config: PathBuf,
+        /// Env key, after [map] and prefixes
+        key: String,
+    },
+    /// List the versions of one env key's secret and mark the one in the env file
+    History {
+        #[arg(long, default_value = "envit.toml")]
+        config: PathBuf,
+        /// Env key, after [map] and prefixes
+        key: String,
+    },
+    /// Write one env key's secret to the provider; prompts for the value unless given or piped
+    Set {
+        #[arg(long, default_value = "envit.toml")]
+        config: PathBuf,
+        /// Env key, e.g. DATABASE_URL
+        key: String,
+        /// The value (ends up in shell history; prefer stdin or the prompt)
+        #[arg(long)]
+        value: Option<String>,
+        /// Secret name to write, instead of the one [map] or the naming rule gives
+        #[arg(long)]
+        secret: Option<String>,
+        /// Add the key and --secret to [map] when the naming rule would not connect them
+        #[arg(long)]
+        save_map: bool,
+        /// Allow changing a key `[owners]` assigns to another team
+        #[arg(long)]
+        override_owner: bool,
+    },
+    /// Delete one env key's secret from the provider (Key Vault: disable it), after confirming
+    Delete {
+        #[arg(long, default_value = "envit.toml")]
+        config: PathBuf,
+        /// Env key, e.g. DATABASE_URL
+        key: String,
+        /// Also remove the key's line from the env file
+        #[arg(long)]
+        strip_env: bool,
+        /// Delete without asking for confirmation
+        #[arg(long, short)]
+        yes: bool,
+        /// Allow deleting a key `[owners]` assigns to another team
+        #[arg(long)]
+        override_owner: bool,
+    },
+    /// Rename an env key and its secret: copy the value, update [map] and the env file, delete the old secret
+    Rename {
+        #[arg(long, default_value = "envit.toml")]
+        config: PathBuf,
+        /// Current env key, e.g. DB_URL
+        old_key: String,
+        /// New env key, e.g. DATABASE_URL
+        new_key: String,
+        /// Name of the new secret (default: derived from the new key)
+        #[arg(long)]
+        secret: Option<String>,
+        #[arg(long)]
+        dry_run: bool,
+        /// Rename without asking for confirmation
+        #[arg(long, short)]
+        yes: bool,
+        /// Allow renaming keys `[owners]` assigns to another team
+        #[arg(long)]
+        override_owner: bool,
+    },
+    /// Remove keys an earlier pull wrote whose secrets are gone (local-only keys are kept)
+    Prune {
+        #[arg(long, default_value = "envit.toml")]
+        config: PathBuf,
+        /// List the keys that would go and stop
+        #[arg(long)]
+        dry_run: bool,
+    },
+    /// Remove every key envit manages from the env file (local-only keys and comments are kept)
+    Clean {
+        #[arg(long, default_value = "envit.toml")]
+        config: PathBuf,
+        /// List the keys that would go and stop
+        #[arg(long)]
+        dry_run: bool,
+    },
+    /// Restore the env file from a snapshot taken before an earlier write (see output.backups)
+    Rollback {
+        #[arg(long, default_value = "envit.toml")]
+        config: PathBuf,
+        /// Restore the newest snapshot taken at or before this RFC 3339 time
+        #[arg(long, value_name = "TIMESTAMP")]
+        to: Option<String>,
+        /// Print the snapshots, newest first, and stop
+        #[arg(long, conflicts_with = "to")]
+        list: bool,
+    },
+    /// Run a command with the resolved values in its environment (no file is written)
+    Run {
+        #[arg(long, default_value = "envit.toml")]
+        config: PathBuf,
+        /// Take the values from the env file (decrypted) instead of the providers
+        #[arg(long)]
+        from_env_file: bool,
+        /// Linux: pass the values in a sealed memfd (ENVIT_ENV_FD, ENVIT_ENV_FILE), not the environment
+        #[arg(long)]
+        memfd: bool,
+        /// The command and its arguments, after `--`
+        #[arg(last = true, required = true)]
+        command: Vec<String>,
+    },
+    /// Write env file values back to the provider, after showing the plan
+    Push {
+        #[arg(long, default_value = "envit.toml")]
+        config: PathBuf,
+        /// Env keys to push (default: every key in the env file)
+        keys: Vec<String>,
+        /// Show what would be written and stop
+        #[arg(long)]
+        dry_run: bool,
+        /// Write without asking for confirmation
+        #[arg(long, short, conflicts_with = "dry_run")]
+        yes: bool,
+        /// Allow changing keys `[owners]` assigns to another team
+        #[arg(long)]
+        override_owner: bool,
+        /// Secrets read and written at once
+        #[arg(long, default_value_t = commands::push::DEFAULT_CONCURRENCY)]
+        concurrency: usize,
+    },
+    /// Print the env file decrypted (output.encrypt or output.codec)
+    Decrypt {
+        #[arg(long, default_value = "envit.toml")]
+        config: PathBuf,
+        /// age identity file (default: ENVIT_AGE_IDENTITY, then output.codec.identity_file)
+        #[arg(long)]
+        identity: Option<PathBuf>,
+        /// Write to this file (private to you) instead of stdout
+        #[arg(long, short)]
+        output: Option<PathBuf>,
+    },
+    /// Encrypt a plaintext env file under output.encrypt
+    Encrypt {
+        #[arg(long, default_value = "envit.toml")]
+        config: PathBuf,
+        /// Plaintext env file to encrypt into output.env_file (default: output.env_file itself)
+        input: Option<PathBuf>,
+    },
+    /// Create secrets from an existing .env or JSON file, reporting values that conflict
+    Import {
+        #[arg(long, default_value = "envit.toml")]
+        config: PathBuf,
+        /// The .env or JSON file to import
+        file: PathBuf,
+        /// Input format (default: json for *.json files, dotenv otherwise)
+        #[arg(long, value_enum)]
+        format: Option<commands::import::Format>,
+        /// Show what would be written and stop
+        #[arg(long)]
+        dry_run: bool,
+        /// Write without asking for confirmation
+        #[arg(long, short, conflicts_with = "dry_run")]
+        yes: bool,
+        /// Replace secrets that hold a different value instead of reporting them
+        #[arg(long)]
+        overwrite: bool,
+        /// Allow changing keys `[owners]` assigns to another team
+        #[arg(long)]
+        override_owner: bool,
+        /// Secrets read and written at once
+        #[arg(long, default_value_t = commands::push::DEFAULT_CONCURRENCY)]
+        concurrency: usize,
+    },
+    /// Copy secrets from one profile's providers to another's, e.g. to seed an environment
+    Copy {
+        /// Profile to read: `staging` is envit.staging.toml, a path names a config
+        #[arg(long, value_hint = ValueHint::FilePath)]
+        from: String,
+        /// Profile whose provider is written
+        #[arg(long, value_hint = ValueHint::FilePath)]
+        to: String,
+        /// Env keys to copy, `*` matching any characters (default: all)
+        keys: Vec<String>,
+        /// Leave out env keys matching this pattern; repeatable
+        #[arg(long = "exclude", value_name = "PATTERN")]
+        excludes: Vec<String>,
+        /// Show what would be written and stop
+        #[arg(long)]
+        dry_run: bool,
+        /// Write without asking for confirmation
+        #[arg(long, short, conflicts_with = "dry_run")]
+        yes: bool,
+        /// Replace secrets that hold a different value instead of reporting them
+        #[arg(long)]
+        overwrite: bool,
+        /// Allow changing keys `[owners]` assigns to another team
+        #[arg(long)]
+        override_owner: bool,
+        /// Secrets read and written at once
+        #[arg(long, default_value_t = commands::push::DEFAULT_CONCURRENCY)]
+        concurrency: usize,
+    },
+    /// Print the resolved KEY=VALUE set to stdout without writing a file
+    /// (`eval "$(envit env --export)"`)
+    Env {
+        #[arg(long, default_value = "envit.toml")]
+        config: PathBuf,
+        /// Prefix each line with `export` and quote the values for the shell
+        #[arg(long)]
+        export: bool,
+    },
+    /// Print resolved values (plaintext): dotenv, JSON, YAML, shell exports, a Kubernetes Secret, …
+    Export {
+        #[arg(long, default_value = "envit.toml")]
+        config: PathBuf,
+        #[arg(long, value_enum, default_value = "dotenv")]
+        format: commands::export::Format,
+        /// Dotted path to nest the keys under (YAML formats), e.g. "secrets"
+        #[arg(long)]
+        under: Option<String>,
+        /// Encrypt the ansible output with `ansible-vault encrypt`
+        #[arg(long)]
+        ansible_vault: bool,
+        /// Secret name (k8s-secret) or ExternalSecret and target Secret name
+        /// (external-secret); default "envit"
+        #[arg(long)]
+        name: Option<String>,
+        /// SecretStore the ExternalSecret reads from; repeat once per provider, in config order
+        #[arg(long = "secret-store", value_name = "STORE")]
+        secret_stores: Vec<String>,
+        /// Compose service to give every secret (compose-secrets format); repeatable
+        #[arg(long = "service", value_name = "SERVICE")]
+        services: Vec<String>,
+        /// Write to this file (private to you) instead of stdout
+        #[arg(long, short)]
+        output: Option<PathBuf>,
+        /// Export even when the config is protected; requires --reason
+        #[arg(long, requires = "reason")]
+        break_glass: bool,
+        /// Justification recorded in the audit log and webhook
+        #[arg(long)]
+        reason: Option<String>,
+    },
+    /// Rotate secrets in the provider: one env key (also updating the env file) or a campaign
+    Rotate {
+        #[arg(long, default_value = "envit.toml")]
+        config: PathBuf,
+        /// Env key to rotate with its [rotate] policy, e.g. SESSION_SECRET
+        #[arg(required_unless_present = "campaign", conflicts_with = "campaign")]
+        key: Option<String>,
+        /// Campaign file listing keys, generators and verification hooks
+        #[arg(long)]
+        campaign: Option<PathBuf>,
+        /// Allow rotating keys `[owners]` assigns to another team
+        #[arg(long)]
+        override_owner: bool,
+    },
+    /// Create envit.toml by answering a few questions, optionally seeding [map] from the provider
+    Init {
+        #[arg(long, default_value = "envit.toml")]
+        config: PathBuf,
+        /// Overwrite an existing config
+        #[arg(long)]
+        force: bool,
+        /// Write a complete setup from a preset instead of asking for each setting
+        #[arg(long, value_enum)]
+        template: Option<commands::init::Template>,
+    },
+    /// Check envit.toml (schema, [map] collisions, env key names) without contacting the provider
+    Validate {
+        #[arg(long, default_value = "envit.toml")]
+        config: PathBuf,
+    },
+    /// Check a process's environment has every required key in a valid format (pre-start gate)
+    Assert {
+        #[arg(long, default_value = "envit.toml")]
+        config: PathBuf,
+        /// Pid whose environment to check, or `self` for envit's own (the calling shell's)
+        #[arg(long, value_name = "PID", default_value = "self")]
+        env_from_process: String,
+    },
+    /// Check network, credentials, permissions and the env file, with fixes for failures
+    Doctor {
+        #[arg(long, default_value = "envit.toml")]
+        config: PathBuf,
+    },
+    /// Explain why the last pull failed and how to fix it
+    WhyFailed {
+        #[arg(long, default_value = "envit.toml")]
+        config: PathBuf,
+    },
+    /// Inspect envit.toml
+    Config {
+        #[command(subcommand)]
+        command: ConfigCommand,
+    },
+    /// Upgrade an older config to the current `version` in place, printing a diff
+    Migrate {
+        #[arg(long, default_value = "envit.toml")]
+        config: PathBuf,
+        /// Print the diff without writing the file
+        #[arg(long)]
+        dry_run: bool,
+    },
+    /// List provider secrets with their env keys and whether a pull would add or update them
+    List {
+        #[arg(long, default_value = "envit.toml")]
+        config: PathBuf,
+        /// Align the columns under a header instead of tab-separated lines
+        #[arg(long)]
+        table: bool,
+    },
+    /// Report each secret's metadata (created, updated, enabled, expiry, last rotation) for reviews
+    Audit {
+        #[arg(long, default_value = "envit.toml")]
+        config: PathBuf,
+        #[arg(long, value_enum, default_value = "table")]
+        format: commands::audit::Format,
+    },
+    /// Write .env.example with every managed key, blank, under a comment naming its secret
+    Example {
+        #[arg(long, default_value = "envit.toml")]
+        config: PathBuf,
+        /// Where to write (default: .env.example next to the config)
+        #[arg(long)]
+        output: Option<PathBuf>,
+        /// Fill values with `<class>` placeholders instead of leaving them blank
+        #[arg(long)]
+        placeholders: bool,
+        /// Write nothing; exit with code 2 if the file is out of date
+        #[arg(long)]
+        check: bool,
+    },
+    /// List secrets that expire within a window (exit code 2 if any), for scheduled CI alerts
+    Expiry {
+        #[arg(long, default_value = "envit.toml")]
+        config: PathBuf,
+        /// How far ahead to look
+        #[arg(long, default_value = "30d", value_parser = humantime::parse_duration)]
+        within: Duration,
+    },
+    /// Explain which env key a secret pulls to, or which secret feeds a key
+    Which {
+        #[arg(long, default_value = "envit.toml")]
+        config: PathBuf,
+        /// Secret name as the provider lists it
+        #[arg(required_unless_present = "key")]
+        secret: Option<String>,
+        /// Env key to trace back instead
+        #[arg(long, conflicts_with = "secret")]
+        key: Option<String>,
+    },
+    /// Summarize managed, local-only and not yet pulled keys, and the last sync
+    Status {
+        #[arg(long, default_value = "envit.toml")]
+        config: PathBuf,
+    },
+    /// List every key with its owning team from `[owners]`
+    Owners {
+        #[arg(long, default_value = "envit.toml")]
+        config: PathBuf,
+    },
+    /// Keep the env file in sync: poll periodically and react to file changes
+    Watch {
+        #[arg(long, default_value = "envit.toml")]
+        config: PathBuf,
+        /// Polling interval, e.g. "30s", "5m"
+        #[arg(long, default_value = "5m", value_parser = humantime::parse_duration)]
+        interval: Duration,
+        /// Keep last-known values while a provider is temporarily unreachable
+        #[arg(long)]
+        degraded_ok: bool,
+        /// Shell command to run after each pull that changed keys (see pull --on-change)
+        #[arg(long, value_name = "COMMAND")]
+        on_change: Option<String>,
+    },
+    /// Container entrypoint: wait for workload identity, pull, optionally keep syncing
+    Container {
+        #[arg(long, default_value = "envit.toml")]
+        config: PathBuf,
+        /// How long to wait for the provider's credentials before giving up
+        #[arg(long, default_value = "2m", value_parser = humantime::parse_duration)]
+        identity_timeout: Duration,
+        /// Keep running as a sidecar, pulling at this interval
+        #[arg(long, value_parser = humantime::parse_duration)]
+        watch: Option<Duration>,
+    },
+    /// Scaffold a Dev Container Feature and devcontainer.json that pull on container creation
+    Devcontainer {
+        #[arg(long, default_value = "envit.toml")]
+        config: PathBuf,
+        /// The .devcontainer directory; the feature goes into its envit/ subdirectory
+        #[arg(long, default_value = ".devcontainer")]
+        dir: PathBuf,
+        /// Copy this binary into the feature (Linux builds; the container must match its architecture)
+        #[arg(long)]
+        bundle_binary: bool,
+        /// Overwrite an existing feature
+        #[arg(long)]
+        force: bool,
+    },
+    /// Kubernetes init container: one pull, one JSON status line, sysexits-style exit codes
+    K8sInit {
+        #[arg(long, default_value = "/etc/envit/envit.toml")]
+        config: PathBuf,
+        /// Env file to write (usually on an emptyDir); overrides output.env_file
+        #[arg(long)]
+        env_file: Option<PathBuf>,
+        /// Octal permission bits for the env file
+        #[arg(long, default_value = "0600", value_parser = commands::k8s_init::parse_mode)]
+        mode: u32,
+    },
+    /// Print `export` statements for direnv (see --stdlib for `use envit`)
+    DirenvExport {
+        #[arg(long, default_value = "envit.toml")]
+        config: PathBuf,
+        /// Print the `use_envit` direnv stdlib function instead
+        #[arg(long)]
+        stdlib: bool,
+    },
+    /// Print a shell hook that pulls (or exports) on entering a directory
+    /// with an allowed envit.toml (`eval "$(envit hook bash)"`)
+    Hook {
+        shell: commands::hook::Shell,
+        #[arg(long, value_enum, default_value = "pull")]
+        action: commands::hook::Action,
+    },
+    /// Trust envit.toml as it is now, so `envit hook` acts on it
+    Allow {
+        #[arg(long, default_value = "envit.toml")]
+        config: PathBuf,
+        /// Withdraw the trust instead
+        #[arg(long, conflicts_with = "check")]
+        revoke: bool,
+        /// Fail unless the config is allowed as it is now (used by the hook)
+        #[arg(long)]
+        check: bool,
+    },
+    /// Session agent that keeps provider logins and fetched values for `pull`
+    /// (`eval "$(envit agent)"`)
+    Agent {
+        #[arg(long)]
+        socket: Option<PathBuf>,
+        /// Exit after this long without requests
+        #[arg(long, default_value = "1h", value_parser = humantime::parse_duration)]
+        idle_timeout: Duration,
+        /// Drop provider connections and fetched values after this long
+        #[arg(long, default_value = "5m", value_parser = humantime::parse_duration)]
+        cache_ttl: Duration,
+        /// Run in the foreground instead of starting in the background
+        #[arg(long)]
+        foreground: bool,
+        /// Stop the running agent
+        #[arg(long, conflicts_with = "foreground")]
+        stop: bool,
+    },
+    /// Signed JSON manifest of keys, providers, versions and value hashes (no values)
+    Manifest {
+        #[arg(long, default_value = "envit.toml")]
+        config: PathBuf,
+        /// Write the manifest to a file instead of stdout
+        #[arg(long, short)]
+        output: Option<PathBuf>,
+        /// Check the signature of an existing manifest instead
+        #[arg(long, conflicts_with = "output")]
+        verify: Option<PathBuf>,
+    },
+    /// Refresh the env file periodically via a systemd user timer or launchd agent
+    Schedule {
+        #[command(subcommand)]
+        command: ScheduleCommand,
+    },
+    /// Terraform `external` data source: JSON query on stdin, values as JSON on stdout
+    TfExternal,
+    /// JSON Schema of envit.toml for editor validation and completion
+    Schema,
+    /// Print the catalog of status messages, to translate via ENVIT_MESSAGES
+    Messages,
+    /// Print a shell completion script (`envit completions zsh > ~/.zfunc/_envit`)
+    Completions { shell: clap_complete::Shell },
+    /// Manage secrets in the local encrypted vault (`local_vault` provider)
+    Vault {
+        #[command(subcommand)]
+        command: VaultCommand,
+    },
+}
+
+#[derive(Debug, Subcommand)]
+enum ConfigCommand {
+    /// Flag risky setups (env file outside the repo or not ignored, plain HTTP
+    /// webhooks, ...); exits 1 when anything is flagged
+    Lint {
+        #[arg(long, default_value = "envit.toml")]
+        config: PathBuf,
+    },
+}
+
+#[derive(Debug, Subcommand)]
+enum ScheduleCommand {
+    /// Run `envit pull --quiet` for this config every interval
+    Install {
+        #[arg(long, default_value = "envit.toml")]
+        config: PathBuf,
+        #[arg(long, default_value = "6h", value_parser = humantime::parse_duration)]
+        every: Duration,
+        /// Print the files and commands instead of installing them
+        #[arg(long)]
+        dry_run: bool,
+    },
+    /// Stop and remove the schedule for this config
+    Uninstall {
+        #[arg(long, default_value = "envit.toml")]
+        config: PathBuf,
+    },
+}
+
+#[derive(Debug, Subcommand)]
+enum VaultCommand {
+    /// Print a secret's value
+    Get {
+        #[arg(long, default_value = "envit.toml")]
+        config: PathBuf,
+        name: String,
+    },
+    /// Add or replace a secret; the value is read from stdin unless --value is given
+    Set {
+        #[arg(long, default_value = "envit.toml")]
+        config: PathBuf,
+        name: String,
+        #[arg(long)]
+        value: Option<String>,
     },
 }
 
@@ -37,131 +664,421 @@ async fn main() {
 }
 
 async fn run() -> Result<()> {
-    let cli = Cli::parse();
-
-    match cli.command {
-        Commands::Pull { config, dry_run } => run_pull(&config, dry_run).await,
+    // Exit code 2 means drift (`diff`, `check`); clap uses it for usage
+    // errors too, so those exit with 1 like any other error.
+    let cli = Cli::try_parse().unwrap_or_else(|err| {
+        let _ = err.print();
+        std::process::exit(if err.use_stderr() { 1 } else { 0 })
+    });
+    if cli.verbose {
+        verbose::enable();
     }
-}
-
-async fn run_pull(config_path: &Path, dry_run: bool) -> Result<()> {
-    let cfg = config::load(config_path)?;
-    let env_path = resolve_env_path(config_path, &cfg);
-
-    let provider = build_provider(&cfg.provider)?;
-    let listed = provider
-        .list_secrets()
-        .await
-        .map_err(|e| anyhow::anyhow!("failed to list secrets: {e}"))?;
-
-    let reverse_map = build_reverse_map(&cfg.map)?;
-    let mut target_secret_to_env = Vec::with_capacity(listed.len());
-    for meta in listed {
-        let env_key = reverse_map
-            .get(&meta.name)
-            .cloned()
-            .unwrap_or_else(|| to_env_key(&meta.name));
-        target_secret_to_env.push((meta.name, env_key));
-    }
-
-    validate_no_duplicate_env_keys(&target_secret_to_env)?;
-
-    let mut updates = HashMap::new();
-    for (secret_name, env_key) in target_secret_to_env {
-        let value = provider
-            .get_secret(&secret_name)
-            .await
-            .map_err(|e| anyhow::anyhow!("failed to fetch secret {secret_name}: {e}"))?;
-
-        if let Some(value) = value {
-            updates.insert(env_key, value);
-        }
-    }
-
-    let existing = envfile::load_for_merge(&env_path, cfg.output.create_if_missing)?;
-    let (merged_content, changes) = envfile::merge(existing, &updates);
-
-    if dry_run {
-        print_dry_run(&changes);
+    if cli.features {
+        print_features();
         return Ok(());
     }
-
-    if changes.is_empty() && env_path.exists() {
-        println!("No changes.");
+    let Some(command) = cli.command else {
+        Cli::command().print_help()?;
         return Ok(());
-    }
-
-    envfile::write_atomic(&env_path, &merged_content)
-        .with_context(|| format!("failed to write {}", env_path.display()))?;
-
-    println!("Updated {} keys in {}", changes.len(), env_path.display());
-    Ok(())
-}
+    };
 
-fn resolve_env_path(config_path: &Path, cfg: &Config) -> PathBuf {
-    let env_path = PathBuf::from(&cfg.output.env_file);
-    if env_path.is_absolute() {
-        return env_path;
-    }
-
-    if let Some(parent) = config_path.parent() {
-        return parent.join(env_path);
-    }
-
-    env_path
-}
-
-fn build_reverse_map(map: &HashMap<String, String>) -> Result<HashMap<String, String>> {
-    let mut reverse = HashMap::with_capacity(map.len());
-    for (env_key, secret_name) in map {
-        if let Some(existing) = reverse.insert(secret_name.clone(), env_key.clone()) {
-            bail!(
-                "duplicate manual mapping for secret {secret_name}: {existing} and {env_key}"
-            );
+    match command {
+        Commands::Pull {
+            config,
+            dry_run,
+            quiet,
+            degraded_ok,
+            on_change,
+            frozen,
+            summary,
+            envs,
+            out_dir,
+        } => {
+            let mode = if frozen {
+                lock::Mode::Frozen
+            } else {
+                lock::Mode::Use
+            };
+            if let Some(out_dir) = out_dir {
+                if commands::pull::run_profiles(&envs, &out_dir, dry_run, quiet, degraded_ok, mode)
+                    .await?
+                {
+                    std::process::exit(exit::TRANSIENT)
+                }
+                return Ok(());
+            }
+            if commands::pull::run(
+                &config,
+                dry_run,
+                quiet,
+                degraded_ok,
+                on_change.as_deref(),
+                mode,
+                summary,
+            )
+            .await?
+            {
+                std::process::exit(exit::TRANSIENT)
+            }
+            Ok(())
         }
-    }
-    Ok(reverse)
-}
-
-fn validate_no_duplicate_env_keys(pairs: &[(String, String)]) -> Result<()> {
-    let mut seen: HashMap<&str, &str> = HashMap::new();
-    for (secret, key) in pairs {
-        if let Some(existing_secret) = seen.insert(key, secret) {
-            bail!(
-                "duplicate env key mapping detected: {key} mapped from both {existing_secret} and {secret}"
-            );
+        Commands::Update {
+            config,
+            keys,
+            summary,
+        } => commands::pull::run(
+            &config,
+            false,
+            false,
+            false,
+            None,
+            lock::Mode::Update(&keys),
+            summary,
+        )
+        .await
+        .map(drop),
+        Commands::Check { config } => {
+            if commands::diff::run(&config, false, None).await? {
+                std::process::exit(exit::DRIFT)
+            }
+            Ok(())
         }
-    }
-    Ok(())
-}
-
-fn to_env_key(secret_name: &str) -> String {
-    secret_name.replace('-', "_").to_ascii_uppercase()
-}
-
-fn print_dry_run(changes: &[envfile::Change]) {
-    if changes.is_empty() {
-        println!("No changes.");
-        return;
-    }
-
-    for change in changes {
-        let label = match change.kind {
-            ChangeKind::Add => "ADD",
-            ChangeKind::Update => "UPDATE",
-        };
-        println!("{label} {}=********", change.key);
+        Commands::Diff {
+            config,
+            values,
+            against_git,
+            path,
+            identity,
+        } => {
+            let against = against_git
+                .as_deref()
+                .map(|rev| commands::diff::AgainstGit {
+                    rev,
+                    path: path.as_deref(),
+                    identity: identity.as_deref(),
+                });
+            if commands::diff::run(&config, values, against).await? {
+                std::process::exit(exit::DRIFT)
+            }
+            Ok(())
+        }
+        Commands::Compare { profiles, values } => {
+            if commands::compare::run(&profiles, values).await? {
+                std::process::exit(exit::DRIFT)
+            }
+            Ok(())
+        }
+        Commands::Get { config, key } => commands::get::run(&config, &key).await,
+        Commands::History { config, key } => commands::history::run(&config, &key).await,
+        Commands::Set {
+            config,
+            key,
+            value,
+            secret,
+            save_map,
+            override_owner,
+        } => {
+            commands::set::run(
+                &config,
+                &key,
+                value,
+                secret.as_deref(),
+                save_map,
+                override_owner,
+            )
+            .await
+        }
+        Commands::Run {
+            config,
+            from_env_file,
+            memfd,
+            command,
+        } => {
+            let code = commands::run::run(&config, &command, from_env_file, memfd).await?;
+            std::process::exit(code)
+        }
+        Commands::Delete {
+            config,
+            key,
+            strip_env,
+            yes,
+            override_owner,
+        } => commands::delete::run(&config, &key, strip_env, yes, override_owner).await,
+        Commands::Rename {
+            config,
+            old_key,
+            new_key,
+            secret,
+            dry_run,
+            yes,
+            override_owner,
+        } => {
+            commands::rename::run(
+                &config,
+                &old_key,
+                &new_key,
+                secret.as_deref(),
+                dry_run,
+                yes,
+                override_owner,
+            )
+            .await
+        }
+        Commands::Prune { config, dry_run } => commands::prune::run(&config, dry_run).await,
+        Commands::Clean { config, dry_run } => commands::clean::run(&config, dry_run),
+        Commands::Rollback { config, to, list } => {
+            commands::rollback::run(&config, to.as_deref(), list)
+        }
+        Commands::Push {
+            config,
+            keys,
+            dry_run,
+            yes,
+            override_owner,
+            concurrency,
+        } => commands::push::run(&config, &keys, dry_run, yes, override_owner, concurrency).await,
+        Commands::Decrypt {
+            config,
+            identity,
+            output,
+        } => commands::decrypt::run(&config, identity.as_deref(), output.as_deref()),
+        Commands::Encrypt { config, input } => commands::encrypt::run(&config, input.as_deref()),
+        Commands::Import {
+            config,
+            file,
+            format,
+            dry_run,
+            yes,
+            overwrite,
+            override_owner,
+            concurrency,
+        } => {
+            let options = commands::import::Options {
+                dry_run,
+                yes,
+                overwrite,
+                override_owner,
+                concurrency,
+            };
+            commands::import::run(&config, &file, format, options).await
+        }
+        Commands::Copy {
+            from,
+            to,
+            keys,
+            excludes,
+            dry_run,
+            yes,
+            overwrite,
+            override_owner,
+            concurrency,
+        } => {
+            let options = commands::import::Options {
+                dry_run,
+                yes,
+                overwrite,
+                override_owner,
+                concurrency,
+            };
+            commands::copy::run(&from, &to, &keys, &excludes, options).await
+        }
+        Commands::Env { config, export } => commands::env::run(&config, export).await,
+        Commands::Export {
+            config,
+            format,
+            under,
+            ansible_vault,
+            name,
+            secret_stores,
+            services,
+            output,
+            break_glass,
+            reason,
+        } => {
+            let output = commands::export::Output {
+                format,
+                under: under.as_deref(),
+                ansible_vault,
+                name: name.as_deref(),
+                secret_stores: &secret_stores,
+                services: &services,
+                path: output.as_deref(),
+            };
+            commands::export::run(&config, output, break_glass, reason.as_deref()).await
+        }
+        Commands::Rotate {
+            config,
+            key,
+            campaign,
+            override_owner,
+        } => match (key, campaign) {
+            (_, Some(campaign)) => {
+                commands::rotate::run_campaign(&config, &campaign, override_owner).await
+            }
+            (Some(key), None) => commands::rotate::run_key(&config, &key, override_owner).await,
+            (None, None) => unreachable!("clap requires a key or --campaign"),
+        },
+        Commands::Init {
+            config,
+            force,
+            template,
+        } => commands::init::run(&config, force, template).await,
+        Commands::Validate { config } => commands::validate::run(&config),
+        Commands::Assert {
+            config,
+            env_from_process,
+        } => commands::assert::run(&config, &env_from_process),
+        Commands::Doctor { config } => {
+            if !commands::doctor::run(&config).await? {
+                std::process::exit(1)
+            }
+            Ok(())
+        }
+        Commands::WhyFailed { config } => commands::why_failed::run(&config),
+        Commands::Config {
+            command: ConfigCommand::Lint { config },
+        } => {
+            if commands::config::lint(&config)? {
+                std::process::exit(1)
+            }
+            Ok(())
+        }
+        Commands::Migrate { config, dry_run } => commands::migrate::run(&config, dry_run),
+        Commands::List { config, table } => commands::list::run(&config, table).await,
+        Commands::Audit { config, format } => commands::audit::run(&config, format).await,
+        Commands::Example {
+            config,
+            output,
+            placeholders,
+            check,
+        } => {
+            let stale =
+                commands::example::run(&config, output.as_deref(), placeholders, check).await?;
+            if check && stale {
+                std::process::exit(exit::DRIFT)
+            }
+            Ok(())
+        }
+        Commands::Expiry { config, within } => {
+            if commands::expiry::run(&config, within).await? {
+                std::process::exit(exit::DRIFT)
+            }
+            Ok(())
+        }
+        Commands::Which {
+            config,
+            secret,
+            key,
+        } => {
+            let query = match (&secret, &key) {
+                (_, Some(key)) => commands::which::Query::Key(key),
+                (Some(secret), None) => commands::which::Query::Secret(secret),
+                (None, None) => unreachable!("clap requires a secret or --key"),
+            };
+            if !commands::which::run(&config, query).await? {
+                std::process::exit(1)
+            }
+            Ok(())
+        }
+        Commands::Status { config } => commands::status::run(&config).await,
+        Commands::Owners { config } => commands::owners::run(&config).await,
+        Commands::Watch {
+            config,
+            interval,
+            degraded_ok,
+            on_change,
+        } => commands::watch::run(&config, interval, degraded_ok, on_change.as_deref()).await,
+        Commands::Container {
+            config,
+            identity_timeout,
+            watch,
+        } => commands::container::run(&config, identity_timeout, watch).await,
+        Commands::Devcontainer {
+            config,
+            dir,
+            bundle_binary,
+            force,
+        } => commands::devcontainer::run(&config, &dir, bundle_binary, force),
+        Commands::K8sInit {
+            config,
+            env_file,
+            mode,
+        } => {
+            let code = commands::k8s_init::run(&config, env_file.as_deref(), mode).await;
+            std::process::exit(code)
+        }
+        Commands::DirenvExport { stdlib: true, .. } => {
+            print!("{}", commands::direnv::STDLIB);
+            Ok(())
+        }
+        Commands::DirenvExport { config, .. } => commands::direnv::run(&config).await,
+        Commands::Hook { shell, action } => {
+            print!("{}", commands::hook::script(shell, action));
+            Ok(())
+        }
+        Commands::Allow {
+            config,
+            check: true,
+            ..
+        } => commands::allow::check(&config),
+        Commands::Allow { config, revoke, .. } => commands::allow::run(&config, revoke),
+        Commands::Agent {
+            socket,
+            idle_timeout,
+            cache_ttl,
+            foreground,
+            stop,
+        } => {
+            let socket = socket
+                .or_else(commands::agent::socket_from_env)
+                .unwrap_or_else(commands::agent::default_socket);
+            if stop {
+                commands::agent::stop(&socket).await
+            } else if foreground {
+                commands::agent::run(&socket, idle_timeout, cache_ttl).await
+            } else {
+                commands::agent::start(&socket, idle_timeout, cache_ttl).await
+            }
+        }
+        Commands::Manifest {
+            verify: Some(path), ..
+        } => commands::manifest::verify(&path),
+        Commands::Manifest { config, output, .. } => {
+            commands::manifest::run(&config, output.as_deref()).await
+        }
+        Commands::Schedule { command } => match command {
+            ScheduleCommand::Install {
+                config,
+                every,
+                dry_run,
+            } => commands::schedule::install(&config, every, dry_run),
+            ScheduleCommand::Uninstall { config } => commands::schedule::uninstall(&config),
+        },
+        Commands::TfExternal => commands::tf_external::run().await,
+        Commands::Schema => commands::schema::run(),
+        Commands::Messages => {
+            messages::print_catalog();
+            Ok(())
+        }
+        Commands::Completions { shell } => {
+            clap_complete::generate(shell, &mut Cli::command(), "envit", &mut io::stdout());
+            Ok(())
+        }
+        Commands::Vault { command } => match command {
+            VaultCommand::Get { config, name } => commands::vault::get(&config, &name).await,
+            VaultCommand::Set {
+                config,
+                name,
+                value,
+            } => commands::vault::set(&config, &name, value).await,
+        },
     }
 }
 
-#[cfg(test)]
-mod tests {
-    use super::*;
-
-    #[test]
-    fn secret_name_to_env_key_rule() {
-        assert_eq!(to_env_key("database-url"), "DATABASE_URL");
-        assert_eq!(to_env_key("azure-client-id"), "AZURE_CLIENT_ID");
-        assert_eq!(to_env_key("redis"), "REDIS");
+fn print_features() {
+    println!("envit {}", env!("CARGO_PKG_VERSION"));
+    for kind in provider::KINDS {
+        let state = if kind.compiled { "enabled" } else { "disabled" };
+        println!("{:<16} {:<11} {state}", kind.name, kind.feature);
     }
 }
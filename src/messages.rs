@@ -0,0 +1,408 @@
+//! Status lines commands print, as templates with `{placeholders}`, so
+//! wrappers can ship translations without patching envit.
+//!
+//! `ENVIT_MESSAGES` names a TOML file of `id = "template"` overrides, or a
+//! directory of `<locale>.toml` files picked by `ENVIT_LOCALE` (else
+//! `LC_ALL`, `LC_MESSAGES`, `LANG`; `de_DE.UTF-8` tries `de_DE`, then
+//! `de`). `envit messages` prints the catalog to start from. Errors,
+//! warnings, plan and progress lines (`ADD`/`UPDATE`, `ROTATED`), tables
+//! and machine-readable output stay as they are, since scripts match on
+//! them. So do the texts filled into the templates: doctor's check
+//! details and fixes and why-failed's cause and steps are English.
+
+use std::{
+    collections::HashMap,
+    env,
+    fmt::Display,
+    fs,
+    path::{Path, PathBuf},
+    sync::OnceLock,
+};
+
+use anyhow::{Context, Result, bail};
+
+pub const MESSAGES_ENV: &str = "ENVIT_MESSAGES";
+pub const LOCALE_ENV: &str = "ENVIT_LOCALE";
+
+/// Message id -> English template.
+pub const CATALOG: &[(&str, &str)] = &[
+    ("pull_authenticated", "Authenticated as {principal}"),
+    (
+        "pull_deprecated",
+        "Deprecated {key} ({reason}); kept until {until}",
+    ),
+    ("pull_deprecated_renamed", "renamed to {key}"),
+    ("pull_deprecated_gone", "no longer provided"),
+    ("pull_removed_deprecated", "Removed deprecated {key}"),
+    (
+        "pull_updated",
+        "Updated {count} keys in {path} ({bytes} bytes changed)",
+    ),
+    ("pull_unchanged", "No changes (0 bytes changed)."),
+    ("pull_locked", "Locked {keys} in {path}"),
+    ("pull_profile", "{profile}: {changes} in {path}"),
+    (
+        "pull_degraded",
+        "DEGRADED: {unreachable} of {total} providers unreachable; their keys were not refreshed.",
+    ),
+    (
+        "pull_degraded_warning",
+        "warning: DEGRADED: {source}; keeping last-known values",
+    ),
+    ("dry_run_unchanged", "No changes."),
+    (
+        "plan_total",
+        "Total: {changes} in {files} files from {providers} providers",
+    ),
+    (
+        "push_resumed",
+        "Skipping {count} secrets an earlier push already wrote",
+    ),
+    (
+        "push_nothing",
+        "Nothing to push: the provider already has these values.",
+    ),
+    ("push_confirm", "Push {count} secrets to {kind}?"),
+    ("push_done", "Pushed {count} secrets"),
+    (
+        "push_throttled",
+        "warning: throttled ({message}); retrying in {delay}",
+    ),
+    (
+        "import_resumed",
+        "Skipping {count} secrets an earlier run already wrote",
+    ),
+    (
+        "import_unchanged",
+        "{count} secrets already hold these values",
+    ),
+    (
+        "import_conflicts",
+        "{count} conflicts left as they are (--overwrite replaces them)",
+    ),
+    ("import_nothing", "Nothing to import."),
+    ("import_confirm", "Import {count} secrets into {kind}?"),
+    ("import_done", "Imported {count} secrets"),
+    (
+        "copy_nothing",
+        "Nothing to copy: {to} already has these values.",
+    ),
+    ("copy_confirm", "Copy {count} secrets from {from} to {to}?"),
+    ("copy_done", "Copied {count} secrets to {to}"),
+    ("wrote", "Wrote {path}"),
+    ("agent_stopped", "Stopped envit agent on {socket}"),
+    ("allow_done", "Allowed {path}"),
+    ("allow_revoked", "Revoked {path}"),
+    (
+        "assert_ok",
+        "All {count} required keys are set in process {process}.",
+    ),
+    ("clean_would", "Would remove {key}"),
+    ("clean_done", "Removed {key}"),
+    ("clean_nothing", "Nothing to clean."),
+    ("compare_only_in", "Only in {profile} ({count}):"),
+    ("compare_same", "Same value ({count}):"),
+    ("compare_different", "Different values ({count}):"),
+    ("compare_in_both", "{count} keys in both."),
+    (
+        "compare_same_keys",
+        "{left} and {right} have the same keys.",
+    ),
+    ("delete_done", "Deleted {secret} ({key})"),
+    ("delete_stripped", "Removed {key} from {path}"),
+    ("devcontainer_feature", "Wrote the envit feature to {path}"),
+    (
+        "devcontainer_merge",
+        "{path} exists; merge these settings into it:\n{settings}",
+    ),
+    ("diff_none", "No drift."),
+    ("diff_none_since", "No changes since {rev}."),
+    ("doctor_provider", "provider {index} ({kind}):"),
+    ("doctor_env_file", "env file:"),
+    ("doctor_ok", "  ok    {check}"),
+    ("doctor_fail", "  FAIL  {check}"),
+    ("doctor_fix", "        fix: {fix}"),
+    ("doctor_skip", "  skip  {check}"),
+    ("doctor_passed", "All checks passed."),
+    ("doctor_failed", "{count} check(s) failed."),
+    ("encrypt_done", "Encrypted {source} into {path}"),
+    (
+        "encrypt_plaintext_left",
+        "{source} still holds the plaintext; delete it once nothing reads it",
+    ),
+    ("example_written", "Wrote {count} keys to {path}"),
+    ("example_current", "{path} is up to date."),
+    (
+        "example_stale",
+        "{path} is out of date; run `envit example`.",
+    ),
+    ("expiry_expires", "{secret} expires {at} (in {days}d)"),
+    ("expiry_expired", "{secret} expired {at} ({days}d ago)"),
+    ("expiry_none", "No secrets expire within {window}."),
+    ("expiry_total", "{count} secrets expire within {window}."),
+    ("history_versions", "{secret}: {count} versions"),
+    ("history_absent", "{key} is not in {path}."),
+    (
+        "history_no_match",
+        "{key} in {path} matches no version of {secret}.",
+    ),
+    (
+        "init_done",
+        "Wrote {path} ({count} keys in [map]). Check it with `envit validate`, then run `envit pull`.",
+    ),
+    (
+        "init_template_done",
+        "Wrote {path}, {profile} (a protected profile) and {schema}. Add keys whose derived name does not fit to [map], check with `envit validate`, then run `envit pull`.",
+    ),
+    ("lint_clean", "No problems found in {path}."),
+    (
+        "manifest_written",
+        "Wrote manifest of {count} secrets to {path}",
+    ),
+    (
+        "manifest_valid",
+        "Manifest signature valid: {count} secrets from {config}, generated {at}",
+    ),
+    ("migrate_current", "{path} is already at version {version}."),
+    ("migrate_done", "Migrated {path} to version {version}"),
+    (
+        "owners_enforced",
+        "Ownership is enforced; acting as {team} ({var}).",
+    ),
+    (
+        "owners_enforced_unset",
+        "Ownership is enforced; {var} is not set.",
+    ),
+    (
+        "prune_no_record",
+        "No pulled keys recorded yet; nothing to prune.",
+    ),
+    ("prune_would", "Would prune {key}"),
+    ("prune_done", "Pruned {key}"),
+    ("prune_nothing", "Nothing to prune."),
+    (
+        "rename_done",
+        "Renamed {old_key} ({old_secret}) to {new_key} ({new_secret})",
+    ),
+    ("rollback_none", "No snapshots in {path}."),
+    ("rollback_done", "Restored {path} from the snapshot of {at}"),
+    ("rotate_campaign_done", "Campaign complete: {count} keys"),
+    (
+        "schedule_done",
+        "Scheduled `envit pull --quiet` every {every} ({unit})",
+    ),
+    ("schedule_removed", "Removed {path}"),
+    ("schedule_none", "No schedule installed for {config}"),
+    ("set_done", "Stored {key} in {secret}"),
+    ("set_mapped", "Added {key} = \"{secret}\" to [map]"),
+    ("status_synced", "{path}: last synced {at} ({age} ago)"),
+    ("status_never_synced", "{path}: never synced"),
+    ("status_managed", "Managed ({count}):"),
+    ("status_local_only", "Local only ({count}):"),
+    ("status_not_materialized", "Not materialized ({count}):"),
+    ("validate_ok", "{path} is valid."),
+    ("vault_stored", "Stored {name}"),
+    ("watch_change", "Change detected in {path}"),
+    (
+        "which_no_secret",
+        "No provider lists {secret} and no [map] entry names it. Check the spelling and that the credentials may list secrets (envit doctor).",
+    ),
+    (
+        "which_no_key",
+        "No secret pulls to {key}. Secret names become keys with dashes as underscores, uppercased; for any other name add `{key} = \"<secret>\"` to [map].",
+    ),
+    ("which_route", "{secret} (provider {index}, {kind}):"),
+    ("which_listed", "  listed:  yes"),
+    (
+        "which_map_only",
+        "  listed:  no, fetched through [map] only",
+    ),
+    ("which_rule_map", "  rule:    [map] {key} = \"{secret}\""),
+    (
+        "which_rule_derived",
+        "  rule:    derived (dashes to underscores, uppercased): {key}",
+    ),
+    ("which_prefix", "  prefix:  {prefix}"),
+    ("which_key", "  env key: {key}"),
+    ("which_output", "  output:  {output}"),
+    (
+        "which_overridden",
+        "  note:    overridden by {secret} from provider {index}",
+    ),
+    ("why_failed_config", "The config cannot be loaded: {error}"),
+    ("why_failed_none", "No failed run recorded."),
+    (
+        "why_failed_none_since",
+        "No failed run recorded; the last pull succeeded at {at}.",
+    ),
+    ("why_failed_last", "Last failure: envit {command} at {at}"),
+    ("why_failed_error", "  error: {error}"),
+    ("why_failed_cause", "  cause: {cause}"),
+    ("why_failed_steps", "What to do:"),
+    ("why_failed_step", "  {index}. {step}"),
+];
+
+/// `message!("push_done", count = 3)`: the catalog entry (or its
+/// override) with the placeholders filled in.
+macro_rules! message {
+    ($id:literal $(, $name:ident = $value:expr)* $(,)?) => {
+        $crate::messages::text(
+            $id,
+            &[$((stringify!($name), &$value as &dyn std::fmt::Display)),*],
+        )
+    };
+}
+pub(crate) use message;
+
+pub fn text(id: &str, args: &[(&str, &dyn Display)]) -> String {
+    let template = overrides()
+        .get(id)
+        .map(String::as_str)
+        .or_else(|| default(id))
+        .unwrap_or(id);
+    render(template, args)
+}
+
+fn default(id: &str) -> Option<&'static str> {
+    CATALOG
+        .iter()
+        .find(|(known, _)| *known == id)
+        .map(|(_, template)| *template)
+}
+
+fn render(template: &str, args: &[(&str, &dyn Display)]) -> String {
+    let mut out = template.to_string();
+    for (name, value) in args {
+        out = out.replace(&format!("{{{name}}}"), &value.to_string());
+    }
+    out
+}
+
+/// Prints the catalog (with any overrides applied) as a TOML file to
+/// translate.
+pub fn print_catalog() {
+    for (id, _) in CATALOG {
+        let template = overrides()
+            .get(*id)
+            .map(String::as_str)
+            .or_else(|| default(id))
+            .unwrap_or_default();
+        println!("{id} = {}", toml::Value::String(template.to_string()));
+    }
+}
+
+/// A broken override file should not stop a pull; it is reported and the
+/// English catalog is used.
+fn overrides() -> &'static HashMap<String, String> {
+    static OVERRIDES: OnceLock<HashMap<String, String>> = OnceLock::new();
+    OVERRIDES.get_or_init(|| match override_file().map(|path| load(&path)) {
+        Some(Ok(overrides)) => overrides,
+        Some(Err(err)) => {
+            eprintln!("warning: ignoring {MESSAGES_ENV}: {err:#}");
+            HashMap::new()
+        }
+        None => HashMap::new(),
+    })
+}
+
+fn override_file() -> Option<PathBuf> {
+    let path = PathBuf::from(env::var_os(MESSAGES_ENV)?);
+    if !path.is_dir() {
+        return Some(path);
+    }
+    locales()
+        .into_iter()
+        .map(|locale| path.join(format!("{locale}.toml")))
+        .find(|file| file.is_file())
+}
+
+/// Candidate locale names, most specific first.
+fn locales() -> Vec<String> {
+    let Some(locale) = [LOCALE_ENV, "LC_ALL", "LC_MESSAGES", "LANG"]
+        .iter()
+        .filter_map(|var| env::var(var).ok())
+        .find(|value| !value.is_empty())
+    else {
+        return Vec::new();
+    };
+    let locale = locale.split(['.', '@']).next().unwrap_or_default();
+    if locale.is_empty() || locale == "C" || locale == "POSIX" {
+        return Vec::new();
+    }
+    let mut candidates = vec![locale.to_string()];
+    if let Some((language, _)) = locale.split_once('_') {
+        candidates.push(language.to_string());
+    }
+    candidates
+}
+
+/// Reads an override file; unknown ids and placeholders the English
+/// template does not have are errors, so typos do not go unnoticed.
+fn load(path: &Path) -> Result<HashMap<String, String>> {
+    let raw =
+        fs::read_to_string(path).with_context(|| format!("failed to read {}", path.display()))?;
+    let overrides: HashMap<String, String> =
+        toml::from_str(&raw).with_context(|| format!("invalid {}", path.display()))?;
+    for (id, template) in &overrides {
+        let Some(english) = default(id) else {
+            bail!("{}: unknown message id {id}", path.display());
+        };
+        if let Some(name) = placeholders(template)
+            .into_iter()
+            .find(|name| !placeholders(english).contains(name))
+        {
+            bail!("{}: {id} has no placeholder {{{name}}}", path.display());
+        }
+    }
+    Ok(overrides)
+}
+
+fn placeholders(template: &str) -> Vec<&str> {
+    template
+        .split('{')
+        .skip(1)
+        .filter_map(|rest| rest.split_once('}'))
+        .map(|(name, _)| name)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn render_fills_placeholders() {
+        assert_eq!(
+            render(
+                "Updated {count} keys in {path} ({bytes} bytes changed)",
+                &[("count", &2), ("path", &".env"), ("bytes", &14)]
+            ),
+            "Updated 2 keys in .env (14 bytes changed)"
+        );
+    }
+
+    #[test]
+    fn catalog_ids_are_unique() {
+        let mut ids: Vec<_> = CATALOG.iter().map(|(id, _)| *id).collect();
+        ids.sort_unstable();
+        ids.dedup();
+        assert_eq!(ids.len(), CATALOG.len());
+    }
+
+    #[test]
+    fn overrides_must_match_the_catalog() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let path = dir.path().join("de.toml");
+
+        fs::write(&path, "push_done = \"{count} Secrets übertragen\"\n").unwrap();
+        assert_eq!(
+            load(&path).unwrap()["push_done"],
+            "{count} Secrets übertragen"
+        );
+
+        fs::write(&path, "push_dun = \"x\"\n").unwrap();
+        assert!(load(&path).is_err());
+        fs::write(&path, "push_done = \"{anzahl} Secrets\"\n").unwrap();
+        assert!(load(&path).is_err());
+    }
+}
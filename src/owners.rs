@@ -0,0 +1,102 @@
+//! `[owners]`: which team owns which env keys, CODEOWNERS-style.
+
+use std::{cmp::Reverse, collections::HashMap, env};
+
+use anyhow::{Result, bail};
+
+use crate::config::Config;
+
+/// The team the current user acts for.
+pub const TEAM_ENV: &str = "ENVIT_TEAM";
+
+/// The team owning `key`.
+pub fn owner<'a>(owners: &'a HashMap<String, String>, key: &str) -> Option<&'a str> {
+    best_match(owners, key).map(String::as_str)
+}
+
+/// The entry whose pattern matches `key` most specifically. Patterns may
+/// use `*`; an exact pattern wins over globs, and a longer glob over a
+/// shorter one. Also used for `[classify]`.
+pub fn best_match<'a, V>(patterns: &'a HashMap<String, V>, key: &str) -> Option<&'a V> {
+    patterns
+        .iter()
+        .filter(|(pattern, _)| glob_match(pattern, key))
+        .max_by_key(|(pattern, _)| (!pattern.contains('*'), pattern.len(), Reverse(*pattern)))
+        .map(|(_, value)| value)
+}
+
+/// With `[security] enforce_owners`, refuses to change keys owned by a team
+/// other than `$ENVIT_TEAM`, unless `override_owner` is set.
+pub fn check(cfg: &Config, keys: &[&str], override_owner: bool) -> Result<()> {
+    if !cfg.security.enforce_owners || override_owner {
+        return Ok(());
+    }
+    let team = env::var(TEAM_ENV).ok().filter(|team| !team.is_empty());
+    let foreign: Vec<_> = keys
+        .iter()
+        .filter_map(|key| {
+            let owner = owner(&cfg.owners, key)?;
+            (Some(owner) != team.as_deref()).then(|| format!("{key} ({owner})"))
+        })
+        .collect();
+    if foreign.is_empty() {
+        return Ok(());
+    }
+    let acting = match &team {
+        Some(team) => format!("acting as {team}"),
+        None => format!("{TEAM_ENV} is not set"),
+    };
+    bail!(
+        "refusing to change keys owned by other teams ({acting}): {}; pass --override-owner to proceed",
+        foreign.join(", ")
+    )
+}
+
+/// Whether `pattern` (`*` matches any run of characters) matches all of
+/// `key`.
+pub fn glob_match(pattern: &str, key: &str) -> bool {
+    let Some((first, rest)) = pattern.split_once('*') else {
+        return pattern == key;
+    };
+    let Some(mut remaining) = key.strip_prefix(first) else {
+        return false;
+    };
+    let mut parts: Vec<_> = rest.split('*').collect();
+    let last = parts.pop().unwrap_or_default();
+    for part in parts {
+        match remaining.find(part) {
+            Some(idx) => remaining = &remaining[idx + part.len()..],
+            None => return false,
+        }
+    }
+    remaining.len() >= last.len() && remaining.ends_with(last)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn most_specific_pattern_owns_the_key() {
+        let owners = HashMap::from([
+            ("*".to_string(), "platform".to_string()),
+            ("STRIPE_*".to_string(), "payments".to_string()),
+            ("STRIPE_*_KEY".to_string(), "payments-keys".to_string()),
+            ("STRIPE_WEBHOOK_SECRET".to_string(), "billing".to_string()),
+        ]);
+        assert_eq!(owner(&owners, "DATABASE_URL"), Some("platform"));
+        assert_eq!(owner(&owners, "STRIPE_URL"), Some("payments"));
+        assert_eq!(owner(&owners, "STRIPE_API_KEY"), Some("payments-keys"));
+        assert_eq!(owner(&owners, "STRIPE_WEBHOOK_SECRET"), Some("billing"));
+        assert_eq!(owner(&HashMap::new(), "DATABASE_URL"), None);
+    }
+
+    #[test]
+    fn glob_matches_anchored_wildcards() {
+        assert!(glob_match("A*B*C", "AxxBxxC"));
+        assert!(glob_match("A*", "A"));
+        assert!(glob_match("*_KEY", "API_KEY"));
+        assert!(!glob_match("*_KEY", "API_KEYS"));
+        assert!(!glob_match("A*A", "A"));
+    }
+}
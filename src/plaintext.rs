@@ -0,0 +1,64 @@
+//! `[security] no_plaintext` (or `ENVIT_NO_PLAINTEXT=1`): a hard switch for
+//! shared build agents. Every command that would leave plaintext secrets on
+//! disk refuses to run: a plain env file, `[output.files]`, and values
+//! printed where they could be redirected to a file. `run`, `export
+//! --format shell` to a terminal and encrypted outputs (`output.encrypt`,
+//! `output.codec`, `--ansible-vault`) keep working.
+
+use std::env;
+
+use anyhow::{Result, bail};
+
+use crate::config::Config;
+
+/// Turns the switch on regardless of the config, e.g. in an agent image.
+pub const NO_PLAINTEXT_ENV: &str = "ENVIT_NO_PLAINTEXT";
+
+/// Whether the switch is on. Any value of `ENVIT_NO_PLAINTEXT` but empty,
+/// `0` and `false` turns it on, so a typo fails closed.
+pub fn enabled(cfg: &Config) -> bool {
+    cfg.security.no_plaintext
+        || env::var(NO_PLAINTEXT_ENV).is_ok_and(|value| !matches!(value.trim(), "" | "0" | "false"))
+}
+
+/// Refuses `what` (e.g. "get would print a plaintext value") while the
+/// switch is on.
+pub fn refuse(cfg: &Config, what: &str) -> Result<()> {
+    if enabled(cfg) {
+        bail!(
+            "plaintext secrets are disabled (security.no_plaintext or {NO_PLAINTEXT_ENV}): {what}"
+        );
+    }
+    Ok(())
+}
+
+/// For commands writing the env file: it must be encrypted.
+pub fn check_env_file(cfg: &Config) -> Result<()> {
+    if cfg.output.encrypt.is_some() || cfg.output.codec.is_some() {
+        return Ok(());
+    }
+    refuse(
+        cfg,
+        &format!(
+            "{} would be written in plaintext; configure output.encrypt",
+            cfg.output.env_file
+        ),
+    )
+}
+
+/// For commands writing pulled values: the env file must be encrypted and
+/// no `[output.files]` entry may be configured, since those files are
+/// plaintext.
+pub fn check_outputs(cfg: &Config) -> Result<()> {
+    check_env_file(cfg)?;
+    match cfg.output.files.iter().min_by_key(|(key, _)| *key) {
+        Some((key, file)) => refuse(
+            cfg,
+            &format!(
+                "[output.files] would write {key} to {} in plaintext",
+                file.path
+            ),
+        ),
+        None => Ok(()),
+    }
+}
@@ -1,26 +1,137 @@
+use anyhow::{Result, bail};
 use async_trait::async_trait;
 use azure_core::auth::TokenCredential;
 use azure_identity::create_default_credential;
-use reqwest::{Client, StatusCode};
-use serde::Deserialize;
-use std::sync::Arc;
+use base64::{
+    Engine,
+    engine::general_purpose::{STANDARD, URL_SAFE_NO_PAD},
+};
+use reqwest::{Client, RequestBuilder, Response, StatusCode};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::BTreeMap,
+    sync::{Arc, OnceLock},
+    time::{Duration, Instant, SystemTime},
+};
 
-use super::{ProviderError, SecretMeta, SecretProvider};
+use super::{
+    Capabilities, Fetched, ProviderError, SecretDetails, SecretMeta, SecretProvider, SecretVersion,
+};
+use crate::verbose::verbose;
 
-const API_VERSION: &str = "7.4";
-const SCOPE: &str = "https://vault.azure.net/.default";
+pub(super) const DEFAULT_API_VERSION: &str = "7.4";
+/// Versions tried in turn, newest first, when a vault rejects the default.
+const OLDER_API_VERSIONS: &[&str] = &["7.3", "7.2", "7.1", "7.0", "2016-10-01"];
+const DEFAULT_SCOPE: &str = "https://vault.azure.net/.default";
+/// Tries per page of a secret listing.
+const PAGE_ATTEMPTS: u32 = 3;
+const PAGE_BACKOFF: Duration = Duration::from_millis(500);
+
+/// Token scope for a vault or Managed HSM URL: the host without the vault
+/// name, e.g. `vault.azure.net`, `managedhsm.azure.net`, `vault.azure.cn`.
+pub(super) fn scope_for(url: &str) -> String {
+    host(url)
+        .and_then(|host| host.split_once('.'))
+        .map(|(_, domain)| domain)
+        .filter(|domain| domain.contains('.'))
+        .map(|domain| format!("https://{domain}/.default"))
+        .unwrap_or_else(|| DEFAULT_SCOPE.to_string())
+}
+
+fn host(url: &str) -> Option<&str> {
+    url.split_once("://")
+        .map(|(_, rest)| rest.split(['/', ':']).next().unwrap_or_default())
+}
+
+#[derive(Debug, Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct Settings {
+    pub vault_url: String,
+    /// Certificates pulled alongside secrets, by name, with the form their
+    /// value takes.
+    #[serde(default)]
+    pub certificates: BTreeMap<String, CertificateFormat>,
+    /// Key Vault REST API version; defaults to `DEFAULT_API_VERSION`.
+    pub api_version: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum CertificateFormat {
+    /// Private key and chain as PEM; the certificate's content type must be PEM.
+    Pem,
+    /// Base64 PFX; the certificate's content type must be PKCS#12.
+    Pkcs12,
+    /// Public certificate only, as PEM; works for non-exportable keys.
+    Certificate,
+}
+
+impl CertificateFormat {
+    fn content_type(self) -> Option<&'static str> {
+        match self {
+            Self::Pem => Some("application/x-pem-file"),
+            Self::Pkcs12 => Some("application/x-pkcs12"),
+            Self::Certificate => None,
+        }
+    }
+}
+
+impl Settings {
+    pub fn validate(&self) -> Result<()> {
+        if self.vault_url.trim().is_empty() {
+            bail!("provider.vault_url must not be empty");
+        }
+        // A Managed HSM holds keys only; it has no secrets API to pull from.
+        if host(&self.vault_url).is_some_and(|host| host.split('.').nth(1) == Some("managedhsm")) {
+            bail!(
+                "provider.vault_url {} is a Managed HSM, which has no secrets; use a Key Vault \
+                 URL (https://<name>.vault.azure.net)",
+                self.vault_url
+            );
+        }
+        if self.certificates.keys().any(|name| name.trim().is_empty()) {
+            bail!("provider.certificates names must not be empty");
+        }
+        if let Some(version) = &self.api_version
+            && !version.starts_with(|c: char| c.is_ascii_digit())
+        {
+            bail!("provider.api_version must look like 7.4 (got {version:?})");
+        }
+        Ok(())
+    }
+}
 
 pub struct AzureKeyVaultProvider {
     vault_url: String,
+    scope: String,
+    certificates: BTreeMap<String, CertificateFormat>,
+    /// Tried first: the configured version, else `DEFAULT_API_VERSION`.
+    requested: String,
+    /// The version requests use once one is known to be accepted: the
+    /// configured one from the start, else the first the vault answered
+    /// with anything but 400. Set once, so concurrent first requests that
+    /// fall back cannot overwrite each other's outcome.
+    api_version: OnceLock<String>,
     credential: Arc<dyn TokenCredential>,
     http: Client,
 }
 
 impl AzureKeyVaultProvider {
-    pub fn new(vault_url: String) -> Self {
+    pub fn new(settings: Settings) -> Self {
         let credential = create_default_credential().expect("failed to create Azure credential");
         Self {
-            vault_url: vault_url.trim_end_matches('/').to_string(),
+            scope: scope_for(&settings.vault_url),
+            vault_url: settings.vault_url.trim_end_matches('/').to_string(),
+            certificates: settings.certificates,
+            api_version: settings
+                .api_version
+                .clone()
+                .map(OnceLock::from)
+                .unwrap_or_default(),
+            requested: settings
+                .api_version
+                .unwrap_or_else(|| DEFAULT_API_VERSION.to_string()),
             credential,
             http: Client::new(),
         }
@@ -29,32 +140,152 @@ impl AzureKeyVaultProvider {
     async fn access_token(&self) -> Result<String, ProviderError> {
         let token = self
             .credential
-            .get_token(&[SCOPE])
+            .get_token(&[&self.scope])
             .await
-            .map_err(|e| ProviderError::Other(format!("failed to get Azure token: {e}")))?;
+            .map_err(|e| ProviderError::Auth(format!("failed to get Azure token: {e}")))?;
         Ok(token.token.secret().to_string())
     }
 
-    async fn get_json<T: for<'de> Deserialize<'de>>(&self, url: &str) -> Result<T, ProviderError> {
+    /// Sends the request `build` makes for an API version. Unless
+    /// `api_version` is configured, a vault that answers with 400 Bad
+    /// Request before any version was accepted (e.g. a sovereign cloud
+    /// behind on versions) is retried with each of `OLDER_API_VERSIONS` in
+    /// turn, and the first one it accepts is kept for later requests.
+    async fn send(
+        &self,
+        build: impl Fn(&str) -> RequestBuilder,
+    ) -> Result<Response, ProviderError> {
         let token = self.access_token().await?;
-        let res = self
-            .http
-            .get(url)
+        // Once a version is settled, 400s are the request's own.
+        if let Some(version) = self.api_version.get() {
+            return self.send_as(&build, version, &token).await;
+        }
+        let requested = &self.requested;
+        let res = self.send_as(&build, requested, &token).await?;
+        if res.status() != StatusCode::BAD_REQUEST {
+            let _ = self.api_version.set(requested.clone());
+            return Ok(res);
+        }
+
+        for &older in older_api_versions(requested) {
+            let retried = self.send_as(&build, older, &token).await?;
+            if retried.status() != StatusCode::BAD_REQUEST {
+                verbose!("key vault: api-version {requested} rejected; using {older}");
+                let _ = self.api_version.set(older.to_string());
+                return Ok(retried);
+            }
+        }
+        // No version helped, so the request itself was bad.
+        Ok(res)
+    }
+
+    async fn send_as(
+        &self,
+        build: impl Fn(&str) -> RequestBuilder,
+        version: &str,
+        token: &str,
+    ) -> Result<Response, ProviderError> {
+        build(version)
             .bearer_auth(token)
             .send()
             .await
-            .map_err(|e| ProviderError::Other(format!("request failed: {e}")))?;
+            .map_err(|e| ProviderError::Other(format!("request failed: {e}")))
+    }
+
+    async fn get_json<T: for<'de> Deserialize<'de>>(&self, url: &str) -> Result<T, ProviderError> {
+        self.get_json_opt(url).await?.ok_or_else(|| not_found(url))
+    }
+
+    /// Like `get_json`, with 404 as `None`.
+    async fn get_json_opt<T: for<'de> Deserialize<'de>>(
+        &self,
+        url: &str,
+    ) -> Result<Option<T>, ProviderError> {
+        let res = self
+            .send(|version| self.http.get(with_api_version(url, version)))
+            .await?;
 
+        if res.status() == StatusCode::NOT_FOUND {
+            return Ok(None);
+        }
         if res.status().is_success() {
             res.json::<T>()
                 .await
+                .map(Some)
                 .map_err(|e| ProviderError::Other(format!("invalid response body: {e}")))
         } else {
-            Err(ProviderError::Other(format!(
-                "key vault request failed ({}) for {}",
+            Err(ProviderError::http(
                 res.status(),
-                url
-            )))
+                format!("key vault request failed ({}) for {}", res.status(), url),
+            ))
+        }
+    }
+
+    /// The current version of secret `name`; None when it does not exist or
+    /// is disabled.
+    async fn get_bundle(&self, name: &str) -> Result<Option<SecretGetResponse>, ProviderError> {
+        let url = format!("{}/secrets/{}", self.vault_url, name);
+        let res = self
+            .send(|version| self.http.get(with_api_version(&url, version)))
+            .await?;
+
+        if res.status() == StatusCode::NOT_FOUND {
+            return Ok(None);
+        }
+        if !res.status().is_success() {
+            let status = res.status();
+            if status == StatusCode::FORBIDDEN
+                && res
+                    .text()
+                    .await
+                    .unwrap_or_default()
+                    .contains("SecretDisabled")
+            {
+                return Ok(None);
+            }
+            return Err(ProviderError::http(
+                status,
+                format!("failed to get secret {name} ({status})"),
+            ));
+        }
+
+        let body: SecretGetResponse = res
+            .json()
+            .await
+            .map_err(|e| ProviderError::Other(format!("invalid response body: {e}")))?;
+
+        Ok(Some(body))
+    }
+
+    /// An exportable certificate's key and chain live in the secret of the
+    /// same name, encoded per the certificate policy's content type; the
+    /// public certificate alone is the DER `cer` of the certificate object.
+    async fn get_certificate(
+        &self,
+        name: &str,
+        format: CertificateFormat,
+    ) -> Result<Option<String>, ProviderError> {
+        let Some(content_type) = format.content_type() else {
+            let url = format!("{}/certificates/{}", self.vault_url, name);
+            let Some(cert) = self.get_json_opt::<CertificateBundle>(&url).await? else {
+                return Ok(None);
+            };
+            let der = STANDARD.decode(&cert.cer).map_err(|e| {
+                ProviderError::Other(format!("invalid certificate {name} from key vault: {e}"))
+            })?;
+            return Ok(Some(der_to_pem(&der)));
+        };
+
+        let url = format!("{}/secrets/{}", self.vault_url, name);
+        let Some(secret) = self.get_json_opt::<SecretGetResponse>(&url).await? else {
+            return Ok(None);
+        };
+        match secret.content_type.as_deref() {
+            Some(stored) if stored == content_type => Ok(Some(secret.value)),
+            stored => Err(ProviderError::Other(format!(
+                "certificate {name} is stored as {} but {content_type} was configured",
+                stored.unwrap_or("a plain secret")
+            ))),
         }
     }
 }
@@ -69,21 +300,130 @@ struct SecretListResponse {
 #[derive(Debug, Deserialize)]
 struct SecretListItem {
     id: String,
+    #[serde(default)]
+    attributes: ListedAttributes,
+}
+
+/// Times are Unix seconds.
+#[derive(Debug, Deserialize)]
+struct ListedAttributes {
+    #[serde(default = "enabled_by_default")]
+    enabled: bool,
+    created: Option<u64>,
+    updated: Option<u64>,
+    exp: Option<u64>,
+}
+
+impl Default for ListedAttributes {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            created: None,
+            updated: None,
+            exp: None,
+        }
+    }
+}
+
+fn enabled_by_default() -> bool {
+    true
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+struct SecretAttributes {
+    enabled: bool,
+}
+
+impl Default for SecretAttributes {
+    fn default() -> Self {
+        Self { enabled: true }
+    }
 }
 
 #[derive(Debug, Deserialize)]
 struct SecretGetResponse {
     value: String,
+    /// `https://<vault>/secrets/<name>/<version>`.
+    #[serde(default)]
+    id: String,
+    #[serde(default)]
+    attributes: ListedAttributes,
+    #[serde(rename = "contentType")]
+    content_type: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct CertificateBundle {
+    cer: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct SecretBundle {
+    id: String,
+}
+
+#[derive(Debug, Serialize)]
+struct SecretSetRequest<'a> {
+    value: &'a str,
+}
+
+#[derive(Debug, Serialize)]
+struct SecretUpdateRequest {
+    attributes: SecretAttributes,
+}
+
+#[derive(Debug, Deserialize)]
+struct TokenClaims {
+    oid: Option<String>,
+}
+
+fn der_to_pem(der: &[u8]) -> String {
+    let encoded = STANDARD.encode(der);
+    let mut pem = String::from("-----BEGIN CERTIFICATE-----\n");
+    for line in encoded.as_bytes().chunks(64) {
+        pem.push_str(std::str::from_utf8(line).expect("base64 is ASCII"));
+        pem.push('\n');
+    }
+    pem.push_str("-----END CERTIFICATE-----\n");
+    pem
+}
+
+/// Reads the Entra ID object ID (`oid`) out of an access token. The token is
+/// only decoded, not verified: it was just issued to us by the credential.
+fn token_object_id(token: &str) -> Option<String> {
+    let payload = token.split('.').nth(1)?;
+    let bytes = URL_SAFE_NO_PAD.decode(payload.trim_end_matches('=')).ok()?;
+    serde_json::from_slice::<TokenClaims>(&bytes).ok()?.oid
 }
 
 #[async_trait]
 impl SecretProvider for AzureKeyVaultProvider {
     async fn list_secrets(&self) -> Result<Vec<SecretMeta>, ProviderError> {
-        let mut url = format!("{}/secrets?api-version={API_VERSION}", self.vault_url);
-        let mut out = Vec::new();
+        // Disabled secrets (what `delete` leaves behind) cannot be read.
+        Ok(self
+            .secret_details()
+            .await?
+            .into_iter()
+            .filter(|details| details.enabled != Some(false))
+            .map(|details| SecretMeta {
+                name: details.name,
+                updated: details.updated,
+                expires: details.expires,
+                ..SecretMeta::default()
+            })
+            .collect())
+    }
 
-        loop {
-            let page: SecretListResponse = self.get_json(&url).await?;
+    async fn secret_details(&self) -> Result<Vec<SecretDetails>, ProviderError> {
+        let mut url = format!("{}/secrets", self.vault_url);
+        let mut out = Vec::new();
+        for number in 1.. {
+            let page: SecretListResponse = retry_page(number, PAGE_BACKOFF, || async {
+                self.get_json_opt(&url)
+                    .await?
+                    .ok_or_else(|| not_found(&url))
+            })
+            .await?;
 
             for item in page.value {
                 if let Some(name) = item
@@ -93,8 +433,13 @@ impl SecretProvider for AzureKeyVaultProvider {
                     .and_then(|rest| rest.split('/').next())
                     .filter(|s| !s.is_empty())
                 {
-                    out.push(SecretMeta {
+                    let attributes = item.attributes;
+                    out.push(SecretDetails {
                         name: name.to_string(),
+                        created: unix_time(attributes.created),
+                        updated: unix_time(attributes.updated),
+                        enabled: Some(attributes.enabled),
+                        expires: unix_time(attributes.exp),
                     });
                 }
             }
@@ -106,35 +451,348 @@ impl SecretProvider for AzureKeyVaultProvider {
             }
         }
 
+        // Exportable certificates are already listed through their secret.
+        for name in self.certificates.keys() {
+            if !out.iter().any(|details| &details.name == name) {
+                out.push(SecretDetails {
+                    name: name.clone(),
+                    ..SecretDetails::default()
+                });
+            }
+        }
+
         Ok(out)
     }
 
     async fn get_secret(&self, name: &str) -> Result<Option<String>, ProviderError> {
-        let url = format!("{}/secrets/{}?api-version={API_VERSION}", self.vault_url, name);
-        let token = self.access_token().await?;
+        if let Some(format) = self.certificates.get(name) {
+            return self.get_certificate(name, *format).await;
+        }
+        Ok(self.get_bundle(name).await?.map(|bundle| bundle.value))
+    }
+
+    /// One GET: the bundle carries the version id and update time with the
+    /// value.
+    async fn fetch_secret(&self, name: &str) -> Result<Option<Fetched>, ProviderError> {
+        if let Some(format) = self.certificates.get(name) {
+            let value = self.get_certificate(name, *format).await?;
+            return Ok(value.map(|value| Fetched {
+                value,
+                ..Fetched::default()
+            }));
+        }
+        Ok(self.get_bundle(name).await?.map(|bundle| Fetched {
+            version: secret_id_version(&bundle.id),
+            updated: unix_time(bundle.attributes.updated),
+            value: bundle.value,
+        }))
+    }
+
+    async fn set_secret(&self, name: &str, value: &str) -> Result<(), ProviderError> {
+        let url = format!("{}/secrets/{}", self.vault_url, name);
         let res = self
-            .http
-            .get(&url)
-            .bearer_auth(token)
-            .send()
-            .await
-            .map_err(|e| ProviderError::Other(format!("failed requesting secret {name}: {e}")))?;
+            .send(|version| {
+                self.http
+                    .put(with_api_version(&url, version))
+                    .json(&SecretSetRequest { value })
+            })
+            .await?;
 
-        if res.status() == StatusCode::NOT_FOUND {
-            return Ok(None);
+        if !res.status().is_success() {
+            return Err(ProviderError::http(
+                res.status(),
+                format!("failed to set secret {name} ({})", res.status()),
+            ));
         }
+        Ok(())
+    }
+
+    /// Disables the current version rather than deleting: the secret stops
+    /// being listed or readable, and setting it again brings it back.
+    async fn delete_secret(&self, name: &str) -> Result<(), ProviderError> {
+        let url = format!("{}/secrets/{}", self.vault_url, name);
+        let Some(bundle) = self.get_json_opt::<SecretBundle>(&url).await? else {
+            return Ok(());
+        };
+        let version = secret_id_version(&bundle.id).unwrap_or_default();
+        let url = format!("{url}/{version}");
+        let res = self
+            .send(|api_version| {
+                self.http
+                    .patch(with_api_version(&url, api_version))
+                    .json(&SecretUpdateRequest {
+                        attributes: SecretAttributes { enabled: false },
+                    })
+            })
+            .await?;
+
         if !res.status().is_success() {
-            return Err(ProviderError::Other(format!(
-                "failed to get secret {name} ({})",
-                res.status()
-            )));
+            return Err(ProviderError::http(
+                res.status(),
+                format!("failed to disable secret {name} ({})", res.status()),
+            ));
         }
+        Ok(())
+    }
 
-        let body: SecretGetResponse = res
-            .json()
-            .await
-            .map_err(|e| ProviderError::Other(format!("invalid response body: {e}")))?;
+    async fn identity(&self) -> Result<Option<String>, ProviderError> {
+        let token = self.access_token().await?;
+        Ok(token_object_id(&token))
+    }
+
+    async fn secret_version(&self, name: &str) -> Result<Option<String>, ProviderError> {
+        let url = format!("{}/secrets/{}", self.vault_url, name);
+        let bundle: SecretBundle = self.get_json(&url).await?;
+        Ok(secret_id_version(&bundle.id))
+    }
+
+    async fn secret_versions(&self, name: &str) -> Result<Vec<SecretVersion>, ProviderError> {
+        let mut url = format!("{}/secrets/{}/versions", self.vault_url, name);
+        let mut out = Vec::new();
+        for number in 1.. {
+            let Some(page) = retry_page(number, PAGE_BACKOFF, || {
+                self.get_json_opt::<SecretListResponse>(&url)
+            })
+            .await?
+            else {
+                break;
+            };
+            for item in page.value {
+                if let Some(id) = secret_id_version(&item.id) {
+                    out.push(SecretVersion {
+                        id,
+                        created: unix_time(item.attributes.created),
+                        updated: unix_time(item.attributes.updated),
+                        enabled: Some(item.attributes.enabled),
+                    });
+                }
+            }
+            match page.next_link {
+                Some(next) => url = next,
+                None => break,
+            }
+        }
+        // The API lists versions in no particular order.
+        out.sort_by(|a, b| (a.created, &a.id).cmp(&(b.created, &b.id)));
+        Ok(out)
+    }
+
+    async fn get_secret_version(
+        &self,
+        name: &str,
+        version: &str,
+    ) -> Result<Option<String>, ProviderError> {
+        let url = format!("{}/secrets/{}/{}", self.vault_url, name, version);
+        Ok(self
+            .get_json_opt::<SecretGetResponse>(&url)
+            .await?
+            .map(|secret| secret.value))
+    }
+
+    fn capabilities(&self) -> Capabilities {
+        Capabilities {
+            write: true,
+            delete: true,
+            soft_delete: true,
+            versions: true,
+        }
+    }
+}
+
+/// Fetches page `number` of a listing with `fetch`, trying the same page
+/// (its `nextLink`) again when it fails for any reason but credentials, so
+/// a hiccup deep into a large vault does not restart the listing.
+async fn retry_page<T, Fut>(
+    number: usize,
+    mut backoff: Duration,
+    mut fetch: impl FnMut() -> Fut,
+) -> Result<T, ProviderError>
+where
+    Fut: Future<Output = Result<T, ProviderError>>,
+{
+    let mut attempt = 1;
+    loop {
+        let started = Instant::now();
+        match fetch().await {
+            Ok(page) => {
+                verbose!(
+                    "key vault: listed page {number} in {}ms",
+                    started.elapsed().as_millis()
+                );
+                return Ok(page);
+            }
+            Err(err @ ProviderError::Auth(_)) => return Err(err),
+            Err(err) if attempt < PAGE_ATTEMPTS => {
+                verbose!(
+                    "key vault: page {number} failed after {}ms ({err}); retrying in {}",
+                    started.elapsed().as_millis(),
+                    humantime::format_duration(backoff)
+                );
+                tokio::time::sleep(backoff).await;
+                backoff *= 2;
+                attempt += 1;
+            }
+            Err(err) => return Err(err),
+        }
+    }
+}
+
+/// Adds `api-version` unless the URL has one (`nextLink`s carry theirs).
+fn with_api_version(url: &str, version: &str) -> String {
+    if url.contains("api-version=") {
+        return url.to_string();
+    }
+    let separator = if url.contains('?') { '&' } else { '?' };
+    format!("{url}{separator}api-version={version}")
+}
+
+fn not_found(url: &str) -> ProviderError {
+    ProviderError::Other(format!(
+        "key vault request failed ({}) for {}",
+        StatusCode::NOT_FOUND,
+        url
+    ))
+}
+
+/// The entries of `OLDER_API_VERSIONS` after `version`; all of them for a
+/// version not in the list.
+fn older_api_versions(version: &str) -> &'static [&'static str] {
+    match OLDER_API_VERSIONS.iter().position(|v| *v == version) {
+        Some(index) => &OLDER_API_VERSIONS[index + 1..],
+        None => OLDER_API_VERSIONS,
+    }
+}
+
+/// Key Vault attribute times are Unix seconds.
+fn unix_time(secs: Option<u64>) -> Option<SystemTime> {
+    secs.map(|secs| SystemTime::UNIX_EPOCH + Duration::from_secs(secs))
+}
+
+/// `https://<vault>/secrets/<name>/<version>` -> `<version>`.
+fn secret_id_version(id: &str) -> Option<String> {
+    let rest = id.split("/secrets/").nth(1)?;
+    let (_, version) = rest.split_once('/')?;
+    Some(version.trim_end_matches('/').to_string()).filter(|v| !v.is_empty())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn object_id_is_read_from_token_claims() {
+        let claims = URL_SAFE_NO_PAD.encode(r#"{"oid":"11111111-2222","tid":"t"}"#);
+        let token = format!("eyJhbGciOiJub25lIn0.{claims}.sig");
+        assert_eq!(token_object_id(&token).as_deref(), Some("11111111-2222"));
+        assert_eq!(token_object_id("not-a-jwt"), None);
+    }
+
+    #[test]
+    fn scope_follows_the_vault_host() {
+        assert_eq!(
+            scope_for("https://my-vault.vault.azure.net/"),
+            "https://vault.azure.net/.default"
+        );
+        assert_eq!(
+            scope_for("https://pool.managedhsm.azure.net/keys/k/v"),
+            "https://managedhsm.azure.net/.default"
+        );
+        assert_eq!(
+            scope_for("https://my-vault.vault.azure.cn"),
+            "https://vault.azure.cn/.default"
+        );
+        assert_eq!(scope_for("not a url"), DEFAULT_SCOPE);
+    }
+
+    #[test]
+    fn managed_hsm_urls_are_rejected() {
+        let settings = |vault_url: &str| Settings {
+            vault_url: vault_url.to_string(),
+            certificates: BTreeMap::new(),
+            api_version: None,
+        };
+        assert!(
+            settings("https://my-vault.vault.azure.net/")
+                .validate()
+                .is_ok()
+        );
+        let err = settings("https://pool.managedhsm.azure.net/")
+            .validate()
+            .unwrap_err();
+        assert!(err.to_string().contains("Managed HSM"), "{err}");
+    }
+
+    #[test]
+    fn der_is_wrapped_as_64_column_pem() {
+        let pem = der_to_pem(&[0u8; 60]);
+        let lines: Vec<_> = pem.lines().collect();
+        assert_eq!(lines[0], "-----BEGIN CERTIFICATE-----");
+        assert_eq!(lines[1].len(), 64);
+        assert_eq!(lines[2].len(), 16);
+        assert_eq!(lines[3], "-----END CERTIFICATE-----");
+    }
+
+    #[test]
+    fn rejected_api_version_falls_back_through_older_ones() {
+        assert_eq!(older_api_versions(DEFAULT_API_VERSION), OLDER_API_VERSIONS);
+        assert_eq!(older_api_versions("7.1"), ["7.0", "2016-10-01"]);
+        assert!(older_api_versions("2016-10-01").is_empty());
+        assert_eq!(
+            with_api_version("https://v.vault.azure.net/secrets", "7.2"),
+            "https://v.vault.azure.net/secrets?api-version=7.2"
+        );
+        let next = "https://v.vault.azure.net/secrets?api-version=7.4&$skiptoken=x";
+        assert_eq!(with_api_version(next, "7.2"), next);
+    }
+
+    #[test]
+    fn version_is_the_last_secret_id_segment() {
+        assert_eq!(
+            secret_id_version(
+                "https://v.vault.azure.net/secrets/db-url/4387e9f3d6e14c459867679a90fd0f79"
+            )
+            .as_deref(),
+            Some("4387e9f3d6e14c459867679a90fd0f79")
+        );
+        assert_eq!(
+            secret_id_version("https://v.vault.azure.net/secrets/db-url"),
+            None
+        );
+    }
+
+    #[tokio::test]
+    async fn a_failed_page_is_retried_on_its_own_but_not_for_auth_errors() {
+        let mut calls = 0;
+        let page = retry_page(4, Duration::ZERO, || {
+            calls += 1;
+            let call = calls;
+            async move {
+                if call < PAGE_ATTEMPTS {
+                    Err(ProviderError::Other("connection reset".into()))
+                } else {
+                    Ok(call)
+                }
+            }
+        })
+        .await;
+        assert_eq!(page.unwrap(), PAGE_ATTEMPTS);
+
+        let mut calls = 0;
+        let page = retry_page(1, Duration::ZERO, || {
+            calls += 1;
+            async { Err::<(), _>(ProviderError::Other("connection reset".into())) }
+        })
+        .await;
+        assert!(page.is_err());
+        assert_eq!(calls, PAGE_ATTEMPTS);
 
-        Ok(Some(body.value))
+        let mut calls = 0;
+        let page = retry_page(1, Duration::ZERO, || {
+            calls += 1;
+            async { Err::<(), _>(ProviderError::Auth("forbidden".into())) }
+        })
+        .await;
+        assert!(matches!(page, Err(ProviderError::Auth(_))));
+        assert_eq!(calls, 1);
     }
 }
@@ -0,0 +1,205 @@
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+};
+
+use anyhow::Result;
+use async_trait::async_trait;
+
+use super::{
+    Capabilities, Fetched, ProviderError, SecretDetails, SecretMeta, SecretProvider, SecretVersion,
+    build_provider,
+};
+use crate::config::{Config, ProviderConfig, Source};
+
+/// Memoizes `list_secrets` and `get_secret` results for the lifetime of one
+/// invocation. Errors are never cached.
+pub struct CachedProvider {
+    inner: Box<dyn SecretProvider>,
+    listed: Mutex<Option<Vec<SecretMeta>>>,
+    values: Mutex<HashMap<String, Option<String>>>,
+}
+
+impl CachedProvider {
+    pub fn new(inner: Box<dyn SecretProvider>) -> Self {
+        Self {
+            inner,
+            listed: Mutex::new(None),
+            values: Mutex::new(HashMap::new()),
+        }
+    }
+}
+
+#[async_trait]
+impl SecretProvider for CachedProvider {
+    async fn list_secrets(&self) -> Result<Vec<SecretMeta>, ProviderError> {
+        if let Some(listed) = self.listed.lock().unwrap().clone() {
+            return Ok(listed);
+        }
+        let listed = self.inner.list_secrets().await?;
+        *self.listed.lock().unwrap() = Some(listed.clone());
+        Ok(listed)
+    }
+
+    async fn get_secret(&self, name: &str) -> Result<Option<String>, ProviderError> {
+        if let Some(value) = self.values.lock().unwrap().get(name) {
+            return Ok(value.clone());
+        }
+        let value = self.inner.get_secret(name).await?;
+        self.values
+            .lock()
+            .unwrap()
+            .insert(name.to_string(), value.clone());
+        Ok(value)
+    }
+
+    async fn set_secret(&self, name: &str, value: &str) -> Result<(), ProviderError> {
+        self.inner.set_secret(name, value).await?;
+        self.values
+            .lock()
+            .unwrap()
+            .insert(name.to_string(), Some(value.to_string()));
+        Ok(())
+    }
+
+    async fn delete_secret(&self, name: &str) -> Result<(), ProviderError> {
+        self.inner.delete_secret(name).await?;
+        self.values.lock().unwrap().insert(name.to_string(), None);
+        if let Some(listed) = self.listed.lock().unwrap().as_mut() {
+            listed.retain(|meta| meta.name != name);
+        }
+        Ok(())
+    }
+
+    async fn identity(&self) -> Result<Option<String>, ProviderError> {
+        self.inner.identity().await
+    }
+
+    async fn secret_version(&self, name: &str) -> Result<Option<String>, ProviderError> {
+        self.inner.secret_version(name).await
+    }
+
+    /// Always read through: a remembered value carries no version. The
+    /// value read is remembered for `get_secret`.
+    async fn fetch_secret(&self, name: &str) -> Result<Option<Fetched>, ProviderError> {
+        let fetched = self.inner.fetch_secret(name).await?;
+        self.values.lock().unwrap().insert(
+            name.to_string(),
+            fetched.as_ref().map(|fetched| fetched.value.clone()),
+        );
+        Ok(fetched)
+    }
+
+    async fn secret_versions(&self, name: &str) -> Result<Vec<SecretVersion>, ProviderError> {
+        self.inner.secret_versions(name).await
+    }
+
+    async fn get_secret_version(
+        &self,
+        name: &str,
+        version: &str,
+    ) -> Result<Option<String>, ProviderError> {
+        self.inner.get_secret_version(name, version).await
+    }
+
+    async fn secret_details(&self) -> Result<Vec<SecretDetails>, ProviderError> {
+        self.inner.secret_details().await
+    }
+
+    fn capabilities(&self) -> Capabilities {
+        self.inner.capabilities()
+    }
+}
+
+/// Hands out one shared `CachedProvider` per provider identity (kind plus
+/// settings), so several outputs backed by the same source list it once.
+/// Clones share the providers connected so far.
+#[derive(Clone, Default)]
+pub struct ProviderRegistry {
+    providers: HashMap<String, Arc<CachedProvider>>,
+}
+
+impl ProviderRegistry {
+    pub fn get(&mut self, cfg: &ProviderConfig) -> Result<Arc<CachedProvider>> {
+        let key = identity(cfg);
+        if let Some(provider) = self.providers.get(&key) {
+            return Ok(Arc::clone(provider));
+        }
+        let provider = Arc::new(CachedProvider::new(build_provider(cfg)?));
+        self.providers.insert(key, Arc::clone(&provider));
+        Ok(provider)
+    }
+
+    /// The provider behind every source of `cfg`, in precedence order.
+    pub fn connect<'a>(
+        &mut self,
+        cfg: &'a Config,
+    ) -> Result<Vec<(Source<'a>, Arc<CachedProvider>)>> {
+        cfg.sources()
+            .into_iter()
+            .map(|source| Ok((source, self.get(source.provider)?)))
+            .collect()
+    }
+}
+
+/// Principals of every provider that reports one, comma-separated. Failures
+/// are ignored: they resurface from listing with a proper error.
+pub async fn principals(connected: &[(Source<'_>, Arc<CachedProvider>)]) -> Option<String> {
+    let mut principals: Vec<String> = Vec::new();
+    for (_, provider) in connected {
+        if let Ok(Some(principal)) = provider.identity().await
+            && !principals.contains(&principal)
+        {
+            principals.push(principal);
+        }
+    }
+    (!principals.is_empty()).then(|| principals.join(", "))
+}
+
+fn identity(cfg: &ProviderConfig) -> String {
+    // toml::Table is ordered, so equal settings always render identically.
+    format!("{}:{}", cfg.kind, cfg.settings)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    use super::*;
+
+    #[derive(Default)]
+    struct Counting {
+        lists: AtomicUsize,
+        gets: AtomicUsize,
+    }
+
+    #[async_trait]
+    impl SecretProvider for Arc<Counting> {
+        async fn list_secrets(&self) -> Result<Vec<SecretMeta>, ProviderError> {
+            self.lists.fetch_add(1, Ordering::SeqCst);
+            Ok(vec![SecretMeta {
+                name: "a".to_string(),
+                ..SecretMeta::default()
+            }])
+        }
+
+        async fn get_secret(&self, _name: &str) -> Result<Option<String>, ProviderError> {
+            self.gets.fetch_add(1, Ordering::SeqCst);
+            Ok(Some("v".to_string()))
+        }
+    }
+
+    #[tokio::test]
+    async fn repeated_calls_hit_the_inner_provider_once() {
+        let counts = Arc::new(Counting::default());
+        let cached = CachedProvider::new(Box::new(Arc::clone(&counts)));
+
+        for _ in 0..3 {
+            cached.list_secrets().await.unwrap();
+            assert_eq!(cached.get_secret("a").await.unwrap().as_deref(), Some("v"));
+        }
+
+        assert_eq!(counts.lists.load(Ordering::SeqCst), 1);
+        assert_eq!(counts.gets.load(Ordering::SeqCst), 1);
+    }
+}
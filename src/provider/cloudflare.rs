@@ -0,0 +1,224 @@
+use std::env;
+
+use anyhow::{Context, Result, bail};
+use async_trait::async_trait;
+use reqwest::{Client, StatusCode, Url};
+use schemars::JsonSchema;
+use serde::Deserialize;
+
+use super::{Capabilities, ProviderError, SecretMeta, SecretProvider};
+
+const API_BASE: &str = "https://api.cloudflare.com/client/v4";
+const DEFAULT_TOKEN_ENV: &str = "CLOUDFLARE_API_TOKEN";
+
+#[derive(Debug, Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct Settings {
+    pub account_id: String,
+    pub namespace_id: String,
+    #[serde(default = "default_token_env")]
+    pub api_token_env: String,
+}
+
+fn default_token_env() -> String {
+    DEFAULT_TOKEN_ENV.to_string()
+}
+
+impl Settings {
+    pub fn validate(&self) -> Result<()> {
+        if self.account_id.trim().is_empty() {
+            bail!("provider.account_id must not be empty");
+        }
+        if self.namespace_id.trim().is_empty() {
+            bail!("provider.namespace_id must not be empty");
+        }
+        if self.api_token_env.trim().is_empty() {
+            bail!("provider.api_token_env must not be empty");
+        }
+        Ok(())
+    }
+}
+
+/// Cloudflare Workers KV namespace used as the secret store: one KV key per
+/// secret. Workers secrets themselves are write-only in Cloudflare's API, so
+/// a KV namespace is the readable source of truth for `.dev.vars`/`.env`.
+pub struct CloudflareKvProvider {
+    namespace_url: Url,
+    token: String,
+    http: Client,
+}
+
+impl CloudflareKvProvider {
+    pub fn new(settings: Settings) -> Result<Self> {
+        let token = env::var(&settings.api_token_env)
+            .with_context(|| format!("{} is not set", settings.api_token_env))?;
+        let namespace_url = Url::parse(&format!(
+            "{API_BASE}/accounts/{}/storage/kv/namespaces/{}",
+            settings.account_id, settings.namespace_id
+        ))
+        .context("invalid Cloudflare account_id or namespace_id")?;
+        Ok(Self {
+            namespace_url,
+            token,
+            http: Client::new(),
+        })
+    }
+
+    fn url(&self, segments: &[&str]) -> Url {
+        let mut url = self.namespace_url.clone();
+        url.path_segments_mut()
+            .expect("https URL has path segments")
+            .extend(segments);
+        url
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct KeyListResponse {
+    result: Vec<KeyListItem>,
+    result_info: Option<ResultInfo>,
+}
+
+#[derive(Debug, Deserialize)]
+struct KeyListItem {
+    name: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct ResultInfo {
+    cursor: Option<String>,
+}
+
+#[async_trait]
+impl SecretProvider for CloudflareKvProvider {
+    async fn list_secrets(&self) -> Result<Vec<SecretMeta>, ProviderError> {
+        let mut out = Vec::new();
+        let mut cursor: Option<String> = None;
+
+        loop {
+            let mut url = self.url(&["keys"]);
+            if let Some(cursor) = &cursor {
+                url.query_pairs_mut().append_pair("cursor", cursor);
+            }
+            let res = self
+                .http
+                .get(url)
+                .bearer_auth(&self.token)
+                .send()
+                .await
+                .map_err(|e| ProviderError::Other(format!("request failed: {e}")))?;
+            if !res.status().is_success() {
+                return Err(ProviderError::http(
+                    res.status(),
+                    format!("cloudflare key listing failed ({})", res.status()),
+                ));
+            }
+            let page: KeyListResponse = res
+                .json()
+                .await
+                .map_err(|e| ProviderError::Other(format!("invalid response body: {e}")))?;
+
+            out.extend(page.result.into_iter().map(|k| SecretMeta {
+                name: k.name,
+                ..SecretMeta::default()
+            }));
+
+            match page.result_info.and_then(|info| info.cursor) {
+                Some(next) if !next.is_empty() => cursor = Some(next),
+                _ => break,
+            }
+        }
+
+        Ok(out)
+    }
+
+    async fn get_secret(&self, name: &str) -> Result<Option<String>, ProviderError> {
+        let res = self
+            .http
+            .get(self.url(&["values", name]))
+            .bearer_auth(&self.token)
+            .send()
+            .await
+            .map_err(|e| ProviderError::Other(format!("failed requesting secret {name}: {e}")))?;
+
+        if res.status() == StatusCode::NOT_FOUND {
+            return Ok(None);
+        }
+        if !res.status().is_success() {
+            return Err(ProviderError::http(
+                res.status(),
+                format!("failed to get secret {name} ({})", res.status()),
+            ));
+        }
+
+        res.text()
+            .await
+            .map(Some)
+            .map_err(|e| ProviderError::Other(format!("invalid response body: {e}")))
+    }
+
+    async fn set_secret(&self, name: &str, value: &str) -> Result<(), ProviderError> {
+        let res = self
+            .http
+            .put(self.url(&["values", name]))
+            .bearer_auth(&self.token)
+            .body(value.to_string())
+            .send()
+            .await
+            .map_err(|e| ProviderError::Other(format!("failed writing secret {name}: {e}")))?;
+
+        if !res.status().is_success() {
+            return Err(ProviderError::http(
+                res.status(),
+                format!("failed to set secret {name} ({})", res.status()),
+            ));
+        }
+        Ok(())
+    }
+
+    async fn delete_secret(&self, name: &str) -> Result<(), ProviderError> {
+        let res = self
+            .http
+            .delete(self.url(&["values", name]))
+            .bearer_auth(&self.token)
+            .send()
+            .await
+            .map_err(|e| ProviderError::Other(format!("failed deleting secret {name}: {e}")))?;
+
+        if !res.status().is_success() && res.status() != StatusCode::NOT_FOUND {
+            return Err(ProviderError::http(
+                res.status(),
+                format!("failed to delete secret {name} ({})", res.status()),
+            ));
+        }
+        Ok(())
+    }
+
+    fn capabilities(&self) -> Capabilities {
+        Capabilities {
+            write: true,
+            delete: true,
+            ..Capabilities::default()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn key_names_are_escaped_into_a_single_path_segment() {
+        let provider = CloudflareKvProvider {
+            namespace_url: Url::parse(&format!("{API_BASE}/accounts/a/storage/kv/namespaces/n"))
+                .unwrap(),
+            token: String::new(),
+            http: Client::new(),
+        };
+
+        assert_eq!(
+            provider.url(&["values", "db/url?x"]).as_str(),
+            "https://api.cloudflare.com/client/v4/accounts/a/storage/kv/namespaces/n/values/db%2Furl%3Fx"
+        );
+    }
+}
@@ -0,0 +1,151 @@
+use std::process::Stdio;
+
+use anyhow::{Result, bail};
+use async_trait::async_trait;
+use schemars::JsonSchema;
+use serde::Deserialize;
+use tokio::io::AsyncWriteExt;
+
+use super::{Capabilities, ProviderError, SecretMeta, SecretProvider};
+use crate::shell;
+
+/// Exit code a `get_command` uses to report that the secret does not exist.
+pub const NOT_FOUND_EXIT_CODE: i32 = 3;
+
+/// Shell commands implementing the provider contract:
+/// - `list_command` prints one secret name per line (optional; without it
+///   only `[map]` entries are fetched)
+/// - `get_command` prints the value of `$ENVIT_SECRET_NAME` to stdout (one
+///   trailing newline is dropped) and exits 0, or exits 3 if it doesn't exist
+/// - `set_command` reads the new value of `$ENVIT_SECRET_NAME` from stdin
+///
+/// Any other non-zero exit is an error, reported with the command's stderr.
+#[derive(Debug, Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct Settings {
+    pub list_command: Option<String>,
+    pub get_command: String,
+    pub set_command: Option<String>,
+}
+
+impl Settings {
+    pub fn validate(&self) -> Result<()> {
+        if self.get_command.trim().is_empty() {
+            bail!("provider.get_command must not be empty");
+        }
+        for (field, cmd) in [
+            ("list_command", &self.list_command),
+            ("set_command", &self.set_command),
+        ] {
+            if cmd.as_deref().is_some_and(|c| c.trim().is_empty()) {
+                bail!("provider.{field} must not be empty");
+            }
+        }
+        Ok(())
+    }
+}
+
+pub struct ExecProvider {
+    settings: Settings,
+}
+
+impl ExecProvider {
+    pub fn new(settings: Settings) -> Self {
+        Self { settings }
+    }
+}
+
+fn failure(command: &str, output: &std::process::Output) -> ProviderError {
+    ProviderError::Other(format!(
+        "command failed ({}): {command}: {}",
+        output.status,
+        String::from_utf8_lossy(&output.stderr).trim()
+    ))
+}
+
+fn spawn_error(command: &str, err: std::io::Error) -> ProviderError {
+    ProviderError::Other(format!("failed to run command: {command}: {err}"))
+}
+
+#[async_trait]
+impl SecretProvider for ExecProvider {
+    async fn list_secrets(&self) -> Result<Vec<SecretMeta>, ProviderError> {
+        let Some(command) = &self.settings.list_command else {
+            return Ok(Vec::new());
+        };
+        let output = shell::command(command)
+            .output()
+            .await
+            .map_err(|e| spawn_error(command, e))?;
+        if !output.status.success() {
+            return Err(failure(command, &output));
+        }
+
+        Ok(String::from_utf8_lossy(&output.stdout)
+            .lines()
+            .map(str::trim)
+            .filter(|name| !name.is_empty())
+            .map(|name| SecretMeta {
+                name: name.to_string(),
+                ..SecretMeta::default()
+            })
+            .collect())
+    }
+
+    async fn get_secret(&self, name: &str) -> Result<Option<String>, ProviderError> {
+        let command = &self.settings.get_command;
+        let output = shell::command(command)
+            .env("ENVIT_SECRET_NAME", name)
+            .output()
+            .await
+            .map_err(|e| spawn_error(command, e))?;
+
+        if output.status.code() == Some(NOT_FOUND_EXIT_CODE) {
+            return Ok(None);
+        }
+        if !output.status.success() {
+            return Err(failure(command, &output));
+        }
+        shell::stdout_value(output.stdout)
+            .map(Some)
+            .ok_or_else(|| ProviderError::Other(format!("secret {name} is not valid UTF-8")))
+    }
+
+    async fn set_secret(&self, name: &str, value: &str) -> Result<(), ProviderError> {
+        let Some(command) = &self.settings.set_command else {
+            return Err(ProviderError::Unsupported(format!(
+                "writing secret {name} (no set_command configured)"
+            )));
+        };
+        let mut child = shell::command(command)
+            .env("ENVIT_SECRET_NAME", name)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::null())
+            .stderr(Stdio::piped())
+            .spawn()
+            .map_err(|e| spawn_error(command, e))?;
+
+        let mut stdin = child.stdin.take().expect("stdin is piped");
+        stdin
+            .write_all(value.as_bytes())
+            .await
+            .map_err(|e| spawn_error(command, e))?;
+        drop(stdin);
+
+        let output = child
+            .wait_with_output()
+            .await
+            .map_err(|e| spawn_error(command, e))?;
+        if !output.status.success() {
+            return Err(failure(command, &output));
+        }
+        Ok(())
+    }
+
+    fn capabilities(&self) -> Capabilities {
+        Capabilities {
+            write: self.settings.set_command.is_some(),
+            ..Capabilities::default()
+        }
+    }
+}
@@ -0,0 +1,197 @@
+use std::{collections::BTreeMap, env, sync::Mutex};
+
+use anyhow::{Result, bail};
+use async_trait::async_trait;
+use reqwest::Client;
+use schemars::JsonSchema;
+use serde::Deserialize;
+use serde_json::Value;
+
+use super::{ProviderError, SecretMeta, SecretProvider};
+
+#[derive(Debug, Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct Settings {
+    pub url: String,
+    /// Header values may reference environment variables as `${NAME}`.
+    #[serde(default)]
+    pub headers: BTreeMap<String, String>,
+    /// JSON pointer to the object holding the secrets, e.g. "/data".
+    pub pointer: Option<String>,
+}
+
+impl Settings {
+    pub fn validate(&self) -> Result<()> {
+        if !(self.url.starts_with("https://") || self.url.starts_with("http://")) {
+            bail!("provider.url must be an http(s) URL");
+        }
+        if let Some(pointer) = &self.pointer
+            && !pointer.is_empty()
+            && !pointer.starts_with('/')
+        {
+            bail!("provider.pointer must be a JSON pointer starting with '/'");
+        }
+        Ok(())
+    }
+}
+
+/// Any endpoint that answers a GET with a flat JSON object of
+/// `name -> value`. The whole object is fetched once and served from memory.
+pub struct HttpJsonProvider {
+    settings: Settings,
+    http: Client,
+    secrets: Mutex<Option<BTreeMap<String, Option<String>>>>,
+}
+
+impl HttpJsonProvider {
+    pub fn new(settings: Settings) -> Self {
+        Self {
+            settings,
+            http: Client::new(),
+            secrets: Mutex::new(None),
+        }
+    }
+
+    async fn secrets(&self) -> Result<BTreeMap<String, Option<String>>, ProviderError> {
+        if let Some(secrets) = self.secrets.lock().unwrap().clone() {
+            return Ok(secrets);
+        }
+
+        let mut req = self.http.get(&self.settings.url);
+        for (name, template) in &self.settings.headers {
+            req = req.header(name, expand_env(template)?);
+        }
+        let res = req
+            .send()
+            .await
+            .map_err(|e| ProviderError::Other(format!("request failed: {e}")))?;
+        if !res.status().is_success() {
+            return Err(ProviderError::http(
+                res.status(),
+                format!(
+                    "http_json request failed ({}) for {}",
+                    res.status(),
+                    self.settings.url
+                ),
+            ));
+        }
+        let body: Value = res
+            .json()
+            .await
+            .map_err(|e| ProviderError::Other(format!("invalid response body: {e}")))?;
+
+        let secrets = flatten(&body, self.settings.pointer.as_deref())?;
+        *self.secrets.lock().unwrap() = Some(secrets.clone());
+        Ok(secrets)
+    }
+}
+
+/// Replaces `${NAME}` with the value of environment variable `NAME`.
+fn expand_env(template: &str) -> Result<String, ProviderError> {
+    let mut out = String::with_capacity(template.len());
+    let mut rest = template;
+    while let Some(start) = rest.find("${") {
+        out.push_str(&rest[..start]);
+        let after = &rest[start + 2..];
+        let Some(end) = after.find('}') else {
+            return Err(ProviderError::Other(format!(
+                "unterminated ${{...}} in header template: {template}"
+            )));
+        };
+        let name = &after[..end];
+        let value = env::var(name)
+            .map_err(|_| ProviderError::Other(format!("environment variable {name} is not set")))?;
+        out.push_str(&value);
+        rest = &after[end + 1..];
+    }
+    out.push_str(rest);
+    Ok(out)
+}
+
+/// Strings are taken as-is, numbers and booleans are stringified and `null`
+/// counts as a missing secret. Nested values are rejected.
+fn flatten(
+    body: &Value,
+    pointer: Option<&str>,
+) -> Result<BTreeMap<String, Option<String>>, ProviderError> {
+    let target = match pointer {
+        Some(pointer) => body.pointer(pointer).ok_or_else(|| {
+            ProviderError::Other(format!("JSON pointer {pointer} not found in response"))
+        })?,
+        None => body,
+    };
+    let Value::Object(object) = target else {
+        return Err(ProviderError::Other(
+            "http_json response is not a JSON object".to_string(),
+        ));
+    };
+
+    object
+        .iter()
+        .map(|(name, value)| {
+            let value = match value {
+                Value::String(s) => Some(s.clone()),
+                Value::Number(n) => Some(n.to_string()),
+                Value::Bool(b) => Some(b.to_string()),
+                Value::Null => None,
+                Value::Array(_) | Value::Object(_) => {
+                    return Err(ProviderError::Other(format!(
+                        "http_json value for {name} is not a scalar"
+                    )));
+                }
+            };
+            Ok((name.clone(), value))
+        })
+        .collect()
+}
+
+#[async_trait]
+impl SecretProvider for HttpJsonProvider {
+    async fn list_secrets(&self) -> Result<Vec<SecretMeta>, ProviderError> {
+        Ok(self
+            .secrets()
+            .await?
+            .into_keys()
+            .map(|name| SecretMeta {
+                name,
+                ..SecretMeta::default()
+            })
+            .collect())
+    }
+
+    async fn get_secret(&self, name: &str) -> Result<Option<String>, ProviderError> {
+        Ok(self.secrets().await?.get(name).cloned().flatten())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    #[test]
+    fn flatten_reads_scalars_under_pointer() {
+        let body =
+            json!({"data": {"db-url": "postgres://", "port": 5432, "debug": true, "gone": null}});
+
+        let secrets = flatten(&body, Some("/data")).unwrap();
+
+        assert_eq!(secrets["db-url"].as_deref(), Some("postgres://"));
+        assert_eq!(secrets["port"].as_deref(), Some("5432"));
+        assert_eq!(secrets["debug"].as_deref(), Some("true"));
+        assert_eq!(secrets["gone"], None);
+        assert!(flatten(&json!({"nested": {"a": 1}}), None).is_err());
+    }
+
+    #[test]
+    fn header_templates_expand_environment_variables() {
+        assert_eq!(
+            expand_env("Bearer ${PATH}").unwrap(),
+            format!("Bearer {}", env::var("PATH").unwrap())
+        );
+        assert_eq!(expand_env("static").unwrap(), "static");
+        assert!(expand_env("${ENVIT_SURELY_UNSET_VAR}").is_err());
+        assert!(expand_env("${PATH").is_err());
+    }
+}
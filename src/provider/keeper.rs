@@ -0,0 +1,112 @@
+use std::{fs, path::PathBuf};
+
+use anyhow::{Context, Result, bail};
+use async_trait::async_trait;
+use base64::{Engine, engine::general_purpose::STANDARD};
+use schemars::JsonSchema;
+use serde::Deserialize;
+use tokio::process::Command;
+
+use super::{ProviderError, SecretMeta, SecretProvider};
+use crate::shell;
+
+const DEFAULT_COMMAND: &str = "ksm";
+
+#[derive(Debug, Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct Settings {
+    pub config_file: PathBuf,
+    #[serde(default = "default_command")]
+    pub command: String,
+}
+
+fn default_command() -> String {
+    DEFAULT_COMMAND.to_string()
+}
+
+impl Settings {
+    pub fn validate(&self) -> Result<()> {
+        if self.config_file.as_os_str().is_empty() {
+            bail!("provider.config_file must not be empty");
+        }
+        if self.command.trim().is_empty() {
+            bail!("provider.command must not be empty");
+        }
+        Ok(())
+    }
+}
+
+/// Keeper Secrets Manager, accessed through the `ksm` CLI.
+///
+/// Secret names are Keeper notation without the `keeper://` scheme
+/// (`<record uid or title>/field/<type>`, `<uid>/custom_field/<label>`), so
+/// which record fields are pulled is decided entirely by `[map]`.
+pub struct KeeperProvider {
+    command: String,
+    ksm_config: String,
+}
+
+impl KeeperProvider {
+    pub fn new(settings: Settings) -> Result<Self> {
+        let raw = fs::read_to_string(&settings.config_file).with_context(|| {
+            format!(
+                "failed to read KSM config file: {}",
+                settings.config_file.display()
+            )
+        })?;
+        Ok(Self {
+            command: settings.command,
+            ksm_config: encode_config(&raw),
+        })
+    }
+}
+
+/// `KSM_CONFIG` takes the base64 form; plain JSON config files are encoded here.
+fn encode_config(raw: &str) -> String {
+    let trimmed = raw.trim();
+    if trimmed.starts_with('{') {
+        STANDARD.encode(trimmed)
+    } else {
+        trimmed.to_string()
+    }
+}
+
+#[async_trait]
+impl SecretProvider for KeeperProvider {
+    async fn list_secrets(&self) -> Result<Vec<SecretMeta>, ProviderError> {
+        // Record fields have no natural env key, so nothing is auto-mapped.
+        Ok(Vec::new())
+    }
+
+    async fn get_secret(&self, name: &str) -> Result<Option<String>, ProviderError> {
+        let output = Command::new(&self.command)
+            .args(["secret", "notation", &format!("keeper://{name}")])
+            .env("KSM_CONFIG", &self.ksm_config)
+            .output()
+            .await
+            .map_err(|e| ProviderError::Other(format!("failed to run {}: {e}", self.command)))?;
+
+        if !output.status.success() {
+            return Err(ProviderError::Other(format!(
+                "{} failed for {name}: {}",
+                self.command,
+                String::from_utf8_lossy(&output.stderr).trim()
+            )));
+        }
+
+        shell::stdout_value(output.stdout)
+            .map(Some)
+            .ok_or_else(|| ProviderError::Other(format!("secret {name} is not valid UTF-8")))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn json_config_is_base64_encoded_and_base64_passes_through() {
+        assert_eq!(encode_config("{\"a\":1}\n"), STANDARD.encode("{\"a\":1}"));
+        assert_eq!(encode_config("eyJhIjoxfQ==\n"), "eyJhIjoxfQ==");
+    }
+}
@@ -0,0 +1,319 @@
+use std::{collections::BTreeMap, fs, path::PathBuf, sync::Mutex};
+
+use anyhow::{Result, bail};
+use async_trait::async_trait;
+use base64::{Engine, engine::general_purpose::STANDARD};
+use schemars::JsonSchema;
+use serde::Deserialize;
+use serde_json::Value;
+use tokio::process::Command;
+
+use super::{ProviderError, SecretMeta, SecretProvider};
+use crate::envfile;
+
+const DEFAULT_AWS_COMMAND: &str = "aws";
+#[cfg(feature = "azure")]
+const DEFAULT_ALGORITHM: &str = "RSA-OAEP-256";
+
+#[derive(Debug, Clone, Copy, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum Backend {
+    AwsKms,
+    AzureKeyVault,
+}
+
+#[derive(Debug, Clone, Copy, Default, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum PayloadFormat {
+    /// JSON when the plaintext starts with `{`, dotenv otherwise.
+    #[default]
+    Auto,
+    Dotenv,
+    Json,
+}
+
+#[derive(Debug, Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct Settings {
+    /// Ciphertext, raw or base64 encoded.
+    pub path: PathBuf,
+    pub backend: Backend,
+    /// AWS: optional key id or ARN. Azure: key identifier URL, e.g.
+    /// `https://my-vault.vault.azure.net/keys/bundle/<version>`.
+    pub key_id: Option<String>,
+    /// Azure decrypt algorithm.
+    pub algorithm: Option<String>,
+    #[serde(default)]
+    pub format: PayloadFormat,
+    #[serde(default = "default_aws_command")]
+    pub aws_command: String,
+}
+
+fn default_aws_command() -> String {
+    DEFAULT_AWS_COMMAND.to_string()
+}
+
+impl Settings {
+    pub fn validate(&self) -> Result<()> {
+        if self.path.as_os_str().is_empty() {
+            bail!("provider.path must not be empty");
+        }
+        match self.backend {
+            Backend::AwsKms => {
+                if self.aws_command.trim().is_empty() {
+                    bail!("provider.aws_command must not be empty");
+                }
+                if self.algorithm.is_some() {
+                    bail!("provider.algorithm only applies to backend = \"azure_key_vault\"");
+                }
+            }
+            Backend::AzureKeyVault => {
+                if !cfg!(feature = "azure") {
+                    bail!(
+                        "backend azure_key_vault is not compiled into this build (rebuild with --features azure)"
+                    );
+                }
+                if !self
+                    .key_id
+                    .as_deref()
+                    .is_some_and(|id| id.starts_with("https://") && id.contains("/keys/"))
+                {
+                    bail!(
+                        "provider.key_id must be a Key Vault key URL (https://<vault>/keys/<name>)"
+                    );
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// One KMS-encrypted bundle (dotenv or flat JSON) decrypted on first use and
+/// served from memory. KMS `decrypt` only takes small payloads: 4 KiB for AWS
+/// symmetric keys, a few hundred bytes for Key Vault RSA keys.
+pub struct KmsFileProvider {
+    settings: Settings,
+    secrets: Mutex<Option<BTreeMap<String, String>>>,
+}
+
+impl KmsFileProvider {
+    pub fn new(settings: Settings) -> Self {
+        Self {
+            settings,
+            secrets: Mutex::new(None),
+        }
+    }
+
+    async fn secrets(&self) -> Result<BTreeMap<String, String>, ProviderError> {
+        if let Some(secrets) = self.secrets.lock().unwrap().clone() {
+            return Ok(secrets);
+        }
+
+        let raw = fs::read(&self.settings.path).map_err(|e| {
+            ProviderError::Other(format!(
+                "failed to read {}: {e}",
+                self.settings.path.display()
+            ))
+        })?;
+        let ciphertext = decode_ciphertext(raw);
+        let plaintext = match self.settings.backend {
+            Backend::AwsKms => self.aws_decrypt(&ciphertext).await?,
+            #[cfg(feature = "azure")]
+            Backend::AzureKeyVault => self.azure_decrypt(&ciphertext).await?,
+            #[cfg(not(feature = "azure"))]
+            Backend::AzureKeyVault => {
+                return Err(ProviderError::Unsupported(
+                    "azure_key_vault backend (rebuild with --features azure)".to_string(),
+                ));
+            }
+        };
+        let plaintext = String::from_utf8(plaintext)
+            .map_err(|_| ProviderError::Other("decrypted bundle is not valid UTF-8".to_string()))?;
+
+        let secrets = parse_payload(&plaintext, self.settings.format)?;
+        *self.secrets.lock().unwrap() = Some(secrets.clone());
+        Ok(secrets)
+    }
+
+    async fn aws_decrypt(&self, ciphertext: &[u8]) -> Result<Vec<u8>, ProviderError> {
+        let command = &self.settings.aws_command;
+        let mut cmd = Command::new(command);
+        cmd.args(["kms", "decrypt", "--ciphertext-blob"])
+            .arg(STANDARD.encode(ciphertext))
+            .args(["--output", "text", "--query", "Plaintext"]);
+        if let Some(key_id) = &self.settings.key_id {
+            cmd.args(["--key-id", key_id]);
+        }
+        let output = cmd
+            .output()
+            .await
+            .map_err(|e| ProviderError::Other(format!("failed to run {command}: {e}")))?;
+
+        if !output.status.success() {
+            let stderr = String::from_utf8_lossy(&output.stderr);
+            let message = format!("aws kms decrypt failed: {}", stderr.trim());
+            return Err(if is_aws_auth_error(&stderr) {
+                ProviderError::Auth(message)
+            } else {
+                ProviderError::Other(message)
+            });
+        }
+        STANDARD
+            .decode(String::from_utf8_lossy(&output.stdout).trim())
+            .map_err(|e| ProviderError::Other(format!("invalid aws kms decrypt output: {e}")))
+    }
+
+    #[cfg(feature = "azure")]
+    async fn azure_decrypt(&self, ciphertext: &[u8]) -> Result<Vec<u8>, ProviderError> {
+        use azure_identity::create_default_credential;
+        use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+
+        #[derive(serde::Serialize)]
+        struct DecryptRequest<'a> {
+            alg: &'a str,
+            value: String,
+        }
+        #[derive(Deserialize)]
+        struct DecryptResponse {
+            value: String,
+        }
+
+        let key_id = self.settings.key_id.as_deref().unwrap_or_default();
+        let credential = create_default_credential()
+            .map_err(|e| ProviderError::Auth(format!("failed to create Azure credential: {e}")))?;
+        let token = credential
+            .get_token(&[&super::azure_key_vault::scope_for(key_id)])
+            .await
+            .map_err(|e| ProviderError::Auth(format!("failed to get Azure token: {e}")))?;
+
+        let url = format!(
+            "{}/decrypt?api-version={}",
+            key_id.trim_end_matches('/'),
+            super::azure_key_vault::DEFAULT_API_VERSION
+        );
+        let res = reqwest::Client::new()
+            .post(&url)
+            .bearer_auth(token.token.secret())
+            .json(&DecryptRequest {
+                alg: self
+                    .settings
+                    .algorithm
+                    .as_deref()
+                    .unwrap_or(DEFAULT_ALGORITHM),
+                value: URL_SAFE_NO_PAD.encode(ciphertext),
+            })
+            .send()
+            .await
+            .map_err(|e| ProviderError::Other(format!("request failed: {e}")))?;
+        if !res.status().is_success() {
+            return Err(ProviderError::http(
+                res.status(),
+                format!("key vault decrypt failed ({}) for {key_id}", res.status()),
+            ));
+        }
+        let body: DecryptResponse = res
+            .json()
+            .await
+            .map_err(|e| ProviderError::Other(format!("invalid response body: {e}")))?;
+        URL_SAFE_NO_PAD
+            .decode(body.value.trim_end_matches('='))
+            .map_err(|e| ProviderError::Other(format!("invalid decrypt result: {e}")))
+    }
+}
+
+/// `aws kms encrypt` hands out base64 text; binary blobs are used as-is.
+fn decode_ciphertext(raw: Vec<u8>) -> Vec<u8> {
+    std::str::from_utf8(&raw)
+        .ok()
+        .and_then(|text| STANDARD.decode(text.trim()).ok())
+        .unwrap_or(raw)
+}
+
+fn is_aws_auth_error(stderr: &str) -> bool {
+    [
+        "AccessDenied",
+        "ExpiredToken",
+        "UnrecognizedClientException",
+        "InvalidSignatureException",
+        "Unable to locate credentials",
+    ]
+    .iter()
+    .any(|marker| stderr.contains(marker))
+}
+
+fn parse_payload(
+    plaintext: &str,
+    format: PayloadFormat,
+) -> Result<BTreeMap<String, String>, ProviderError> {
+    let json = match format {
+        PayloadFormat::Auto => plaintext.trim_start().starts_with('{'),
+        PayloadFormat::Dotenv => false,
+        PayloadFormat::Json => true,
+    };
+    if !json {
+        return Ok(envfile::parse_entries(plaintext).into_iter().collect());
+    }
+
+    let object: BTreeMap<String, Value> = serde_json::from_str(plaintext)
+        .map_err(|e| ProviderError::Other(format!("decrypted bundle is not a JSON object: {e}")))?;
+    object
+        .into_iter()
+        .map(|(name, value)| match value {
+            Value::String(s) => Ok((name, s)),
+            Value::Number(_) | Value::Bool(_) => Ok((name, value.to_string())),
+            _ => Err(ProviderError::Other(format!(
+                "bundle value for {name} is not a string, number or boolean"
+            ))),
+        })
+        .collect()
+}
+
+#[async_trait]
+impl SecretProvider for KmsFileProvider {
+    async fn list_secrets(&self) -> Result<Vec<SecretMeta>, ProviderError> {
+        Ok(self
+            .secrets()
+            .await?
+            .into_keys()
+            .map(|name| SecretMeta {
+                name,
+                ..SecretMeta::default()
+            })
+            .collect())
+    }
+
+    async fn get_secret(&self, name: &str) -> Result<Option<String>, ProviderError> {
+        Ok(self.secrets().await?.get(name).cloned())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn payload_is_read_as_dotenv_or_json() {
+        let dotenv = parse_payload(
+            "# bundle\nDB_URL=postgres://x\n\nTOKEN=a=b\n",
+            PayloadFormat::Auto,
+        )
+        .unwrap();
+        assert_eq!(dotenv["DB_URL"], "postgres://x");
+        assert_eq!(dotenv["TOKEN"], "a=b");
+
+        let json = parse_payload(
+            r#"{"db-url": "postgres://x", "port": 5432}"#,
+            PayloadFormat::Auto,
+        )
+        .unwrap();
+        assert_eq!(json["db-url"], "postgres://x");
+        assert_eq!(json["port"], "5432");
+        assert!(parse_payload(r#"{"nested": {}}"#, PayloadFormat::Json).is_err());
+    }
+
+    #[test]
+    fn base64_ciphertext_is_decoded_and_binary_kept() {
+        assert_eq!(decode_ciphertext(b"AQID\n".to_vec()), vec![1, 2, 3]);
+        assert_eq!(decode_ciphertext(vec![0xff, 0x00]), vec![0xff, 0x00]);
+    }
+}
@@ -0,0 +1,248 @@
+use std::{collections::BTreeMap, env, path::PathBuf, sync::Mutex};
+
+use age::{Identity, IdentityFile, Recipient, secrecy::SecretString};
+use anyhow::{Result, bail};
+use async_trait::async_trait;
+use schemars::JsonSchema;
+use serde::Deserialize;
+
+use super::{Capabilities, ProviderError, SecretMeta, SecretProvider};
+use crate::{
+    crypto::{self, CryptoError},
+    envfile,
+};
+
+const DEFAULT_PASSPHRASE_ENV: &str = "ENVIT_VAULT_PASSPHRASE";
+
+#[derive(Debug, Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct Settings {
+    /// Encrypted store, usually committed next to `envit.toml`.
+    pub path: PathBuf,
+    /// age identity file; without one the store is passphrase-encrypted.
+    pub identity_file: Option<PathBuf>,
+    /// Extra age recipients (`age1...`) that can decrypt the store.
+    #[serde(default)]
+    pub recipients: Vec<String>,
+    #[serde(default = "default_passphrase_env")]
+    pub passphrase_env: String,
+}
+
+fn default_passphrase_env() -> String {
+    DEFAULT_PASSPHRASE_ENV.to_string()
+}
+
+impl Settings {
+    pub fn validate(&self) -> Result<()> {
+        if self.path.as_os_str().is_empty() {
+            bail!("provider.path must not be empty");
+        }
+        if !self.recipients.is_empty() && self.identity_file.is_none() {
+            bail!(
+                "provider.recipients requires provider.identity_file (passphrase stores have no recipients)"
+            );
+        }
+        for recipient in &self.recipients {
+            if let Err(err) = recipient.parse::<age::x25519::Recipient>() {
+                bail!("invalid age recipient {recipient}: {err}");
+            }
+        }
+        Ok(())
+    }
+}
+
+/// A TOML table of `name = "value"`, age-encrypted as a whole and ASCII
+/// armored so it can live in git. Decrypted once per invocation.
+pub struct LocalVaultProvider {
+    settings: Settings,
+    secrets: Mutex<Option<BTreeMap<String, String>>>,
+}
+
+impl LocalVaultProvider {
+    pub fn new(settings: Settings) -> Self {
+        Self {
+            settings,
+            secrets: Mutex::new(None),
+        }
+    }
+
+    fn secrets(&self) -> Result<BTreeMap<String, String>, ProviderError> {
+        if let Some(secrets) = self.secrets.lock().unwrap().clone() {
+            return Ok(secrets);
+        }
+        // A store that does not exist yet is empty; the first set creates it.
+        let secrets = match std::fs::read(&self.settings.path) {
+            Ok(armored) => {
+                let plaintext = decrypt(self.key()?, &armored)?;
+                toml::from_str(&plaintext).map_err(|e| {
+                    ProviderError::Other(format!("vault content is not a TOML table: {e}"))
+                })?
+            }
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => BTreeMap::new(),
+            Err(err) => return Err(vault_error("failed to read vault", err)),
+        };
+        *self.secrets.lock().unwrap() = Some(secrets.clone());
+        Ok(secrets)
+    }
+
+    fn key(&self) -> Result<Key, ProviderError> {
+        if let Some(path) = &self.settings.identity_file {
+            let file = IdentityFile::from_file(path.display().to_string()).map_err(|e| {
+                ProviderError::Auth(format!(
+                    "failed to read age identity file {}: {e}",
+                    path.display()
+                ))
+            })?;
+            return Ok(Key::Identities(file));
+        }
+        let passphrase = env::var(&self.settings.passphrase_env).map_err(|_| {
+            ProviderError::Auth(format!(
+                "{} is not set (vault passphrase)",
+                self.settings.passphrase_env
+            ))
+        })?;
+        Ok(Key::Passphrase(SecretString::from(passphrase)))
+    }
+
+    fn recipients(&self, key: &Key) -> Result<Vec<Box<dyn Recipient + Send>>, ProviderError> {
+        let mut recipients = match key {
+            Key::Passphrase(passphrase) => {
+                let recipient: Box<dyn Recipient + Send> =
+                    Box::new(age::scrypt::Recipient::new(passphrase.clone()));
+                vec![recipient]
+            }
+            Key::Identities(file) => file
+                .to_recipients()
+                .map_err(|e| vault_error("identity file has no usable recipient", e))?,
+        };
+        for recipient in &self.settings.recipients {
+            let recipient: age::x25519::Recipient = recipient
+                .parse()
+                .map_err(|e| ProviderError::Other(format!("invalid age recipient: {e}")))?;
+            recipients.push(Box::new(recipient));
+        }
+        Ok(recipients)
+    }
+
+    /// Re-encrypts the whole vault with `secrets` and swaps it in.
+    fn store(&self, secrets: BTreeMap<String, String>) -> Result<(), ProviderError> {
+        let key = self.key()?;
+        let plaintext =
+            toml::to_string(&secrets).map_err(|e| vault_error("failed to serialize vault", e))?;
+        let armored = crypto::age_encrypt(&self.recipients(&key)?, &plaintext)
+            .map_err(|e| vault_error("failed to encrypt vault", e))?;
+        envfile::write_atomic(&self.settings.path, &armored)
+            .map_err(|e| vault_error("failed to write vault", format!("{e:#}")))?;
+
+        *self.secrets.lock().unwrap() = Some(secrets);
+        Ok(())
+    }
+}
+
+enum Key {
+    Passphrase(SecretString),
+    Identities(IdentityFile<age::NoCallbacks>),
+}
+
+fn vault_error(context: &str, err: impl std::fmt::Display) -> ProviderError {
+    ProviderError::Other(format!("{context}: {err}"))
+}
+
+fn decrypt(key: Key, armored: &[u8]) -> Result<String, ProviderError> {
+    let identities: Vec<Box<dyn Identity>> = match key {
+        Key::Passphrase(passphrase) => {
+            vec![Box::new(age::scrypt::Identity::new(passphrase))]
+        }
+        Key::Identities(file) => file
+            .into_identities()
+            .map_err(|e| vault_error("unusable age identity", e))?,
+    };
+    crypto::age_decrypt(&identities, armored).map_err(|err| match err {
+        CryptoError::Decrypt(_) => ProviderError::Auth(format!("failed to decrypt vault: {err}")),
+        CryptoError::Format(_) => vault_error("invalid vault", err),
+    })
+}
+
+#[async_trait]
+impl SecretProvider for LocalVaultProvider {
+    async fn list_secrets(&self) -> Result<Vec<SecretMeta>, ProviderError> {
+        Ok(self
+            .secrets()?
+            .into_keys()
+            .map(|name| SecretMeta {
+                name,
+                ..SecretMeta::default()
+            })
+            .collect())
+    }
+
+    async fn get_secret(&self, name: &str) -> Result<Option<String>, ProviderError> {
+        Ok(self.secrets()?.get(name).cloned())
+    }
+
+    async fn set_secret(&self, name: &str, value: &str) -> Result<(), ProviderError> {
+        let mut secrets = self.secrets()?;
+        secrets.insert(name.to_string(), value.to_string());
+        self.store(secrets)
+    }
+
+    async fn delete_secret(&self, name: &str) -> Result<(), ProviderError> {
+        let mut secrets = self.secrets()?;
+        if secrets.remove(name).is_none() {
+            return Ok(());
+        }
+        self.store(secrets)
+    }
+
+    fn capabilities(&self) -> Capabilities {
+        Capabilities {
+            write: true,
+            delete: true,
+            ..Capabilities::default()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tempfile::TempDir;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn identity_file_vault_round_trips_and_extra_recipients_can_read() {
+        let dir = TempDir::new().unwrap();
+        let owner = age::x25519::Identity::generate();
+        let teammate = age::x25519::Identity::generate();
+        let identity_file = dir.path().join("key.txt");
+        std::fs::write(&identity_file, format!("{}\n", secret_string(&owner))).unwrap();
+
+        let vault = LocalVaultProvider::new(Settings {
+            path: dir.path().join("secrets.age"),
+            identity_file: Some(identity_file),
+            recipients: vec![teammate.to_public().to_string()],
+            passphrase_env: default_passphrase_env(),
+        });
+        assert!(vault.list_secrets().await.unwrap().is_empty());
+        vault
+            .set_secret("database-url", "postgres://")
+            .await
+            .unwrap();
+
+        let armored = std::fs::read(dir.path().join("secrets.age")).unwrap();
+        assert!(armored.starts_with(b"-----BEGIN AGE ENCRYPTED FILE-----"));
+        let key = Key::Identities(
+            IdentityFile::from_buffer(format!("{}\n", secret_string(&teammate)).as_bytes())
+                .unwrap(),
+        );
+        assert_eq!(
+            decrypt(key, &armored).unwrap(),
+            "database-url = \"postgres://\"\n"
+        );
+    }
+
+    fn secret_string(identity: &age::x25519::Identity) -> String {
+        use age::secrecy::ExposeSecret;
+        identity.to_string().expose_secret().to_string()
+    }
+}
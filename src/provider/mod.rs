@@ -1,31 +1,360 @@
+#[cfg(feature = "azure")]
 pub mod azure_key_vault;
+pub mod cache;
+#[cfg(feature = "cloudflare")]
+pub mod cloudflare;
+#[cfg(feature = "exec")]
+pub mod exec;
+#[cfg(feature = "http-json")]
+pub mod http_json;
+#[cfg(feature = "keeper")]
+pub mod keeper;
+#[cfg(feature = "kms-file")]
+pub mod kms_file;
+#[cfg(feature = "local-vault")]
+pub mod local_vault;
+#[cfg(feature = "netlify")]
+pub mod netlify;
+#[cfg(feature = "railway")]
+pub mod railway;
 
-use std::{collections::HashMap, env, fs, path::Path};
+use std::{
+    collections::HashMap,
+    env, fs,
+    io::Write,
+    path::{Path, PathBuf},
+    sync::Mutex,
+    time::SystemTime,
+};
 
 use anyhow::{Context, Result, anyhow};
 use async_trait::async_trait;
+use schemars::{JsonSchema, Schema, SchemaGenerator};
+use serde::de::DeserializeOwned;
+use serde_json::json;
 use thiserror::Error;
 
 use crate::config::ProviderConfig;
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Default)]
 pub struct SecretMeta {
     pub name: String,
+    /// The current version, where the listing names it.
+    pub version: Option<String>,
+    /// When the current version was last updated, where the listing says;
+    /// tells a later version apart where the listing has no version ids.
+    pub updated: Option<SystemTime>,
+    /// When the secret expires, where the listing says.
+    pub expires: Option<SystemTime>,
+}
+
+impl SecretMeta {
+    /// Whether `fetched` belongs to a later version than the one listed.
+    /// Unknown where the listing or the read leaves both markers out.
+    pub fn moved(&self, fetched: &Fetched) -> bool {
+        match (&self.version, &fetched.version) {
+            (Some(listed), Some(read)) => listed != read,
+            _ => matches!(
+                (self.updated, fetched.updated),
+                (Some(listed), Some(read)) if listed != read
+            ),
+        }
+    }
+}
+
+/// A value with the version it belongs to, from `fetch_secret`.
+#[derive(Debug, Clone, Default)]
+pub struct Fetched {
+    pub value: String,
+    pub version: Option<String>,
+    pub updated: Option<SystemTime>,
+}
+
+/// What a provider keeps about a secret besides its value, for `envit
+/// audit`; None where the backend does not say.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SecretDetails {
+    pub name: String,
+    pub created: Option<SystemTime>,
+    pub updated: Option<SystemTime>,
+    /// False for secrets that exist but cannot be read, e.g. disabled.
+    pub enabled: Option<bool>,
+    pub expires: Option<SystemTime>,
+}
+
+/// One version of a secret, for `envit history`; None where the backend
+/// does not say.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SecretVersion {
+    pub id: String,
+    pub created: Option<SystemTime>,
+    pub updated: Option<SystemTime>,
+    pub enabled: Option<bool>,
 }
 
 #[derive(Debug, Error)]
 pub enum ProviderError {
     #[error("provider transport/auth error: {0}")]
     Other(String),
+    #[error("provider authentication failed: {0}")]
+    Auth(String),
+    #[error("operation not supported by this provider: {0}")]
+    Unsupported(String),
+    #[error("provider throttled the request: {0}")]
+    Throttled(String),
+}
+
+impl ProviderError {
+    /// Whether `err` was caused by rejected or missing credentials, which
+    /// retrying will not fix.
+    pub fn is_auth(err: &anyhow::Error) -> bool {
+        err.chain()
+            .any(|cause| matches!(cause.downcast_ref(), Some(ProviderError::Auth(_))))
+    }
+
+    /// Classifies a failed HTTP response: 401/403 mean the credentials are
+    /// wrong or lack access, 429 asks to slow down, anything else may
+    /// succeed on retry.
+    #[cfg_attr(
+        not(any(
+            feature = "azure",
+            feature = "cloudflare",
+            feature = "http-json",
+            feature = "netlify",
+            feature = "railway"
+        )),
+        allow(dead_code)
+    )]
+    pub fn http(status: reqwest::StatusCode, message: String) -> Self {
+        match status {
+            reqwest::StatusCode::UNAUTHORIZED | reqwest::StatusCode::FORBIDDEN => {
+                Self::Auth(message)
+            }
+            reqwest::StatusCode::TOO_MANY_REQUESTS => Self::Throttled(message),
+            _ => Self::Other(message),
+        }
+    }
+}
+
+/// What a provider supports beyond reading, known without contacting it,
+/// so commands can adapt or fail before changing anything.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Capabilities {
+    /// `set_secret` creates and overwrites secrets.
+    pub write: bool,
+    /// `delete_secret` removes secrets.
+    pub delete: bool,
+    /// Deleted secrets stay recoverable for a while.
+    pub soft_delete: bool,
+    /// `secret_version` returns version ids and older versions stay
+    /// readable (`secret_versions`, `get_secret_version`).
+    pub versions: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Capability {
+    Write,
+    Delete,
+}
+
+impl Capabilities {
+    pub fn supports(self, capability: Capability) -> bool {
+        match capability {
+            Capability::Write => self.write,
+            Capability::Delete => self.delete,
+        }
+    }
+
+    /// Fails with e.g. "provider netlify does not support push (it is
+    /// read-only)" unless `capability` is there.
+    pub fn require(self, capability: Capability, kind: &str, command: &str) -> Result<()> {
+        if self.supports(capability) {
+            return Ok(());
+        }
+        let reason = match capability {
+            Capability::Write => "it is read-only",
+            Capability::Delete => "it cannot delete secrets",
+        };
+        Err(anyhow!(
+            "provider {kind} does not support {command} ({reason})"
+        ))
+    }
 }
 
 /// Provider contract:
 /// - get_secret returns Ok(None) for NotFound
-/// - auth/network and other failures return Err
+/// - credential problems return Auth; rate limiting (HTTP 429) returns Throttled;
+///   network and other failures return Other
+/// - identity returns the authenticated principal when the backend exposes one
+/// - set_secret creates or overwrites a secret; read-only providers return Unsupported
+/// - delete_secret removes a secret (Key Vault disables it, so it stays recoverable);
+///   providers that cannot return Unsupported
+/// - secret_version returns the backend's version id when it keeps versions
+/// - fetch_secret reads a value together with the version it belongs to; by
+///   default the value is read pinned to secret_version, backends that return
+///   both at once override it
+/// - secret_versions lists a secret's versions, oldest first, and
+///   get_secret_version reads one (Ok(None) when it does not exist); providers
+///   without versions return Unsupported
+/// - secret_details lists every secret, unreadable ones included, with the
+///   metadata the backend keeps; by default names only, from list_secrets
+/// - capabilities matches the above: write/delete are set exactly when
+///   set_secret/delete_secret are implemented, versions when secret_version and
+///   the two above are
 #[async_trait]
 pub trait SecretProvider: Send + Sync {
     async fn list_secrets(&self) -> Result<Vec<SecretMeta>, ProviderError>;
     async fn get_secret(&self, name: &str) -> Result<Option<String>, ProviderError>;
+
+    async fn set_secret(&self, name: &str, _value: &str) -> Result<(), ProviderError> {
+        Err(ProviderError::Unsupported(format!("writing secret {name}")))
+    }
+
+    async fn delete_secret(&self, name: &str) -> Result<(), ProviderError> {
+        Err(ProviderError::Unsupported(format!(
+            "deleting secret {name}"
+        )))
+    }
+
+    async fn identity(&self) -> Result<Option<String>, ProviderError> {
+        Ok(None)
+    }
+
+    async fn secret_version(&self, _name: &str) -> Result<Option<String>, ProviderError> {
+        Ok(None)
+    }
+
+    async fn fetch_secret(&self, name: &str) -> Result<Option<Fetched>, ProviderError> {
+        let version = self.secret_version(name).await?;
+        let value = match &version {
+            Some(version) => self.get_secret_version(name, version).await?,
+            None => self.get_secret(name).await?,
+        };
+        Ok(value.map(|value| Fetched {
+            value,
+            version,
+            updated: None,
+        }))
+    }
+
+    async fn secret_versions(&self, name: &str) -> Result<Vec<SecretVersion>, ProviderError> {
+        Err(ProviderError::Unsupported(format!(
+            "version history of secret {name}"
+        )))
+    }
+
+    async fn get_secret_version(
+        &self,
+        name: &str,
+        version: &str,
+    ) -> Result<Option<String>, ProviderError> {
+        Err(ProviderError::Unsupported(format!(
+            "reading version {version} of secret {name}"
+        )))
+    }
+
+    async fn secret_details(&self) -> Result<Vec<SecretDetails>, ProviderError> {
+        Ok(self
+            .list_secrets()
+            .await?
+            .into_iter()
+            .map(|meta| SecretDetails {
+                name: meta.name,
+                ..SecretDetails::default()
+            })
+            .collect())
+    }
+
+    /// Read-only unless a provider says otherwise.
+    fn capabilities(&self) -> Capabilities {
+        Capabilities::default()
+    }
+}
+
+/// A provider kind and the cargo feature that compiles it in.
+pub struct Kind {
+    pub name: &'static str,
+    pub feature: &'static str,
+    pub compiled: bool,
+}
+
+pub const KINDS: &[Kind] = &[
+    Kind {
+        name: "azure_key_vault",
+        feature: "azure",
+        compiled: cfg!(feature = "azure"),
+    },
+    Kind {
+        name: "cloudflare_kv",
+        feature: "cloudflare",
+        compiled: cfg!(feature = "cloudflare"),
+    },
+    Kind {
+        name: "exec",
+        feature: "exec",
+        compiled: cfg!(feature = "exec"),
+    },
+    Kind {
+        name: "http_json",
+        feature: "http-json",
+        compiled: cfg!(feature = "http-json"),
+    },
+    Kind {
+        name: "keeper",
+        feature: "keeper",
+        compiled: cfg!(feature = "keeper"),
+    },
+    Kind {
+        name: "kms_file",
+        feature: "kms-file",
+        compiled: cfg!(feature = "kms-file"),
+    },
+    Kind {
+        name: "local_vault",
+        feature: "local-vault",
+        compiled: cfg!(feature = "local-vault"),
+    },
+    Kind {
+        name: "netlify",
+        feature: "netlify",
+        compiled: cfg!(feature = "netlify"),
+    },
+    Kind {
+        name: "railway",
+        feature: "railway",
+        compiled: cfg!(feature = "railway"),
+    },
+];
+
+pub fn validate(cfg: &ProviderConfig) -> Result<()> {
+    match cfg.kind.as_str() {
+        #[cfg(feature = "azure")]
+        "azure_key_vault" => settings::<azure_key_vault::Settings>(cfg)?.validate(),
+        #[cfg(feature = "cloudflare")]
+        "cloudflare_kv" => settings::<cloudflare::Settings>(cfg)?.validate(),
+        #[cfg(feature = "exec")]
+        "exec" => settings::<exec::Settings>(cfg)?.validate(),
+        #[cfg(feature = "http-json")]
+        "http_json" => settings::<http_json::Settings>(cfg)?.validate(),
+        #[cfg(feature = "keeper")]
+        "keeper" => settings::<keeper::Settings>(cfg)?.validate(),
+        #[cfg(feature = "kms-file")]
+        "kms_file" => settings::<kms_file::Settings>(cfg)?.validate(),
+        #[cfg(feature = "local-vault")]
+        "local_vault" => settings::<local_vault::Settings>(cfg)?.validate(),
+        #[cfg(feature = "netlify")]
+        "netlify" => settings::<netlify::Settings>(cfg)?.validate(),
+        #[cfg(feature = "railway")]
+        "railway" => settings::<railway::Settings>(cfg)?.validate(),
+        // The fixture stands in for every kind, compiled in or not.
+        other
+            if env::var_os("ENVIT_TEST_SECRETS_FILE").is_some()
+                && KINDS.iter().any(|k| k.name == other) =>
+        {
+            Ok(())
+        }
+        other => Err(unavailable(other)),
+    }
 }
 
 pub fn build_provider(cfg: &ProviderConfig) -> Result<Box<dyn SecretProvider>> {
@@ -34,19 +363,167 @@ pub fn build_provider(cfg: &ProviderConfig) -> Result<Box<dyn SecretProvider>> {
     }
 
     match cfg.kind.as_str() {
+        #[cfg(feature = "azure")]
         "azure_key_vault" => Ok(Box::new(azure_key_vault::AzureKeyVaultProvider::new(
-            cfg.vault_url.clone(),
+            settings(cfg)?,
         ))),
-        other => Err(anyhow!("unsupported provider kind: {other}")),
+        #[cfg(feature = "cloudflare")]
+        "cloudflare_kv" => Ok(Box::new(cloudflare::CloudflareKvProvider::new(settings(
+            cfg,
+        )?)?)),
+        #[cfg(feature = "exec")]
+        "exec" => Ok(Box::new(exec::ExecProvider::new(settings(cfg)?))),
+        #[cfg(feature = "http-json")]
+        "http_json" => Ok(Box::new(http_json::HttpJsonProvider::new(settings(cfg)?))),
+        #[cfg(feature = "keeper")]
+        "keeper" => Ok(Box::new(keeper::KeeperProvider::new(settings(cfg)?)?)),
+        #[cfg(feature = "kms-file")]
+        "kms_file" => Ok(Box::new(kms_file::KmsFileProvider::new(settings(cfg)?))),
+        #[cfg(feature = "local-vault")]
+        "local_vault" => Ok(Box::new(local_vault::LocalVaultProvider::new(settings(
+            cfg,
+        )?))),
+        #[cfg(feature = "netlify")]
+        "netlify" => Ok(Box::new(netlify::NetlifyProvider::new(settings(cfg)?)?)),
+        #[cfg(feature = "railway")]
+        "railway" => Ok(Box::new(railway::RailwayProvider::new(settings(cfg)?)?)),
+        other => Err(unavailable(other)),
+    }
+}
+
+fn unavailable(kind: &str) -> anyhow::Error {
+    match KINDS.iter().find(|k| k.name == kind) {
+        Some(k) => anyhow!(
+            "provider kind {kind} is not compiled into this build (rebuild with --features {})",
+            k.feature
+        ),
+        None => anyhow!(
+            "unsupported provider kind: {kind} (expected one of: {})",
+            KINDS.iter().map(|k| k.name).collect::<Vec<_>>().join(", ")
+        ),
+    }
+}
+
+#[cfg_attr(
+    not(any(
+        feature = "azure",
+        feature = "cloudflare",
+        feature = "exec",
+        feature = "http-json",
+        feature = "keeper",
+        feature = "kms-file",
+        feature = "local-vault",
+        feature = "netlify",
+        feature = "railway"
+    )),
+    allow(dead_code)
+)]
+fn settings<T: DeserializeOwned>(cfg: &ProviderConfig) -> Result<T> {
+    toml::Value::Table(cfg.settings.clone())
+        .try_into()
+        .with_context(|| format!("invalid [provider] settings for kind {}", cfg.kind))
+}
+
+/// JSON Schema of each compiled-in kind's settings, with its `kind` and the
+/// `extra` properties the surrounding table allows (see `config::SourceConfig`).
+#[cfg_attr(
+    not(any(
+        feature = "azure",
+        feature = "cloudflare",
+        feature = "exec",
+        feature = "http-json",
+        feature = "keeper",
+        feature = "kms-file",
+        feature = "local-vault",
+        feature = "netlify",
+        feature = "railway"
+    )),
+    allow(unused_variables)
+)]
+pub fn settings_schemas(generator: &mut SchemaGenerator, extra: &[(&str, Schema)]) -> Vec<Schema> {
+    vec![
+        #[cfg(feature = "azure")]
+        kind_schema::<azure_key_vault::Settings>(generator, "azure_key_vault", extra),
+        #[cfg(feature = "cloudflare")]
+        kind_schema::<cloudflare::Settings>(generator, "cloudflare_kv", extra),
+        #[cfg(feature = "exec")]
+        kind_schema::<exec::Settings>(generator, "exec", extra),
+        #[cfg(feature = "http-json")]
+        kind_schema::<http_json::Settings>(generator, "http_json", extra),
+        #[cfg(feature = "keeper")]
+        kind_schema::<keeper::Settings>(generator, "keeper", extra),
+        #[cfg(feature = "kms-file")]
+        kind_schema::<kms_file::Settings>(generator, "kms_file", extra),
+        #[cfg(feature = "local-vault")]
+        kind_schema::<local_vault::Settings>(generator, "local_vault", extra),
+        #[cfg(feature = "netlify")]
+        kind_schema::<netlify::Settings>(generator, "netlify", extra),
+        #[cfg(feature = "railway")]
+        kind_schema::<railway::Settings>(generator, "railway", extra),
+    ]
+}
+
+#[cfg_attr(
+    not(any(
+        feature = "azure",
+        feature = "cloudflare",
+        feature = "exec",
+        feature = "http-json",
+        feature = "keeper",
+        feature = "kms-file",
+        feature = "local-vault",
+        feature = "netlify",
+        feature = "railway"
+    )),
+    allow(dead_code)
+)]
+fn kind_schema<T: JsonSchema>(
+    generator: &mut SchemaGenerator,
+    kind: &str,
+    extra: &[(&str, Schema)],
+) -> Schema {
+    let mut schema = T::json_schema(generator);
+    let object = schema.ensure_object();
+    let properties = object
+        .entry("properties")
+        .or_insert_with(|| json!({}))
+        .as_object_mut()
+        .expect("settings are a struct");
+    properties.insert("kind".to_string(), json!({ "const": kind }));
+    for (name, property) in extra {
+        properties.insert(name.to_string(), property.clone().to_value());
     }
+    if let Some(required) = object
+        .entry("required")
+        .or_insert_with(|| json!([]))
+        .as_array_mut()
+    {
+        required.insert(0, json!("kind"));
+    }
+    schema
 }
 
+/// Test double driven by `ENVIT_TEST_SECRETS_FILE`. Writes are appended to
+/// the file as `name=value` lines, so later lines win on the next read;
+/// deletes append `!deleted:name`.
 #[derive(Debug, Default)]
 struct FixtureProvider {
+    path: PathBuf,
     listed: Vec<String>,
-    values: HashMap<String, String>,
+    values: Mutex<HashMap<String, String>>,
     error_on_get: Vec<String>,
     missing_on_get: Vec<String>,
+    identity: Option<String>,
+    /// Every value a secret had, oldest first. Version ids count from 1,
+    /// so a secret's current version is the length of its history.
+    history: Mutex<HashMap<String, Vec<String>>>,
+    /// `!details:name created=... enabled=false`, for `envit audit`.
+    details: HashMap<String, SecretDetails>,
+    /// Secrets whose next write is rejected as throttled.
+    throttled: Mutex<Vec<String>>,
+    unauthenticated: bool,
+    /// Reports no capabilities, like a read-only provider.
+    read_only: bool,
 }
 
 impl FixtureProvider {
@@ -54,12 +531,47 @@ impl FixtureProvider {
         let raw = fs::read_to_string(path)
             .with_context(|| format!("failed to read fixture secrets file: {}", path.display()))?;
 
-        let mut provider = Self::default();
+        let mut provider = Self {
+            path: path.to_path_buf(),
+            ..Self::default()
+        };
         for line in raw.lines() {
             let trimmed = line.trim();
             if trimmed.is_empty() || trimmed.starts_with('#') {
                 continue;
             }
+            if trimmed == "!unauthenticated" {
+                provider.unauthenticated = true;
+                continue;
+            }
+            if trimmed == "!read_only" {
+                provider.read_only = true;
+                continue;
+            }
+            if let Some(principal) = trimmed.strip_prefix("!identity:") {
+                provider.identity = Some(principal.trim().to_string());
+                continue;
+            }
+            if let Some(details) = trimmed.strip_prefix("!details:") {
+                let details = parse_fixture_details(details)
+                    .with_context(|| format!("invalid fixture details: {trimmed}"))?;
+                provider.details.insert(details.name.clone(), details);
+                continue;
+            }
+            if let Some(name) = trimmed.strip_prefix("!deleted:") {
+                let name = name.trim();
+                provider.listed.retain(|it| it != name);
+                provider.values.get_mut().unwrap().remove(name);
+                continue;
+            }
+            if let Some(name) = trimmed.strip_prefix("!throttle:") {
+                provider
+                    .throttled
+                    .get_mut()
+                    .unwrap()
+                    .push(name.trim().to_string());
+                continue;
+            }
             if let Some(name) = trimmed.strip_prefix("!error:") {
                 provider.error_on_get.push(name.trim().to_string());
                 provider.listed.push(name.trim().to_string());
@@ -81,22 +593,79 @@ impl FixtureProvider {
                 return Err(anyhow!("invalid fixture entry (empty name): {trimmed}"));
             }
             provider.listed.push(name.to_string());
-            provider.values.insert(name.to_string(), value);
+            provider
+                .history
+                .get_mut()
+                .unwrap()
+                .entry(name.to_string())
+                .or_default()
+                .push(value.clone());
+            provider
+                .values
+                .get_mut()
+                .unwrap()
+                .insert(name.to_string(), value);
         }
 
         provider.listed.sort();
         provider.listed.dedup();
         Ok(provider)
     }
+
+    /// The id of the value `get_secret` returns.
+    fn current_version(&self, name: &str) -> Option<String> {
+        self.values.lock().unwrap().get(name)?;
+        let count = self.history.lock().unwrap().get(name).map_or(0, Vec::len);
+        (count > 0).then(|| count.to_string())
+    }
+
+    fn check_auth(&self) -> Result<(), ProviderError> {
+        if self.unauthenticated {
+            return Err(ProviderError::Auth(
+                "fixture induced authentication error".to_string(),
+            ));
+        }
+        Ok(())
+    }
+}
+
+/// `name created=<RFC 3339> updated=... expires=... enabled=<bool>`.
+fn parse_fixture_details(raw: &str) -> Result<SecretDetails> {
+    let mut fields = raw.split_whitespace();
+    let mut details = SecretDetails {
+        name: fields.next().context("missing name")?.to_string(),
+        ..SecretDetails::default()
+    };
+    for field in fields {
+        let (name, value) = field.split_once('=').context("expected field=value")?;
+        let time = || humantime::parse_rfc3339(value).context("expected an RFC 3339 time");
+        match name {
+            "created" => details.created = Some(time()?),
+            "updated" => details.updated = Some(time()?),
+            "expires" => details.expires = Some(time()?),
+            "enabled" => details.enabled = Some(value.parse()?),
+            _ => return Err(anyhow!("unknown field {name}")),
+        }
+    }
+    Ok(details)
 }
 
 #[async_trait]
 impl SecretProvider for FixtureProvider {
     async fn list_secrets(&self) -> Result<Vec<SecretMeta>, ProviderError> {
+        self.check_auth()?;
         Ok(self
             .listed
             .iter()
-            .map(|name| SecretMeta { name: name.clone() })
+            .map(|name| {
+                let details = self.details.get(name);
+                SecretMeta {
+                    name: name.clone(),
+                    version: self.current_version(name),
+                    updated: details.and_then(|details| details.updated),
+                    expires: details.and_then(|details| details.expires),
+                }
+            })
             .collect())
     }
 
@@ -109,6 +678,105 @@ impl SecretProvider for FixtureProvider {
         if self.missing_on_get.iter().any(|it| it == name) {
             return Ok(None);
         }
-        Ok(self.values.get(name).cloned())
+        Ok(self.values.lock().unwrap().get(name).cloned())
+    }
+
+    async fn set_secret(&self, name: &str, value: &str) -> Result<(), ProviderError> {
+        {
+            let mut throttled = self.throttled.lock().unwrap();
+            if let Some(index) = throttled.iter().position(|it| it == name) {
+                throttled.remove(index);
+                return Err(ProviderError::Throttled(format!(
+                    "fixture induced throttling for secret: {name}"
+                )));
+            }
+        }
+        fs::OpenOptions::new()
+            .append(true)
+            .open(&self.path)
+            .and_then(|mut f| writeln!(f, "{name}={value}"))
+            .map_err(|e| ProviderError::Other(format!("fixture write failed: {e}")))?;
+        self.history
+            .lock()
+            .unwrap()
+            .entry(name.to_string())
+            .or_default()
+            .push(value.to_string());
+        self.values
+            .lock()
+            .unwrap()
+            .insert(name.to_string(), value.to_string());
+        Ok(())
+    }
+
+    async fn delete_secret(&self, name: &str) -> Result<(), ProviderError> {
+        fs::OpenOptions::new()
+            .append(true)
+            .open(&self.path)
+            .and_then(|mut f| writeln!(f, "!deleted:{name}"))
+            .map_err(|e| ProviderError::Other(format!("fixture write failed: {e}")))?;
+        self.values.lock().unwrap().remove(name);
+        Ok(())
+    }
+
+    async fn identity(&self) -> Result<Option<String>, ProviderError> {
+        self.check_auth()?;
+        Ok(self.identity.clone())
+    }
+
+    async fn secret_version(&self, name: &str) -> Result<Option<String>, ProviderError> {
+        Ok(self.current_version(name))
+    }
+
+    async fn secret_versions(&self, name: &str) -> Result<Vec<SecretVersion>, ProviderError> {
+        self.check_auth()?;
+        let count = self.history.lock().unwrap().get(name).map_or(0, Vec::len);
+        Ok((1..=count)
+            .map(|id| SecretVersion {
+                id: id.to_string(),
+                enabled: Some(true),
+                ..SecretVersion::default()
+            })
+            .collect())
+    }
+
+    async fn get_secret_version(
+        &self,
+        name: &str,
+        version: &str,
+    ) -> Result<Option<String>, ProviderError> {
+        let history = self.history.lock().unwrap();
+        Ok(version
+            .parse::<usize>()
+            .ok()
+            .and_then(|id| history.get(name)?.get(id.checked_sub(1)?).cloned()))
+    }
+
+    async fn secret_details(&self) -> Result<Vec<SecretDetails>, ProviderError> {
+        self.check_auth()?;
+        let mut names: Vec<_> = self.listed.iter().chain(self.details.keys()).collect();
+        names.sort_unstable();
+        names.dedup();
+        Ok(names
+            .into_iter()
+            .map(|name| {
+                self.details.get(name).cloned().unwrap_or(SecretDetails {
+                    name: name.clone(),
+                    ..SecretDetails::default()
+                })
+            })
+            .collect())
+    }
+
+    fn capabilities(&self) -> Capabilities {
+        if self.read_only {
+            return Capabilities::default();
+        }
+        Capabilities {
+            write: true,
+            delete: true,
+            soft_delete: false,
+            versions: true,
+        }
     }
 }
@@ -0,0 +1,238 @@
+use std::{collections::BTreeMap, env, sync::Mutex};
+
+use anyhow::{Context, Result, bail};
+use async_trait::async_trait;
+use reqwest::{Client, Url};
+use schemars::JsonSchema;
+use serde::Deserialize;
+
+use super::{ProviderError, SecretMeta, SecretProvider};
+
+const API_BASE: &str = "https://api.netlify.com/api/v1";
+const DEFAULT_TOKEN_ENV: &str = "NETLIFY_AUTH_TOKEN";
+const DEFAULT_CONTEXT: &str = "dev";
+const CONTEXTS: &[&str] = &[
+    "production",
+    "deploy-preview",
+    "branch-deploy",
+    "dev",
+    "branch",
+];
+
+#[derive(Debug, Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct Settings {
+    /// Team slug the site belongs to.
+    pub account_id: String,
+    pub site_id: String,
+    /// Deploy context whose values are pulled.
+    #[serde(default = "default_context")]
+    pub context: String,
+    /// Branch name for `context = "branch"`.
+    pub branch: Option<String>,
+    /// Only variables available to this scope (builds, functions, runtime,
+    /// post-processing).
+    pub scope: Option<String>,
+    #[serde(default = "default_token_env")]
+    pub api_token_env: String,
+}
+
+fn default_context() -> String {
+    DEFAULT_CONTEXT.to_string()
+}
+
+fn default_token_env() -> String {
+    DEFAULT_TOKEN_ENV.to_string()
+}
+
+impl Settings {
+    pub fn validate(&self) -> Result<()> {
+        if self.account_id.trim().is_empty() {
+            bail!("provider.account_id must not be empty");
+        }
+        if self.site_id.trim().is_empty() {
+            bail!("provider.site_id must not be empty");
+        }
+        if !CONTEXTS.contains(&self.context.as_str()) {
+            bail!(
+                "provider.context must be one of: {} (got {})",
+                CONTEXTS.join(", "),
+                self.context
+            );
+        }
+        match (self.context.as_str(), &self.branch) {
+            ("branch", None) => bail!("provider.branch is required for context = \"branch\""),
+            (context, Some(_)) if context != "branch" => {
+                bail!("provider.branch only applies to context = \"branch\"")
+            }
+            _ => {}
+        }
+        if self.api_token_env.trim().is_empty() {
+            bail!("provider.api_token_env must not be empty");
+        }
+        Ok(())
+    }
+}
+
+/// Site-level environment variables of one Netlify site, resolved for a
+/// single deploy context the way Netlify does: a context-specific value wins
+/// over the `all` value. Fetched once and served from memory.
+pub struct NetlifyProvider {
+    settings: Settings,
+    url: Url,
+    token: String,
+    http: Client,
+    variables: Mutex<Option<BTreeMap<String, Option<String>>>>,
+}
+
+impl NetlifyProvider {
+    pub fn new(settings: Settings) -> Result<Self> {
+        let token = env::var(&settings.api_token_env)
+            .with_context(|| format!("{} is not set", settings.api_token_env))?;
+        let mut url = Url::parse(API_BASE).expect("valid API base");
+        url.path_segments_mut()
+            .expect("https URL has path segments")
+            .extend(["accounts", &settings.account_id, "env"]);
+        url.query_pairs_mut()
+            .append_pair("site_id", &settings.site_id);
+        if let Some(scope) = &settings.scope {
+            url.query_pairs_mut().append_pair("scope", scope);
+        }
+        Ok(Self {
+            settings,
+            url,
+            token,
+            http: Client::new(),
+            variables: Mutex::new(None),
+        })
+    }
+
+    async fn variables(&self) -> Result<BTreeMap<String, Option<String>>, ProviderError> {
+        if let Some(variables) = self.variables.lock().unwrap().clone() {
+            return Ok(variables);
+        }
+        let res = self
+            .http
+            .get(self.url.clone())
+            .bearer_auth(&self.token)
+            .send()
+            .await
+            .map_err(|e| ProviderError::Other(format!("request failed: {e}")))?;
+        if !res.status().is_success() {
+            return Err(ProviderError::http(
+                res.status(),
+                format!(
+                    "netlify env request failed ({}) for site {}",
+                    res.status(),
+                    self.settings.site_id
+                ),
+            ));
+        }
+        let listed: Vec<EnvVar> = res
+            .json()
+            .await
+            .map_err(|e| ProviderError::Other(format!("invalid response body: {e}")))?;
+
+        let variables: BTreeMap<_, _> = listed
+            .into_iter()
+            .map(|var| {
+                let value = var.value_for(&self.settings.context, self.settings.branch.as_deref());
+                (var.key, value)
+            })
+            .collect();
+        *self.variables.lock().unwrap() = Some(variables.clone());
+        Ok(variables)
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct EnvVar {
+    key: String,
+    #[serde(default)]
+    values: Vec<EnvValue>,
+}
+
+#[derive(Debug, Deserialize)]
+struct EnvValue {
+    /// Absent for secret values Netlify does not hand back.
+    value: Option<String>,
+    context: String,
+    context_parameter: Option<String>,
+}
+
+impl EnvVar {
+    /// Most specific value first: branch, then the context, then `all`.
+    fn value_for(&self, context: &str, branch: Option<&str>) -> Option<String> {
+        let matches = |want: &str| {
+            self.values.iter().find(|v| {
+                v.context == want && (want != "branch" || v.context_parameter.as_deref() == branch)
+            })
+        };
+        let specific = if context == "branch" {
+            matches("branch").or_else(|| matches("branch-deploy"))
+        } else {
+            matches(context)
+        };
+        specific
+            .or_else(|| matches("all"))
+            .and_then(|v| v.value.clone())
+            .filter(|v| !v.is_empty())
+    }
+}
+
+#[async_trait]
+impl SecretProvider for NetlifyProvider {
+    async fn list_secrets(&self) -> Result<Vec<SecretMeta>, ProviderError> {
+        Ok(self
+            .variables()
+            .await?
+            .into_keys()
+            .map(|name| SecretMeta {
+                name,
+                ..SecretMeta::default()
+            })
+            .collect())
+    }
+
+    async fn get_secret(&self, name: &str) -> Result<Option<String>, ProviderError> {
+        Ok(self.variables().await?.get(name).cloned().flatten())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn context_value_wins_over_all_and_branch_falls_back_to_branch_deploy() {
+        let var: EnvVar = serde_json::from_str(
+            r#"{
+                "key": "API_URL",
+                "values": [
+                    { "value": "https://api.example.com", "context": "all" },
+                    { "value": "http://localhost:3000", "context": "dev" },
+                    { "value": "https://staging.example.com", "context": "branch-deploy" },
+                    { "value": "https://feat.example.com", "context": "branch", "context_parameter": "feat" }
+                ]
+            }"#,
+        )
+        .unwrap();
+
+        assert_eq!(
+            var.value_for("dev", None).as_deref(),
+            Some("http://localhost:3000")
+        );
+        assert_eq!(
+            var.value_for("production", None).as_deref(),
+            Some("https://api.example.com")
+        );
+        assert_eq!(
+            var.value_for("branch", Some("feat")).as_deref(),
+            Some("https://feat.example.com")
+        );
+        assert_eq!(
+            var.value_for("branch", Some("other")).as_deref(),
+            Some("https://staging.example.com")
+        );
+    }
+}
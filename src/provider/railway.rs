@@ -0,0 +1,229 @@
+use std::{collections::BTreeMap, env, sync::Mutex};
+
+use anyhow::{Result, anyhow, bail};
+use async_trait::async_trait;
+use reqwest::Client;
+use schemars::JsonSchema;
+use serde::Deserialize;
+use serde_json::{Value, json};
+
+use super::{Capabilities, ProviderError, SecretMeta, SecretProvider};
+
+const API_URL: &str = "https://backboard.railway.com/graphql/v2";
+const DEFAULT_PROJECT_TOKEN_ENV: &str = "RAILWAY_TOKEN";
+const DEFAULT_API_TOKEN_ENV: &str = "RAILWAY_API_TOKEN";
+
+const VARIABLES_QUERY: &str = concat!(
+    "query variables($projectId: String!, $environmentId: String!, $serviceId: String) {",
+    " variables(projectId: $projectId, environmentId: $environmentId, serviceId: $serviceId) }",
+);
+const UPSERT_MUTATION: &str =
+    "mutation variableUpsert($input: VariableUpsertInput!) { variableUpsert(input: $input) }";
+
+#[derive(Debug, Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct Settings {
+    pub project_id: String,
+    pub environment_id: String,
+    /// Without a service, the environment's shared variables are read.
+    pub service_id: Option<String>,
+    /// Project token (scoped to one environment); preferred when set.
+    #[serde(default = "default_project_token_env")]
+    pub project_token_env: String,
+    /// Account or team token, used when no project token is set.
+    #[serde(default = "default_api_token_env")]
+    pub api_token_env: String,
+}
+
+fn default_project_token_env() -> String {
+    DEFAULT_PROJECT_TOKEN_ENV.to_string()
+}
+
+fn default_api_token_env() -> String {
+    DEFAULT_API_TOKEN_ENV.to_string()
+}
+
+impl Settings {
+    pub fn validate(&self) -> Result<()> {
+        if self.project_id.trim().is_empty() {
+            bail!("provider.project_id must not be empty");
+        }
+        if self.environment_id.trim().is_empty() {
+            bail!("provider.environment_id must not be empty");
+        }
+        if self
+            .service_id
+            .as_deref()
+            .is_some_and(|id| id.trim().is_empty())
+        {
+            bail!("provider.service_id must not be empty");
+        }
+        Ok(())
+    }
+}
+
+enum Token {
+    Project(String),
+    Api(String),
+}
+
+/// Service (or shared) variables of one Railway environment, read through
+/// the public GraphQL API. All variables come back in one query and are
+/// served from memory; sealed variables are never returned by Railway.
+pub struct RailwayProvider {
+    settings: Settings,
+    token: Token,
+    http: Client,
+    variables: Mutex<Option<BTreeMap<String, String>>>,
+}
+
+impl RailwayProvider {
+    pub fn new(settings: Settings) -> Result<Self> {
+        let token = match (
+            env::var(&settings.project_token_env),
+            env::var(&settings.api_token_env),
+        ) {
+            (Ok(token), _) if !token.is_empty() => Token::Project(token),
+            (_, Ok(token)) if !token.is_empty() => Token::Api(token),
+            _ => {
+                return Err(anyhow!(
+                    "neither {} nor {} is set",
+                    settings.project_token_env,
+                    settings.api_token_env
+                ));
+            }
+        };
+        Ok(Self {
+            settings,
+            token,
+            http: Client::new(),
+            variables: Mutex::new(None),
+        })
+    }
+
+    async fn graphql(&self, query: &str, variables: Value) -> Result<Value, ProviderError> {
+        let req = self
+            .http
+            .post(API_URL)
+            .json(&json!({ "query": query, "variables": variables }));
+        let req = match &self.token {
+            Token::Project(token) => req.header("Project-Access-Token", token),
+            Token::Api(token) => req.bearer_auth(token),
+        };
+        let res = req
+            .send()
+            .await
+            .map_err(|e| ProviderError::Other(format!("request failed: {e}")))?;
+        if !res.status().is_success() {
+            return Err(ProviderError::http(
+                res.status(),
+                format!("railway request failed ({})", res.status()),
+            ));
+        }
+        let body: Value = res
+            .json()
+            .await
+            .map_err(|e| ProviderError::Other(format!("invalid response body: {e}")))?;
+        graphql_data(body)
+    }
+
+    async fn variables(&self) -> Result<BTreeMap<String, String>, ProviderError> {
+        if let Some(variables) = self.variables.lock().unwrap().clone() {
+            return Ok(variables);
+        }
+        let data = self
+            .graphql(
+                VARIABLES_QUERY,
+                json!({
+                    "projectId": self.settings.project_id,
+                    "environmentId": self.settings.environment_id,
+                    "serviceId": self.settings.service_id,
+                }),
+            )
+            .await?;
+        let variables: BTreeMap<String, String> = serde_json::from_value(data["variables"].clone())
+            .map_err(|e| ProviderError::Other(format!("unexpected variables response: {e}")))?;
+        *self.variables.lock().unwrap() = Some(variables.clone());
+        Ok(variables)
+    }
+}
+
+/// GraphQL reports failures in `errors` with a 200 status; Railway says
+/// "Not Authorized" for missing, expired or under-scoped tokens.
+fn graphql_data(mut body: Value) -> Result<Value, ProviderError> {
+    if let Some(errors) = body["errors"].as_array().filter(|e| !e.is_empty()) {
+        let message = errors
+            .iter()
+            .map(|e| e["message"].as_str().unwrap_or("unknown error"))
+            .collect::<Vec<_>>()
+            .join("; ");
+        return Err(if message.contains("Not Authorized") {
+            ProviderError::Auth(format!("railway: {message}"))
+        } else {
+            ProviderError::Other(format!("railway: {message}"))
+        });
+    }
+    Ok(body["data"].take())
+}
+
+#[async_trait]
+impl SecretProvider for RailwayProvider {
+    async fn list_secrets(&self) -> Result<Vec<SecretMeta>, ProviderError> {
+        Ok(self
+            .variables()
+            .await?
+            .into_keys()
+            .map(|name| SecretMeta {
+                name,
+                ..SecretMeta::default()
+            })
+            .collect())
+    }
+
+    async fn get_secret(&self, name: &str) -> Result<Option<String>, ProviderError> {
+        Ok(self.variables().await?.get(name).cloned())
+    }
+
+    async fn set_secret(&self, name: &str, value: &str) -> Result<(), ProviderError> {
+        self.graphql(
+            UPSERT_MUTATION,
+            json!({
+                "input": {
+                    "projectId": self.settings.project_id,
+                    "environmentId": self.settings.environment_id,
+                    "serviceId": self.settings.service_id,
+                    "name": name,
+                    "value": value,
+                }
+            }),
+        )
+        .await?;
+        if let Some(variables) = self.variables.lock().unwrap().as_mut() {
+            variables.insert(name.to_string(), value.to_string());
+        }
+        Ok(())
+    }
+
+    fn capabilities(&self) -> Capabilities {
+        Capabilities {
+            write: true,
+            ..Capabilities::default()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn graphql_errors_are_classified() {
+        let data = graphql_data(json!({ "data": { "variables": { "A": "1" } } })).unwrap();
+        assert_eq!(data["variables"]["A"], "1");
+
+        let err = graphql_data(json!({ "errors": [{ "message": "Not Authorized" }] }));
+        assert!(matches!(err, Err(ProviderError::Auth(_))));
+        let err = graphql_data(json!({ "errors": [{ "message": "Project not found" }] }));
+        assert!(matches!(err, Err(ProviderError::Other(m)) if m.contains("Project not found")));
+    }
+}
@@ -0,0 +1,337 @@
+use std::{collections::HashMap, sync::Arc, time::Instant};
+
+use anyhow::{Context, Result, bail};
+
+use crate::{
+    config::Source,
+    provider::{SecretMeta, SecretProvider, cache::CachedProvider},
+    verbose::verbose,
+};
+
+/// Resolves every source in order. When two sources produce the same env
+/// key, the later one wins.
+pub async fn resolve_all(
+    sources: &[(Source<'_>, Arc<CachedProvider>)],
+) -> Result<HashMap<String, String>> {
+    let mut values = HashMap::new();
+    for (source, provider) in sources {
+        values.extend(resolve(*source, provider.as_ref()).await?);
+    }
+    Ok(values)
+}
+
+/// Lists the provider, maps secret names to env keys and fetches every value.
+/// Secrets the provider reports as missing are left out of the result.
+pub async fn resolve(
+    source: Source<'_>,
+    provider: &dyn SecretProvider,
+) -> Result<HashMap<String, String>> {
+    Ok(resolve_entries(source, provider)
+        .await?
+        .into_iter()
+        .map(|entry| (entry.key, entry.value))
+        .collect())
+}
+
+/// One resolved env key and the secret it came from.
+pub struct Resolved {
+    pub key: String,
+    pub secret: String,
+    pub value: String,
+}
+
+/// Like `resolve`, keeping the secret name behind each key.
+///
+/// With `verify_versions`, each value is read together with its version and
+/// compared with the version listed; secrets that changed in between are
+/// named in a warning, since the result then mixes two points in time.
+pub async fn resolve_entries(
+    source: Source<'_>,
+    provider: &dyn SecretProvider,
+) -> Result<Vec<Resolved>> {
+    let listed = listed_keys(source, provider).await?;
+    // Providers without versions would report every secret as unchanged.
+    let verify_versions = source.verify_versions && provider.capabilities().versions;
+
+    let mut resolved = Vec::new();
+    let mut moved = Vec::new();
+    for (meta, key) in &listed {
+        let secret_name = &meta.name;
+        let value = if verify_versions {
+            provider.fetch_secret(secret_name).await.map(|fetched| {
+                fetched.map(|fetched| {
+                    if meta.moved(&fetched) {
+                        moved.push(secret_name.as_str());
+                    }
+                    fetched.value
+                })
+            })
+        } else {
+            provider.get_secret(secret_name).await
+        }
+        .with_context(|| format!("failed to fetch secret {secret_name}"))?;
+
+        if let Some(value) = value {
+            resolved.push(Resolved {
+                key: key.clone(),
+                secret: secret_name.clone(),
+                value,
+            });
+        }
+    }
+
+    warn_moved(&moved);
+    Ok(resolved)
+}
+
+/// Warns about secrets `verify_versions` found changed between the listing
+/// and the read of their value.
+pub fn warn_moved(moved: &[&str]) {
+    if moved.is_empty() {
+        return;
+    }
+    eprintln!(
+        "warning: {} changed since {} listed; the values mix two points in time \
+         (rotation in progress?)",
+        moved.join(", "),
+        if moved.len() == 1 {
+            "it was"
+        } else {
+            "they were"
+        }
+    );
+}
+
+/// Every (secret, env key) pair a source would produce, without fetching
+/// values.
+pub async fn keys(
+    source: Source<'_>,
+    provider: &dyn SecretProvider,
+) -> Result<Vec<(String, String)>> {
+    Ok(listed_keys(source, provider)
+        .await?
+        .into_iter()
+        .map(|(meta, key)| (meta.name, key))
+        .collect())
+}
+
+/// Like `keys`, with what the listing says about each secret (only the
+/// name for mapped secrets it leaves out).
+pub async fn listed_keys(
+    source: Source<'_>,
+    provider: &dyn SecretProvider,
+) -> Result<Vec<(SecretMeta, String)>> {
+    let started = Instant::now();
+    let listed = provider
+        .list_secrets()
+        .await
+        .context("failed to list secrets")?;
+    verbose!(
+        "{}: listed {} secrets in {}ms",
+        source.provider.kind,
+        listed.len(),
+        started.elapsed().as_millis()
+    );
+
+    let reverse_map = build_reverse_map(source.map)?;
+    let mut target_secret_to_env = Vec::with_capacity(listed.len());
+    for meta in listed {
+        let env_key = reverse_map
+            .get(&meta.name)
+            .cloned()
+            .unwrap_or_else(|| to_env_key(&meta.name));
+        target_secret_to_env.push((meta, env_key));
+    }
+
+    // Manually mapped secrets are fetched even when the provider does not list
+    // them (e.g. Keeper record fields, which are only reachable via [map]).
+    let mut unlisted: Vec<_> = source
+        .map
+        .iter()
+        .filter(|(_, secret)| !target_secret_to_env.iter().any(|(s, _)| s.name == **secret))
+        .map(|(env_key, secret)| (secret.clone(), env_key.clone()))
+        .collect();
+    unlisted.sort();
+    target_secret_to_env.extend(unlisted.into_iter().map(|(name, env_key)| {
+        let meta = SecretMeta {
+            name,
+            ..SecretMeta::default()
+        };
+        (meta, env_key)
+    }));
+
+    validate_no_duplicate_env_keys(&target_secret_to_env)?;
+
+    Ok(target_secret_to_env
+        .into_iter()
+        .map(|(meta, env_key)| (meta, format!("{}{env_key}", source.prefix)))
+        .collect())
+}
+
+/// The env key `secret` pulls to from `source`, after `[map]` and the
+/// prefix.
+pub fn env_key(source: Source<'_>, secret: &str) -> String {
+    let key = source
+        .map
+        .iter()
+        .find(|(_, mapped)| *mapped == secret)
+        .map_or_else(|| to_env_key(secret), |(key, _)| key.clone());
+    format!("{}{key}", source.prefix)
+}
+
+fn build_reverse_map(map: &HashMap<String, String>) -> Result<HashMap<String, String>> {
+    let mut reverse = HashMap::with_capacity(map.len());
+    for (env_key, secret_name) in map {
+        if let Some(existing) = reverse.insert(secret_name.clone(), env_key.clone()) {
+            bail!("duplicate manual mapping for secret {secret_name}: {existing} and {env_key}");
+        }
+    }
+    Ok(reverse)
+}
+
+fn validate_no_duplicate_env_keys(pairs: &[(SecretMeta, String)]) -> Result<()> {
+    let mut seen: HashMap<&str, &str> = HashMap::new();
+    for (meta, key) in pairs {
+        let secret = &meta.name;
+        if let Some(existing_secret) = seen.insert(key, secret) {
+            bail!(
+                "duplicate env key mapping detected: {key} mapped from both {existing_secret} and {secret}"
+            );
+        }
+    }
+    Ok(())
+}
+
+/// The env key a secret pulls to when `[map]` does not name one.
+pub fn to_env_key(secret_name: &str) -> String {
+    secret_name.replace('-', "_").to_ascii_uppercase()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::{collections::HashMap, sync::Mutex};
+
+    use async_trait::async_trait;
+
+    use crate::{
+        config::ProviderConfig,
+        provider::{Capabilities, Fetched, ProviderError, SecretMeta},
+    };
+
+    /// `db` was rotated to version 2 after it was listed at version 1;
+    /// `api` is stable.
+    struct RotatedProvider {
+        reads: Mutex<Vec<String>>,
+    }
+
+    #[async_trait]
+    impl SecretProvider for RotatedProvider {
+        async fn list_secrets(&self) -> Result<Vec<SecretMeta>, ProviderError> {
+            Ok(["api", "db"]
+                .into_iter()
+                .map(|name| SecretMeta {
+                    name: name.to_string(),
+                    version: Some("1".to_string()),
+                    ..SecretMeta::default()
+                })
+                .collect())
+        }
+
+        async fn get_secret(&self, name: &str) -> Result<Option<String>, ProviderError> {
+            self.reads.lock().unwrap().push(format!("{name}@latest"));
+            Ok(Some(match name {
+                "api" => "stable".to_string(),
+                _ => "db-v2".to_string(),
+            }))
+        }
+
+        async fn secret_version(&self, name: &str) -> Result<Option<String>, ProviderError> {
+            Ok(Some(if name == "api" { "1" } else { "2" }.to_string()))
+        }
+
+        async fn get_secret_version(
+            &self,
+            name: &str,
+            version: &str,
+        ) -> Result<Option<String>, ProviderError> {
+            self.reads.lock().unwrap().push(format!("{name}@{version}"));
+            Ok(Some(match name {
+                "api" => "stable".to_string(),
+                _ => format!("db-v{version}"),
+            }))
+        }
+
+        fn capabilities(&self) -> Capabilities {
+            Capabilities {
+                versions: true,
+                ..Capabilities::default()
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn verify_versions_reads_each_value_pinned_to_the_version_it_checks() {
+        let provider_cfg = ProviderConfig {
+            kind: "test".to_string(),
+            settings: toml::Table::new(),
+        };
+        let map = HashMap::new();
+        let source = |verify_versions| Source {
+            provider: &provider_cfg,
+            map: &map,
+            prefix: "",
+            verify_versions,
+        };
+
+        let provider = RotatedProvider {
+            reads: Mutex::new(Vec::new()),
+        };
+        let values = resolve(source(false), &provider).await.unwrap();
+        assert_eq!(values["DB"], "db-v2");
+        assert_eq!(*provider.reads.lock().unwrap(), ["api@latest", "db@latest"]);
+
+        let provider = RotatedProvider {
+            reads: Mutex::new(Vec::new()),
+        };
+        let values = resolve(source(true), &provider).await.unwrap();
+        assert_eq!(values["DB"], "db-v2");
+        assert_eq!(values["API"], "stable");
+        assert_eq!(*provider.reads.lock().unwrap(), ["api@1", "db@2"]);
+    }
+
+    #[test]
+    fn a_read_moved_past_the_listing_by_version_or_else_update_time() {
+        let at =
+            |secs| Some(std::time::SystemTime::UNIX_EPOCH + std::time::Duration::from_secs(secs));
+        let listed = SecretMeta {
+            name: "db".to_string(),
+            version: Some("1".to_string()),
+            updated: at(10),
+            expires: None,
+        };
+        let read = |version: Option<&str>, updated| Fetched {
+            value: String::new(),
+            version: version.map(str::to_string),
+            updated,
+        };
+        assert!(!listed.moved(&read(Some("1"), at(20))));
+        assert!(listed.moved(&read(Some("2"), at(10))));
+
+        let listed = SecretMeta {
+            version: None,
+            ..listed
+        };
+        assert!(listed.moved(&read(Some("2"), at(20))));
+        assert!(!listed.moved(&read(Some("2"), at(10))));
+        assert!(!listed.moved(&read(Some("2"), None)));
+    }
+
+    #[test]
+    fn secret_name_to_env_key_rule() {
+        assert_eq!(to_env_key("database-url"), "DATABASE_URL");
+        assert_eq!(to_env_key("azure-client-id"), "AZURE_CLIENT_ID");
+        assert_eq!(to_env_key("redis"), "REDIS");
+    }
+}
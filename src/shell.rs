@@ -0,0 +1,36 @@
+use tokio::process::Command;
+
+/// Runs `command_line` through the platform shell (`sh -c` / `cmd /C`).
+pub fn command(command_line: &str) -> Command {
+    if cfg!(windows) {
+        let mut cmd = Command::new("cmd");
+        cmd.args(["/C", command_line]);
+        cmd
+    } else {
+        let mut cmd = Command::new("sh");
+        cmd.args(["-c", command_line]);
+        cmd
+    }
+}
+
+/// Quotes `arg` as one word for the platform shell.
+pub fn quote(arg: &str) -> String {
+    if cfg!(windows) {
+        format!("\"{arg}\"")
+    } else {
+        format!("'{}'", arg.replace('\'', r"'\''"))
+    }
+}
+
+#[cfg(any(feature = "exec", feature = "keeper"))]
+/// Turns command stdout into a value, dropping one trailing (CR)LF.
+pub fn stdout_value(stdout: Vec<u8>) -> Option<String> {
+    let mut value = String::from_utf8(stdout).ok()?;
+    if value.ends_with('\n') {
+        value.pop();
+        if value.ends_with('\r') {
+            value.pop();
+        }
+    }
+    Some(value)
+}
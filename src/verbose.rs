@@ -0,0 +1,25 @@
+//! `--verbose`: diagnostics on stderr, such as request timings, that would
+//! be noise on every run.
+
+use std::sync::atomic::{AtomicBool, Ordering};
+
+static VERBOSE: AtomicBool = AtomicBool::new(false);
+
+pub fn enable() {
+    VERBOSE.store(true, Ordering::Relaxed);
+}
+
+pub fn enabled() -> bool {
+    VERBOSE.load(Ordering::Relaxed)
+}
+
+/// `verbose!("listed {count} secrets")`: an `eprintln!` that only prints
+/// with `--verbose`.
+macro_rules! verbose {
+    ($($arg:tt)*) => {
+        if $crate::verbose::enabled() {
+            eprintln!("verbose: {}", format_args!($($arg)*));
+        }
+    };
+}
+pub(crate) use verbose;
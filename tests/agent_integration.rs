@@ -0,0 +1,157 @@
+#![cfg(unix)]
+
+use std::{
+    fs,
+    os::unix::fs::PermissionsExt,
+    path::Path,
+    process::{Child, Command, Stdio},
+    thread,
+    time::Duration,
+};
+
+use predicates::prelude::*;
+use tempfile::TempDir;
+
+const CONFIG: &str = r#"version = 1
+
+[provider]
+kind = "azure_key_vault"
+vault_url = "https://example.vault.azure.net/"
+"#;
+
+fn write_file(path: &Path, content: &str) {
+    fs::write(path, content).expect("failed to write file");
+}
+
+fn envit(dir: &Path) -> assert_cmd::Command {
+    let mut cmd = assert_cmd::Command::new(assert_cmd::cargo::cargo_bin!("envit"));
+    cmd.current_dir(dir)
+        .env("ENVIT_TEST_SECRETS_FILE", dir.join("local.txt"))
+        .env("ENVIT_AGENT_SOCK", dir.join("agent/agent.sock"));
+    cmd
+}
+
+/// A foreground agent, killed on drop so a failing test doesn't leave it
+/// running.
+struct Agent(Child);
+
+impl Agent {
+    fn start(secrets: &Path, socket: &Path) -> Self {
+        let agent = Command::new(assert_cmd::cargo::cargo_bin!("envit"))
+            .env("ENVIT_TEST_SECRETS_FILE", secrets)
+            .args(["agent", "--foreground", "--socket"])
+            .arg(socket)
+            .stderr(Stdio::null())
+            .spawn()
+            .unwrap();
+        let agent = Self(agent);
+        for _ in 0..50 {
+            if socket.exists() {
+                break;
+            }
+            thread::sleep(Duration::from_millis(100));
+        }
+        agent
+    }
+}
+
+impl Drop for Agent {
+    fn drop(&mut self) {
+        let _ = self.0.kill();
+        let _ = self.0.wait();
+    }
+}
+
+#[test]
+fn pull_reuses_the_agent_session_until_it_stops() {
+    let dir = TempDir::new().unwrap();
+    write_file(&dir.path().join("envit.toml"), CONFIG);
+    write_file(&dir.path().join("local.txt"), "database-url=direct\n");
+    write_file(&dir.path().join("agent.txt"), "database-url=from-agent\n");
+    let socket = dir.path().join("agent/agent.sock");
+
+    let mut agent = Agent::start(&dir.path().join("agent.txt"), &socket);
+
+    envit(dir.path()).arg("pull").assert().success();
+    assert_eq!(
+        fs::read_to_string(dir.path().join(".env")).unwrap(),
+        "DATABASE_URL=from-agent\n"
+    );
+
+    // Values fetched in this session are reused, not fetched again.
+    write_file(&dir.path().join("agent.txt"), "database-url=rotated\n");
+    envit(dir.path()).arg("pull").assert().success();
+    assert_eq!(
+        fs::read_to_string(dir.path().join(".env")).unwrap(),
+        "DATABASE_URL=from-agent\n"
+    );
+
+    envit(dir.path())
+        .args(["agent", "--stop"])
+        .assert()
+        .success();
+    assert!(agent.0.wait().unwrap().success());
+    assert!(!socket.exists());
+
+    envit(dir.path())
+        .arg("pull")
+        .assert()
+        .success()
+        .stderr(predicate::str::contains("envit agent unavailable"));
+    assert_eq!(
+        fs::read_to_string(dir.path().join(".env")).unwrap(),
+        "DATABASE_URL=direct\n"
+    );
+}
+
+#[test]
+fn locked_pull_resolves_through_the_agent_at_the_pinned_versions() {
+    let dir = TempDir::new().unwrap();
+    write_file(&dir.path().join("envit.toml"), CONFIG);
+    write_file(&dir.path().join("local.txt"), "api-token=direct\n");
+    write_file(
+        &dir.path().join("agent.txt"),
+        "api-token=agent-one\napi-token=agent-two\n",
+    );
+    let socket = dir.path().join("agent/agent.sock");
+
+    // `update` reads current versions directly, without the agent.
+    envit(dir.path()).arg("update").assert().success();
+    assert_eq!(
+        fs::read_to_string(dir.path().join(".env")).unwrap(),
+        "API_TOKEN=direct\n"
+    );
+
+    let mut agent = Agent::start(&dir.path().join("agent.txt"), &socket);
+
+    envit(dir.path())
+        .args(["pull", "--frozen"])
+        .assert()
+        .success();
+    assert_eq!(
+        fs::read_to_string(dir.path().join(".env")).unwrap(),
+        "API_TOKEN=agent-one\n"
+    );
+
+    envit(dir.path())
+        .args(["agent", "--stop"])
+        .assert()
+        .success();
+    assert!(agent.0.wait().unwrap().success());
+}
+
+#[test]
+fn agent_refuses_a_socket_directory_others_can_enter() {
+    let dir = TempDir::new().unwrap();
+    let socket = dir.path().join("agent/agent.sock");
+    fs::create_dir(dir.path().join("agent")).unwrap();
+    fs::set_permissions(dir.path().join("agent"), fs::Permissions::from_mode(0o755)).unwrap();
+
+    envit(dir.path())
+        .args(["agent", "--foreground", "--socket"])
+        .arg(&socket)
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("expected 700"));
+    assert!(!socket.exists());
+}
@@ -0,0 +1,337 @@
+// Most of these tests drive the azure_key_vault kind.
+#![cfg_attr(not(feature = "azure"), allow(unused_imports))]
+
+use std::fs;
+
+use assert_cmd::Command;
+use predicates::prelude::*;
+use tempfile::TempDir;
+
+#[cfg(all(feature = "azure", feature = "keeper"))]
+#[test]
+fn features_flag_lists_compiled_provider_kinds() {
+    Command::new(assert_cmd::cargo::cargo_bin!("envit"))
+        .arg("--features")
+        .assert()
+        .success()
+        .stdout(predicate::str::is_match(r"azure_key_vault\s+azure\s+enabled").unwrap())
+        .stdout(predicate::str::is_match(r"keeper\s+keeper\s+enabled").unwrap());
+}
+
+#[test]
+fn completions_cover_subcommands() {
+    Command::new(assert_cmd::cargo::cargo_bin!("envit"))
+        .args(["completions", "bash"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("_envit()"))
+        .stdout(predicate::str::contains("why-failed"));
+    Command::new(assert_cmd::cargo::cargo_bin!("envit"))
+        .args(["completions", "fish"])
+        .assert()
+        .success()
+        .stdout(predicate::str::is_match(r#"subcommand compare" -l profile .* -r -F\n"#).unwrap())
+        .stdout(predicate::str::is_match(r#"subcommand copy" -l to .* -r -F\n"#).unwrap());
+    Command::new(assert_cmd::cargo::cargo_bin!("envit"))
+        .args(["completions", "tcsh"])
+        .assert()
+        .failure();
+}
+
+#[cfg(all(feature = "azure", feature = "exec", feature = "railway"))]
+#[test]
+fn schema_describes_config_and_each_provider_kind() {
+    let output = Command::new(assert_cmd::cargo::cargo_bin!("envit"))
+        .arg("schema")
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    let schema: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert_eq!(schema["title"], "envit.toml");
+    assert_eq!(schema["required"], serde_json::json!(["version"]));
+    let output = &schema["definitions"]["OutputConfig"]["properties"];
+    assert_eq!(output["env_file"]["default"], ".env");
+
+    let kinds: Vec<_> = schema["definitions"]["ProviderConfig"]["oneOf"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|branch| branch["properties"]["kind"]["const"].as_str().unwrap())
+        .collect();
+    assert!(kinds.contains(&"azure_key_vault") && kinds.contains(&"railway"));
+    let exec = schema["definitions"]["SourceConfig"]["oneOf"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .find(|branch| branch["properties"]["kind"]["const"] == "exec")
+        .unwrap();
+    assert_eq!(exec["required"], serde_json::json!(["kind", "get_command"]));
+    assert!(exec["properties"]["prefix"].is_object());
+}
+
+#[cfg(feature = "azure")]
+#[test]
+fn validate_reports_map_collisions_and_invalid_keys_offline() {
+    let dir = TempDir::new().unwrap();
+    let config = dir.path().join("envit.toml");
+    let validate = || {
+        let mut cmd = Command::new(assert_cmd::cargo::cargo_bin!("envit"));
+        cmd.arg("validate").arg("--config").arg(&config);
+        cmd
+    };
+
+    fs::write(
+        &config,
+        r#"version = 1
+
+[provider]
+kind = "azure_key_vault"
+vault_url = "https://example.vault.azure.net/"
+
+[map]
+DATABASE_URL = "db-url"
+DB_URL = "db-url"
+"2FA_SEED" = "totp-seed"
+"#,
+    )
+    .unwrap();
+    validate()
+        .assert()
+        .code(1)
+        .stderr(predicate::str::contains(
+            "error: [map]: secret db-url is mapped to more than one key (DATABASE_URL, DB_URL)",
+        ))
+        .stderr(predicate::str::contains(
+            "error: [map]: 2FA_SEED is not a valid env key",
+        ))
+        .stderr(predicate::str::contains("2 problem(s)"));
+
+    fs::write(
+        &config,
+        r#"version = 1
+
+[[providers]]
+prefix = "APP_"
+kind = "azure_key_vault"
+vault_url = "https://example.vault.azure.net/"
+[providers.map]
+"2FA_SEED" = "totp-seed"
+"#,
+    )
+    .unwrap();
+    validate()
+        .assert()
+        .success()
+        .stdout(predicate::str::ends_with("is valid.\n"));
+}
+
+#[cfg(feature = "azure")]
+#[test]
+fn init_writes_config_and_seeds_map_from_provider() {
+    let dir = TempDir::new().unwrap();
+    fs::write(dir.path().join("secrets.txt"), "database-url=x\nredis=y\n").unwrap();
+    let init = || {
+        let mut cmd = Command::new(assert_cmd::cargo::cargo_bin!("envit"));
+        cmd.current_dir(dir.path())
+            .env("ENVIT_TEST_SECRETS_FILE", dir.path().join("secrets.txt"))
+            .arg("init");
+        cmd
+    };
+
+    init()
+        .write_stdin("azure_key_vault\nhttps://my-vault.vault.azure.net/\n\ny\n")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains(
+            "Wrote envit.toml (2 keys in [map])",
+        ));
+    assert_eq!(
+        fs::read_to_string(dir.path().join("envit.toml")).unwrap(),
+        r#"version = 1
+
+[output]
+env_file = ".env"
+create_if_missing = true
+
+[provider]
+kind = "azure_key_vault"
+vault_url = "https://my-vault.vault.azure.net/"
+
+[map]
+DATABASE_URL = "database-url"
+REDIS = "redis"
+"#
+    );
+
+    init()
+        .write_stdin("\n")
+        .assert()
+        .code(1)
+        .stderr(predicate::str::contains("already exists"));
+}
+
+#[cfg(feature = "azure")]
+#[test]
+fn init_template_writes_a_validated_setup_with_a_protected_profile() {
+    let dir = TempDir::new().unwrap();
+    let envit = |args: &[&str]| {
+        let mut cmd = Command::new(assert_cmd::cargo::cargo_bin!("envit"));
+        cmd.current_dir(dir.path()).args(args);
+        cmd
+    };
+
+    envit(&["init", "--template", "microservice-azure"])
+        .write_stdin("https://app-dev.vault.azure.net/\nhttps://app-prod.vault.azure.net/\n")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains(
+            "Wrote envit.toml, envit.prod.toml (a protected profile) and envit.schema.json.",
+        ));
+    let dev = fs::read_to_string(dir.path().join("envit.toml")).unwrap();
+    let prod = fs::read_to_string(dir.path().join("envit.prod.toml")).unwrap();
+    assert!(dev.starts_with("#:schema ./envit.schema.json\n"));
+    assert!(dev.contains("vault_url = \"https://app-dev.vault.azure.net/\"\n"));
+    assert!(!dev.contains("[security]"));
+    assert!(prod.contains("vault_url = \"https://app-prod.vault.azure.net/\"\n"));
+    assert!(prod.contains("env_file = \".env.prod\"\n"));
+    assert!(prod.ends_with("[security]\nprotected = true\n"));
+    let schema: serde_json::Value =
+        serde_json::from_str(&fs::read_to_string(dir.path().join("envit.schema.json")).unwrap())
+            .unwrap();
+    assert_eq!(schema["title"], "envit.toml");
+
+    envit(&["validate"]).assert().success();
+    envit(&["validate", "--config", "envit.prod.toml"])
+        .assert()
+        .success();
+    envit(&["config", "lint", "--config", "envit.prod.toml"])
+        .assert()
+        .success();
+    envit(&["init", "--template", "microservice-azure"])
+        .assert()
+        .code(1)
+        .stderr(predicate::str::contains("already exists"));
+}
+
+#[cfg(feature = "azure")]
+#[test]
+fn config_lint_flags_risky_setups() {
+    let dir = TempDir::new().unwrap();
+    let config = dir.path().join("envit.toml");
+    let lint = || {
+        let mut cmd = Command::new(assert_cmd::cargo::cargo_bin!("envit"));
+        cmd.args(["config", "lint", "--config"]).arg(&config);
+        cmd
+    };
+
+    fs::write(
+        &config,
+        r#"version = 1
+
+[output]
+env_file = "../shared/.env"
+create_if_missing = false
+
+[provider]
+kind = "azure_key_vault"
+vault_url = "https://example.vault.azure.net/"
+
+[[hooks]]
+url = "http://hooks.example.com/envit"
+on = ["pull"]
+"#,
+    )
+    .unwrap();
+    lint()
+        .assert()
+        .code(1)
+        .stdout(predicate::str::contains(
+            "is outside the config's directory",
+        ))
+        .stdout(predicate::str::contains(
+            "output.create_if_missing is false",
+        ))
+        .stdout(predicate::str::contains(
+            "hooks.url http://hooks.example.com/envit is plain HTTP",
+        ));
+
+    fs::write(
+        &config,
+        r#"version = 1
+
+[provider]
+kind = "azure_key_vault"
+vault_url = "https://example.vault.azure.net/"
+
+[[hooks]]
+url = "http://localhost:8080/envit"
+on = ["pull"]
+"#,
+    )
+    .unwrap();
+    lint()
+        .assert()
+        .success()
+        .stdout(predicate::str::starts_with("No problems found"));
+}
+
+#[cfg(feature = "azure")]
+#[test]
+fn devcontainer_scaffolds_a_feature_that_pulls_on_create() {
+    let dir = TempDir::new().unwrap();
+    fs::write(
+        dir.path().join("envit.toml"),
+        r#"version = 1
+
+[provider]
+kind = "azure_key_vault"
+vault_url = "https://example.vault.azure.net/"
+"#,
+    )
+    .unwrap();
+    let devcontainer = || {
+        let mut cmd = Command::new(assert_cmd::cargo::cargo_bin!("envit"));
+        cmd.current_dir(dir.path()).arg("devcontainer");
+        cmd
+    };
+
+    devcontainer()
+        .assert()
+        .success()
+        .stdout(predicate::str::contains(
+            "Wrote .devcontainer/devcontainer.json",
+        ));
+    let feature = dir.path().join(".devcontainer/envit");
+    assert!(
+        fs::read_to_string(feature.join("devcontainer-feature.json"))
+            .unwrap()
+            .contains("\"id\": \"envit\"")
+    );
+    assert!(
+        fs::read_to_string(feature.join("install.sh"))
+            .unwrap()
+            .contains("$INSTALLCOMMAND")
+    );
+    let settings: serde_json::Value = serde_json::from_str(
+        &fs::read_to_string(dir.path().join(".devcontainer/devcontainer.json")).unwrap(),
+    )
+    .unwrap();
+    assert_eq!(
+        settings["postCreateCommand"],
+        "envit pull --config envit.toml"
+    );
+    assert!(settings["features"]["./envit"].is_object());
+    assert!(settings["secrets"]["AZURE_CLIENT_SECRET"].is_object());
+
+    devcontainer()
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("already exists (use --force"));
+    devcontainer()
+        .arg("--force")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains(
+            "exists; merge these settings into it",
+        ));
+}
@@ -0,0 +1,79 @@
+use std::{
+    fs,
+    path::Path,
+    process::{Command, Stdio},
+    thread,
+    time::Duration,
+};
+
+use tempfile::TempDir;
+
+const CONFIG: &str = r#"version = 1
+
+[provider]
+kind = "azure_key_vault"
+vault_url = "https://example.vault.azure.net/"
+"#;
+
+fn write_file(path: &Path, content: &str) {
+    fs::write(path, content).expect("failed to write file");
+}
+
+#[test]
+fn container_waits_for_identity_then_pulls() {
+    let dir = TempDir::new().unwrap();
+    write_file(&dir.path().join("envit.toml"), CONFIG);
+    let secrets = dir.path().join("secrets.txt");
+    write_file(&secrets, "!unauthenticated\ndatabase-url=vault\n");
+
+    let child = Command::new(assert_cmd::cargo::cargo_bin!("envit"))
+        .current_dir(dir.path())
+        .env("ENVIT_TEST_SECRETS_FILE", &secrets)
+        .args(["container", "--identity-timeout", "30s"])
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+
+    thread::sleep(Duration::from_millis(1500));
+    write_file(&secrets, "!identity:pod-identity\ndatabase-url=vault\n");
+    let output = child.wait_with_output().unwrap();
+
+    assert!(output.status.success());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(
+        stderr.contains("Waiting for identity (attempt 1)"),
+        "{stderr}"
+    );
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("Authenticated as pod-identity"), "{stdout}");
+    assert_eq!(
+        fs::read_to_string(dir.path().join(".env")).unwrap(),
+        "DATABASE_URL=vault\n"
+    );
+}
+
+#[test]
+fn container_gives_up_after_identity_timeout() {
+    let dir = TempDir::new().unwrap();
+    write_file(&dir.path().join("envit.toml"), CONFIG);
+    write_file(
+        &dir.path().join("secrets.txt"),
+        "!unauthenticated\ndatabase-url=vault\n",
+    );
+
+    let output = Command::new(assert_cmd::cargo::cargo_bin!("envit"))
+        .current_dir(dir.path())
+        .env("ENVIT_TEST_SECRETS_FILE", dir.path().join("secrets.txt"))
+        .args(["container", "--identity-timeout", "1s"])
+        .output()
+        .unwrap();
+
+    assert!(!output.status.success());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(
+        stderr.contains("identity not available after 1s"),
+        "{stderr}"
+    );
+    assert!(!dir.path().join(".env").exists());
+}
@@ -0,0 +1,565 @@
+use std::{fs, path::Path};
+
+use assert_cmd::Command;
+use predicates::prelude::*;
+use tempfile::TempDir;
+
+fn write_file(path: &Path, content: &str) {
+    fs::write(path, content).expect("failed to write file");
+}
+
+fn setup(security: &str) -> TempDir {
+    let dir = TempDir::new().unwrap();
+    let config = format!(
+        r#"version = 1
+
+[provider]
+kind = "azure_key_vault"
+vault_url = "https://example.vault.azure.net/"
+
+[security]
+{security}
+"#
+    );
+    write_file(&dir.path().join("envit.toml"), &config);
+    write_file(
+        &dir.path().join("secrets.txt"),
+        "!identity:11111111-aaaa\ndatabase-url=super-secret\nredis=redis://localhost\n",
+    );
+    dir
+}
+
+fn envit(dir: &TempDir) -> Command {
+    let mut cmd = Command::new(assert_cmd::cargo::cargo_bin!("envit"));
+    cmd.current_dir(dir.path())
+        .env("ENVIT_TEST_SECRETS_FILE", dir.path().join("secrets.txt"));
+    cmd
+}
+
+#[test]
+fn export_prints_sorted_plaintext_values() {
+    let dir = setup("");
+
+    envit(&dir)
+        .arg("export")
+        .assert()
+        .success()
+        .stdout("DATABASE_URL=super-secret\nREDIS=redis://localhost\n");
+}
+
+#[test]
+fn env_prints_dotenv_lines_or_shell_exports() {
+    let dir = setup("");
+
+    envit(&dir)
+        .arg("env")
+        .assert()
+        .success()
+        .stdout("DATABASE_URL=super-secret\nREDIS=redis://localhost\n");
+    envit(&dir)
+        .args(["env", "--export"])
+        .assert()
+        .success()
+        .stdout("export DATABASE_URL='super-secret'\nexport REDIS='redis://localhost'\n");
+    assert!(!dir.path().join(".env").exists());
+}
+
+#[test]
+fn env_has_its_own_refusals_and_audit_entries() {
+    let dir = setup("protected = true");
+    envit(&dir)
+        .arg("env")
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains(
+            "`envit env` would print plaintext values",
+        ))
+        .stderr(predicate::str::contains("--break-glass").not());
+
+    let dir = setup("no_plaintext = true");
+    envit(&dir)
+        .args(["env", "--export"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains(
+            "env only prints --export lines to a terminal",
+        ));
+
+    let dir = setup("");
+    let config = fs::read_to_string(dir.path().join("envit.toml")).unwrap();
+    write_file(
+        &dir.path().join("envit.toml"),
+        &format!("{config}\n[audit]\npath = \"audit.jsonl\"\n"),
+    );
+    envit(&dir).arg("env").assert().success();
+    let log = fs::read_to_string(dir.path().join("audit.jsonl")).unwrap();
+    assert!(log.contains("\"command\":\"env\""), "{log}");
+}
+
+#[test]
+fn get_prints_one_value_by_env_key() {
+    let dir = setup("");
+
+    envit(&dir)
+        .args(["get", "DATABASE_URL"])
+        .assert()
+        .success()
+        .stdout("super-secret\n");
+    envit(&dir)
+        .args(["get", "MISSING"])
+        .assert()
+        .code(1)
+        .stderr(predicate::str::contains("no secret pulls to MISSING"));
+
+    let dir = setup("protected = true");
+    envit(&dir)
+        .args(["get", "DATABASE_URL"])
+        .assert()
+        .code(1)
+        .stdout("");
+}
+
+#[test]
+fn export_helm_values_nests_keys_under_path() {
+    let dir = setup("");
+
+    envit(&dir)
+        .args(["export", "--format", "helm-values", "--under", "secrets"])
+        .assert()
+        .success()
+        .stdout(
+            "\"secrets\":\n  \"DATABASE_URL\": \"super-secret\"\n  \"REDIS\": \"redis://localhost\"\n",
+        );
+
+    envit(&dir)
+        .args(["export", "--under", "secrets"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("--under only applies"));
+}
+
+#[test]
+fn export_renders_json_and_writes_k8s_secret_to_a_file() {
+    let dir = setup("");
+
+    envit(&dir)
+        .args(["export", "--format", "json"])
+        .assert()
+        .success()
+        .stdout(
+            "{\n  \"DATABASE_URL\": \"super-secret\",\n  \"REDIS\": \"redis://localhost\"\n}\n",
+        );
+
+    envit(&dir)
+        .args(["export", "--format", "k8s-secret", "--name", "app"])
+        .args(["--output", "secret.yaml"])
+        .assert()
+        .success()
+        .stdout("");
+    let path = dir.path().join("secret.yaml");
+    assert_eq!(
+        fs::read_to_string(&path).unwrap(),
+        "apiVersion: v1\nkind: Secret\nmetadata:\n  name: \"app\"\ntype: Opaque\ndata:\n  \
+         \"DATABASE_URL\": c3VwZXItc2VjcmV0\n  \"REDIS\": cmVkaXM6Ly9sb2NhbGhvc3Q=\n"
+    );
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        let mode = fs::metadata(&path).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o600);
+    }
+
+    envit(&dir)
+        .args(["export", "--format", "json", "--name", "app"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("--name requires"));
+}
+
+#[cfg(unix)]
+#[test]
+fn export_ansible_vault_pipes_group_vars_through_ansible_vault() {
+    use std::os::unix::fs::PermissionsExt;
+
+    let dir = setup("protected = true");
+    let bin = dir.path().join("bin");
+    fs::create_dir(&bin).unwrap();
+    let fake = bin.join("ansible-vault");
+    write_file(
+        &fake,
+        "#!/bin/sh\necho \"$@\" > args.txt\necho '$ANSIBLE_VAULT;1.1;AES256'\ncat | tr 'a-z' 'A-Z'\n",
+    );
+    fs::set_permissions(&fake, fs::Permissions::from_mode(0o755)).unwrap();
+    let path = format!("{}:{}", bin.display(), std::env::var("PATH").unwrap());
+
+    envit(&dir)
+        .env("PATH", path)
+        .args(["export", "--format", "ansible", "--ansible-vault"])
+        .assert()
+        .success()
+        .stdout(
+            "$ANSIBLE_VAULT;1.1;AES256\n\"DATABASE_URL\": !UNSAFE \"SUPER-SECRET\"\n\"REDIS\": !UNSAFE \"REDIS://LOCALHOST\"\n",
+        );
+    assert_eq!(
+        fs::read_to_string(dir.path().join("args.txt")).unwrap(),
+        "encrypt --output -\n"
+    );
+}
+
+#[test]
+fn tf_external_answers_terraform_query_with_json_map() {
+    let dir = setup("");
+
+    envit(&dir)
+        .arg("tf-external")
+        .write_stdin(r#"{"config": "envit.toml", "keys": "REDIS"}"#)
+        .assert()
+        .success()
+        .stdout("{\"REDIS\":\"redis://localhost\"}\n");
+
+    envit(&dir)
+        .arg("tf-external")
+        .write_stdin("{}")
+        .assert()
+        .success()
+        .stdout("{\"DATABASE_URL\":\"super-secret\",\"REDIS\":\"redis://localhost\"}\n");
+}
+
+#[test]
+fn direnv_export_prints_quoted_export_statements() {
+    let dir = setup("");
+
+    envit(&dir)
+        .arg("direnv-export")
+        .assert()
+        .success()
+        .stdout("export DATABASE_URL='super-secret'\nexport REDIS='redis://localhost'\n");
+
+    envit(&dir)
+        .args(["direnv-export", "--stdlib"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("use_envit() {"));
+}
+
+#[cfg(unix)]
+#[test]
+fn hook_acts_only_on_allowed_configs_and_restores_values_on_leaving() {
+    let dir = setup("");
+    let data = TempDir::new().unwrap();
+    let envit_bin = assert_cmd::cargo::cargo_bin!("envit");
+    let path = format!(
+        "{}:{}",
+        envit_bin.parent().unwrap().display(),
+        std::env::var("PATH").unwrap_or_default()
+    );
+    let hook = envit(&dir)
+        .args(["hook", "bash", "--action", "export"])
+        .output()
+        .unwrap()
+        .stdout;
+    let shell = || {
+        let output = std::process::Command::new("bash")
+            .env("PATH", &path)
+            .env("XDG_DATA_HOME", data.path())
+            .env("ENVIT_TEST_SECRETS_FILE", dir.path().join("secrets.txt"))
+            .env("HOOK", String::from_utf8(hook.clone()).unwrap())
+            .env("PROJECT", dir.path())
+            .args([
+                "-c",
+                r#"eval "$HOOK"
+DATABASE_URL="it's mine"
+cd "$PROJECT" && _envit_hook; echo "in: $DATABASE_URL ${REDIS-unset}"
+false; _envit_hook; echo "status: $?"
+cd / && _envit_hook; echo "out: $DATABASE_URL ${REDIS-unset}""#,
+            ])
+            .output()
+            .unwrap();
+        (
+            String::from_utf8_lossy(&output.stdout).into_owned(),
+            String::from_utf8_lossy(&output.stderr).into_owned(),
+        )
+    };
+
+    let (stdout, stderr) = shell();
+    assert_eq!(
+        stdout,
+        "in: it's mine unset\nstatus: 1\nout: it's mine unset\n"
+    );
+    assert!(stderr.contains("is not allowed; review it and run `envit allow`"));
+
+    envit(&dir)
+        .env("XDG_DATA_HOME", data.path())
+        .arg("allow")
+        .assert()
+        .success();
+    let (stdout, stderr) = shell();
+    assert_eq!(
+        stdout, "in: super-secret redis://localhost\nstatus: 1\nout: it's mine unset\n",
+        "{stderr}"
+    );
+
+    let config = fs::read_to_string(dir.path().join("envit.toml")).unwrap();
+    write_file(&dir.path().join("envit.toml"), &format!("{config}\n"));
+    let (stdout, stderr) = shell();
+    assert!(stdout.starts_with("in: it's mine unset\n"), "{stdout}");
+    assert!(stderr.contains("changed since it was allowed"));
+}
+
+#[cfg(unix)]
+#[test]
+fn run_injects_values_into_the_child_and_passes_its_exit_code() {
+    let dir = setup("");
+
+    envit(&dir)
+        .env("REDIS", "inherited")
+        .args([
+            "run",
+            "--",
+            "sh",
+            "-c",
+            "echo \"$DATABASE_URL $REDIS\"; exit 3",
+        ])
+        .assert()
+        .code(3)
+        .stdout("super-secret redis://localhost\n");
+    assert!(!dir.path().join(".env").exists());
+}
+
+#[cfg(target_os = "linux")]
+#[test]
+fn run_memfd_passes_values_in_a_sealed_file_instead_of_the_environment() {
+    let dir = setup("");
+
+    envit(&dir)
+        .args([
+            "run",
+            "--memfd",
+            "--",
+            "sh",
+            "-c",
+            "cat \"$ENVIT_ENV_FILE\"; echo \"[$DATABASE_URL]\"; \
+             echo x >> \"$ENVIT_ENV_FILE\" 2>/dev/null || echo sealed",
+        ])
+        .assert()
+        .success()
+        .stdout("DATABASE_URL=super-secret\nREDIS=redis://localhost\n[]\nsealed\n");
+}
+
+#[test]
+fn manifest_lists_keys_and_versions_without_values_and_verifies() {
+    let dir = setup("");
+    write_file(
+        &dir.path().join("secrets.txt"),
+        "database-url=old\ndatabase-url=super-secret\nredis=redis://localhost\n",
+    );
+
+    envit(&dir)
+        .args(["manifest", "--output", "manifest.json"])
+        .env("ENVIT_MANIFEST_KEY", "build-key")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Wrote manifest of 2 secrets"));
+
+    let raw = fs::read_to_string(dir.path().join("manifest.json")).unwrap();
+    assert!(!raw.contains("super-secret"), "{raw}");
+    let manifest: serde_json::Value = serde_json::from_str(&raw).unwrap();
+    let secrets = manifest["secrets"].as_array().unwrap();
+    assert_eq!(secrets[0]["key"], "DATABASE_URL");
+    assert_eq!(secrets[0]["provider"], "azure_key_vault");
+    assert_eq!(secrets[0]["secret"], "database-url");
+    assert_eq!(secrets[0]["version"], "2");
+    assert_eq!(secrets[1]["version"], "1");
+    assert!(
+        secrets[1]["hash"]
+            .as_str()
+            .unwrap()
+            .starts_with("hmac-sha256:")
+    );
+
+    envit(&dir)
+        .args(["manifest", "--verify", "manifest.json"])
+        .env("ENVIT_MANIFEST_KEY", "build-key")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains(
+            "Manifest signature valid: 2 secrets",
+        ));
+    envit(&dir)
+        .args(["manifest", "--verify", "manifest.json"])
+        .env("ENVIT_MANIFEST_KEY", "other-key")
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("signature does not match"));
+
+    let tampered = raw.replacen("\"version\": \"2\"", "\"version\": \"3\"", 1);
+    assert_ne!(tampered, raw);
+    write_file(&dir.path().join("manifest.json"), &tampered);
+    envit(&dir)
+        .args(["manifest", "--verify", "manifest.json"])
+        .env("ENVIT_MANIFEST_KEY", "build-key")
+        .assert()
+        .failure();
+}
+
+#[test]
+fn export_refuses_protected_config_without_break_glass() {
+    let dir = setup("protected = true");
+
+    envit(&dir)
+        .arg("export")
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("config is protected"))
+        .stdout(predicate::str::is_empty());
+}
+
+#[test]
+fn break_glass_requires_reason() {
+    let dir = setup("protected = true");
+
+    envit(&dir)
+        .args(["export", "--break-glass"])
+        .assert()
+        .failure();
+    envit(&dir)
+        .args(["export", "--break-glass", "--reason", "  "])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("non-empty --reason"));
+}
+
+#[test]
+fn break_glass_export_is_audited_with_reason() {
+    let dir = setup("protected = true");
+
+    envit(&dir)
+        .args(["export", "--break-glass", "--reason", "INC-42 db outage"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("DATABASE_URL=super-secret"))
+        .stderr(predicate::str::contains("BREAK-GLASS EXPORT"));
+
+    let log = fs::read_to_string(dir.path().join(".envit/audit.jsonl")).unwrap();
+    assert!(log.contains(r#""break_glass":true"#));
+    assert!(log.contains(r#""reason":"INC-42 db outage""#));
+    assert!(log.contains(r#""exported_keys":["DATABASE_URL","REDIS"]"#));
+    assert!(!log.contains("super-secret"));
+}
+
+#[test]
+fn export_external_secret_lists_names_even_when_protected() {
+    let dir = setup("protected = true");
+
+    envit(&dir)
+        .args(["export", "--format", "external-secret"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains(
+            "needs one --secret-store per provider (1 configured)",
+        ));
+    envit(&dir)
+        .args([
+            "export",
+            "--format",
+            "external-secret",
+            "--name",
+            "app-env",
+            "--secret-store",
+            "azure-kv",
+        ])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("kind: ExternalSecret"))
+        .stdout(predicate::str::contains(
+            "    - secretKey: \"DATABASE_URL\"\n      remoteRef:\n        key: \"database-url\"\n",
+        ))
+        .stdout(predicate::str::contains("super-secret").not());
+}
+
+#[test]
+fn export_compose_secrets_references_the_files_envit_writes() {
+    let dir = setup("protected = true");
+    envit(&dir)
+        .args(["export", "--format", "compose-secrets"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("no [output.files] configured"));
+
+    let config = fs::read_to_string(dir.path().join("envit.toml")).unwrap();
+    write_file(
+        &dir.path().join("envit.toml"),
+        &format!(
+            "{config}\n[output.files]\nTLS_KEY = \"secrets/tls.key\"\nDATABASE_URL = {{ path = \"/run/db-url\" }}\n"
+        ),
+    );
+    envit(&dir)
+        .args(["export", "--format", "compose-secrets", "--service", "web"])
+        .assert()
+        .success()
+        .stdout(
+            "secrets:\n  \"database_url\":\n    file: \"/run/db-url\"\n  \"tls_key\":\n    file: \"./secrets/tls.key\"\n\
+             services:\n  \"web\":\n    secrets:\n      - \"database_url\"\n      - \"tls_key\"\n",
+        );
+}
+
+#[cfg(feature = "local-vault")]
+#[test]
+fn no_plaintext_allows_only_run_and_encrypted_outputs() {
+    let dir = setup("no_plaintext = true");
+
+    envit(&dir)
+        .arg("pull")
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains(
+            "plaintext secrets are disabled (security.no_plaintext or ENVIT_NO_PLAINTEXT): \
+             .env would be written in plaintext",
+        ));
+    assert!(!dir.path().join(".env").exists());
+    for args in [
+        &["export"][..],
+        &["export", "--format", "shell"],
+        &["get", "DATABASE_URL"],
+    ] {
+        envit(&dir)
+            .args(args)
+            .assert()
+            .failure()
+            .stderr(predicate::str::contains("plaintext secrets are disabled"))
+            .stdout(predicate::str::is_empty());
+    }
+    envit(&dir).args(["pull", "--dry-run"]).assert().success();
+    #[cfg(unix)]
+    envit(&dir)
+        .args(["run", "--", "sh", "-c", "echo $DATABASE_URL"])
+        .assert()
+        .success()
+        .stdout("super-secret\n");
+
+    let key = age::x25519::Identity::generate();
+    let config = fs::read_to_string(dir.path().join("envit.toml")).unwrap();
+    write_file(
+        &dir.path().join("envit.toml"),
+        &format!(
+            "{config}\n[output]\nenv_file = \".env.enc\"\n\n[output.encrypt]\nkind = \"age\"\nrecipients = [\"{}\"]\n",
+            key.to_public()
+        ),
+    );
+    envit(&dir).arg("pull").assert().success();
+    let encrypted = fs::read_to_string(dir.path().join(".env.enc")).unwrap();
+    assert!(!encrypted.contains("super-secret"));
+
+    // The environment variable turns the switch on without the config.
+    let dir = setup("");
+    envit(&dir)
+        .env("ENVIT_NO_PLAINTEXT", "1")
+        .arg("pull")
+        .assert()
+        .failure();
+    envit(&dir)
+        .env("ENVIT_NO_PLAINTEXT", "0")
+        .arg("pull")
+        .assert()
+        .success();
+}
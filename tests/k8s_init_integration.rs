@@ -0,0 +1,85 @@
+use std::{fs, path::Path, process::Command};
+
+use serde_json::Value;
+use tempfile::TempDir;
+
+const CONFIG: &str = r#"version = 1
+
+[provider]
+kind = "azure_key_vault"
+vault_url = "https://example.vault.azure.net/"
+"#;
+
+fn write_file(path: &Path, content: &str) {
+    fs::write(path, content).expect("failed to write file");
+}
+
+fn k8s_init(dir: &TempDir, secrets: &str, config: &str) -> (Option<i32>, Value) {
+    let config_dir = dir.path().join("config");
+    fs::create_dir_all(&config_dir).unwrap();
+    write_file(&config_dir.join("envit.toml"), config);
+    write_file(&dir.path().join("secrets.txt"), secrets);
+
+    let output = Command::new(assert_cmd::cargo::cargo_bin!("envit"))
+        .env("ENVIT_TEST_SECRETS_FILE", dir.path().join("secrets.txt"))
+        .arg("k8s-init")
+        .arg("--config")
+        .arg(config_dir.join("envit.toml"))
+        .arg("--env-file")
+        .arg(dir.path().join("shared/.env"))
+        .output()
+        .unwrap();
+
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert_eq!(stdout.lines().count(), 1, "{stdout}");
+    (output.status.code(), serde_json::from_str(&stdout).unwrap())
+}
+
+#[test]
+fn k8s_init_writes_shared_env_file_and_reports_json() {
+    let dir = TempDir::new().unwrap();
+    fs::create_dir(dir.path().join("shared")).unwrap();
+
+    let (code, status) = k8s_init(
+        &dir,
+        "!identity:workload\ndatabase-url=vault\nredis=cache\n",
+        CONFIG,
+    );
+
+    assert_eq!(code, Some(0));
+    assert_eq!(status["status"], "ok");
+    assert_eq!(status["principal"], "workload");
+    assert_eq!(status["keys"], 2);
+    assert_eq!(status["changed_keys"], 2);
+    let env = dir.path().join("shared/.env");
+    assert_eq!(
+        fs::read_to_string(&env).unwrap(),
+        "DATABASE_URL=vault\nREDIS=cache\n"
+    );
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        let mode = fs::metadata(&env).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o600);
+    }
+}
+
+#[test]
+fn k8s_init_exit_codes_separate_auth_transient_and_config_failures() {
+    let dir = TempDir::new().unwrap();
+    fs::create_dir(dir.path().join("shared")).unwrap();
+
+    let (code, status) = k8s_init(&dir, "!unauthenticated\n", CONFIG);
+    assert_eq!(code, Some(77));
+    assert_eq!(status["status"], "auth_error");
+
+    let (code, status) = k8s_init(&dir, "!error:database-url\n", CONFIG);
+    assert_eq!(code, Some(75));
+    assert_eq!(status["status"], "transient_error");
+    assert!(status["error"].as_str().unwrap().contains("database-url"));
+
+    let (code, status) = k8s_init(&dir, "", "version = 2\n");
+    assert_eq!(code, Some(78));
+    assert_eq!(status["status"], "config_error");
+    assert!(!dir.path().join("shared/.env").exists());
+}
@@ -53,6 +53,40 @@ fn pull_updates_and_adds_without_deleting_local_only_keys() {
     assert!(env_after.contains("LOCAL_ONLY=keep"));
 }
 
+#[test]
+fn status_messages_follow_the_locale_file() {
+    let dir = TempDir::new().unwrap();
+    write_config(&dir, "");
+    write_file(&dir.path().join("secrets.txt"), "database-url=new\n");
+    let messages = dir.path().join("messages");
+    fs::create_dir(&messages).unwrap();
+    write_file(
+        &messages.join("de.toml"),
+        "pull_updated = \"{count} Schlüssel in {path} aktualisiert\"\n",
+    );
+
+    Command::new(assert_cmd::cargo::cargo_bin!("envit"))
+        .current_dir(dir.path())
+        .env("ENVIT_TEST_SECRETS_FILE", dir.path().join("secrets.txt"))
+        .env("ENVIT_MESSAGES", &messages)
+        .env("ENVIT_LOCALE", "de_DE.UTF-8")
+        .arg("pull")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("1 Schlüssel in "))
+        .stdout(predicate::str::contains("Updated").not());
+
+    Command::new(assert_cmd::cargo::cargo_bin!("envit"))
+        .env("ENVIT_MESSAGES", &messages)
+        .env("ENVIT_LOCALE", "fr_FR")
+        .arg("messages")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains(
+            "pull_updated = \"Updated {count} keys in {path} ({bytes} bytes changed)\"",
+        ));
+}
+
 #[test]
 fn pull_preserves_comments_and_order() {
     let dir = TempDir::new().unwrap();
@@ -129,6 +163,43 @@ fn pull_aborts_without_writing_on_any_fetch_error() {
     assert_eq!(after, initial);
 }
 
+#[test]
+fn pull_degraded_ok_keeps_last_known_values_when_provider_fails() {
+    let dir = TempDir::new().unwrap();
+    write_config(&dir, "");
+    write_file(&dir.path().join(".env"), "DATABASE_URL=last-known\n");
+    write_file(
+        &dir.path().join("secrets.txt"),
+        "database-url=new\n!error:redis\n",
+    );
+    let pull = || {
+        let mut cmd = Command::new(assert_cmd::cargo::cargo_bin!("envit"));
+        cmd.current_dir(dir.path())
+            .env("ENVIT_TEST_SECRETS_FILE", dir.path().join("secrets.txt"))
+            .args(["pull", "--degraded-ok"]);
+        cmd
+    };
+
+    pull()
+        .assert()
+        .code(75)
+        .stderr(predicate::str::contains("warning: DEGRADED: azure_key_vault:"))
+        .stdout(predicate::str::contains(
+            "DEGRADED: 1 of 1 providers unreachable",
+        ));
+    assert_eq!(
+        fs::read_to_string(dir.path().join(".env")).unwrap(),
+        "DATABASE_URL=last-known\n"
+    );
+
+    // Rejected credentials are not papered over.
+    write_file(&dir.path().join("secrets.txt"), "!unauthenticated\n");
+    pull()
+        .assert()
+        .code(1)
+        .stderr(predicate::str::contains("authentication"));
+}
+
 #[test]
 fn pull_errors_when_env_missing_and_create_if_missing_false() {
     let dir = TempDir::new().unwrap();
@@ -154,3 +225,1834 @@ vault_url = "https://example.vault.azure.net/"
         .failure()
         .stderr(predicate::str::contains("env file does not exist"));
 }
+
+#[test]
+fn why_failed_explains_the_last_failed_pull_until_one_succeeds() {
+    let dir = TempDir::new().unwrap();
+    write_file(
+        &dir.path().join("envit.toml"),
+        r#"version = 1
+
+[provider]
+kind = "azure_key_vault"
+vault_url = "https://example.vault.azure.net/"
+"#,
+    );
+    let envit = |command: &str| {
+        let mut cmd = Command::new(assert_cmd::cargo::cargo_bin!("envit"));
+        cmd.current_dir(dir.path())
+            .env("ENVIT_TEST_SECRETS_FILE", dir.path().join("secrets.txt"))
+            .arg(command);
+        cmd
+    };
+
+    envit("why-failed")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("No failed run recorded."));
+
+    write_file(&dir.path().join("secrets.txt"), "!unauthenticated\n");
+    envit("pull").assert().code(1);
+    envit("why-failed")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Last failure: envit pull at "))
+        .stdout(predicate::str::contains("cause: authentication"))
+        .stdout(predicate::str::contains("1. azure_key_vault: sign in with `az login`"))
+        .stdout(predicate::str::contains("2. confirm the credentials work: envit doctor"));
+
+    write_file(&dir.path().join("secrets.txt"), "database-url=ok\n");
+    envit("pull").assert().success();
+    envit("why-failed")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains(
+            "No failed run recorded; the last pull succeeded at ",
+        ));
+}
+
+#[cfg(all(unix, feature = "keeper"))]
+#[test]
+fn pull_keeper_fetches_mapped_record_fields_via_ksm() {
+    use std::os::unix::fs::PermissionsExt;
+
+    let dir = TempDir::new().unwrap();
+    let config = r#"version = 1
+
+[provider]
+kind = "keeper"
+config_file = "ksm-config.json"
+command = "./fake-ksm"
+
+[map]
+DATABASE_PASSWORD = "AbCdEf123/field/password"
+"#;
+    write_file(&dir.path().join("envit.toml"), config);
+    write_file(&dir.path().join("ksm-config.json"), "{\"clientId\":\"x\"}\n");
+
+    let script = dir.path().join("fake-ksm");
+    write_file(
+        &script,
+        "#!/bin/sh\n[ -n \"$KSM_CONFIG\" ] || exit 3\n[ \"$3\" = \"keeper://AbCdEf123/field/password\" ] || exit 4\necho hunter2\n",
+    );
+    fs::set_permissions(&script, fs::Permissions::from_mode(0o755)).unwrap();
+
+    Command::new(assert_cmd::cargo::cargo_bin!("envit"))
+        .current_dir(dir.path())
+        .env_remove("ENVIT_TEST_SECRETS_FILE")
+        .arg("pull")
+        .assert()
+        .success();
+
+    let env_after = fs::read_to_string(dir.path().join(".env")).unwrap();
+    assert_eq!(env_after, "DATABASE_PASSWORD=hunter2\n");
+}
+
+#[test]
+fn pull_records_principal_in_audit_log_and_report() {
+    let dir = TempDir::new().unwrap();
+    let config = r#"version = 1
+
+[provider]
+kind = "azure_key_vault"
+vault_url = "https://example.vault.azure.net/"
+
+[audit]
+path = ".envit/audit.jsonl"
+"#;
+    write_file(&dir.path().join("envit.toml"), config);
+    write_file(
+        &dir.path().join("secrets.txt"),
+        "!identity:11111111-aaaa\ndatabase-url=super-secret\n",
+    );
+
+    Command::new(assert_cmd::cargo::cargo_bin!("envit"))
+        .current_dir(dir.path())
+        .env("ENVIT_TEST_SECRETS_FILE", dir.path().join("secrets.txt"))
+        .arg("pull")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Authenticated as 11111111-aaaa"));
+
+    let log = fs::read_to_string(dir.path().join(".envit/audit.jsonl")).unwrap();
+    assert_eq!(log.lines().count(), 1);
+    assert!(log.contains(r#""principal":"11111111-aaaa""#));
+    assert!(log.contains(r#""changed_keys":["DATABASE_URL"]"#));
+    assert!(!log.contains("super-secret"));
+}
+
+#[test]
+fn pull_with_canary_writes_fresh_canary_and_audits_it() {
+    let dir = TempDir::new().unwrap();
+    let config = r#"version = 1
+
+[output]
+canary = true
+
+[provider]
+kind = "azure_key_vault"
+vault_url = "https://example.vault.azure.net/"
+
+[audit]
+path = "audit.jsonl"
+"#;
+    write_file(&dir.path().join("envit.toml"), config);
+    write_file(&dir.path().join("secrets.txt"), "database-url=new\n");
+
+    let canary = |env: &str| {
+        env.lines()
+            .find_map(|l| l.strip_prefix("ENVIT_CANARY="))
+            .expect("canary line")
+            .to_string()
+    };
+
+    let mut seen = Vec::new();
+    for _ in 0..2 {
+        Command::new(assert_cmd::cargo::cargo_bin!("envit"))
+            .current_dir(dir.path())
+            .env("ENVIT_TEST_SECRETS_FILE", dir.path().join("secrets.txt"))
+            .arg("pull")
+            .assert()
+            .success();
+        seen.push(canary(&fs::read_to_string(dir.path().join(".env")).unwrap()));
+    }
+
+    assert_ne!(seen[0], seen[1]);
+    let log = fs::read_to_string(dir.path().join("audit.jsonl")).unwrap();
+    for canary in &seen {
+        assert!(log.contains(&format!(r#""canary":"{canary}""#)));
+    }
+}
+
+#[test]
+fn pull_skips_byte_identical_file_and_reports_zero_bytes() {
+    let dir = TempDir::new().unwrap();
+    write_config(&dir, "");
+    write_file(&dir.path().join(".env"), "DATABASE_URL=same\n");
+    write_file(&dir.path().join("secrets.txt"), "database-url=same\n");
+    let before = fs::metadata(dir.path().join(".env")).unwrap().modified().unwrap();
+
+    Command::new(assert_cmd::cargo::cargo_bin!("envit"))
+        .current_dir(dir.path())
+        .env("ENVIT_TEST_SECRETS_FILE", dir.path().join("secrets.txt"))
+        .arg("pull")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("0 bytes changed"));
+
+    let after = fs::metadata(dir.path().join(".env")).unwrap().modified().unwrap();
+    assert_eq!(before, after);
+}
+
+#[test]
+fn pull_creating_empty_env_file_reports_zero_bytes() {
+    let dir = TempDir::new().unwrap();
+    write_config(&dir, "");
+    write_file(&dir.path().join("secrets.txt"), "");
+
+    Command::new(assert_cmd::cargo::cargo_bin!("envit"))
+        .current_dir(dir.path())
+        .env("ENVIT_TEST_SECRETS_FILE", dir.path().join("secrets.txt"))
+        .arg("pull")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("(0 bytes changed)"));
+
+    assert_eq!(fs::read_to_string(dir.path().join(".env")).unwrap(), "");
+}
+
+#[test]
+fn pull_writes_output_files_and_puts_their_paths_in_env() {
+    let dir = TempDir::new().unwrap();
+    write_config(&dir, "\n[output.files]\nTLS_CERT = \"certs/tls.pem\"");
+    write_file(
+        &dir.path().join("secrets.txt"),
+        "tls-cert=-----BEGIN CERTIFICATE-----\nredis=redis://localhost\n",
+    );
+
+    Command::new(assert_cmd::cargo::cargo_bin!("envit"))
+        .current_dir(dir.path())
+        .env("ENVIT_TEST_SECRETS_FILE", dir.path().join("secrets.txt"))
+        .args(["pull", "--dry-run"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("ADD TLS_CERT=********"));
+    assert!(!dir.path().join("certs").exists());
+
+    Command::new(assert_cmd::cargo::cargo_bin!("envit"))
+        .current_dir(dir.path())
+        .env("ENVIT_TEST_SECRETS_FILE", dir.path().join("secrets.txt"))
+        .arg("pull")
+        .assert()
+        .success();
+
+    let env_after = fs::read_to_string(dir.path().join(".env")).unwrap();
+    assert!(env_after.contains("TLS_CERT=certs/tls.pem"));
+    assert!(env_after.contains("REDIS=redis://localhost"));
+    assert_eq!(
+        fs::read_to_string(dir.path().join("certs/tls.pem")).unwrap(),
+        "-----BEGIN CERTIFICATE-----"
+    );
+}
+
+#[test]
+fn pull_writes_base64_output_files_byte_exact_with_checksum() {
+    let dir = TempDir::new().unwrap();
+    write_config(
+        &dir,
+        "\n[output.files]\nKEYSTORE = { path = \"certs/app.p12\", encoding = \"base64\", checksum = true }",
+    );
+    write_file(&dir.path().join("secrets.txt"), "keystore=AP8QgA==\n");
+
+    Command::new(assert_cmd::cargo::cargo_bin!("envit"))
+        .current_dir(dir.path())
+        .env("ENVIT_TEST_SECRETS_FILE", dir.path().join("secrets.txt"))
+        .arg("pull")
+        .assert()
+        .success();
+
+    assert_eq!(
+        fs::read(dir.path().join("certs/app.p12")).unwrap(),
+        [0x00, 0xff, 0x10, 0x80]
+    );
+    assert_eq!(
+        fs::read_to_string(dir.path().join("certs/app.p12.sha256")).unwrap(),
+        "a33bb2aed757bc839807d7a9deab0688c3cf06d36e53cb428f2e539c8dc76c5b  app.p12\n"
+    );
+    assert!(
+        fs::read_to_string(dir.path().join(".env"))
+            .unwrap()
+            .contains("KEYSTORE=certs/app.p12")
+    );
+
+    Command::new(assert_cmd::cargo::cargo_bin!("envit"))
+        .current_dir(dir.path())
+        .env("ENVIT_TEST_SECRETS_FILE", dir.path().join("secrets.txt"))
+        .arg("diff")
+        .assert()
+        .success()
+        .stdout("No drift.\n");
+
+    write_file(&dir.path().join("secrets.txt"), "keystore=not base64!\n");
+    Command::new(assert_cmd::cargo::cargo_bin!("envit"))
+        .current_dir(dir.path())
+        .env("ENVIT_TEST_SECRETS_FILE", dir.path().join("secrets.txt"))
+        .arg("pull")
+        .assert()
+        .code(1)
+        .stderr(predicate::str::contains("KEYSTORE is not valid base64"));
+}
+
+#[test]
+fn pull_deprecates_keys_renamed_in_map_instead_of_leaving_them_silently() {
+    let dir = TempDir::new().unwrap();
+    write_config(&dir, "deprecation_grace = \"14d\"");
+    write_file(&dir.path().join("secrets.txt"), "db-url=postgres://x\n");
+
+    let pull = || {
+        Command::new(assert_cmd::cargo::cargo_bin!("envit"))
+            .current_dir(dir.path())
+            .env("ENVIT_TEST_SECRETS_FILE", dir.path().join("secrets.txt"))
+            .arg("pull")
+            .assert()
+            .success()
+    };
+    pull();
+    assert_eq!(
+        fs::read_to_string(dir.path().join(".env")).unwrap(),
+        "DB_URL=postgres://x\n"
+    );
+
+    let config = fs::read_to_string(dir.path().join("envit.toml")).unwrap();
+    write_file(
+        &dir.path().join("envit.toml"),
+        &format!("{config}\n[map]\nDATABASE_URL = \"db-url\"\n"),
+    );
+    pull().stdout(predicate::str::contains(
+        "Deprecated DB_URL (renamed to DATABASE_URL); kept until",
+    ));
+
+    let env_after = fs::read_to_string(dir.path().join(".env")).unwrap();
+    let lines: Vec<_> = env_after.lines().collect();
+    assert!(lines[0].starts_with(
+        "# envit: deprecated DB_URL (renamed to DATABASE_URL), remove after "
+    ));
+    assert_eq!(&lines[1..], ["DB_URL=postgres://x", "DATABASE_URL=postgres://x"]);
+
+    // Reported once; the comment carries the deadline from here on.
+    pull().stdout(predicate::str::contains("Deprecated").not());
+    assert_eq!(fs::read_to_string(dir.path().join(".env")).unwrap(), env_after);
+}
+
+#[test]
+fn prune_removes_only_pulled_keys_whose_secrets_are_gone() {
+    let dir = TempDir::new().unwrap();
+    write_config(&dir, "");
+    write_file(&dir.path().join(".env"), "LOCAL_ONLY=keep\n");
+    write_file(
+        &dir.path().join("secrets.txt"),
+        "database-url=postgres://x\nold-token=t\n",
+    );
+    let envit = |command: &str| {
+        let mut cmd = Command::new(assert_cmd::cargo::cargo_bin!("envit"));
+        cmd.current_dir(dir.path())
+            .env("ENVIT_TEST_SECRETS_FILE", dir.path().join("secrets.txt"))
+            .arg(command);
+        cmd
+    };
+
+    envit("prune")
+        .assert()
+        .success()
+        .stdout("No pulled keys recorded yet; nothing to prune.\n");
+    envit("pull").assert().success();
+    write_file(
+        &dir.path().join("secrets.txt"),
+        "database-url=postgres://x\n",
+    );
+
+    envit("prune")
+        .arg("--dry-run")
+        .assert()
+        .success()
+        .stdout("Would prune OLD_TOKEN\n");
+    envit("prune")
+        .assert()
+        .success()
+        .stdout("Pruned OLD_TOKEN\n");
+    assert_eq!(
+        fs::read_to_string(dir.path().join(".env")).unwrap(),
+        "LOCAL_ONLY=keep\nDATABASE_URL=postgres://x\n"
+    );
+    envit("prune")
+        .assert()
+        .success()
+        .stdout("Nothing to prune.\n");
+}
+
+#[test]
+fn clean_removes_pulled_and_deprecated_keys_but_keeps_local_ones() {
+    let dir = TempDir::new().unwrap();
+    write_config(&dir, "deprecation_grace = \"14d\"");
+    write_file(&dir.path().join(".env"), "# local\nLOCAL_ONLY=keep\n");
+    write_file(
+        &dir.path().join("secrets.txt"),
+        "database-url=postgres://x\nold-token=t\n",
+    );
+    let envit = |command: &str| {
+        let mut cmd = Command::new(assert_cmd::cargo::cargo_bin!("envit"));
+        cmd.current_dir(dir.path())
+            .env("ENVIT_TEST_SECRETS_FILE", dir.path().join("secrets.txt"))
+            .arg(command);
+        cmd
+    };
+    envit("pull").assert().success();
+    write_file(
+        &dir.path().join("secrets.txt"),
+        "database-url=postgres://x\n",
+    );
+    envit("pull").assert().success();
+    assert!(
+        fs::read_to_string(dir.path().join(".env"))
+            .unwrap()
+            .contains("# envit: deprecated OLD_TOKEN")
+    );
+
+    envit("clean")
+        .arg("--dry-run")
+        .assert()
+        .success()
+        .stdout("Would remove DATABASE_URL\nWould remove OLD_TOKEN\n");
+    envit("clean")
+        .assert()
+        .success()
+        .stdout("Removed DATABASE_URL\nRemoved OLD_TOKEN\n");
+    assert_eq!(
+        fs::read_to_string(dir.path().join(".env")).unwrap(),
+        "# local\nLOCAL_ONLY=keep\n"
+    );
+    envit("clean")
+        .assert()
+        .success()
+        .stdout("Nothing to clean.\n");
+}
+
+#[test]
+fn rollback_restores_snapshots_taken_before_each_write() {
+    let dir = TempDir::new().unwrap();
+    write_config(&dir, "backups = 3");
+    write_file(&dir.path().join(".env"), "LOCAL=1\n");
+    let envit = |args: &[&str]| {
+        let mut cmd = Command::new(assert_cmd::cargo::cargo_bin!("envit"));
+        cmd.current_dir(dir.path())
+            .env("ENVIT_TEST_SECRETS_FILE", dir.path().join("secrets.txt"))
+            .args(args);
+        cmd
+    };
+    for value in ["1", "2", "3"] {
+        write_file(&dir.path().join("secrets.txt"), &format!("token={value}\n"));
+        envit(&["pull"]).assert().success();
+    }
+    let env = || fs::read_to_string(dir.path().join(".env")).unwrap();
+    assert_eq!(env(), "LOCAL=1\nTOKEN=3\n");
+
+    let listed = envit(&["rollback", "--list"]).assert().success();
+    let listed = String::from_utf8_lossy(&listed.get_output().stdout).into_owned();
+    let snapshots: Vec<_> = listed.lines().collect();
+    assert_eq!(snapshots.len(), 3);
+
+    envit(&["rollback", "--to", snapshots[1]])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("from the snapshot of"));
+    assert_eq!(env(), "LOCAL=1\nTOKEN=1\n");
+    // The rollback snapshotted the file it replaced, so it can be undone.
+    envit(&["rollback"]).assert().success();
+    assert_eq!(env(), "LOCAL=1\nTOKEN=3\n");
+    envit(&["rollback", "--to", "2001-01-01T00:00:00Z"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("no snapshot taken at or before"));
+}
+
+#[test]
+fn assert_checks_a_process_environment_against_the_pulled_keys() {
+    let dir = TempDir::new().unwrap();
+    write_config(&dir, "");
+    let config = dir.path().join("envit.toml");
+    let raw = fs::read_to_string(&config).unwrap();
+    write_file(
+        &config,
+        &format!("{raw}\n[classify]\nDATABASE_URL = \"connection_string\"\n"),
+    );
+    write_file(
+        &dir.path().join("secrets.txt"),
+        "database-url=postgres://app:pw@db/app\napi-token=abc\n",
+    );
+    let envit = |args: &[&str]| {
+        let mut cmd = Command::new(assert_cmd::cargo::cargo_bin!("envit"));
+        cmd.current_dir(dir.path())
+            .env("ENVIT_TEST_SECRETS_FILE", dir.path().join("secrets.txt"))
+            .args(args);
+        cmd
+    };
+    envit(&["pull"]).assert().success();
+
+    envit(&["assert"])
+        .env("DATABASE_URL", "postgres://app:pw@db/app")
+        .env("API_TOKEN", "abc")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains(
+            "All 2 required keys are set in process self.",
+        ));
+    envit(&["assert", "--env-from-process", "self"])
+        .env("DATABASE_URL", "db:5432")
+        .env_remove("API_TOKEN")
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("error: API_TOKEN is not set"))
+        .stderr(predicate::str::contains(
+            "error: DATABASE_URL is not a valid connection_string",
+        ))
+        .stderr(predicate::str::contains("2 problem(s)"));
+
+    if cfg!(target_os = "linux") {
+        let mut app = std::process::Command::new("sleep")
+            .arg("30")
+            .env("DATABASE_URL", "postgres://app:pw@db/app")
+            .env("API_TOKEN", "abc")
+            .spawn()
+            .unwrap();
+        let pid = app.id().to_string();
+        let checked = envit(&["assert", "--env-from-process", &pid])
+            .env_remove("API_TOKEN")
+            .assert();
+        app.kill().unwrap();
+        app.wait().unwrap();
+        checked.success();
+    }
+}
+
+#[test]
+fn history_marks_the_version_the_env_file_holds() {
+    let dir = TempDir::new().unwrap();
+    write_config(&dir, "");
+    write_file(
+        &dir.path().join("secrets.txt"),
+        "api-token=first\napi-token=second\napi-token=third\n",
+    );
+    let envit = |args: &[&str]| {
+        let mut cmd = Command::new(assert_cmd::cargo::cargo_bin!("envit"));
+        cmd.current_dir(dir.path())
+            .env("ENVIT_TEST_SECRETS_FILE", dir.path().join("secrets.txt"))
+            .args(args);
+        cmd
+    };
+    let marked = |stdout: &[u8]| -> Vec<String> {
+        String::from_utf8_lossy(stdout)
+            .lines()
+            .filter(|line| line.ends_with(".env"))
+            .map(|line| line.split_whitespace().next().unwrap().to_string())
+            .collect()
+    };
+    envit(&["pull"]).assert().success();
+
+    let shown = envit(&["history", "API_TOKEN"]).assert().success();
+    let stdout = &shown.get_output().stdout;
+    assert!(String::from_utf8_lossy(stdout).starts_with("api-token: 3 versions\nVERSION"));
+    assert!(!String::from_utf8_lossy(stdout).contains("third"));
+    assert_eq!(marked(stdout), ["3"]);
+
+    write_file(&dir.path().join(".env"), "API_TOKEN=second\n");
+    let shown = envit(&["history", "API_TOKEN"]).assert().success();
+    assert_eq!(marked(&shown.get_output().stdout), ["2"]);
+
+    write_file(&dir.path().join(".env"), "API_TOKEN=edited\n");
+    envit(&["history", "API_TOKEN"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains(
+            "API_TOKEN in .env matches no version of api-token.",
+        ));
+}
+
+#[test]
+fn example_writes_blank_managed_keys_and_check_reports_drift() {
+    let dir = TempDir::new().unwrap();
+    write_config(&dir, "");
+    write_file(
+        &dir.path().join("secrets.txt"),
+        "api-token=abc\ndb-password=pw\n",
+    );
+    let envit = |args: &[&str]| {
+        let mut cmd = Command::new(assert_cmd::cargo::cargo_bin!("envit"));
+        cmd.current_dir(dir.path())
+            .env("ENVIT_TEST_SECRETS_FILE", dir.path().join("secrets.txt"))
+            .args(args);
+        cmd
+    };
+
+    envit(&["example"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Wrote 2 keys to"));
+    let example = fs::read_to_string(dir.path().join(".env.example")).unwrap();
+    assert_eq!(
+        example,
+        "# envit: azure_key_vault secret api-token (token)\nAPI_TOKEN=\n\
+         # envit: azure_key_vault secret db-password (password)\nDB_PASSWORD=\n"
+    );
+    assert!(!example.contains("abc"));
+    envit(&["example", "--check"]).assert().success();
+
+    write_file(
+        &dir.path().join("secrets.txt"),
+        "api-token=abc\ndb-password=pw\nsentry-dsn=x\n",
+    );
+    envit(&["example", "--check"])
+        .assert()
+        .code(2)
+        .stdout(predicate::str::contains("is out of date"));
+    assert_eq!(
+        fs::read_to_string(dir.path().join(".env.example")).unwrap(),
+        example
+    );
+}
+
+#[test]
+fn expiry_comments_sit_above_keys_whose_secret_expires() {
+    let dir = TempDir::new().unwrap();
+    write_config(&dir, "expiry_comments = true");
+    write_file(
+        &dir.path().join("secrets.txt"),
+        "api-token=abc\ndb-password=pw\n\
+         !details:api-token expires=2020-03-01T00:00:00Z\n",
+    );
+    write_file(&dir.path().join(".env"), "LOCAL=1\n");
+    let pull = || {
+        Command::new(assert_cmd::cargo::cargo_bin!("envit"))
+            .current_dir(dir.path())
+            .env("ENVIT_TEST_SECRETS_FILE", dir.path().join("secrets.txt"))
+            .arg("pull")
+            .assert()
+            .success();
+        fs::read_to_string(dir.path().join(".env")).unwrap()
+    };
+
+    let env = pull();
+    let lines: Vec<_> = env.lines().collect();
+    assert_eq!(lines[0], "LOCAL=1");
+    assert!(
+        lines[1].starts_with("# envit: expired 2020-03-01 (") && lines[1].ends_with(" days ago)"),
+        "{env}"
+    );
+    assert_eq!(lines[2..], ["API_TOKEN=abc", "DB_PASSWORD=pw"]);
+    assert_eq!(pull(), env);
+
+    write_file(
+        &dir.path().join("secrets.txt"),
+        "api-token=abc\ndb-password=pw\n",
+    );
+    assert_eq!(pull(), "LOCAL=1\nAPI_TOKEN=abc\nDB_PASSWORD=pw\n");
+}
+
+#[test]
+fn diff_reports_drift_by_key_and_exits_2_without_writing() {
+    let dir = TempDir::new().unwrap();
+    write_config(&dir, "");
+    write_file(
+        &dir.path().join(".env"),
+        "DATABASE_URL=old\nSAME=1\nLOCAL_ONLY=keep\n",
+    );
+    write_file(
+        &dir.path().join("secrets.txt"),
+        "database-url=new\nsame=1\nredis=redis://localhost\n",
+    );
+    let diff = || {
+        let mut cmd = Command::new(assert_cmd::cargo::cargo_bin!("envit"));
+        cmd.current_dir(dir.path())
+            .env("ENVIT_TEST_SECRETS_FILE", dir.path().join("secrets.txt"))
+            .arg("diff");
+        cmd
+    };
+
+    diff()
+        .assert()
+        .code(2)
+        .stdout("ADD REDIS\nUPDATE DATABASE_URL\n");
+    diff()
+        .arg("--values")
+        .assert()
+        .code(2)
+        .stdout(predicate::str::contains("UPDATE DATABASE_URL: old -> new"));
+    assert_eq!(
+        fs::read_to_string(dir.path().join(".env")).unwrap(),
+        "DATABASE_URL=old\nSAME=1\nLOCAL_ONLY=keep\n"
+    );
+
+    write_file(
+        &dir.path().join(".env"),
+        "DATABASE_URL=new\nSAME=1\nREDIS=redis://localhost\n",
+    );
+    diff().assert().success().stdout("No drift.\n");
+}
+
+#[test]
+fn diff_values_masks_credentials_by_class() {
+    let dir = TempDir::new().unwrap();
+    write_config(&dir, "");
+    let config = fs::read_to_string(dir.path().join("envit.toml")).unwrap();
+    write_file(
+        &dir.path().join("envit.toml"),
+        &format!("{config}\n[classify]\n\"SIGNING_*\" = \"token\"\n"),
+    );
+    write_file(&dir.path().join(".env"), "LOG_LEVEL=warn\n");
+    write_file(
+        &dir.path().join("secrets.txt"),
+        "log-level=info\ndb-password=hunter2\nsigning-salt=abc\n\
+         primary=postgres://app:hunter2@db/app\n",
+    );
+
+    Command::new(assert_cmd::cargo::cargo_bin!("envit"))
+        .current_dir(dir.path())
+        .env("ENVIT_TEST_SECRETS_FILE", dir.path().join("secrets.txt"))
+        .args(["diff", "--values"])
+        .assert()
+        .code(2)
+        .stdout(
+            "ADD DB_PASSWORD: (unset) -> ********\n\
+             ADD PRIMARY: (unset) -> postgres://app:****@db/app\n\
+             ADD SIGNING_SALT: (unset) -> ********\n\
+             UPDATE LOG_LEVEL: warn -> info\n",
+        );
+}
+
+#[test]
+fn check_separates_drift_from_errors_by_exit_code() {
+    let dir = TempDir::new().unwrap();
+    write_config(&dir, "");
+    write_file(&dir.path().join(".env"), "DATABASE_URL=old\n");
+    write_file(&dir.path().join("secrets.txt"), "database-url=new\n");
+    let check = || {
+        let mut cmd = Command::new(assert_cmd::cargo::cargo_bin!("envit"));
+        cmd.current_dir(dir.path())
+            .env("ENVIT_TEST_SECRETS_FILE", dir.path().join("secrets.txt"))
+            .arg("check");
+        cmd
+    };
+
+    check().assert().code(2).stdout("UPDATE DATABASE_URL\n");
+    check().arg("--no-such-flag").assert().code(1);
+    check()
+        .args(["--config", "missing.toml"])
+        .assert()
+        .code(1)
+        .stdout("");
+
+    write_file(&dir.path().join(".env"), "DATABASE_URL=new\n");
+    check().assert().code(0).stdout("No drift.\n");
+}
+
+#[cfg(feature = "local-vault")]
+#[test]
+fn pull_encrypts_values_for_committing_and_decrypt_restores_them() {
+    use age::secrecy::ExposeSecret;
+
+    let dir = TempDir::new().unwrap();
+    let key = age::x25519::Identity::generate();
+    write_file(
+        &dir.path().join("me.key"),
+        key.to_string().expose_secret(),
+    );
+    write_config(
+        &dir,
+        &format!(
+            "\n[output.codec]\nkind = \"age\"\nrecipients = [\"{}\"]\nidentity_file = \"me.key\"",
+            key.to_public()
+        ),
+    );
+    write_file(&dir.path().join(".env"), "# team env\nLOCAL=plain\n");
+    write_file(
+        &dir.path().join("secrets.txt"),
+        "database-url=postgres://db\nredis=redis://localhost\n",
+    );
+    let envit = |command: &str| {
+        let mut cmd = Command::new(assert_cmd::cargo::cargo_bin!("envit"));
+        cmd.current_dir(dir.path())
+            .env("ENVIT_TEST_SECRETS_FILE", dir.path().join("secrets.txt"))
+            .env_remove("ENVIT_AGE_IDENTITY")
+            .arg(command);
+        cmd
+    };
+
+    envit("pull").assert().success();
+    let first = fs::read_to_string(dir.path().join(".env")).unwrap();
+    assert!(first.starts_with("# team env\nLOCAL=plain\nDATABASE_URL=ENC[age:"));
+    assert!(!first.contains("postgres://db"));
+
+    // Unchanged values keep their ciphertext; only the changed key moves.
+    envit("pull")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("No changes"));
+    write_file(
+        &dir.path().join("secrets.txt"),
+        "database-url=postgres://db\nredis=redis://other\n",
+    );
+    envit("pull").assert().success();
+    let second = fs::read_to_string(dir.path().join(".env")).unwrap();
+    let database_url = |content: &str| {
+        content
+            .lines()
+            .find(|line| line.starts_with("DATABASE_URL="))
+            .unwrap()
+            .to_string()
+    };
+    assert_eq!(database_url(&first), database_url(&second));
+    assert_ne!(first, second);
+
+    envit("decrypt").assert().success().stdout(
+        "# team env\nLOCAL=plain\nDATABASE_URL=postgres://db\nREDIS=redis://other\n",
+    );
+}
+
+#[cfg(feature = "local-vault")]
+#[test]
+fn encrypted_env_file_stays_ciphertext_on_disk_and_run_decrypts_it() {
+    use age::secrecy::ExposeSecret;
+
+    let dir = TempDir::new().unwrap();
+    let key = age::x25519::Identity::generate();
+    write_file(
+        &dir.path().join("me.key"),
+        key.to_string().expose_secret(),
+    );
+    write_config(
+        &dir,
+        &format!(
+            "env_file = \".env.enc\"\n\n[output.encrypt]\nkind = \"age\"\nrecipients = [\"{}\"]\nidentity_file = \"me.key\"",
+            key.to_public()
+        ),
+    );
+    let config = fs::read_to_string(dir.path().join("envit.toml")).unwrap();
+    write_file(
+        &dir.path().join("envit.toml"),
+        &config.replacen("env_file = \".env\"\n", "", 1),
+    );
+    write_file(&dir.path().join(".env"), "# team env\nLOCAL=plain\n");
+    write_file(&dir.path().join("secrets.txt"), "database-url=postgres://db\n");
+    let envit = |args: &[&str]| {
+        let mut cmd = Command::new(assert_cmd::cargo::cargo_bin!("envit"));
+        cmd.current_dir(dir.path())
+            .env("ENVIT_TEST_SECRETS_FILE", dir.path().join("secrets.txt"))
+            .env_remove("ENVIT_AGE_IDENTITY")
+            .args(args);
+        cmd
+    };
+
+    envit(&["encrypt", ".env"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains(".env still holds the plaintext"));
+    envit(&["pull"]).assert().success();
+    let encrypted = fs::read_to_string(dir.path().join(".env.enc")).unwrap();
+    assert!(encrypted.starts_with("-----BEGIN AGE ENCRYPTED FILE-----"));
+    assert!(!encrypted.contains("LOCAL") && !encrypted.contains("postgres"));
+
+    // Compared as plaintext, so an unchanged pull leaves the file alone.
+    envit(&["pull"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("No changes"));
+    assert_eq!(
+        fs::read_to_string(dir.path().join(".env.enc")).unwrap(),
+        encrypted
+    );
+    envit(&["diff"]).assert().code(0);
+    envit(&["decrypt"])
+        .assert()
+        .success()
+        .stdout("# team env\nLOCAL=plain\nDATABASE_URL=postgres://db\n");
+
+    #[cfg(unix)]
+    envit(&["run", "--from-env-file", "--", "sh", "-c", "echo $LOCAL $DATABASE_URL"])
+        .env_remove("ENVIT_TEST_SECRETS_FILE")
+        .assert()
+        .success()
+        .stdout("plain postgres://db\n");
+    envit(&["encrypt"])
+        .assert()
+        .code(1)
+        .stderr(predicate::str::contains("already encrypted"));
+}
+
+#[cfg(feature = "local-vault")]
+#[test]
+fn diff_against_git_compares_with_a_committed_and_encrypted_env_file() {
+    use std::io::Write;
+
+    use age::{
+        armor::{ArmoredWriter, Format},
+        secrecy::SecretString,
+    };
+
+    let dir = TempDir::new().unwrap();
+    write_config(&dir, "");
+    let git = |args: &[&str]| {
+        let status = std::process::Command::new("git")
+            .args(["-c", "user.name=test", "-c", "user.email=test@example.com"])
+            .args(args)
+            .current_dir(dir.path())
+            .status()
+            .unwrap();
+        assert!(status.success(), "git {args:?}");
+    };
+    let committed = "DATABASE_URL=old\nSAME=1\nREMOVED=x\n";
+    let encryptor =
+        age::Encryptor::with_user_passphrase(SecretString::from("passphrase".to_string()));
+    let mut writer = encryptor
+        .wrap_output(ArmoredWriter::wrap_output(Vec::new(), Format::AsciiArmor).unwrap())
+        .unwrap();
+    writer.write_all(committed.as_bytes()).unwrap();
+    let armored = writer.finish().unwrap().finish().unwrap();
+    write_file(&dir.path().join(".env"), committed);
+    fs::write(dir.path().join(".env.enc"), armored).unwrap();
+    git(&["init", "--quiet"]);
+    git(&["add", ".env", ".env.enc"]);
+    git(&["commit", "--quiet", "-m", "release"]);
+    write_file(&dir.path().join(".env"), "DATABASE_URL=local-edit\n");
+    write_file(
+        &dir.path().join("secrets.txt"),
+        "database-url=new\nsame=1\nredis=redis://localhost\n",
+    );
+    let diff = || {
+        let mut cmd = Command::new(assert_cmd::cargo::cargo_bin!("envit"));
+        cmd.current_dir(dir.path())
+            .env("ENVIT_TEST_SECRETS_FILE", dir.path().join("secrets.txt"))
+            .args(["diff", "--against-git", "HEAD"]);
+        cmd
+    };
+
+    diff()
+        .assert()
+        .code(2)
+        .stdout("ADD REDIS\nUPDATE DATABASE_URL\nREMOVE REMOVED\n");
+    diff()
+        .args([".env.enc", "--values"])
+        .env("ENVIT_VAULT_PASSPHRASE", "passphrase")
+        .assert()
+        .code(2)
+        .stdout(predicate::str::contains("UPDATE DATABASE_URL: old -> new"));
+    diff()
+        .arg(".env.enc")
+        .env_remove("ENVIT_VAULT_PASSPHRASE")
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("set ENVIT_VAULT_PASSPHRASE"));
+    Command::new(assert_cmd::cargo::cargo_bin!("envit"))
+        .current_dir(dir.path())
+        .env("ENVIT_TEST_SECRETS_FILE", dir.path().join("secrets.txt"))
+        .args(["diff", "--against-git", "no-such-rev"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("git show no-such-rev:./.env failed"));
+}
+
+#[test]
+fn status_lists_managed_local_and_missing_keys_and_last_sync() {
+    let dir = TempDir::new().unwrap();
+    write_config(&dir, "");
+    write_file(
+        &dir.path().join(".env"),
+        "DATABASE_URL=old\nLOCAL_ONLY=keep\n",
+    );
+    write_file(
+        &dir.path().join("secrets.txt"),
+        "database-url=new\nredis=redis://localhost\n",
+    );
+    let envit = |command: &str| {
+        let mut cmd = Command::new(assert_cmd::cargo::cargo_bin!("envit"));
+        cmd.current_dir(dir.path())
+            .env("ENVIT_TEST_SECRETS_FILE", dir.path().join("secrets.txt"))
+            .arg(command);
+        cmd
+    };
+
+    envit("status").assert().success().stdout(
+        ".env: never synced\n\
+         Managed (1):\n  DATABASE_URL\n\
+         Local only (1):\n  LOCAL_ONLY\n\
+         Not materialized (1):\n  REDIS\n",
+    );
+
+    envit("pull").assert().success();
+    envit("status")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains(".env: last synced "))
+        .stdout(predicate::str::contains("Managed (2):\n  DATABASE_URL\n  REDIS\n"))
+        .stdout(predicate::str::contains("Not materialized (0):\n"))
+        .stdout(predicate::str::contains("new").not());
+}
+
+#[test]
+fn list_shows_secrets_keys_and_pull_status() {
+    let dir = TempDir::new().unwrap();
+    write_config(&dir, "");
+    write_file(&dir.path().join(".env"), "DATABASE_URL=old\nSAME=1\n");
+    write_file(
+        &dir.path().join("secrets.txt"),
+        "database-url=new\nsame=1\nredis=redis://localhost\n",
+    );
+    let list = || {
+        let mut cmd = Command::new(assert_cmd::cargo::cargo_bin!("envit"));
+        cmd.current_dir(dir.path())
+            .env("ENVIT_TEST_SECRETS_FILE", dir.path().join("secrets.txt"))
+            .arg("list");
+        cmd
+    };
+
+    list().assert().success().stdout(
+        "database-url\tDATABASE_URL\tUPDATE\nredis\tREDIS\tADD\nsame\tSAME\tunchanged\n",
+    );
+    list().arg("--table").assert().success().stdout(
+        "SECRET        KEY           STATUS\n\
+         database-url  DATABASE_URL  UPDATE\n\
+         redis         REDIS         ADD\n\
+         same          SAME          unchanged\n",
+    );
+}
+
+#[test]
+fn which_traces_secret_to_key_and_back() {
+    let dir = TempDir::new().unwrap();
+    write_file(
+        &dir.path().join("envit.toml"),
+        r#"version = 1
+
+[provider]
+kind = "azure_key_vault"
+vault_url = "https://example.vault.azure.net/"
+
+[map]
+DB = "database-url"
+"#,
+    );
+    write_file(
+        &dir.path().join("secrets.txt"),
+        "database-url=x\nredis-url=y\n",
+    );
+    let which = || {
+        let mut cmd = Command::new(assert_cmd::cargo::cargo_bin!("envit"));
+        cmd.current_dir(dir.path())
+            .env("ENVIT_TEST_SECRETS_FILE", dir.path().join("secrets.txt"))
+            .arg("which");
+        cmd
+    };
+
+    which()
+        .arg("database-url")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains(
+            "database-url (provider 1, azure_key_vault):\n  listed:  yes\n  rule:    [map] DB = \"database-url\"\n  env key: DB\n",
+        ));
+    which()
+        .args(["--key", "REDIS_URL"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains(
+            "rule:    derived (dashes to underscores, uppercased): REDIS_URL",
+        ));
+    which()
+        .args(["--key", "DATABASE_URL"])
+        .assert()
+        .code(1)
+        .stdout(predicate::str::contains("No secret pulls to DATABASE_URL"));
+}
+
+#[test]
+fn pull_posts_masked_change_summary_to_hooks() {
+    use std::{
+        io::{BufRead, BufReader, Read, Write},
+        net::TcpListener,
+        thread,
+    };
+
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let port = listener.local_addr().unwrap().port();
+    let server = thread::spawn(move || {
+        let (stream, _) = listener.accept().unwrap();
+        let mut reader = BufReader::new(stream);
+        let (mut length, mut auth) = (0, String::new());
+        loop {
+            let mut line = String::new();
+            reader.read_line(&mut line).unwrap();
+            let lower = line.to_ascii_lowercase();
+            if let Some(value) = lower.strip_prefix("content-length:") {
+                length = value.trim().parse().unwrap();
+            }
+            if lower.starts_with("authorization:") {
+                auth = line["authorization:".len()..].trim().to_string();
+            }
+            if line == "\r\n" {
+                break;
+            }
+        }
+        let mut body = vec![0; length];
+        reader.read_exact(&mut body).unwrap();
+        write!(
+            reader.get_mut(),
+            "HTTP/1.1 204 No Content\r\nconnection: close\r\n\r\n"
+        )
+        .unwrap();
+        (auth, String::from_utf8(body).unwrap())
+    });
+
+    let dir = TempDir::new().unwrap();
+    write_config(&dir, "");
+    let config = fs::read_to_string(dir.path().join("envit.toml")).unwrap();
+    write_file(
+        &dir.path().join("envit.toml"),
+        &format!(
+            r#"{config}
+[[hooks]]
+url = "http://127.0.0.1:{port}/issue/OPS-1/comment"
+on = ["pull"]
+template = '{{"body": "{{{{actor}}}} pulled {{{{changes}}}}"}}'
+headers_env = {{ Authorization = "ENVIT_TEST_HOOK_AUTH" }}
+"#
+        ),
+    );
+    write_file(&dir.path().join(".env"), "DATABASE_URL=old\n");
+    write_file(
+        &dir.path().join("secrets.txt"),
+        "database-url=s3cret\nredis=redis://localhost\n",
+    );
+
+    Command::new(assert_cmd::cargo::cargo_bin!("envit"))
+        .current_dir(dir.path())
+        .env("ENVIT_TEST_SECRETS_FILE", dir.path().join("secrets.txt"))
+        .env("ENVIT_TEST_HOOK_AUTH", "Basic b3Bz")
+        .env("USER", "ana")
+        .arg("pull")
+        .assert()
+        .success();
+
+    let (auth, body) = server.join().unwrap();
+    assert_eq!(auth, "Basic b3Bz");
+    assert_eq!(
+        body,
+        r#"{"body": "ana pulled UPDATE DATABASE_URL, ADD REDIS"}"#
+    );
+}
+
+#[cfg(unix)]
+#[test]
+fn hook_commands_get_changed_keys_and_a_json_file() {
+    use std::os::unix::fs::PermissionsExt;
+
+    let dir = TempDir::new().unwrap();
+    write_config(&dir, "");
+    let config = fs::read_to_string(dir.path().join("envit.toml")).unwrap();
+    write_file(
+        &dir.path().join("envit.toml"),
+        &format!(
+            "{config}\n[[hooks]]\ncommand = 'echo \"$ENVIT_CHANGED_KEYS\" > hook.txt && test -f'\non = [\"pull\"]\n"
+        ),
+    );
+    write_file(&dir.path().join(".env"), "DATABASE_URL=old\n");
+    let script = dir.path().join("on-change.sh");
+    write_file(&script, "#!/bin/sh\ncp \"$1\" changes-copy.json\n");
+    fs::set_permissions(&script, fs::Permissions::from_mode(0o755)).unwrap();
+    let pull = |secrets: &str| {
+        write_file(&dir.path().join("secrets.txt"), secrets);
+        Command::new(assert_cmd::cargo::cargo_bin!("envit"))
+            .current_dir(dir.path())
+            .env("ENVIT_TEST_SECRETS_FILE", dir.path().join("secrets.txt"))
+            .args(["pull", "--on-change", "./on-change.sh"])
+            .assert()
+            .success();
+    };
+
+    pull("database-url=s3cret\nredis=redis://localhost\n");
+    assert_eq!(
+        fs::read_to_string(dir.path().join("hook.txt")).unwrap(),
+        "DATABASE_URL,REDIS\n"
+    );
+    let changes: serde_json::Value =
+        serde_json::from_str(&fs::read_to_string(dir.path().join("changes-copy.json")).unwrap())
+            .unwrap();
+    assert_eq!(
+        changes["changes"],
+        serde_json::json!([
+            { "key": "DATABASE_URL", "kind": "update" },
+            { "key": "REDIS", "kind": "add" }
+        ])
+    );
+    assert!(!changes.to_string().contains("s3cret"));
+
+    // Unchanged pulls run nothing.
+    fs::remove_file(dir.path().join("hook.txt")).unwrap();
+    fs::remove_file(dir.path().join("changes-copy.json")).unwrap();
+    pull("database-url=s3cret\nredis=redis://localhost\n");
+    assert!(!dir.path().join("hook.txt").exists());
+    assert!(!dir.path().join("changes-copy.json").exists());
+}
+
+#[cfg(feature = "http-json")]
+#[test]
+fn pull_http_json_reads_flat_object_with_templated_auth_header() {
+    use std::{
+        io::{BufRead, BufReader, Write},
+        net::TcpListener,
+        thread,
+    };
+
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let port = listener.local_addr().unwrap().port();
+    let server = thread::spawn(move || {
+        let (stream, _) = listener.accept().unwrap();
+        let mut reader = BufReader::new(stream);
+        let mut authorized = false;
+        loop {
+            let mut line = String::new();
+            reader.read_line(&mut line).unwrap();
+            if line.to_ascii_lowercase().starts_with("authorization: bearer t0ken") {
+                authorized = true;
+            }
+            if line == "\r\n" {
+                break;
+            }
+        }
+        let (status, body) = if authorized {
+            ("200 OK", r#"{"data":{"database-url":"postgres://db","port":5432}}"#)
+        } else {
+            ("401 Unauthorized", "{}")
+        };
+        write!(
+            reader.get_mut(),
+            "HTTP/1.1 {status}\r\ncontent-type: application/json\r\ncontent-length: {}\r\nconnection: close\r\n\r\n{body}",
+            body.len()
+        )
+        .unwrap();
+    });
+
+    let dir = TempDir::new().unwrap();
+    let config = format!(
+        r#"version = 1
+
+[provider]
+kind = "http_json"
+url = "http://127.0.0.1:{port}/secrets"
+pointer = "/data"
+
+[provider.headers]
+Authorization = "Bearer ${{ENVIT_TEST_TOKEN}}"
+"#
+    );
+    write_file(&dir.path().join("envit.toml"), &config);
+
+    Command::new(assert_cmd::cargo::cargo_bin!("envit"))
+        .current_dir(dir.path())
+        .env_remove("ENVIT_TEST_SECRETS_FILE")
+        .env("ENVIT_TEST_TOKEN", "t0ken")
+        .arg("pull")
+        .assert()
+        .success();
+    server.join().unwrap();
+
+    let env_after = fs::read_to_string(dir.path().join(".env")).unwrap();
+    assert_eq!(env_after, "DATABASE_URL=postgres://db\nPORT=5432\n");
+}
+
+#[cfg(all(unix, feature = "exec"))]
+#[test]
+fn pull_exec_provider_follows_stdout_and_exit_code_contract() {
+    let dir = TempDir::new().unwrap();
+    let config = r#"version = 1
+
+[provider]
+kind = "exec"
+list_command = "printf 'database-url\nredis\n'"
+get_command = "case $ENVIT_SECRET_NAME in database-url) echo postgres://db ;; *) exit 3 ;; esac"
+"#;
+    write_file(&dir.path().join("envit.toml"), config);
+    write_file(&dir.path().join(".env"), "REDIS=local\n");
+
+    Command::new(assert_cmd::cargo::cargo_bin!("envit"))
+        .current_dir(dir.path())
+        .env_remove("ENVIT_TEST_SECRETS_FILE")
+        .arg("pull")
+        .assert()
+        .success();
+
+    let env_after = fs::read_to_string(dir.path().join(".env")).unwrap();
+    assert_eq!(env_after, "REDIS=local\nDATABASE_URL=postgres://db\n");
+}
+
+#[test]
+fn pull_aggregates_providers_with_prefixes_and_later_entries_winning() {
+    let dir = TempDir::new().unwrap();
+    let config = r#"version = 1
+
+[[providers]]
+kind = "azure_key_vault"
+vault_url = "https://prod.vault.azure.net/"
+map = { TOKEN = "api-key" }
+
+[[providers]]
+kind = "azure_key_vault"
+vault_url = "https://shared.vault.azure.net/"
+map = { TOKEN = "redis" }
+
+[[providers]]
+kind = "azure_key_vault"
+vault_url = "https://shared.vault.azure.net/"
+prefix = "SHARED_"
+"#;
+    write_file(&dir.path().join("envit.toml"), config);
+    write_file(&dir.path().join("secrets.txt"), "api-key=k\nredis=r\n");
+
+    Command::new(assert_cmd::cargo::cargo_bin!("envit"))
+        .current_dir(dir.path())
+        .env("ENVIT_TEST_SECRETS_FILE", dir.path().join("secrets.txt"))
+        .arg("pull")
+        .assert()
+        .success();
+
+    let env_after = fs::read_to_string(dir.path().join(".env")).unwrap();
+    let mut lines: Vec<_> = env_after.lines().collect();
+    lines.sort_unstable();
+    assert_eq!(
+        lines,
+        [
+            "API_KEY=k",
+            "REDIS=r",
+            "SHARED_API_KEY=k",
+            "SHARED_REDIS=r",
+            "TOKEN=r"
+        ]
+    );
+}
+
+#[cfg(all(unix, feature = "kms-file"))]
+#[test]
+fn pull_kms_file_decrypts_bundle_through_aws_cli() {
+    use std::os::unix::fs::PermissionsExt;
+
+    let dir = TempDir::new().unwrap();
+    let config = r#"version = 1
+
+[provider]
+kind = "kms_file"
+backend = "aws_kms"
+path = "bundle.enc"
+aws_command = "./fake-aws"
+"#;
+    write_file(&dir.path().join("envit.toml"), config);
+    write_file(&dir.path().join("bundle.enc"), "AQID\n");
+    let fake = dir.path().join("fake-aws");
+    write_file(
+        &fake,
+        "#!/bin/sh\n[ \"$*\" = 'kms decrypt --ciphertext-blob AQID --output text --query Plaintext' ] || exit 1\necho REJfVVJMPXBvc3RncmVzOi8va21zCg==\n",
+    );
+    fs::set_permissions(&fake, fs::Permissions::from_mode(0o755)).unwrap();
+
+    Command::new(assert_cmd::cargo::cargo_bin!("envit"))
+        .current_dir(dir.path())
+        .env_remove("ENVIT_TEST_SECRETS_FILE")
+        .arg("pull")
+        .assert()
+        .success();
+
+    let env_after = fs::read_to_string(dir.path().join(".env")).unwrap();
+    assert_eq!(env_after, "DB_URL=postgres://kms\n");
+}
+
+#[test]
+fn doctor_checks_each_step_and_suggests_fixes() {
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let port = listener.local_addr().unwrap().port();
+    let dir = TempDir::new().unwrap();
+    write_file(
+        &dir.path().join("envit.toml"),
+        &format!(
+            r#"version = 1
+
+[provider]
+kind = "azure_key_vault"
+vault_url = "http://127.0.0.1:{port}/"
+"#
+        ),
+    );
+    write_file(&dir.path().join("secrets.txt"), "database-url=x\n");
+    let doctor = || {
+        let mut cmd = Command::new(assert_cmd::cargo::cargo_bin!("envit"));
+        cmd.current_dir(dir.path())
+            .env("ENVIT_TEST_SECRETS_FILE", dir.path().join("secrets.txt"))
+            .arg("doctor");
+        cmd
+    };
+
+    doctor()
+        .assert()
+        .success()
+        .stdout(predicate::str::contains(format!(
+            "ok    network: reached 127.0.0.1:{port}"
+        )))
+        .stdout(predicate::str::contains("ok    list: 1 secrets"))
+        .stdout(predicate::str::contains("ok    get: read database-url"))
+        .stdout(predicate::str::contains(".env is writable"))
+        .stdout(predicate::str::contains("x\n").not());
+
+    write_file(&dir.path().join("secrets.txt"), "!unauthenticated\n");
+    doctor()
+        .assert()
+        .code(1)
+        .stdout(predicate::str::contains("FAIL  credentials:"))
+        .stdout(predicate::str::contains("az login"))
+        .stdout(predicate::str::contains("skip  list and get permissions"))
+        .stdout(predicate::str::contains("1 check(s) failed."));
+}
+
+#[test]
+fn dry_run_groups_changes_by_file_and_provider() {
+    let dir = TempDir::new().unwrap();
+    let config = r#"version = 1
+
+[output.files]
+TLS_CERT = "certs/tls.pem"
+
+[[providers]]
+kind = "azure_key_vault"
+vault_url = "https://prod.vault.azure.net/"
+
+[[providers]]
+kind = "azure_key_vault"
+vault_url = "https://shared.vault.azure.net/"
+prefix = "SHARED_"
+"#;
+    write_file(&dir.path().join("envit.toml"), config);
+    write_file(&dir.path().join("secrets.txt"), "api-key=k\ntls-cert=pem\n");
+    write_file(&dir.path().join(".env"), "API_KEY=old\n");
+    let envit = |args: &[&str]| {
+        let mut cmd = Command::new(assert_cmd::cargo::cargo_bin!("envit"));
+        cmd.current_dir(dir.path())
+            .env("ENVIT_TEST_SECRETS_FILE", dir.path().join("secrets.txt"))
+            .args(args);
+        cmd
+    };
+
+    envit(&["pull", "--dry-run"]).assert().success().stdout(
+        ".env: 4 changes\n\
+         \x20 provider 1 (azure_key_vault): 2 changes\n\
+         \x20   UPDATE API_KEY=********\n\
+         \x20   ADD TLS_CERT=********\n\
+         \x20 provider 2 (azure_key_vault, SHARED_): 2 changes\n\
+         \x20   ADD SHARED_API_KEY=********\n\
+         \x20   ADD SHARED_TLS_CERT=********\n\
+         certs/tls.pem: 1 change\n\
+         \x20 provider 1 (azure_key_vault): 1 change\n\
+         \x20   ADD TLS_CERT\n\
+         Total: 5 changes in 2 files from 2 providers\n",
+    );
+    envit(&["diff"])
+        .assert()
+        .code(2)
+        .stdout(predicate::str::starts_with(".env: 3 changes\n"))
+        .stdout(predicate::str::contains(
+            "certs/tls.pem: 1 change\n  provider 1 (azure_key_vault): 1 change\n    ADD TLS_CERT\n",
+        ))
+        .stdout(predicate::str::ends_with(
+            "Total: 4 changes in 2 files from 2 providers\n",
+        ));
+}
+
+#[test]
+fn expiry_lists_secrets_expiring_within_the_window_and_exits_2() {
+    let dir = TempDir::new().unwrap();
+    write_config(&dir, "");
+    let in_days = |days: i64| {
+        let now = std::time::SystemTime::now();
+        let offset = std::time::Duration::from_secs(days.unsigned_abs() * 24 * 60 * 60 + 60);
+        let at = if days < 0 { now - offset } else { now + offset };
+        humantime::format_rfc3339_seconds(at).to_string()
+    };
+    write_file(
+        &dir.path().join("secrets.txt"),
+        &format!(
+            "soon=a\nlater=b\nlapsed=c\noff=d\nforever=e\n\
+             !details:soon expires={}\n!details:later expires={}\n\
+             !details:lapsed expires={}\n!details:off expires={} enabled=false\n",
+            in_days(10),
+            in_days(60),
+            in_days(-5),
+            in_days(1)
+        ),
+    );
+    let expiry = |args: &[&str]| {
+        let mut cmd = Command::new(assert_cmd::cargo::cargo_bin!("envit"));
+        cmd.current_dir(dir.path())
+            .env("ENVIT_TEST_SECRETS_FILE", dir.path().join("secrets.txt"))
+            .arg("expiry")
+            .args(args);
+        cmd.assert()
+    };
+
+    let listed = expiry(&[]).code(2);
+    let stdout = String::from_utf8_lossy(&listed.get_output().stdout).into_owned();
+    let lines: Vec<_> = stdout.lines().collect();
+    assert_eq!(lines.len(), 3, "{stdout}");
+    assert!(lines[0].starts_with("lapsed expired ") && lines[0].ends_with("(5d ago)"));
+    assert!(lines[1].starts_with("soon expires ") && lines[1].ends_with("(in 10d)"));
+    assert_eq!(lines[2], "2 secrets expire within 30days.");
+
+    expiry(&["--within", "90d"])
+        .code(2)
+        .stdout(predicate::str::contains("later expires "));
+    expiry(&["--within", "1h"])
+        .code(2)
+        .stdout(predicate::str::contains("1 secrets expire within 1h."));
+
+    write_file(
+        &dir.path().join("secrets.txt"),
+        &format!("later=b\n!details:later expires={}\n", in_days(60)),
+    );
+    expiry(&[])
+        .success()
+        .stdout("No secrets expire within 30days.\n");
+}
+
+#[test]
+fn audit_reports_provider_metadata_and_last_rotation() {
+    let dir = TempDir::new().unwrap();
+    write_config(&dir, "");
+    let config = fs::read_to_string(dir.path().join("envit.toml")).unwrap();
+    write_file(
+        &dir.path().join("envit.toml"),
+        &format!("{config}\n[audit]\npath = \".envit/audit.jsonl\"\n"),
+    );
+    write_file(
+        &dir.path().join("secrets.txt"),
+        "database-url=postgres://db\napi-key=abc\n\
+         !details:database-url created=2024-01-01T00:00:00Z updated=2024-06-01T00:00:00Z \
+         expires=2025-01-01T00:00:00Z enabled=false\n",
+    );
+    fs::create_dir(dir.path().join(".envit")).unwrap();
+    write_file(
+        &dir.path().join(".envit/audit.jsonl"),
+        "{\"timestamp\":\"2024-03-01T12:00:00Z\",\"command\":\"rotate\",\"changed_keys\":[\"database-url\"]}\n\
+         {\"timestamp\":\"2024-05-01T12:00:00Z\",\"command\":\"rotate\",\"changed_keys\":[\"database-url\"],\"error\":\"failed\"}\n",
+    );
+    let audit = |format: &str| {
+        Command::new(assert_cmd::cargo::cargo_bin!("envit"))
+            .current_dir(dir.path())
+            .env("ENVIT_TEST_SECRETS_FILE", dir.path().join("secrets.txt"))
+            .args(["audit", "--format", format])
+            .assert()
+            .success()
+    };
+
+    let table = audit("table");
+    let table = String::from_utf8_lossy(&table.get_output().stdout).into_owned();
+    let lines: Vec<_> = table.lines().collect();
+    assert_eq!(
+        lines,
+        [
+            "SECRET        CLASS              ENABLED  CREATED     UPDATED     EXPIRES     LAST ROTATED",
+            "api-key       token              -        -           -           -           -",
+            "database-url  connection_string  no       2024-01-01  2024-06-01  2025-01-01  2024-03-01",
+        ]
+    );
+
+    let json = audit("json");
+    let json: serde_json::Value = serde_json::from_slice(&json.get_output().stdout).unwrap();
+    assert_eq!(json[1]["secret"], "database-url");
+    assert_eq!(json[1]["enabled"], false);
+    assert_eq!(json[1]["class"], "connection_string");
+    assert_eq!(json[1]["expires"], "2025-01-01T00:00:00Z");
+    assert_eq!(json[1]["last_rotated"], "2024-03-01T12:00:00Z");
+    assert!(json[0]["created"].is_null());
+
+    let log = fs::read_to_string(dir.path().join(".envit/audit.jsonl")).unwrap();
+    assert!(log.lines().last().unwrap().contains(r#""command":"audit""#));
+    assert!(!log.contains("postgres://db"));
+}
+
+#[test]
+fn compare_lists_keys_missing_from_either_profile() {
+    let dir = TempDir::new().unwrap();
+    let config = |map: &str| {
+        format!(
+            "version = 1\n\n[provider]\nkind = \"azure_key_vault\"\nvault_url = \"https://example.vault.azure.net/\"\n\n[map]\n{map}\n"
+        )
+    };
+    write_file(
+        &dir.path().join("envit.staging.toml"),
+        &config("DATABASE_URL = \"staging-db\"\nPROD_DB = \"prod-db\""),
+    );
+    write_file(
+        &dir.path().join("envit.prod.toml"),
+        &config("DATABASE_URL = \"prod-db\"\nSTAGING_DB = \"staging-db\""),
+    );
+    write_file(
+        &dir.path().join("secrets.txt"),
+        "staging-db=postgres://staging\nprod-db=postgres://prod\nlog-level=info\n",
+    );
+    let compare = |args: &[&str]| {
+        let mut cmd = Command::new(assert_cmd::cargo::cargo_bin!("envit"));
+        cmd.current_dir(dir.path())
+            .env("ENVIT_TEST_SECRETS_FILE", dir.path().join("secrets.txt"))
+            .args(["compare", "--profile", "staging", "--profile", "prod"])
+            .args(args);
+        cmd
+    };
+
+    compare(&[]).assert().code(2).stdout(
+        "Only in staging (1):\n  PROD_DB\nOnly in prod (1):\n  STAGING_DB\n2 keys in both.\n",
+    );
+    compare(&["--values"])
+        .assert()
+        .code(2)
+        .stdout(predicate::str::contains(
+            "Same value (1):\n  LOG_LEVEL\nDifferent values (1):\n  DATABASE_URL\n",
+        ))
+        .stdout(predicate::str::contains("postgres").not());
+
+    Command::new(assert_cmd::cargo::cargo_bin!("envit"))
+        .current_dir(dir.path())
+        .env("ENVIT_TEST_SECRETS_FILE", dir.path().join("secrets.txt"))
+        .args(["compare", "--profile", "staging", "--profile", "envit.staging.toml"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("have the same keys"));
+}
+
+#[test]
+fn pull_env_writes_each_profile_into_the_out_dir() {
+    let dir = TempDir::new().unwrap();
+    let config = |map: &str| {
+        format!(
+            "version = 1\n\n[provider]\nkind = \"azure_key_vault\"\nvault_url = \"https://example.vault.azure.net/\"\n\n[map]\n{map}\n"
+        )
+    };
+    write_file(
+        &dir.path().join("envit.staging.toml"),
+        &config("DATABASE_URL = \"staging-db\""),
+    );
+    write_file(
+        &dir.path().join("envit.prod.toml"),
+        &config("DATABASE_URL = \"prod-db\""),
+    );
+    write_file(
+        &dir.path().join("secrets.txt"),
+        "staging-db=postgres://staging\nprod-db=postgres://prod\n",
+    );
+    let pull = |args: &[&str]| {
+        let mut cmd = Command::new(assert_cmd::cargo::cargo_bin!("envit"));
+        cmd.current_dir(dir.path())
+            .env("ENVIT_TEST_SECRETS_FILE", dir.path().join("secrets.txt"))
+            .arg("pull")
+            .args(args);
+        cmd
+    };
+
+    pull(&["--env", "staging"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("--out-dir"));
+    pull(&["--env", "staging,prod", "--out-dir", "envs"])
+        .assert()
+        .success()
+        .stdout(
+            "staging: 2 changes in envs/.env.staging\nprod: 2 changes in envs/.env.prod\n",
+        );
+    let staging = fs::read_to_string(dir.path().join("envs/.env.staging")).unwrap();
+    assert!(staging.contains("DATABASE_URL=postgres://staging\n"));
+    let prod = fs::read_to_string(dir.path().join("envs/.env.prod")).unwrap();
+    assert!(prod.contains("DATABASE_URL=postgres://prod\n"));
+    assert!(!dir.path().join(".env").exists());
+
+    write_file(&dir.path().join("envit.prod.toml"), "version = 1\n");
+    pull(&["--env", "staging", "--env", "prod", "--out-dir", "envs"])
+        .assert()
+        .failure()
+        .stdout("")
+        .stderr(predicate::str::contains("profile prod: config needs"));
+}
+
+#[test]
+fn lockfile_pins_versions_until_update_advances_them() {
+    let dir = TempDir::new().unwrap();
+    write_config(&dir, "");
+    let secrets = dir.path().join("secrets.txt");
+    write_file(&secrets, "api-token=one\ndb-password=pw\n");
+    let envit = |args: &[&str]| {
+        let mut cmd = Command::new(assert_cmd::cargo::cargo_bin!("envit"));
+        cmd.current_dir(dir.path())
+            .env("ENVIT_TEST_SECRETS_FILE", &secrets)
+            .args(args);
+        cmd
+    };
+    let env = || fs::read_to_string(dir.path().join(".env")).unwrap();
+
+    envit(&["pull", "--frozen"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("create it with `envit update`"));
+    envit(&["update"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains(
+            "Locked API_TOKEN, DB_PASSWORD in envit.lock",
+        ));
+    let lock = fs::read_to_string(dir.path().join("envit.lock")).unwrap();
+    assert!(lock.contains("secret = \"api-token\"\nversion = \"1\""));
+
+    write_file(
+        &secrets,
+        "api-token=one\napi-token=two\ndb-password=pw\n",
+    );
+    envit(&["pull"]).assert().success();
+    assert!(env().contains("API_TOKEN=one\n"));
+    envit(&["pull", "--frozen"]).assert().success();
+
+    write_file(
+        &secrets,
+        "api-token=one\napi-token=two\ndb-password=pw\nnew-key=x\n",
+    );
+    envit(&["pull", "--frozen"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("does not pin NEW_KEY"));
+    assert!(!env().contains("NEW_KEY"));
+    envit(&["pull"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Locked NEW_KEY"));
+
+    envit(&["update", "NOPE"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("no source provides NOPE"));
+    envit(&["update", "API_TOKEN"]).assert().success();
+    assert!(env().contains("API_TOKEN=two\n"));
+    let lock = fs::read_to_string(dir.path().join("envit.lock")).unwrap();
+    assert!(lock.contains("version = \"2\""));
+}
+
+#[test]
+fn pull_summary_ends_the_output_with_one_line() {
+    let dir = TempDir::new().unwrap();
+    write_config(&dir, "");
+    write_file(&dir.path().join(".env"), "API_TOKEN=old\n");
+    let secrets = dir.path().join("secrets.txt");
+    write_file(&secrets, "api-token=new\ndb-password=pw\n");
+    let envit = |args: &[&str]| {
+        let mut cmd = Command::new(assert_cmd::cargo::cargo_bin!("envit"));
+        cmd.current_dir(dir.path())
+            .env("ENVIT_TEST_SECRETS_FILE", &secrets)
+            .args(args);
+        cmd
+    };
+    let last_line = |stdout: &[u8]| String::from_utf8_lossy(stdout).lines().last().unwrap().to_string();
+
+    let pulled = envit(&["pull", "--summary", "line"]).assert().success();
+    let line = last_line(&pulled.get_output().stdout);
+    assert!(
+        line.starts_with("envit: 1 added, 1 updated, 0 removed, ") && line.ends_with("s, profile=default"),
+        "{line}"
+    );
+
+    write_file(&secrets, "api-token=new\n!error:db-password\n");
+    let failed = envit(&["pull", "--summary", "json"]).assert().failure();
+    let summary: serde_json::Value =
+        serde_json::from_str(&last_line(&failed.get_output().stdout)).unwrap();
+    assert_eq!(summary["status"], "failed");
+    assert_eq!(summary["profile"], "default");
+    assert!(summary.get("added").is_none());
+}
+
+#[test]
+fn copy_seeds_one_profile_from_another_leaving_conflicts_alone() {
+    let dir = TempDir::new().unwrap();
+    let config = |map: &str| {
+        format!(
+            "version = 1\n\n[provider]\nkind = \"azure_key_vault\"\nvault_url = \"https://example.vault.azure.net/\"\n\n[map]\n{map}\n"
+        )
+    };
+    write_file(
+        &dir.path().join("envit.staging.toml"),
+        &config("DATABASE_URL = \"staging-db\"\nAPI_TOKEN = \"staging-token\""),
+    );
+    write_file(
+        &dir.path().join("envit.prod.toml"),
+        &config("DATABASE_URL = \"prod-db\"\nAPI_TOKEN = \"prod-token\""),
+    );
+    let secrets = dir.path().join("secrets.txt");
+    write_file(
+        &secrets,
+        "staging-db=postgres://staging\nstaging-token=abc\nprod-db=postgres://prod\nlog-level=info\n",
+    );
+    let copy = |args: &[&str]| {
+        let mut cmd = Command::new(assert_cmd::cargo::cargo_bin!("envit"));
+        cmd.current_dir(dir.path())
+            .env("ENVIT_TEST_SECRETS_FILE", &secrets)
+            .args(["copy", "--from", "staging", "--to", "prod"])
+            .args(args);
+        cmd
+    };
+
+    copy(&["--exclude", "PROD_*", "--dry-run"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains(
+            "ADD prod-token <- API_TOKEN=********",
+        ))
+        .stdout(predicate::str::contains(
+            "CONFLICT prod-db <- DATABASE_URL: the provider holds a different value",
+        ))
+        .stdout(predicate::str::contains("1 secrets already hold these values"))
+        .stdout(predicate::str::contains("PROD_DB").not())
+        .stdout(predicate::str::contains("abc").not());
+    assert!(!fs::read_to_string(&secrets).unwrap().contains("prod-token"));
+
+    copy(&["--exclude", "PROD_*", "--yes"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Copied 1 secrets to prod"));
+    let written = fs::read_to_string(&secrets).unwrap();
+    assert!(written.contains("prod-token=abc\n"));
+    assert!(!written.contains("prod-db=postgres://staging"));
+
+    copy(&["API_TOKEN", "--yes"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains(
+            "Nothing to copy: prod already has these values.",
+        ));
+    copy(&["NOPE", "--dry-run"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains(
+            "NOPE is not provided by the source profile",
+        ));
+
+    write_file(
+        &dir.path().join("envit.prod.toml"),
+        &format!(
+            "{}\n[owners]\n\"*\" = \"platform\"\n\n[security]\nenforce_owners = true\n",
+            config("DATABASE_URL = \"prod-db\"\nAPI_TOKEN = \"prod-token\"")
+        ),
+    );
+    write_file(&secrets, "staging-token=rotated\n");
+    copy(&["API_TOKEN", "--dry-run"])
+        .env("ENVIT_TEAM", "payments")
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains(
+            "refusing to change keys owned by other teams",
+        ));
+}
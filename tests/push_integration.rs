@@ -0,0 +1,408 @@
+use std::{fs, path::Path};
+
+use assert_cmd::Command;
+use predicates::prelude::*;
+use tempfile::TempDir;
+
+fn write_file(path: &Path, content: &str) {
+    fs::write(path, content).expect("failed to write file");
+}
+
+fn setup() -> TempDir {
+    let dir = TempDir::new().unwrap();
+    write_file(
+        &dir.path().join("envit.toml"),
+        r#"version = 1
+
+[provider]
+kind = "azure_key_vault"
+vault_url = "https://example.vault.azure.net/"
+
+[map]
+REDIS = "cache-url"
+"#,
+    );
+    write_file(
+        &dir.path().join("secrets.txt"),
+        "database-url=old\nunchanged=same\n",
+    );
+    write_file(
+        &dir.path().join(".env"),
+        "# local\nDATABASE_URL=new\nUNCHANGED=same\nREDIS=redis://localhost\nAPI_KEY=k\n",
+    );
+    dir
+}
+
+fn push(dir: &TempDir) -> Command {
+    let mut cmd = Command::new(assert_cmd::cargo::cargo_bin!("envit"));
+    cmd.current_dir(dir.path())
+        .env("ENVIT_TEST_SECRETS_FILE", dir.path().join("secrets.txt"))
+        .arg("push");
+    cmd
+}
+
+#[test]
+fn writes_to_a_read_only_provider_fail_before_anything_is_read() {
+    let dir = setup();
+    write_file(
+        &dir.path().join("secrets.txt"),
+        "!read_only\ndatabase-url=old\n",
+    );
+
+    push(&dir)
+        .arg("--yes")
+        .assert()
+        .failure()
+        .stdout(predicate::str::contains("ADD").not())
+        .stderr(predicate::str::contains(
+            "provider azure_key_vault does not support push (it is read-only)",
+        ));
+    Command::new(assert_cmd::cargo::cargo_bin!("envit"))
+        .current_dir(dir.path())
+        .env("ENVIT_TEST_SECRETS_FILE", dir.path().join("secrets.txt"))
+        .args(["delete", "DATABASE_URL", "--yes"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains(
+            "provider azure_key_vault does not support delete",
+        ));
+    assert_eq!(
+        fs::read_to_string(dir.path().join("secrets.txt")).unwrap(),
+        "!read_only\ndatabase-url=old\n"
+    );
+}
+
+#[test]
+fn rename_moves_the_secret_map_entry_and_env_key() {
+    let dir = setup();
+    write_file(
+        &dir.path().join("secrets.txt"),
+        "database-url=old\ncache-url=redis://cache\n",
+    );
+    let rename = |args: &[&str]| {
+        let mut cmd = Command::new(assert_cmd::cargo::cargo_bin!("envit"));
+        cmd.current_dir(dir.path())
+            .env("ENVIT_TEST_SECRETS_FILE", dir.path().join("secrets.txt"))
+            .arg("rename")
+            .args(args);
+        cmd
+    };
+
+    rename(&["DATABASE_URL", "PRIMARY_DB", "--dry-run"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains(
+            "RENAME database-url -> primary-db",
+        ))
+        .stdout(predicate::str::contains("ENV DATABASE_URL -> PRIMARY_DB"));
+    rename(&["DATABASE_URL", "PRIMARY_DB", "--yes"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains(
+            "Renamed DATABASE_URL (database-url) to PRIMARY_DB (primary-db)",
+        ));
+    let secrets = fs::read_to_string(dir.path().join("secrets.txt")).unwrap();
+    assert!(secrets.ends_with("primary-db=old\n!deleted:database-url\n"));
+    let env = fs::read_to_string(dir.path().join(".env")).unwrap();
+    assert!(env.starts_with("# local\nPRIMARY_DB=new\nUNCHANGED=same\n"));
+
+    // REDIS is mapped to cache-url, which CACHE_URL derives: only the key moves.
+    rename(&["REDIS", "CACHE_URL", "--yes"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("RENAME").not())
+        .stdout(predicate::str::contains("MAP REDIS -> CACHE_URL"));
+    let config = fs::read_to_string(dir.path().join("envit.toml")).unwrap();
+    assert!(!config.contains("REDIS"));
+    let env = fs::read_to_string(dir.path().join(".env")).unwrap();
+    assert!(env.contains("CACHE_URL=redis://localhost\n"));
+    assert_eq!(
+        fs::read_to_string(dir.path().join("secrets.txt")).unwrap(),
+        secrets
+    );
+
+    rename(&["CACHE_URL", "PRIMARY_DB", "--yes"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains(
+            "the provider already has a secret primary-db",
+        ));
+}
+
+#[test]
+fn push_shows_the_plan_and_needs_confirmation() {
+    let dir = setup();
+
+    push(&dir)
+        .arg("--dry-run")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("ADD api-key <- API_KEY=********"))
+        .stdout(predicate::str::contains(
+            "UPDATE database-url <- DATABASE_URL=********",
+        ))
+        .stdout(predicate::str::contains("ADD cache-url <- REDIS=********"))
+        .stdout(predicate::str::contains("UNCHANGED").not());
+
+    push(&dir)
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains(
+            "refusing to push without confirmation",
+        ));
+    assert_eq!(
+        fs::read_to_string(dir.path().join("secrets.txt")).unwrap(),
+        "database-url=old\nunchanged=same\n"
+    );
+}
+
+#[test]
+fn push_writes_selected_keys_with_yes() {
+    let dir = setup();
+
+    push(&dir)
+        .args(["--yes", "DATABASE_URL", "REDIS"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Pushed 2 secrets"));
+
+    // Writes run in parallel, so they may land in either order.
+    let secrets = fs::read_to_string(dir.path().join("secrets.txt")).unwrap();
+    assert!(secrets.starts_with("database-url=old\nunchanged=same\n"));
+    assert!(secrets.contains("\ndatabase-url=new\n"));
+    assert!(secrets.contains("\ncache-url=redis://localhost\n"));
+    assert!(!secrets.contains("api-key"));
+
+    push(&dir)
+        .args(["--yes", "MISSING"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("MISSING is not in the env file"));
+}
+
+#[test]
+fn push_backs_off_when_throttled_and_resumes_an_interrupted_run() {
+    use sha2::{Digest, Sha256};
+
+    let dir = setup();
+    let progress = dir.path().join(".envit/push-progress");
+    fs::create_dir_all(progress.parent().unwrap()).unwrap();
+    // An earlier run wrote API_KEY's current value before stopping.
+    let hash: String = Sha256::digest(b"k")
+        .iter()
+        .map(|b| format!("{b:02x}"))
+        .collect();
+    write_file(&progress, &format!("api-key {hash}\n"));
+    let secrets = dir.path().join("secrets.txt");
+    write_file(
+        &secrets,
+        "database-url=old\nunchanged=same\n!throttle:cache-url\n",
+    );
+
+    push(&dir)
+        .arg("--dry-run")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains(
+            "Skipping 1 secrets an earlier push already wrote",
+        ))
+        .stdout(predicate::str::contains("api-key").not());
+    assert!(progress.exists());
+
+    push(&dir)
+        .args(["--yes", "--concurrency", "2"])
+        .assert()
+        .success()
+        .stderr(predicate::str::contains(
+            "warning: throttled (fixture induced throttling for secret: cache-url); retrying in 500ms",
+        ))
+        .stdout(predicate::str::contains("Pushed 2 secrets"));
+    let written = fs::read_to_string(&secrets).unwrap();
+    assert!(written.contains("\ncache-url=redis://localhost\n"));
+    assert!(!written.contains("api-key"));
+    assert!(!progress.exists());
+}
+
+#[test]
+fn push_refuses_keys_owned_by_other_teams_without_override() {
+    let dir = setup();
+    let config = fs::read_to_string(dir.path().join("envit.toml")).unwrap();
+    write_file(
+        &dir.path().join("envit.toml"),
+        &format!(
+            "{config}\n[owners]\n\"*\" = \"platform\"\nREDIS = \"cache\"\n\n[security]\nenforce_owners = true\n"
+        ),
+    );
+
+    Command::new(assert_cmd::cargo::cargo_bin!("envit"))
+        .current_dir(dir.path())
+        .env("ENVIT_TEST_SECRETS_FILE", dir.path().join("secrets.txt"))
+        .arg("owners")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("DATABASE_URL  platform"))
+        .stdout(predicate::str::contains("REDIS         cache"));
+
+    push(&dir)
+        .env("ENVIT_TEAM", "platform")
+        .args(["--yes", "DATABASE_URL", "REDIS"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains(
+            "refusing to change keys owned by other teams (acting as platform): REDIS (cache)",
+        ));
+
+    push(&dir)
+        .env("ENVIT_TEAM", "platform")
+        .args(["--yes", "--override-owner", "DATABASE_URL", "REDIS"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Pushed 2 secrets"));
+}
+
+#[test]
+fn set_writes_one_secret_and_can_save_the_mapping() {
+    let dir = setup();
+    let set = || {
+        let mut cmd = Command::new(assert_cmd::cargo::cargo_bin!("envit"));
+        cmd.current_dir(dir.path())
+            .env("ENVIT_TEST_SECRETS_FILE", dir.path().join("secrets.txt"))
+            .arg("set");
+        cmd
+    };
+
+    set()
+        .arg("DATABASE_URL")
+        .write_stdin("rotated\n")
+        .assert()
+        .success()
+        .stdout("Stored DATABASE_URL in database-url\n");
+    set()
+        .args(["SESSION_KEY", "--secret", "web-session", "--save-map"])
+        .write_stdin("s3cret")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains(
+            "Added SESSION_KEY = \"web-session\" to [map]",
+        ));
+
+    let secrets = fs::read_to_string(dir.path().join("secrets.txt")).unwrap();
+    assert!(secrets.ends_with("database-url=rotated\nweb-session=s3cret\n"));
+    let config = fs::read_to_string(dir.path().join("envit.toml")).unwrap();
+    assert!(config.contains("[map]\nSESSION_KEY = \"web-session\"\nREDIS = \"cache-url\"\n"));
+
+    set()
+        .args(["REDIS", "--secret", "other", "--save-map"])
+        .write_stdin("x")
+        .assert()
+        .code(1)
+        .stderr(predicate::str::contains(
+            "[map] already maps REDIS to cache-url",
+        ));
+}
+
+#[test]
+fn delete_confirms_then_removes_the_secret_and_optionally_the_env_line() {
+    let dir = setup();
+    let delete = || {
+        let mut cmd = Command::new(assert_cmd::cargo::cargo_bin!("envit"));
+        cmd.current_dir(dir.path())
+            .env("ENVIT_TEST_SECRETS_FILE", dir.path().join("secrets.txt"))
+            .arg("delete");
+        cmd
+    };
+
+    delete()
+        .arg("DATABASE_URL")
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains(
+            "refusing to delete without confirmation",
+        ));
+    delete()
+        .args(["--yes", "REDIS"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains(
+            "REDIS: the provider has no secret cache-url",
+        ));
+
+    delete()
+        .args(["--yes", "--strip-env", "DATABASE_URL"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains(
+            "Deleted database-url (DATABASE_URL)",
+        ))
+        .stdout(predicate::str::contains("Removed DATABASE_URL from"));
+    assert!(
+        fs::read_to_string(dir.path().join("secrets.txt"))
+            .unwrap()
+            .ends_with("!deleted:database-url\n")
+    );
+    assert_eq!(
+        fs::read_to_string(dir.path().join(".env")).unwrap(),
+        "# local\nUNCHANGED=same\nREDIS=redis://localhost\nAPI_KEY=k\n"
+    );
+
+    delete().args(["--yes", "UNCHANGED"]).assert().success();
+    delete()
+        .args(["--yes", "UNCHANGED"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains(
+            "the provider has no secret unchanged",
+        ));
+    assert!(
+        fs::read_to_string(dir.path().join(".env"))
+            .unwrap()
+            .contains("UNCHANGED=same")
+    );
+}
+
+#[test]
+fn import_creates_missing_secrets_and_reports_conflicts() {
+    let dir = setup();
+    write_file(
+        &dir.path().join("legacy.json"),
+        r#"{"DATABASE_URL": "new", "UNCHANGED": "same", "REDIS": "redis://localhost", "PORT": 5432}"#,
+    );
+    let import = || {
+        let mut cmd = Command::new(assert_cmd::cargo::cargo_bin!("envit"));
+        cmd.current_dir(dir.path())
+            .env("ENVIT_TEST_SECRETS_FILE", dir.path().join("secrets.txt"))
+            .args(["import", "legacy.json"]);
+        cmd
+    };
+
+    import()
+        .args(["--yes"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("ADD cache-url <- REDIS=********"))
+        .stdout(predicate::str::contains("ADD port <- PORT=********"))
+        .stdout(predicate::str::contains(
+            "CONFLICT database-url <- DATABASE_URL: the provider holds a different value",
+        ))
+        .stdout(predicate::str::contains(
+            "1 secrets already hold these values",
+        ))
+        .stdout(predicate::str::contains("Imported 2 secrets"));
+    let secrets = fs::read_to_string(dir.path().join("secrets.txt")).unwrap();
+    assert!(secrets.contains("\nport=5432\n"));
+    assert!(!secrets.contains("database-url=new"));
+
+    import()
+        .args(["--yes", "--overwrite"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains(
+            "UPDATE database-url <- DATABASE_URL=********",
+        ))
+        .stdout(predicate::str::contains("Imported 1 secrets"));
+    assert!(
+        fs::read_to_string(dir.path().join("secrets.txt"))
+            .unwrap()
+            .ends_with("database-url=new\n")
+    );
+}
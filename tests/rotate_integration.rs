@@ -0,0 +1,189 @@
+use std::{fs, path::Path};
+
+use assert_cmd::Command;
+use predicates::prelude::*;
+use tempfile::TempDir;
+
+fn write_file(path: &Path, content: &str) {
+    fs::write(path, content).expect("failed to write file");
+}
+
+fn setup(campaign: &str) -> TempDir {
+    let dir = TempDir::new().unwrap();
+    write_file(
+        &dir.path().join("envit.toml"),
+        r#"version = 1
+
+[provider]
+kind = "azure_key_vault"
+vault_url = "https://example.vault.azure.net/"
+"#,
+    );
+    write_file(
+        &dir.path().join("secrets.txt"),
+        "db-password=old\napi-key=old\n",
+    );
+    write_file(&dir.path().join("campaign.toml"), campaign);
+    dir
+}
+
+fn rotate(dir: &TempDir) -> Command {
+    let mut cmd = Command::new(assert_cmd::cargo::cargo_bin!("envit"));
+    cmd.current_dir(dir.path())
+        .env("ENVIT_TEST_SECRETS_FILE", dir.path().join("secrets.txt"))
+        .args(["rotate", "--campaign", "campaign.toml"]);
+    cmd
+}
+
+#[test]
+fn campaign_rotates_each_key_with_its_generator() {
+    let dir = setup(
+        r#"
+[[keys]]
+secret = "db-password"
+generator = { format = "hex", length = 8 }
+
+[[keys]]
+secret = "api-key"
+generator = { format = "uuid" }
+"#,
+    );
+
+    rotate(&dir)
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("VERIFIED db-password"))
+        .stdout(predicate::str::contains("Campaign complete: 2 keys"));
+
+    let secrets = fs::read_to_string(dir.path().join("secrets.txt")).unwrap();
+    let rotated: Vec<_> = secrets.lines().skip(2).collect();
+    assert_eq!(rotated.len(), 2);
+    assert!(
+        rotated[0].starts_with("db-password=") && rotated[0].len() == "db-password=".len() + 16
+    );
+    assert!(rotated[1].starts_with("api-key=") && rotated[1].len() == "api-key=".len() + 36);
+}
+
+#[test]
+fn failed_verification_stops_and_resume_does_not_rotate_again() {
+    let dir = setup(
+        r#"
+[verify]
+command = "test -f healthy"
+
+[[keys]]
+secret = "db-password"
+
+[[keys]]
+secret = "api-key"
+"#,
+    );
+
+    rotate(&dir)
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("campaign stopped at db-password"));
+    let after_failure = fs::read_to_string(dir.path().join("secrets.txt")).unwrap();
+    assert_eq!(after_failure.lines().count(), 3);
+
+    write_file(&dir.path().join("healthy"), "");
+    rotate(&dir)
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("ROTATED db-password").not())
+        .stdout(predicate::str::contains("ROTATED api-key"));
+
+    let secrets = fs::read_to_string(dir.path().join("secrets.txt")).unwrap();
+    assert_eq!(secrets.lines().count(), 4);
+
+    rotate(&dir)
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("SKIP db-password"))
+        .stdout(predicate::str::contains("SKIP api-key"));
+}
+
+#[test]
+fn rotating_one_key_updates_the_env_file_or_restores_the_secret() {
+    let dir = setup("");
+    let config = fs::read_to_string(dir.path().join("envit.toml")).unwrap();
+    write_file(
+        &dir.path().join("envit.toml"),
+        &format!(
+            "{config}\n[output]\ncreate_if_missing = false\n\n\
+             [rotate]\nDB_PASSWORD = {{ charset = \"0123456789\", length = 6 }}\n"
+        ),
+    );
+    let rotate_key = || {
+        let mut cmd = Command::new(assert_cmd::cargo::cargo_bin!("envit"));
+        cmd.current_dir(dir.path())
+            .env("ENVIT_TEST_SECRETS_FILE", dir.path().join("secrets.txt"))
+            .args(["rotate", "DB_PASSWORD"]);
+        cmd
+    };
+
+    rotate_key()
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains(
+            "env file not updated; db-password was restored to its previous value",
+        ));
+    assert!(
+        fs::read_to_string(dir.path().join("secrets.txt"))
+            .unwrap()
+            .ends_with("db-password=old\n")
+    );
+
+    write_file(&dir.path().join(".env"), "DB_PASSWORD=old\nLOCAL=x\n");
+    rotate_key()
+        .assert()
+        .success()
+        .stdout("ROTATED db-password (DB_PASSWORD)\n");
+    let secrets = fs::read_to_string(dir.path().join("secrets.txt")).unwrap();
+    let value = secrets
+        .lines()
+        .last()
+        .unwrap()
+        .strip_prefix("db-password=")
+        .unwrap();
+    assert!(value.len() == 6 && value.chars().all(|c| c.is_ascii_digit()));
+    assert_eq!(
+        fs::read_to_string(dir.path().join(".env")).unwrap(),
+        format!("DB_PASSWORD={value}\nLOCAL=x\n")
+    );
+}
+
+#[test]
+fn rotation_defaults_follow_the_secret_class() {
+    let dir = setup("");
+    write_file(
+        &dir.path().join("secrets.txt"),
+        "api-key=old\nprimary=postgres://app:pw@db/app\n",
+    );
+    write_file(&dir.path().join(".env"), "");
+    let rotate_key = |key: &str| {
+        let mut cmd = Command::new(assert_cmd::cargo::cargo_bin!("envit"));
+        cmd.current_dir(dir.path())
+            .env("ENVIT_TEST_SECRETS_FILE", dir.path().join("secrets.txt"))
+            .args(["rotate", key]);
+        cmd
+    };
+
+    rotate_key("API_KEY").assert().success();
+    let secrets = fs::read_to_string(dir.path().join("secrets.txt")).unwrap();
+    let value = secrets
+        .lines()
+        .rev()
+        .find_map(|line| line.strip_prefix("api-key="))
+        .unwrap();
+    assert!(value.len() == 64 && value.chars().all(|c| c.is_ascii_hexdigit()));
+
+    // Told apart from a password by its value.
+    rotate_key("PRIMARY")
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains(
+            "PRIMARY is classified as connection_string, which envit cannot generate",
+        ));
+    assert!(secrets.contains("primary=postgres://app:pw@db/app"));
+}
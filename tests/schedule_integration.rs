@@ -0,0 +1,108 @@
+#![cfg(target_os = "linux")]
+
+use std::{fs, os::unix::fs::PermissionsExt, path::Path};
+
+use assert_cmd::Command;
+use predicates::prelude::*;
+use tempfile::TempDir;
+
+fn write_file(path: &Path, content: &str) {
+    fs::write(path, content).expect("failed to write file");
+}
+
+/// A `systemctl` stand-in that appends its arguments to `systemctl.log`.
+fn fake_systemctl(dir: &Path) {
+    let bin = dir.join("bin");
+    fs::create_dir(&bin).unwrap();
+    let script = bin.join("systemctl");
+    write_file(
+        &script,
+        &format!(
+            "#!/bin/sh\necho \"$@\" >> {}\n",
+            dir.join("systemctl.log").display()
+        ),
+    );
+    fs::set_permissions(&script, fs::Permissions::from_mode(0o755)).unwrap();
+}
+
+fn envit(dir: &Path) -> Command {
+    let mut cmd = Command::new(assert_cmd::cargo::cargo_bin!("envit"));
+    let path = format!(
+        "{}:{}",
+        dir.join("bin").display(),
+        std::env::var("PATH").unwrap_or_default()
+    );
+    cmd.current_dir(dir.join("project"))
+        .env("XDG_CONFIG_HOME", dir.join("config"))
+        .env("PATH", path);
+    cmd
+}
+
+#[test]
+fn schedule_install_writes_and_enables_a_user_timer() {
+    let dir = TempDir::new().unwrap();
+    fs::create_dir(dir.path().join("project")).unwrap();
+    write_file(
+        &dir.path().join("project/envit.toml"),
+        "version = 1\n\n[provider]\nkind = \"azure_key_vault\"\nvault_url = \"https://example.vault.azure.net/\"\n",
+    );
+    fake_systemctl(dir.path());
+
+    envit(dir.path())
+        .args(["schedule", "install", "--every", "6h"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains(
+            "Scheduled `envit pull --quiet` every 6h",
+        ));
+
+    let units: Vec<_> = fs::read_dir(dir.path().join("config/systemd/user"))
+        .unwrap()
+        .map(|entry| entry.unwrap().path())
+        .collect();
+    assert_eq!(units.len(), 2);
+    let timer = units
+        .iter()
+        .find(|path| path.extension().unwrap() == "timer")
+        .unwrap();
+    let timer_name = timer.file_name().unwrap().to_string_lossy().into_owned();
+    assert!(timer_name.starts_with("envit-project-"), "{timer_name}");
+    assert!(
+        fs::read_to_string(timer)
+            .unwrap()
+            .contains("OnUnitActiveSec=21600s")
+    );
+    let service = fs::read_to_string(timer.with_extension("service")).unwrap();
+    assert!(service.contains(" pull --quiet --config "), "{service}");
+
+    let log = fs::read_to_string(dir.path().join("systemctl.log")).unwrap();
+    assert_eq!(
+        log,
+        format!("--user daemon-reload\n--user enable --now {timer_name}\n")
+    );
+
+    envit(dir.path())
+        .args(["schedule", "uninstall"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Removed"));
+    assert_eq!(
+        fs::read_dir(dir.path().join("config/systemd/user"))
+            .unwrap()
+            .count(),
+        0
+    );
+}
+
+#[test]
+fn schedule_rejects_intervals_under_a_minute() {
+    let dir = TempDir::new().unwrap();
+    fs::create_dir(dir.path().join("project")).unwrap();
+    write_file(&dir.path().join("project/envit.toml"), "version = 1\n");
+
+    envit(dir.path())
+        .args(["schedule", "install", "--every", "30s"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("--every must be at least 1m"));
+}
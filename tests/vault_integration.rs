@@ -0,0 +1,62 @@
+#![cfg(feature = "local-vault")]
+
+use std::{fs, path::Path};
+
+use assert_cmd::Command;
+use predicates::prelude::*;
+use tempfile::TempDir;
+
+const CONFIG: &str = r#"version = 1
+
+[provider]
+kind = "local_vault"
+path = "secrets.age"
+"#;
+
+fn write_file(path: &Path, content: &str) {
+    fs::write(path, content).expect("failed to write file");
+}
+
+fn envit(dir: &TempDir) -> Command {
+    let mut cmd = Command::new(assert_cmd::cargo::cargo_bin!("envit"));
+    cmd.current_dir(dir.path())
+        .env_remove("ENVIT_TEST_SECRETS_FILE")
+        .env("ENVIT_VAULT_PASSPHRASE", "correct horse battery staple");
+    cmd
+}
+
+#[test]
+fn vault_set_get_and_pull_round_trip_through_encrypted_file() {
+    let dir = TempDir::new().unwrap();
+    write_file(&dir.path().join("envit.toml"), CONFIG);
+
+    envit(&dir)
+        .args(["vault", "set", "database-url"])
+        .write_stdin("postgres://db\n")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Stored database-url"));
+
+    let stored = fs::read_to_string(dir.path().join("secrets.age")).unwrap();
+    assert!(stored.starts_with("-----BEGIN AGE ENCRYPTED FILE-----"));
+    assert!(!stored.contains("postgres://db"));
+
+    envit(&dir)
+        .args(["vault", "get", "database-url"])
+        .assert()
+        .success()
+        .stdout("postgres://db\n");
+
+    envit(&dir).arg("pull").assert().success();
+    assert_eq!(
+        fs::read_to_string(dir.path().join(".env")).unwrap(),
+        "DATABASE_URL=postgres://db\n"
+    );
+
+    envit(&dir)
+        .env("ENVIT_VAULT_PASSPHRASE", "wrong")
+        .args(["vault", "get", "database-url"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("failed to decrypt vault"));
+}
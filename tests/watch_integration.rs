@@ -0,0 +1,103 @@
+use std::{
+    fs,
+    path::Path,
+    process::{Command, Stdio},
+    thread,
+    time::{Duration, Instant},
+};
+
+use tempfile::TempDir;
+
+fn write_file(path: &Path, content: &str) {
+    fs::write(path, content).expect("failed to write file");
+}
+
+fn wait_for(path: &Path, expected: &str) -> bool {
+    let deadline = Instant::now() + Duration::from_secs(10);
+    while Instant::now() < deadline {
+        if fs::read_to_string(path).is_ok_and(|c| c == expected) {
+            return true;
+        }
+        thread::sleep(Duration::from_millis(100));
+    }
+    false
+}
+
+#[test]
+fn watch_repulls_immediately_on_manual_env_edit_and_config_change() {
+    let dir = TempDir::new().unwrap();
+    let config = r#"version = 1
+
+[provider]
+kind = "azure_key_vault"
+vault_url = "https://example.vault.azure.net/"
+"#;
+    write_file(&dir.path().join("envit.toml"), config);
+    write_file(&dir.path().join("secrets.txt"), "database-url=vault\n");
+    let env = dir.path().join(".env");
+
+    let mut child = Command::new(assert_cmd::cargo::cargo_bin!("envit"))
+        .current_dir(dir.path())
+        .env("ENVIT_TEST_SECRETS_FILE", dir.path().join("secrets.txt"))
+        .args(["watch", "--interval", "1h"])
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn()
+        .unwrap();
+
+    let initial = wait_for(&env, "DATABASE_URL=vault\n");
+
+    write_file(&env, "DATABASE_URL=edited\n");
+    let restored = wait_for(&env, "DATABASE_URL=vault\n");
+
+    write_file(
+        &dir.path().join("envit.toml"),
+        &format!("{config}\n[map]\nDB = \"database-url\"\n"),
+    );
+    let remapped = wait_for(&env, "DATABASE_URL=vault\nDB=vault\n");
+
+    child.kill().unwrap();
+    child.wait().unwrap();
+    assert!(initial, "initial pull did not happen");
+    assert!(restored, "manual edit did not trigger a pull");
+    assert!(remapped, "config change did not trigger a pull");
+}
+
+#[test]
+fn watch_polls_and_logs_each_changed_key() {
+    let dir = TempDir::new().unwrap();
+    write_file(
+        &dir.path().join("envit.toml"),
+        r#"version = 1
+
+[provider]
+kind = "azure_key_vault"
+vault_url = "https://example.vault.azure.net/"
+"#,
+    );
+    write_file(&dir.path().join("secrets.txt"), "database-url=vault\n");
+    let env = dir.path().join(".env");
+
+    let mut child = Command::new(assert_cmd::cargo::cargo_bin!("envit"))
+        .current_dir(dir.path())
+        .env("ENVIT_TEST_SECRETS_FILE", dir.path().join("secrets.txt"))
+        .args(["watch", "--interval", "1s"])
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null())
+        .spawn()
+        .unwrap();
+
+    let initial = wait_for(&env, "DATABASE_URL=vault\n");
+    write_file(&dir.path().join("secrets.txt"), "database-url=rotated\n");
+    let rotated = wait_for(&env, "DATABASE_URL=rotated\n");
+    // The change is logged just after the file is written.
+    thread::sleep(Duration::from_millis(500));
+
+    child.kill().unwrap();
+    let output = child.wait_with_output().unwrap();
+    let log = String::from_utf8(output.stdout).unwrap();
+    assert!(initial && rotated, "polling did not pick up the rotation");
+    let lines: Vec<_> = log.lines().collect();
+    assert!(lines[0].ends_with("Z ADD DATABASE_URL"), "{log}");
+    assert!(lines[1].ends_with("Z UPDATE DATABASE_URL"), "{log}");
+}